@@ -0,0 +1,13 @@
+//! Tools for finding types in a buffer, rather than reading a known type.
+//!
+//! Everything in here is built on top of [`crate::H2Type`], so the results
+//! can be resolved and displayed like any other type.
+
+mod string_scanner;
+pub use string_scanner::*;
+
+mod signature;
+pub use signature::*;
+
+mod type_scanner;
+pub use type_scanner::*;
@@ -0,0 +1,409 @@
+#[cfg(feature = "serialize")]
+use serde::{Serialize, Deserialize};
+
+use simple_error::{bail, SimpleResult};
+use sized_number::{Context, Endian, SizedDefinition};
+use std::cmp::Ordering;
+
+use crate::{H2Type, Offset};
+use crate::composite::{Constrained, Constraint, ConstraintAction, H2Struct};
+use crate::composite::string::H2String;
+use crate::formats::{elf, gzip, pe, png, zip};
+use crate::formats::common::{equals, hex, magic, number, one_of};
+use crate::simple::character::{ASCII, StrictASCII};
+
+/// A single test that a buffer has to pass to match a [`Signature`].
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum SignatureCheck {
+    /// The bytes must match exactly.
+    Magic(Vec<u8>),
+
+    /// The type must resolve, and must be valid (if it can be validated) and
+    /// have no warnings. This is normally a [`Constrained`] type.
+    Type(H2Type),
+}
+
+impl SignatureCheck {
+    fn check(&self, offset: Offset) -> bool {
+        match self {
+            Self::Magic(magic) => {
+                let context = match offset.get_dynamic() {
+                    Ok(c) => c,
+                    Err(_) => return false,
+                };
+
+                magic.iter().enumerate().all(|(i, &b)| {
+                    context.at(offset.position() + i as u64).read_u8().ok() == Some(b)
+                })
+            },
+            Self::Type(t) => {
+                match t.resolve(offset, None) {
+                    Ok(r)  => r.valid != Some(false) && r.warnings.is_empty(),
+                    Err(_) => false,
+                }
+            },
+        }
+    }
+}
+
+/// A known format - a set of checks that identify it, and a definition that
+/// describes it.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct Signature {
+    /// A short name, like `"ELF"`.
+    pub name: String,
+
+    /// The checks, at offsets relative to the start of the data. All of them
+    /// must pass for the signature to match.
+    pub checks: Vec<(u64, SignatureCheck)>,
+
+    /// The type that describes the format, starting at the start of the data.
+    pub definition: H2Type,
+}
+
+impl Signature {
+    pub fn new(name: &str, checks: Vec<(u64, SignatureCheck)>, definition: H2Type) -> SimpleResult<Self> {
+        if checks.len() == 0 {
+            bail!("Signatures must have at least one check");
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            checks: checks,
+            definition: definition,
+        })
+    }
+}
+
+/// A [`Signature`] that matched, from [`SignatureEngine::identify`].
+#[derive(Debug, Clone)]
+pub struct SignatureMatch {
+    /// The name of the [`Signature`].
+    pub name: String,
+
+    /// The definition from the [`Signature`], which can be resolved at the
+    /// start of the data.
+    pub definition: H2Type,
+
+    /// The number of fields in the definition that resolved cleanly - that
+    /// is, without an error, a failed validation, or a warning.
+    pub clean_fields: u64,
+
+    /// The total number of fields in the definition.
+    pub total_fields: u64,
+}
+
+impl SignatureMatch {
+    /// How much of the definition resolved cleanly, from `0.0` to `1.0`.
+    pub fn confidence(&self) -> f64 {
+        match self.total_fields {
+            0 => 0.0,
+            _ => self.clean_fields as f64 / self.total_fields as f64,
+        }
+    }
+
+    /// Compare by confidence, then by the number of clean fields (so a more
+    /// detailed definition wins a tie).
+    fn compare(&self, other: &Self) -> Ordering {
+        (self.clean_fields * other.total_fields).cmp(&(other.clean_fields * self.total_fields))
+            .then(self.clean_fields.cmp(&other.clean_fields))
+    }
+}
+
+/// Count the clean fields (and total fields) in a type, recursively.
+///
+/// Leaf fields are clean if they can be displayed, aren't invalid, and have no
+/// warnings. Fields with children count once for themselves (which only
+/// considers validity and warnings, since a bad child will break the display)
+/// plus however many their children count. If the children can't be read at
+/// all, the field counts as one unclean field.
+fn count_fields(t: &H2Type, offset: Offset) -> (u64, u64) {
+    let healthy = t.is_valid(offset).ok() != Some(false) && t.warnings(offset).map(|w| w.is_empty()).unwrap_or(false);
+
+    let children = match t.children_with_range(offset) {
+        Ok(c) => c,
+        Err(_) => return (0, 1),
+    };
+
+    if children.len() == 0 {
+        return match healthy && t.to_display(offset).is_ok() {
+            true  => (1, 1),
+            false => (0, 1),
+        };
+    }
+
+    children.iter().fold((healthy as u64, 1), |(clean, total), (range, bit, _, child)| {
+        let (child_clean, child_total) = count_fields(child, offset.at_bit(range.start, *bit));
+
+        (clean + child_clean, total + child_total)
+    })
+}
+
+/// Identifies data by matching it against a set of [`Signature`]s, somewhat
+/// like `libmagic`.
+///
+/// Each signature's checks are run against the data; signatures where every
+/// check passes are matches. Matches are then ranked by how much of their
+/// definition resolves cleanly, so a file that merely starts with the right
+/// magic bytes ranks below one whose entire header makes sense.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct SignatureEngine {
+    signatures: Vec<Signature>,
+}
+
+impl SignatureEngine {
+    /// Create an engine with no signatures.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an engine with the signatures from [`builtin_signatures`].
+    pub fn with_builtins() -> SimpleResult<Self> {
+        Ok(Self {
+            signatures: builtin_signatures()?,
+        })
+    }
+
+    /// Add a signature.
+    pub fn add(&mut self, signature: Signature) {
+        self.signatures.push(signature);
+    }
+
+    /// Get the registered signatures.
+    pub fn signatures(&self) -> &Vec<Signature> {
+        &self.signatures
+    }
+
+    /// Find the signatures that match the data at the [`Context`]'s
+    /// position, best first.
+    pub fn identify(&self, context: Context) -> Vec<SignatureMatch> {
+        let offset = Offset::from(context);
+
+        let mut matches: Vec<SignatureMatch> = self.signatures.iter().filter(|signature| {
+            signature.checks.iter().all(|(position, check)| {
+                check.check(offset.at(offset.position() + position))
+            })
+        }).map(|signature| {
+            let (clean, total) = count_fields(&signature.definition, offset);
+
+            SignatureMatch {
+                name: signature.name.clone(),
+                definition: signature.definition.clone(),
+                clean_fields: clean,
+                total_fields: total,
+            }
+        }).collect();
+
+        matches.sort_by(|a, b| b.compare(a));
+
+        matches
+    }
+}
+
+fn ascii(length: u64) -> SimpleResult<H2Type> {
+    H2String::new(length, ASCII::new(StrictASCII::Permissive))
+}
+
+fn elf() -> SimpleResult<Signature> {
+    // Just the identification bytes, since everything after depends on the
+    // class and byte order
+    Signature::new("ELF", vec![(0, SignatureCheck::Magic(elf::MAGIC.to_vec()))], elf::ident()?)
+}
+
+fn pe() -> SimpleResult<Signature> {
+    // The DOS header, where the last field points to the PE signature and
+    // file header (the optional header's layout depends on its magic)
+    let nt_headers = H2Struct::new(vec![
+        ("Signature".to_string(),  magic(pe::SIGNATURE)?),
+        ("FileHeader".to_string(), pe::file_header()?),
+    ])?;
+
+    Signature::new("PE", vec![(0, SignatureCheck::Magic(pe::DOS_MAGIC.to_vec()))], pe::dos_header(Some(nt_headers))?)
+}
+
+fn macho() -> SimpleResult<Signature> {
+    let magic = one_of(hex(SizedDefinition::U32(Endian::Big)), vec![0xfeedface, 0xfeedfacf, 0xcefaedfe, 0xcffaedfe])?;
+
+    // The rest of the header is in the file's byte order, which we don't know
+    // yet; little endian is by far the most common
+    let definition = H2Struct::new(vec![
+        ("magic".to_string(),      magic.clone()),
+        ("cputype".to_string(),    hex(SizedDefinition::U32(Endian::Little))),
+        ("cpusubtype".to_string(), hex(SizedDefinition::U32(Endian::Little))),
+        ("filetype".to_string(),   one_of(number(SizedDefinition::U32(Endian::Little)), (1..=12).collect())?),
+        ("ncmds".to_string(),      number(SizedDefinition::U32(Endian::Little))),
+        ("sizeofcmds".to_string(), number(SizedDefinition::U32(Endian::Little))),
+        ("flags".to_string(),      hex(SizedDefinition::U32(Endian::Little))),
+    ])?;
+
+    Signature::new("Mach-O", vec![(0, SignatureCheck::Type(magic))], definition)
+}
+
+fn zip() -> SimpleResult<Signature> {
+    // The first local file header
+    Signature::new("ZIP", vec![(0, SignatureCheck::Magic(zip::LOCAL_FILE_SIGNATURE.to_vec()))], zip::local_file(0, 0, 0)?)
+}
+
+fn png() -> SimpleResult<Signature> {
+    // The signature, then the IHDR chunk (which must come first)
+    let definition = H2Struct::new(vec![
+        ("signature".to_string(), magic(png::SIGNATURE)?),
+        ("IHDR".to_string(),      png::chunk(png::IHDR_LENGTH, Some(png::ihdr()?))?),
+    ])?;
+
+    Signature::new("PNG", vec![(0, SignatureCheck::Magic(png::SIGNATURE.to_vec()))], definition)
+}
+
+fn gzip() -> SimpleResult<Signature> {
+    Signature::new("GZIP", vec![(0, SignatureCheck::Magic(gzip::MAGIC.to_vec()))], gzip::header(0)?)
+}
+
+fn pdf() -> SimpleResult<Signature> {
+    let definition = H2Struct::new(vec![
+        ("magic".to_string(),   magic(b"%PDF-")?),
+        ("version".to_string(), Constrained::new(ascii(3)?, Constraint::Regex("^[12]\\.[0-9]$".to_string()), ConstraintAction::Warn)?),
+    ])?;
+
+    Signature::new("PDF", vec![(0, SignatureCheck::Magic(b"%PDF-".to_vec()))], definition)
+}
+
+fn sqlite() -> SimpleResult<Signature> {
+    let definition = H2Struct::new(vec![
+        ("magic".to_string(),         magic(b"SQLite format 3\x00")?),
+        // 1 means 65536
+        ("page_size".to_string(),     Constrained::new(number(SizedDefinition::U16(Endian::Big)), Constraint::Predicate(|size| size == 1 || (size >= 512 && size.is_power_of_two())), ConstraintAction::Warn)?),
+        ("write_version".to_string(), one_of(number(SizedDefinition::U8), vec![1, 2])?),
+        ("read_version".to_string(),  one_of(number(SizedDefinition::U8), vec![1, 2])?),
+        ("reserved".to_string(),      number(SizedDefinition::U8)),
+        ("max_payload".to_string(),   equals(number(SizedDefinition::U8), 64)?),
+        ("min_payload".to_string(),   equals(number(SizedDefinition::U8), 32)?),
+        ("leaf_payload".to_string(),  equals(number(SizedDefinition::U8), 32)?),
+        ("change_count".to_string(),  number(SizedDefinition::U32(Endian::Big))),
+        ("page_count".to_string(),    number(SizedDefinition::U32(Endian::Big))),
+    ])?;
+
+    Signature::new("SQLite", vec![(0, SignatureCheck::Magic(b"SQLite format 3\x00".to_vec()))], definition)
+}
+
+/// Get signatures for some common formats: ELF, PE, Mach-O, ZIP, PNG, GZIP,
+/// PDF, and SQLite.
+///
+/// The definitions only cover the headers, which is enough to identify (and
+/// rank) the formats.
+pub fn builtin_signatures() -> SimpleResult<Vec<Signature>> {
+    Ok(vec![
+        elf()?,
+        pe()?,
+        macho()?,
+        zip()?,
+        png()?,
+        gzip()?,
+        pdf()?,
+        sqlite()?,
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+    use sized_number::Context;
+
+    #[test]
+    fn test_builtins() -> SimpleResult<()> {
+        let engine = SignatureEngine::with_builtins()?;
+
+        let tests: Vec<(&[u8], &str)> = vec![
+            (&b"\x7fELF\x02\x01\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00"[..], "ELF"),
+            (&b"\xcf\xfa\xed\xfe\x07\x00\x00\x01\x03\x00\x00\x00\x02\x00\x00\x00\x10\x00\x00\x00\x00\x01\x00\x00\x85\x00\x20\x00"[..], "Mach-O"),
+            (&b"PK\x03\x04\x14\x00\x00\x00\x08\x00\x00\x00\x21\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00"[..], "ZIP"),
+            (&b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR\x00\x00\x00\x01\x00\x00\x00\x01\x08\x06\x00\x00\x00\x1f\x15\xc4\x89"[..], "PNG"),
+            (&b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x00\x03"[..], "GZIP"),
+            (&b"%PDF-1.7\n"[..], "PDF"),
+            (&b"SQLite format 3\x00\x10\x00\x01\x01\x00\x40\x20\x20\x00\x00\x00\x01\x00\x00\x00\x02"[..], "SQLite"),
+        ];
+
+        for (data, name) in tests {
+            let data = data.to_vec();
+            let matches = engine.identify(Context::new(&data));
+
+            assert_eq!(1, matches.len());
+            assert_eq!(name, matches[0].name);
+            assert_eq!(matches[0].total_fields, matches[0].clean_fields);
+            assert_eq!(1.0, matches[0].confidence());
+        }
+
+        // Nothing matches
+        let data = b"hello, world!".to_vec();
+        assert_eq!(0, engine.identify(Context::new(&data)).len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pe() -> SimpleResult<()> {
+        let mut data = vec![0; 0x40];
+        data[0..2].copy_from_slice(b"MZ");
+        data[0x3c] = 0x40;
+        data.extend_from_slice(b"PE\x00\x00\x4c\x01\x03\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\xe0\x00\x02\x01");
+
+        let engine = SignatureEngine::with_builtins()?;
+        let matches = engine.identify(Context::new(&data));
+
+        assert_eq!(1, matches.len());
+        assert_eq!("PE", matches[0].name);
+
+        // The PE header is found through the pointer
+        let r = matches[0].definition.resolve(Offset::Dynamic(Context::new(&data)), None)?;
+        let e_lfanew = r.children.last().unwrap();
+        assert_eq!(1, e_lfanew.related.len());
+        assert_eq!(0x40, e_lfanew.related[0].0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ranking() -> SimpleResult<()> {
+        // A PNG with a bad bit depth and a bad CRC, so it's still a PNG, just
+        // not a very good one
+        let data = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR\x00\x00\x00\x01\x00\x00\x00\x01\x07\x06\x00\x00\x00\x00\x00\x00\x00".to_vec();
+
+        let engine = SignatureEngine::with_builtins()?;
+        let matches = engine.identify(Context::new(&data));
+
+        assert_eq!(1, matches.len());
+        assert_eq!("PNG", matches[0].name);
+        assert_eq!(19, matches[0].total_fields);
+        assert_eq!(17, matches[0].clean_fields);
+
+        // Add a loose signature that matches anything starting with \x89, and
+        // make sure the PNG is better
+        let mut engine = engine;
+        engine.add(Signature::new("Loose", vec![(0, SignatureCheck::Magic(b"\x89".to_vec()))], number(SizedDefinition::U8))?);
+        let data = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR\x00\x00\x00\x01\x00\x00\x00\x01\x08\x06\x00\x00\x00\x1f\x15\xc4\x89".to_vec();
+
+        let matches = engine.identify(Context::new(&data));
+        assert_eq!(2, matches.len());
+        assert_eq!("PNG", matches[0].name);
+        assert_eq!("Loose", matches[1].name);
+
+        // A truncated PNG ranks below the loose signature
+        let data = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0d".to_vec();
+        let matches = engine.identify(Context::new(&data));
+        assert_eq!(2, matches.len());
+        assert_eq!("Loose", matches[0].name);
+        assert_eq!("PNG", matches[1].name);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_checks() -> SimpleResult<()> {
+        assert!(Signature::new("Bad", vec![], number(SizedDefinition::U8)).is_err());
+
+        Ok(())
+    }
+}
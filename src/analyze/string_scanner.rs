@@ -0,0 +1,240 @@
+#[cfg(feature = "serialize")]
+use serde::{Serialize, Deserialize};
+
+use simple_error::{bail, SimpleResult};
+use sized_number::{Context, Endian, SizedDefinition, SizedDisplay};
+
+use crate::{H2Type, Offset};
+use crate::composite::string::{H2String, LPString, LPStringOptions, LengthAdjustment, LengthUnit, NTString};
+use crate::simple::H2Number;
+use crate::simple::character::{ASCII, StrictASCII, StringDisplay, UTF16, UTF32, UTF8};
+
+/// The character encodings that [`find_strings`] can search for.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum StringEncoding {
+    ASCII,
+    UTF8,
+    UTF16(Endian),
+    UTF32(Endian),
+}
+
+impl StringEncoding {
+    fn character(self) -> H2Type {
+        match self {
+            Self::ASCII         => ASCII::new(StrictASCII::Strict),
+            Self::UTF8          => UTF8::new(),
+            Self::UTF16(endian) => UTF16::new(endian),
+            Self::UTF32(endian) => UTF32::new(endian),
+        }
+    }
+
+    /// Is this a character that `strings(1)` would consider part of a string?
+    ///
+    /// Like `strings(1)`, the wide encodings only accept Latin-1 characters.
+    /// Otherwise, almost any misaligned pair of bytes looks like a valid
+    /// UTF-16 character.
+    fn is_printable(self, c: char) -> bool {
+        let printable = c == '\t' || !(c.is_control() || c == '\u{fffd}');
+
+        match self {
+            Self::UTF16(_) | Self::UTF32(_) => printable && (c as u32) < 0x100,
+            _                               => printable,
+        }
+    }
+}
+
+/// Read printable characters starting at `offset`, returning the number of
+/// characters and the position just past the last one.
+fn read_run(encoding: StringEncoding, character: &H2Type, offset: Offset) -> (u64, u64) {
+    let mut count = 0;
+    let mut position = offset.position();
+
+    loop {
+        let this_offset = offset.at(position);
+
+        match (character.to_char(this_offset), character.actual_size(this_offset)) {
+            (Ok(c), Ok(size)) if encoding.is_printable(c) => {
+                count += 1;
+                position += size;
+            },
+            _ => break,
+        }
+    }
+
+    (count, position)
+}
+
+/// Look for a length prefix immediately before `start` that matches the
+/// string, either in characters or in bytes.
+///
+/// Longer prefixes are checked first, since a 32-bit length of `5` also looks
+/// like an 8-bit length of `5` (for big endian, anyways).
+fn find_length_prefix(offset: Offset, start: u64, characters: u64, bytes: u64) -> Option<(u64, H2Type, LengthUnit)> {
+    let candidates = vec![
+        SizedDefinition::U32(Endian::Little),
+        SizedDefinition::U32(Endian::Big),
+        SizedDefinition::U16(Endian::Little),
+        SizedDefinition::U16(Endian::Big),
+        SizedDefinition::U8,
+    ];
+
+    for definition in candidates {
+        let size = definition.size();
+        if size > start {
+            continue;
+        }
+
+        let length = H2Number::new(definition, SizedDisplay::Decimal);
+        let value = match length.to_u64(offset.at(start - size)) {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+
+        if value == characters {
+            return Some((start - size, length, LengthUnit::Characters));
+        }
+
+        if value == bytes {
+            return Some((start - size, length, LengthUnit::Bytes));
+        }
+    }
+
+    None
+}
+
+/// Find strings in a buffer, the same way that `strings(1)` does.
+///
+/// Starting at the [`Context`]'s position and continuing to the end of the
+/// buffer, this looks for runs of at least `minimum_length` printable
+/// characters in the given encoding. Each run is returned as the offset where
+/// it starts and an [`H2Type`] that can be used to resolve it:
+///
+/// * If the run is immediately preceded by an 8-, 16-, or 32-bit length that
+///   matches it (in either characters or bytes), it's an [`LPString`]
+///   starting at the length.
+/// * Otherwise, if it's followed by a NUL character, it's an [`NTString`].
+/// * Otherwise, it's an [`H2String`] of exactly the run's length.
+///
+/// A length-prefixed string that's also followed by a NUL gets the NUL as a
+/// terminator.
+pub fn find_strings(context: Context, encoding: StringEncoding, minimum_length: u64) -> SimpleResult<Vec<(u64, H2Type)>> {
+    if minimum_length == 0 {
+        bail!("Minimum length must be at least 1 character");
+    }
+
+    let offset = Offset::from(context);
+    let character = encoding.character();
+
+    let mut results = Vec::new();
+    let mut position = offset.position();
+
+    // Keep going until we run off the end of the buffer
+    while context.at(position).read_u8().is_ok() {
+        let (count, end) = read_run(encoding, &character, offset.at(position));
+
+        if count < minimum_length {
+            position += 1;
+            continue;
+        }
+
+        let terminated = character.to_char(offset.at(end)).ok() == Some('\0');
+        let next = match terminated {
+            true  => end + character.actual_size(offset.at(end))?,
+            false => end,
+        };
+
+        let found = match find_length_prefix(offset, position, count, end - position) {
+            Some((start, length, unit)) => {
+                let options = LPStringOptions {
+                    unit: unit,
+                    adjustment: LengthAdjustment::None,
+                    terminator: terminated,
+                };
+
+                (start, LPString::new_with_options(length, character.clone(), options, StringDisplay::default())?)
+            },
+            None => match terminated {
+                true  => (position, NTString::new(character.clone())?),
+                false => (position, H2String::new(count, character.clone())?),
+            },
+        };
+
+        results.push(found);
+        position = next;
+    }
+
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+    use sized_number::Context;
+
+    #[test]
+    fn test_find_ascii_strings() -> SimpleResult<()> {
+        // A NUL-terminated string, a short string, a length-prefixed string,
+        // and a plain string
+        let data = b"hello\x00\x01\x02ab\x00\x03\x06string\xff\xffnothing".to_vec();
+        let context = Context::new(&data);
+        let offset = Offset::from(context);
+
+        let strings = find_strings(context, StringEncoding::ASCII, 4)?;
+        assert_eq!(3, strings.len());
+
+        assert_eq!(0, strings[0].0);
+        assert_eq!("\"hello\"", strings[0].1.to_display(offset.at(strings[0].0))?);
+        assert_eq!(6, strings[0].1.actual_size(offset.at(strings[0].0))?);
+
+        assert_eq!(12, strings[1].0);
+        assert_eq!("\"string\"", strings[1].1.to_display(offset.at(strings[1].0))?);
+        assert_eq!(7, strings[1].1.actual_size(offset.at(strings[1].0))?);
+
+        assert_eq!(21, strings[2].0);
+        assert_eq!("\"nothing\"", strings[2].1.to_display(offset.at(strings[2].0))?);
+
+        // Make sure they resolve
+        for (position, t) in strings {
+            t.resolve(offset.at(position), None)?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_utf16_strings() -> SimpleResult<()> {
+        // A BSTR-style string (32-bit length in bytes), then a NUL-terminated
+        // string
+        let data = b"\x08\x00\x00\x00t\x00e\x00s\x00t\x00\x00\x00\xff\xffh\x00i\x00!\x00!\x00\x00\x00".to_vec();
+        let context = Context::new(&data);
+        let offset = Offset::from(context);
+
+        let strings = find_strings(context, StringEncoding::UTF16(Endian::Little), 4)?;
+        assert_eq!(2, strings.len());
+
+        assert_eq!(0, strings[0].0);
+        assert_eq!("\"test\"", strings[0].1.to_display(offset)?);
+        assert_eq!(14, strings[0].1.actual_size(offset)?);
+
+        assert_eq!(16, strings[1].0);
+        assert_eq!("\"hi!!\"", strings[1].1.to_display(offset.at(16))?);
+        assert_eq!(10, strings[1].1.actual_size(offset.at(16))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_minimum_length() -> SimpleResult<()> {
+        let data = b"abc\x00abcd\x00".to_vec();
+        let context = Context::new(&data);
+
+        assert_eq!(2, find_strings(context, StringEncoding::ASCII, 3)?.len());
+        assert_eq!(1, find_strings(context, StringEncoding::ASCII, 4)?.len());
+        assert_eq!(0, find_strings(context, StringEncoding::ASCII, 5)?.len());
+        assert!(find_strings(context, StringEncoding::ASCII, 0).is_err());
+
+        Ok(())
+    }
+}
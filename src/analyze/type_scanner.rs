@@ -0,0 +1,190 @@
+use simple_error::{bail, SimpleResult};
+use sized_number::Context;
+use std::ops::Range;
+use std::thread;
+
+use crate::{Alignment, H2Type, Offset, ResolvedType};
+use crate::offset::buffer_end;
+
+/// Does a resolved type (and everything in it) pass its checks?
+fn is_clean(resolved: &ResolvedType) -> bool {
+    resolved.valid != Some(false)
+        && resolved.warnings.is_empty()
+        && resolved.children.iter().all(is_clean)
+}
+
+/// Get the positions to try, from the [`Context`]'s position to the end of
+/// the buffer, as a range and a step.
+///
+/// With [`Alignment::None`], that's every position; otherwise, it's every
+/// position that's a multiple of the alignment. The positions aren't
+/// collected, since there can be billions of them.
+fn positions(context: Context, alignment: Alignment) -> (Range<u64>, u64) {
+    let start = context.position();
+    let end = buffer_end(context);
+
+    match alignment {
+        Alignment::None => (start..end, 1),
+        Alignment::Loose(m) | Alignment::Strict(m) if m <= 1 => (start..end, 1),
+        Alignment::Loose(m) | Alignment::Strict(m) => {
+            let first = (start + m - 1) / m * m;
+
+            (std::cmp::min(first, end)..end, m)
+        },
+    }
+}
+
+/// Try the type at each position, keeping the ones that resolve cleanly.
+fn scan_positions(context: Context, t: &H2Type, range: Range<u64>, step: u64) -> Vec<(u64, ResolvedType)> {
+    let offset = Offset::from(context);
+
+    range.step_by(step as usize).filter_map(|position| {
+        match t.resolve(offset.at(position), None) {
+            Ok(r) if is_clean(&r) => Some((position, r)),
+            _                     => None,
+        }
+    }).collect()
+}
+
+/// Slide a type across a buffer, and find everywhere it fits.
+///
+/// Starting at the [`Context`]'s position and continuing to the end of the
+/// buffer, this tries to resolve `t` at every position (or, with an
+/// [`Alignment`] other than [`Alignment::None`], at every multiple of the
+/// alignment). A position is a candidate if the type resolves, and neither
+/// it nor any of its children is invalid or has warnings - so wrapping
+/// fields in a [`crate::composite::Constrained`] (or adding a
+/// [`crate::simple::Checksum`]) is how to narrow down the results.
+///
+/// Each candidate is returned as its position and the [`ResolvedType`].
+pub fn scan_type(context: Context, t: &H2Type, alignment: Alignment) -> Vec<(u64, ResolvedType)> {
+    let (range, step) = positions(context, alignment);
+
+    scan_positions(context, t, range, step)
+}
+
+/// Exactly like [`scan_type`], but splits the buffer between `threads`
+/// threads.
+///
+/// The results are the same, and in the same order, as [`scan_type`].
+pub fn scan_type_parallel(context: Context, t: &H2Type, alignment: Alignment, threads: usize) -> SimpleResult<Vec<(u64, ResolvedType)>> {
+    if threads == 0 {
+        bail!("Must use at least one thread");
+    }
+
+    let (range, step) = positions(context, alignment);
+    let count = (range.end.saturating_sub(range.start) + step - 1) / step;
+    if count == 0 {
+        return Ok(vec![]);
+    }
+
+    // Round up so we don't end up with an extra, tiny chunk
+    let chunk_size = (count + threads as u64 - 1) / threads as u64 * step;
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = (range.start..range.end).step_by(chunk_size as usize).map(|chunk_start| {
+            let chunk = chunk_start..std::cmp::min(chunk_start + chunk_size, range.end);
+
+            scope.spawn(move || scan_positions(context, t, chunk, step))
+        }).collect();
+
+        let mut results = Vec::new();
+        for handle in handles {
+            match handle.join() {
+                Ok(r) => results.extend(r),
+                Err(_) => bail!("Scanner thread panicked"),
+            }
+        }
+
+        Ok(results)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+    use sized_number::{Context, Endian, SizedDefinition, SizedDisplay};
+
+    use crate::composite::{Constrained, Constraint, ConstraintAction, ConstraintValue, H2Struct};
+    use crate::simple::H2Number;
+
+    fn header() -> SimpleResult<H2Type> {
+        // A magic number, then a version that has to be 1 or 2
+        H2Struct::new(vec![
+            ("magic".to_string(), Constrained::new(
+                H2Number::new(SizedDefinition::U16(Endian::Big), SizedDisplay::Hex(Default::default())),
+                Constraint::Equals(ConstraintValue::Integer(0xcafe)),
+                ConstraintAction::Fail,
+            )?),
+            ("version".to_string(), Constrained::new(
+                H2Number::new(SizedDefinition::U8, SizedDisplay::Decimal),
+                Constraint::Range(1..=2),
+                ConstraintAction::Warn,
+            )?),
+        ])
+    }
+
+    #[test]
+    fn test_scan() -> SimpleResult<()> {
+        // A good header at 1, a bad version at 6, a good header at 9, and
+        // one that runs off the end
+        let data = b"\x00\xca\xfe\x01\x00\x00\xca\xfe\x03\xca\xfe\x02\xca\xfe".to_vec();
+        let context = Context::new(&data);
+
+        let results = scan_type(context, &header()?, Alignment::None);
+        assert_eq!(vec![1, 9], results.iter().map(|(position, _)| *position).collect::<Vec<u64>>());
+        assert_eq!("{ magic: 0xcafe, version: 1 }", results[0].1.display);
+
+        // Starting partway through
+        let results = scan_type(context.at(2), &header()?, Alignment::None);
+        assert_eq!(vec![9], results.iter().map(|(position, _)| *position).collect::<Vec<u64>>());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_aligned() -> SimpleResult<()> {
+        let data = b"\xca\xfe\x01\x00\x00\xca\xfe\x02\xca\xfe\x02\x00".to_vec();
+        let context = Context::new(&data);
+
+        let results = scan_type(context, &header()?, Alignment::Loose(4));
+        assert_eq!(vec![0, 8], results.iter().map(|(position, _)| *position).collect::<Vec<u64>>());
+
+        let results = scan_type(context.at(1), &header()?, Alignment::Strict(4));
+        assert_eq!(vec![8], results.iter().map(|(position, _)| *position).collect::<Vec<u64>>());
+
+        let results = scan_type(context, &header()?, Alignment::None);
+        assert_eq!(vec![0, 5, 8], results.iter().map(|(position, _)| *position).collect::<Vec<u64>>());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_parallel() -> SimpleResult<()> {
+        let data = b"\xca\xfe\x01".repeat(100);
+        let context = Context::new(&data);
+        let t = header()?;
+
+        let expected: Vec<u64> = scan_type(context, &t, Alignment::None).into_iter().map(|(position, _)| position).collect();
+        assert_eq!(100, expected.len());
+
+        for threads in 1..=8 {
+            let results = scan_type_parallel(context, &t, Alignment::None, threads)?;
+            assert_eq!(expected, results.into_iter().map(|(position, _)| position).collect::<Vec<u64>>());
+        }
+
+        // Chunks have to stay on the alignment, even from an unaligned start
+        let expected: Vec<u64> = scan_type(context.at(1), &t, Alignment::Loose(3)).into_iter().map(|(position, _)| position).collect();
+        assert_eq!(99, expected.len());
+
+        for threads in 1..=8 {
+            let results = scan_type_parallel(context.at(1), &t, Alignment::Loose(3), threads)?;
+            assert_eq!(expected, results.into_iter().map(|(position, _)| position).collect::<Vec<u64>>());
+        }
+
+        assert!(scan_type_parallel(context, &t, Alignment::None, 0).is_err());
+
+        Ok(())
+    }
+}
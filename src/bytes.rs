@@ -0,0 +1,57 @@
+//! Helpers for reading and displaying raw bytes, shared by the types that
+//! read lengths out of the data.
+
+use simple_error::{bail, SimpleResult};
+use sized_number::Context;
+
+/// Skip `length` bytes starting at `position`, returning the end. The bytes
+/// aren't read, but the last one has to exist.
+pub(crate) fn skip_bytes(context: Context, position: u64, length: u64) -> SimpleResult<u64> {
+    let end = match position.checked_add(length) {
+        Some(end) => end,
+        None      => bail!("Length is too large: {}", length),
+    };
+
+    if length > 0 {
+        context.at(end - 1).read_u8()?;
+    }
+
+    Ok(end)
+}
+
+/// Read `length` bytes starting at `position`.
+pub(crate) fn read_bytes(context: Context, position: u64, length: u64) -> SimpleResult<Vec<u8>> {
+    let end = skip_bytes(context, position, length)?;
+
+    (position..end).map(|i| context.at(i).read_u8()).collect()
+}
+
+/// Display bytes as hex, like `00ff`, with `separator` between them.
+pub(crate) fn to_hex(bytes: &[u8], separator: &str) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect::<Vec<String>>().join(separator)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+
+    #[test]
+    fn test_bytes() -> SimpleResult<()> {
+        let data = b"\x00\x01\x02\xff".to_vec();
+        let context = Context::new(&data);
+
+        assert_eq!(4, skip_bytes(context, 1, 3)?);
+        assert_eq!(1, skip_bytes(context, 1, 0)?);
+        assert!(skip_bytes(context, 1, 4).is_err());
+        assert!(skip_bytes(context, 1, u64::MAX).is_err());
+
+        assert_eq!(b"\x01\x02".to_vec(), read_bytes(context, 1, 2)?);
+        assert!(read_bytes(context, 3, 2).is_err());
+
+        assert_eq!("000102ff", to_hex(&data, ""));
+        assert_eq!("00 01 02 ff", to_hex(&data, " "));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,696 @@
+#[cfg(feature = "serialize")]
+use serde::{Serialize, Deserialize};
+
+use simple_error::{bail, SimpleResult};
+use sized_number::Context;
+use std::ops::Range;
+
+use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset};
+use crate::bytes::{read_bytes, skip_bytes, to_hex};
+use crate::simple::character::common::{self, StringDisplay};
+
+/// Which set of ASN.1 encoding rules to accept.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum ASN1Rules {
+    /// Basic Encoding Rules: anything valid, including indefinite lengths.
+    BER,
+
+    /// Distinguished Encoding Rules: like BER, but lengths must be definite
+    /// and as short as possible.
+    DER,
+}
+
+const CLASS_UNIVERSAL: u8 = 0;
+const CLASS_APPLICATION: u8 = 1;
+const CLASS_CONTEXT: u8 = 2;
+
+/// The identifier and length octets of an ASN.1 value.
+struct Header {
+    class: u8,
+    constructed: bool,
+    number: u64,
+
+    /// The length of the contents, or `None` for an indefinite length.
+    length: Option<u64>,
+
+    /// The size of the identifier and length octets.
+    size: u64,
+}
+
+impl Header {
+    fn read(context: Context, rules: ASN1Rules) -> SimpleResult<Self> {
+        let mut position = context.position();
+
+        // The identifier octet(s)
+        let identifier = context.at(position).read_u8()?;
+        position = position + 1;
+
+        let mut number = (identifier & 0x1f) as u64;
+        if number == 0x1f {
+            // High tag number form: base 128, most significant first
+            number = 0;
+            loop {
+                let b = context.at(position).read_u8()?;
+                position = position + 1;
+
+                if number.leading_zeros() < 7 {
+                    bail!("ASN.1 tag number is too large");
+                }
+                number = (number << 7) | (b & 0x7f) as u64;
+
+                if b & 0x80 == 0 {
+                    break;
+                }
+            }
+        }
+
+        let constructed = identifier & 0x20 != 0;
+
+        // The length octet(s)
+        let first = context.at(position).read_u8()?;
+        position = position + 1;
+
+        let length = match first {
+            0x00..=0x7f => Some(first as u64),
+            0x80 => {
+                if rules == ASN1Rules::DER {
+                    bail!("Indefinite lengths aren't allowed in DER");
+                }
+
+                if !constructed {
+                    bail!("Primitive ASN.1 values can't have an indefinite length");
+                }
+
+                None
+            },
+            0xff => bail!("Reserved ASN.1 length: 0xff"),
+            _ => {
+                let count = first & 0x7f;
+                if count > 8 {
+                    bail!("ASN.1 length is too large ({} bytes)", count);
+                }
+
+                let mut length: u64 = 0;
+                for _ in 0..count {
+                    length = (length << 8) | context.at(position).read_u8()? as u64;
+                    position = position + 1;
+                }
+
+                // DER requires the shortest possible length
+                if rules == ASN1Rules::DER && (length < 0x80 || length >> ((count - 1) * 8) == 0) {
+                    bail!("ASN.1 length isn't minimally encoded, which DER requires");
+                }
+
+                Some(length)
+            },
+        };
+
+        Ok(Self {
+            class: identifier >> 6,
+            constructed: constructed,
+            number: number,
+            length: length,
+            size: position - context.position(),
+        })
+    }
+
+    fn name(&self) -> String {
+        match (self.class, self.number) {
+            (CLASS_UNIVERSAL, 1)  => "BOOLEAN".to_string(),
+            (CLASS_UNIVERSAL, 2)  => "INTEGER".to_string(),
+            (CLASS_UNIVERSAL, 3)  => "BIT STRING".to_string(),
+            (CLASS_UNIVERSAL, 4)  => "OCTET STRING".to_string(),
+            (CLASS_UNIVERSAL, 5)  => "NULL".to_string(),
+            (CLASS_UNIVERSAL, 6)  => "OBJECT IDENTIFIER".to_string(),
+            (CLASS_UNIVERSAL, 7)  => "ObjectDescriptor".to_string(),
+            (CLASS_UNIVERSAL, 8)  => "EXTERNAL".to_string(),
+            (CLASS_UNIVERSAL, 9)  => "REAL".to_string(),
+            (CLASS_UNIVERSAL, 10) => "ENUMERATED".to_string(),
+            (CLASS_UNIVERSAL, 12) => "UTF8String".to_string(),
+            (CLASS_UNIVERSAL, 13) => "RELATIVE-OID".to_string(),
+            (CLASS_UNIVERSAL, 16) => "SEQUENCE".to_string(),
+            (CLASS_UNIVERSAL, 17) => "SET".to_string(),
+            (CLASS_UNIVERSAL, 18) => "NumericString".to_string(),
+            (CLASS_UNIVERSAL, 19) => "PrintableString".to_string(),
+            (CLASS_UNIVERSAL, 20) => "T61String".to_string(),
+            (CLASS_UNIVERSAL, 22) => "IA5String".to_string(),
+            (CLASS_UNIVERSAL, 23) => "UTCTime".to_string(),
+            (CLASS_UNIVERSAL, 24) => "GeneralizedTime".to_string(),
+            (CLASS_UNIVERSAL, 26) => "VisibleString".to_string(),
+            (CLASS_UNIVERSAL, 27) => "GeneralString".to_string(),
+            (CLASS_UNIVERSAL, 28) => "UniversalString".to_string(),
+            (CLASS_UNIVERSAL, 30) => "BMPString".to_string(),
+            (CLASS_UNIVERSAL, n)  => format!("[UNIVERSAL {}]", n),
+            (CLASS_APPLICATION, n) => format!("[APPLICATION {}]", n),
+            (CLASS_CONTEXT, n)    => format!("[{}]", n),
+            (_, n)                => format!("[PRIVATE {}]", n),
+        }
+    }
+}
+
+/// Values can only be nested this deep, so hostile data can't overflow the
+/// stack.
+const MAX_DEPTH: u64 = 128;
+
+/// Decode a two's complement, big endian integer.
+fn decode_integer(bytes: &[u8]) -> SimpleResult<i128> {
+    if bytes.len() == 0 {
+        bail!("ASN.1 integers can't be empty");
+    }
+
+    if bytes.len() > 16 {
+        bail!("ASN.1 integer is too large ({} bytes)", bytes.len());
+    }
+
+    // Start with all ones if it's negative, so it's sign extended
+    let initial: i128 = if bytes[0] & 0x80 != 0 { -1 } else { 0 };
+
+    Ok(bytes.iter().fold(initial, |value, &b| (value << 8) | b as i128))
+}
+
+/// Decode an object identifier (or, if `relative`, a relative OID) into
+/// dotted form.
+fn decode_oid(bytes: &[u8], relative: bool) -> SimpleResult<String> {
+    let mut arcs: Vec<u64> = vec![];
+    let mut arc: u64 = 0;
+    let mut in_arc = false;
+
+    for &b in bytes {
+        if arc.leading_zeros() < 7 {
+            bail!("OID arc is too large");
+        }
+
+        arc = (arc << 7) | (b & 0x7f) as u64;
+        in_arc = true;
+
+        if b & 0x80 == 0 {
+            arcs.push(arc);
+            arc = 0;
+            in_arc = false;
+        }
+    }
+
+    if in_arc || arcs.len() == 0 {
+        bail!("Truncated OID");
+    }
+
+    // The first two arcs of an absolute OID are packed into one
+    if !relative {
+        let first = arcs[0];
+        let (a, b) = match first {
+            0..=39  => (0, first),
+            40..=79 => (1, first - 40),
+            _       => (2, first - 80),
+        };
+
+        arcs[0] = b;
+        arcs.insert(0, a);
+    }
+
+    Ok(arcs.iter().map(|a| a.to_string()).collect::<Vec<String>>().join("."))
+}
+
+/// Format a UTCTime (`YYMMDDHHMM[SS]Z`) or GeneralizedTime
+/// (`YYYYMMDDHHMM[SS[.fff]]Z`) in ISO-8601, or `None` if it's not in one of
+/// those forms or isn't a real date and time.
+fn decode_time(s: &str, generalized: bool) -> Option<String> {
+    let s = s.strip_suffix('Z')?;
+    let (date, fraction) = match s.find('.') {
+        Some(i) if generalized => (&s[..i], &s[i..]),
+        _                      => (s, ""),
+    };
+
+    if !date.chars().all(|c| c.is_ascii_digit()) || !fraction.chars().skip(1).all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    // Normalize the year to four digits (RFC 5280: 50-99 are 19xx)
+    let date = match generalized {
+        true  => date.to_string(),
+        false => format!("{}{}", if &date[..2.min(date.len())] >= "50" { "19" } else { "20" }, date),
+    };
+
+    let (year, month, day, hour, minute, second) = match date.len() {
+        12 => (&date[0..4], &date[4..6], &date[6..8], &date[8..10], &date[10..12], "00"),
+        14 => (&date[0..4], &date[4..6], &date[6..8], &date[8..10], &date[10..12], &date[12..14]),
+        _  => return None,
+    };
+
+    let number = |digits: &str| digits.parse::<u32>().ok();
+    let y = number(year)?;
+    let leap_year = y % 4 == 0 && (y % 100 != 0 || y % 400 == 0);
+
+    let days = match number(month)? {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11              => 30,
+        2 if leap_year              => 29,
+        2                           => 28,
+        _                           => return None,
+    };
+
+    // A second of 60 is a leap second
+    if !(1..=days).contains(&number(day)?) || number(hour)? > 23 || number(minute)? > 59 || number(second)? > 60 {
+        return None;
+    }
+
+    Some(format!("{}-{}-{}T{}:{}:{}{}Z", year, month, day, hour, minute, second, fraction))
+}
+
+/// Defines a single ASN.1 value, encoded with BER or DER.
+///
+/// The tag, length, and value are read from the data, recursively.
+/// Constructed values (such as `SEQUENCE` and `SET`) have a child for each
+/// value they contain, named after its tag, so the structure of something
+/// like an X.509 certificate can be navigated. Primitive values are decoded
+/// based on their universal tag - integers, OIDs (in dotted form), strings,
+/// times (in ISO-8601), and so on - and anything else is displayed in hex.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct ASN1 {
+    rules: ASN1Rules,
+}
+
+impl ASN1 {
+    pub fn new_aligned(alignment: Alignment, rules: ASN1Rules) -> H2Type {
+        H2Type::new(alignment, H2Types::ASN1(Self {
+            rules: rules,
+        }))
+    }
+
+    pub fn new(rules: ASN1Rules) -> H2Type {
+        Self::new_aligned(Alignment::None, rules)
+    }
+
+    /// Walk the value, returning its header, total size, and the positions
+    /// of its children (if it's constructed).
+    fn walk(&self, context: Context) -> SimpleResult<(Header, u64, Vec<u64>)> {
+        self.walk_nested(context, 0)
+    }
+
+    /// Walk a value that's nested `depth` values deep.
+    fn walk_nested(&self, context: Context, depth: u64) -> SimpleResult<(Header, u64, Vec<u64>)> {
+        if depth > MAX_DEPTH {
+            bail!("ASN.1 values are nested too deeply");
+        }
+
+        let header = Header::read(context, self.rules)?;
+        let start = context.position() + header.size;
+        let mut position = start;
+        let mut children = vec![];
+
+        match (header.constructed, header.length) {
+            (true, Some(length)) => {
+                let end = match start.checked_add(length) {
+                    Some(end) => end,
+                    None      => bail!("ASN.1 length is too large"),
+                };
+
+                while position < end {
+                    children.push(position);
+                    position = position + self.walk_nested(context.at(position), depth + 1)?.1;
+                }
+
+                if position != end {
+                    bail!("ASN.1 value runs past the end of its parent");
+                }
+            },
+            (true, None) => {
+                // Read values until the end-of-contents marker (two NULs)
+                loop {
+                    if context.at(position).read_u8()? == 0 && context.at(position + 1).read_u8()? == 0 {
+                        position = position + 2;
+                        break;
+                    }
+
+                    children.push(position);
+                    position = position + self.walk_nested(context.at(position), depth + 1)?.1;
+                }
+            },
+            (false, length) => {
+                position = skip_bytes(context, start, length.unwrap_or(0))?;
+            },
+        };
+
+        Ok((header, position - context.position(), children))
+    }
+
+    /// Read the contents of a primitive value.
+    fn contents(&self, context: Context) -> SimpleResult<(Header, Vec<u8>)> {
+        let header = Header::read(context, self.rules)?;
+
+        if header.constructed {
+            bail!("ASN.1 value is constructed");
+        }
+
+        let bytes = read_bytes(context, context.position() + header.size, header.length.unwrap_or(0))?;
+
+        Ok((header, bytes))
+    }
+
+    /// Read a string type as a [`String`].
+    fn read_string(&self, context: Context) -> SimpleResult<String> {
+        let (header, bytes) = self.contents(context)?;
+
+        if header.class != CLASS_UNIVERSAL {
+            bail!("ASN.1 value isn't a string");
+        }
+
+        match header.number {
+            // UTF-8 (and the ASCII subsets of it)
+            12 | 18 | 19 | 22 | 23 | 24 | 26 | 27 => match String::from_utf8(bytes) {
+                Ok(s)  => Ok(s),
+                Err(e) => bail!("Invalid string in ASN.1 value: {}", e),
+            },
+
+            // Treat T61 as Latin-1, which is how it's used in practice
+            20 => Ok(bytes.iter().map(|&b| b as char).collect()),
+
+            // UTF-32 and UTF-16, both big endian
+            28 => bytes.chunks(4).map(|c| {
+                match (c.len(), std::char::from_u32(c.iter().fold(0, |v, &b| (v << 8) | b as u32))) {
+                    (4, Some(c)) => Ok(c),
+                    _            => bail!("Invalid UniversalString"),
+                }
+            }).collect(),
+            30 => {
+                if bytes.len() % 2 != 0 {
+                    bail!("Invalid BMPString");
+                }
+
+                let units: Vec<u16> = bytes.chunks(2).map(|c| ((c[0] as u16) << 8) | c[1] as u16).collect();
+                match String::from_utf16(&units) {
+                    Ok(s)  => Ok(s),
+                    Err(e) => bail!("Invalid BMPString: {}", e),
+                }
+            },
+
+            _ => bail!("ASN.1 value isn't a string"),
+        }
+    }
+
+    fn read_integer(&self, offset: Offset) -> SimpleResult<i128> {
+        let (header, bytes) = self.contents(offset.get_dynamic()?)?;
+
+        match (header.class, header.number) {
+            (CLASS_UNIVERSAL, 2) | (CLASS_UNIVERSAL, 10) => decode_integer(&bytes),
+            _ => bail!("ASN.1 value isn't an integer"),
+        }
+    }
+
+    fn display(&self, context: Context) -> SimpleResult<String> {
+        let (header, _, children) = self.walk(context)?;
+
+        if header.constructed {
+            return match children.len() {
+                1 => Ok(format!("{} (1 element)", header.name())),
+                n => Ok(format!("{} ({} elements)", header.name(), n)),
+            };
+        }
+
+        let (header, bytes) = self.contents(context)?;
+        if header.class != CLASS_UNIVERSAL {
+            return Ok(to_hex(&bytes, ""));
+        }
+
+        match header.number {
+            1 => match bytes.as_slice() {
+                [0] => Ok("FALSE".to_string()),
+                [_] => Ok("TRUE".to_string()),
+                _   => bail!("Invalid ASN.1 BOOLEAN"),
+            },
+            2 | 10 => match decode_integer(&bytes) {
+                Ok(n) => Ok(n.to_string()),
+                Err(_) if bytes.len() > 0 => Ok(format!("0x{}", to_hex(&bytes, ""))),
+                Err(e) => Err(e),
+            },
+            3 => match bytes.split_first() {
+                Some((0, data))      => Ok(to_hex(data, "")),
+                Some((unused, data)) if *unused > 7 || data.is_empty() => bail!("Invalid ASN.1 BIT STRING: {} unused bits", unused),
+                Some((unused, data)) => Ok(format!("{} ({} unused bits)", to_hex(data, ""), unused)),
+                None                 => bail!("Invalid ASN.1 BIT STRING"),
+            },
+            5 => Ok("NULL".to_string()),
+            6 => decode_oid(&bytes, false),
+            13 => decode_oid(&bytes, true),
+            23 | 24 => {
+                let s = self.read_string(context)?;
+
+                match decode_time(&s, header.number == 24) {
+                    Some(time) => Ok(time),
+                    None       => Ok(common::display_string(&s, StringDisplay::default())),
+                }
+            },
+            12 | 18 | 19 | 20 | 22 | 26 | 27 | 28 | 30 => {
+                Ok(common::display_string(&self.read_string(context)?, StringDisplay::default()))
+            },
+            _ => Ok(to_hex(&bytes, "")),
+        }
+    }
+}
+
+impl H2TypeTrait for ASN1 {
+    fn is_static(&self) -> bool {
+        false
+    }
+
+    fn actual_size(&self, offset: Offset) -> SimpleResult<u64> {
+        Ok(self.walk(offset.get_dynamic()?)?.1)
+    }
+
+    fn to_display(&self, offset: Offset) -> SimpleResult<String> {
+        match offset {
+            Offset::Static(_) => Ok("ASN.1".to_string()),
+            Offset::Dynamic(context) | Offset::Bounded(context, ..) | Offset::Windowed(context, ..) => self.display(context),
+        }
+    }
+
+    fn children(&self, offset: Offset) -> SimpleResult<Vec<(Option<String>, H2Type)>> {
+        Ok(self.children_with_range(offset)?.into_iter().map(|(_, _, name, child)| {
+            (name, child)
+        }).collect())
+    }
+
+    /// We must implement this ourselves, because the header comes before the
+    /// first child (and BER's end-of-contents marker after the last)
+    fn children_with_range(&self, offset: Offset) -> SimpleResult<Vec<(Range<u64>, u8, Option<String>, H2Type)>> {
+        let context = match offset {
+            Offset::Static(_) => return Ok(vec![]),
+            Offset::Dynamic(context) | Offset::Bounded(context, ..) | Offset::Windowed(context, ..) => context,
+        };
+
+        self.walk(context)?.2.into_iter().map(|position| {
+            let name = Header::read(context.at(position), self.rules)?.name();
+            let child = ASN1::new(self.rules);
+
+            Ok((child.aligned_range(offset.at(position))?, 0, Some(name), child))
+        }).collect()
+    }
+
+    fn can_be_string(&self) -> bool {
+        true
+    }
+
+    fn to_string(&self, offset: Offset) -> SimpleResult<String> {
+        self.read_string(offset.get_dynamic()?)
+    }
+
+    fn can_be_u64(&self) -> bool {
+        true
+    }
+
+    fn to_u64(&self, offset: Offset) -> SimpleResult<u64> {
+        let value = self.read_integer(offset)?;
+
+        if value < 0 || value > u64::MAX as i128 {
+            bail!("ASN.1 integer {} doesn't fit in a u64", value);
+        }
+
+        Ok(value as u64)
+    }
+
+    fn can_be_i64(&self) -> bool {
+        true
+    }
+
+    fn to_i64(&self, offset: Offset) -> SimpleResult<i64> {
+        let value = self.read_integer(offset)?;
+
+        if value < i64::MIN as i128 || value > i64::MAX as i128 {
+            bail!("ASN.1 integer {} doesn't fit in an i64", value);
+        }
+
+        Ok(value as i64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+    use sized_number::Context;
+
+    #[test]
+    fn test_primitives() -> SimpleResult<()> {
+        let tests: Vec<(Vec<u8>, &str)> = vec![
+            (b"\x01\x01\xff".to_vec(),                                  "TRUE"),
+            (b"\x01\x01\x00".to_vec(),                                  "FALSE"),
+            (b"\x02\x01\x05".to_vec(),                                  "5"),
+            (b"\x02\x02\x00\x80".to_vec(),                              "128"),
+            (b"\x02\x01\x80".to_vec(),                                  "-128"),
+            (b"\x02\x11\x01\x02\x03\x04\x05\x06\x07\x08\x09\x0a\x0b\x0c\x0d\x0e\x0f\x10\x11".to_vec(), "0x0102030405060708090a0b0c0d0e0f1011"),
+            (b"\x03\x03\x06\x6e\x5d".to_vec(),                          "6e5d (6 unused bits)"),
+            (b"\x03\x02\x00\xff".to_vec(),                              "ff"),
+            (b"\x04\x03\xaa\xbb\xcc".to_vec(),                          "aabbcc"),
+            (b"\x05\x00".to_vec(),                                      "NULL"),
+            (b"\x06\x09\x2a\x86\x48\x86\xf7\x0d\x01\x01\x0b".to_vec(),  "1.2.840.113549.1.1.11"),
+            (b"\x06\x03\x55\x04\x03".to_vec(),                          "2.5.4.3"),
+            (b"\x0c\x02hi".to_vec(),                                    "\"hi\""),
+            (b"\x13\x02US".to_vec(),                                    "\"US\""),
+            (b"\x1e\x04\x00h\x00i".to_vec(),                            "\"hi\""),
+            (b"\x17\x0d210101000000Z".to_vec(),                         "2021-01-01T00:00:00Z"),
+            (b"\x17\x0d991231235959Z".to_vec(),                         "1999-12-31T23:59:59Z"),
+            (b"\x18\x1320210101123456.789Z".to_vec(),                   "2021-01-01T12:34:56.789Z"),
+            (b"\x18\x0f20200229000000Z".to_vec(),                       "2020-02-29T00:00:00Z"),
+
+            // Times that aren't real are displayed as strings
+            (b"\x17\x0d211301000000Z".to_vec(),                         "\"211301000000Z\""),
+            (b"\x17\x0d210229000000Z".to_vec(),                         "\"210229000000Z\""),
+            (b"\x17\x0d210101240000Z".to_vec(),                         "\"210101240000Z\""),
+
+            (b"\x80\x02\xab\xcd".to_vec(),                              "abcd"),
+        ];
+
+        for (data, expected) in tests {
+            let offset = Offset::Dynamic(Context::new(&data));
+
+            assert_eq!(data.len() as u64, ASN1::new(ASN1Rules::DER).actual_size(offset)?);
+            assert_eq!(expected, ASN1::new(ASN1Rules::DER).to_display(offset)?);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_conversions() -> SimpleResult<()> {
+        let data = b"\x02\x02\xff\x7f\x0c\x02hi".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+        let t = ASN1::new(ASN1Rules::DER);
+
+        assert_eq!(-129, t.to_i64(offset)?);
+        assert!(t.to_u64(offset).is_err());
+        assert_eq!("hi", t.to_string(offset.at(4))?);
+        assert!(t.to_i64(offset.at(4)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sequence() -> SimpleResult<()> {
+        // The start of a certificate's TBSCertificate: the version ([0]
+        // EXPLICIT INTEGER), the serial number, and the signature algorithm
+        let data = b"\x30\x17\xa0\x03\x02\x01\x02\x02\x01\x01\x30\x0d\x06\x09\x2a\x86\x48\x86\xf7\x0d\x01\x01\x0b\x05\x00".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = ASN1::new(ASN1Rules::DER);
+        assert_eq!(25, t.actual_size(offset)?);
+        assert_eq!("SEQUENCE (3 elements)", t.to_display(offset)?);
+
+        let r = t.resolve(offset, None)?;
+        assert_eq!(3, r.children.len());
+
+        assert_eq!(Some("[0]".to_string()), r.children[0].field_name);
+        assert_eq!(2..7, r.children[0].actual_range);
+        assert_eq!("[0] (1 element)", r.children[0].display);
+        assert_eq!(Some(2), r.children[0].children[0].as_u64);
+
+        assert_eq!(Some("INTEGER".to_string()), r.children[1].field_name);
+        assert_eq!(7..10, r.children[1].actual_range);
+
+        let algorithm = &r.children[2];
+        assert_eq!(Some("SEQUENCE".to_string()), algorithm.field_name);
+        assert_eq!(10..25, algorithm.actual_range);
+        assert_eq!(Some("OBJECT IDENTIFIER".to_string()), algorithm.children[0].field_name);
+        assert_eq!("1.2.840.113549.1.1.11", algorithm.children[0].display);
+        assert_eq!(Some("NULL".to_string()), algorithm.children[1].field_name);
+        assert_eq!(23..25, algorithm.children[1].actual_range);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ber() -> SimpleResult<()> {
+        // An indefinite length, and a long-form length that isn't needed
+        let data = b"\x30\x80\x02\x01\x01\x04\x81\x01\xaa\x00\x00".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = ASN1::new(ASN1Rules::BER);
+        assert_eq!(11, t.actual_size(offset)?);
+        assert_eq!("SEQUENCE (2 elements)", t.to_display(offset)?);
+
+        let r = t.resolve(offset, None)?;
+        assert_eq!(2..5, r.children[0].actual_range);
+        assert_eq!(5..9, r.children[1].actual_range);
+        assert_eq!("aa", r.children[1].display);
+
+        // Neither is allowed in DER
+        let t = ASN1::new(ASN1Rules::DER);
+        assert!(t.to_display(offset).is_err());
+        assert!(t.to_display(offset.at(5)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_high_tag_number() -> SimpleResult<()> {
+        let data = b"\x9f\x81\x00\x01\xff".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = ASN1::new(ASN1Rules::DER);
+        assert_eq!(5, t.actual_size(offset)?);
+        assert_eq!("ff", t.to_display(offset)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_errors() -> SimpleResult<()> {
+        // Truncated, a child that runs past its parent, a bad OID, a
+        // reserved length, and BIT STRINGs with too many unused bits
+        for data in vec![b"\x02\x05\x01".to_vec(), b"\x30\x02\x02\x01\x01".to_vec(), b"\x06\x02\x2a\x86".to_vec(), b"\x04\xff".to_vec(), b"\x03\x02\x08\xff".to_vec(), b"\x03\x01\x01".to_vec()] {
+            let offset = Offset::Dynamic(Context::new(&data));
+
+            assert!(ASN1::new(ASN1Rules::BER).to_display(offset).is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_deep_nesting() -> SimpleResult<()> {
+        // An INTEGER in indefinite-length SEQUENCEs
+        let nested = |depth: usize| {
+            let mut data = b"\x30\x80".repeat(depth);
+            data.extend_from_slice(b"\x02\x01\x00");
+            data.extend_from_slice(&b"\x00\x00".repeat(depth));
+
+            data
+        };
+
+        // Nested too deeply (and then very deeply)
+        for depth in vec![MAX_DEPTH as usize + 1, 500000] {
+            let data = nested(depth);
+            let offset = Offset::Dynamic(Context::new(&data));
+
+            assert!(ASN1::new(ASN1Rules::BER).actual_size(offset).is_err());
+            assert!(ASN1::new(ASN1Rules::BER).to_display(offset).is_err());
+        }
+
+        // Right at the limit is fine
+        let data = nested(MAX_DEPTH as usize);
+        let offset = Offset::Dynamic(Context::new(&data));
+        assert_eq!(data.len() as u64, ASN1::new(ASN1Rules::BER).actual_size(offset)?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,502 @@
+#[cfg(feature = "serialize")]
+use serde::{Serialize, Deserialize};
+
+use simple_error::{bail, SimpleResult};
+use sized_number::{Context, Endian};
+use std::ops::Range;
+
+use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset};
+use crate::bytes::{read_bytes, skip_bytes, to_hex};
+use crate::simple::character::common::{self, StringDisplay};
+
+/// The initial byte of a CBOR item, plus its argument.
+struct Header {
+    major: u8,
+    info: u8,
+
+    /// The argument, or `None` for an indefinite length.
+    argument: Option<u64>,
+
+    /// The size of the initial byte plus the argument.
+    size: u64,
+}
+
+impl Header {
+    fn read(context: Context) -> SimpleResult<Self> {
+        let initial = context.read_u8()?;
+        let major = initial >> 5;
+        let info = initial & 0x1f;
+        let following = context.at(context.position() + 1);
+
+        let (argument, size) = match info {
+            0..=23 => (Some(info as u64), 1),
+            24     => (Some(following.read_u8()? as u64), 2),
+            25     => (Some(following.read_u16(Endian::Big)? as u64), 3),
+            26     => (Some(following.read_u32(Endian::Big)? as u64), 5),
+            27     => (Some(following.read_u64(Endian::Big)?), 9),
+            31     => match major {
+                2..=5 | 7 => (None, 1),
+                _         => bail!("CBOR major type {} can't have an indefinite length", major),
+            },
+            _      => bail!("Reserved CBOR additional information: {}", info),
+        };
+
+        Ok(Self {
+            major: major,
+            info: info,
+            argument: argument,
+            size: size,
+        })
+    }
+}
+
+/// Items can only be nested this deep, so hostile data can't overflow the
+/// stack.
+const MAX_DEPTH: u64 = 128;
+
+/// Convert an IEEE 754 half-precision float.
+fn from_f16(bits: u16) -> f64 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let fraction = (bits & 0x3ff) as f64;
+
+    sign * match exponent {
+        0  => fraction * 2f64.powi(-24),
+        31 => if fraction == 0.0 { f64::INFINITY } else { f64::NAN },
+        _  => (1.0 + fraction / 1024.0) * 2f64.powi(exponent - 15),
+    }
+}
+
+/// Defines a single CBOR (RFC 8949) data item.
+///
+/// The item describes itself, so the size and children are found by reading
+/// it. Arrays, maps, and tags have children: each element is another
+/// [`CBOR`] item, and map values are named after their keys (text keys are
+/// used as-is, other keys by their display). The display is similar to CBOR's
+/// diagnostic notation - for example, `{ "a": [ 1, h'ff', 1(0) ] }`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct CBOR;
+
+impl CBOR {
+    pub fn new_aligned(alignment: Alignment) -> H2Type {
+        H2Type::new(alignment, H2Types::CBOR(Self))
+    }
+
+    pub fn new() -> H2Type {
+        Self::new_aligned(Alignment::None)
+    }
+
+    /// Walk the item, returning its size and the positions of its children -
+    /// the optional key position (for maps), and the value position.
+    ///
+    /// The `depth` is how deep the item is nested, starting at 0.
+    fn walk(context: Context, depth: u64) -> SimpleResult<(u64, Vec<(Option<u64>, u64)>)> {
+        if depth > MAX_DEPTH {
+            bail!("CBOR items are nested too deeply");
+        }
+
+        let header = Header::read(context)?;
+        let mut position = context.position() + header.size;
+        let mut children = vec![];
+
+        match header.major {
+            // Integers (and simple values / floats), which are just a header
+            0 | 1 => (),
+            7 => {
+                if header.argument.is_none() {
+                    bail!("Unexpected CBOR break");
+                }
+            },
+
+            // Byte and text strings
+            2 | 3 => match header.argument {
+                Some(length) => position = skip_bytes(context, position, length)?,
+                None => {
+                    // An indefinite-length string is a series of definite
+                    // ones of the same type, then a break
+                    while context.at(position).read_u8()? != 0xff {
+                        let chunk = Header::read(context.at(position))?;
+
+                        match (chunk.major == header.major, chunk.argument) {
+                            (true, Some(length)) => position = skip_bytes(context, position + chunk.size, length)?,
+                            _ => bail!("Invalid chunk in indefinite-length CBOR string"),
+                        }
+                    }
+
+                    position = position + 1;
+                },
+            },
+
+            // Arrays and maps
+            4 | 5 => {
+                let mut count = 0;
+
+                loop {
+                    match header.argument {
+                        Some(n) => if count == n { break; },
+                        None => if context.at(position).read_u8()? == 0xff {
+                            position = position + 1;
+                            break;
+                        },
+                    }
+
+                    let key = match header.major {
+                        5 => {
+                            let key = position;
+                            position = position + Self::walk(context.at(position), depth + 1)?.0;
+
+                            Some(key)
+                        },
+                        _ => None,
+                    };
+
+                    children.push((key, position));
+                    position = position + Self::walk(context.at(position), depth + 1)?.0;
+                    count = count + 1;
+                }
+            },
+
+            // Tags, which have one item
+            _ => {
+                children.push((None, position));
+                position = position + Self::walk(context.at(position), depth + 1)?.0;
+            },
+        };
+
+        Ok((position - context.position(), children))
+    }
+
+    /// Read the contents of a (possibly indefinite-length) string.
+    fn read_string(context: Context) -> SimpleResult<Vec<u8>> {
+        let header = Header::read(context)?;
+        let position = context.position() + header.size;
+
+        match header.argument {
+            Some(length) => read_bytes(context, position, length),
+            None => {
+                let mut position = position;
+                let mut result = vec![];
+
+                while context.at(position).read_u8()? != 0xff {
+                    let chunk = Header::read(context.at(position))?;
+                    let length = match (chunk.major == header.major, chunk.argument) {
+                        (true, Some(length)) => length,
+                        _ => bail!("Invalid chunk in indefinite-length CBOR string"),
+                    };
+
+                    result.append(&mut read_bytes(context, position + chunk.size, length)?);
+                    position = position + chunk.size + length;
+                }
+
+                Ok(result)
+            },
+        }
+    }
+
+    fn read_text(context: Context) -> SimpleResult<String> {
+        match String::from_utf8(Self::read_string(context)?) {
+            Ok(s)  => Ok(s),
+            Err(e) => bail!("Invalid UTF-8 in CBOR text string: {}", e),
+        }
+    }
+
+    fn display(context: Context, depth: u64) -> SimpleResult<String> {
+        if depth > MAX_DEPTH {
+            bail!("CBOR items are nested too deeply");
+        }
+
+        let header = Header::read(context)?;
+
+        match header.major {
+            0 => Ok(format!("{}", header.argument.unwrap_or(0))),
+            1 => Ok(format!("{}", -1 - header.argument.unwrap_or(0) as i128)),
+            2 => Ok(format!("h'{}'", to_hex(&Self::read_string(context)?, ""))),
+            3 => Ok(common::display_string(&Self::read_text(context)?, StringDisplay::default())),
+            4 | 5 => {
+                let strings = Self::walk(context, depth)?.1.into_iter().map(|(key, value)| {
+                    let value = Self::display(context.at(value), depth + 1)?;
+
+                    match key {
+                        Some(key) => Ok(format!("{}: {}", Self::display(context.at(key), depth + 1)?, value)),
+                        None      => Ok(value),
+                    }
+                }).collect::<SimpleResult<Vec<String>>>()?;
+
+                let (open, close) = match header.major {
+                    4 => ("[", "]"),
+                    _ => ("{", "}"),
+                };
+
+                match strings.len() {
+                    0 => Ok(format!("{}{}", open, close)),
+                    _ => Ok(format!("{} {} {}", open, strings.join(", "), close)),
+                }
+            },
+            6 => {
+                let (_, children) = Self::walk(context, depth)?;
+
+                Ok(format!("{}({})", header.argument.unwrap_or(0), Self::display(context.at(children[0].1), depth + 1)?))
+            },
+            _ => {
+                let argument = match header.argument {
+                    Some(argument) => argument,
+                    None           => bail!("Unexpected CBOR break"),
+                };
+
+                match header.info {
+                    20 => Ok("false".to_string()),
+                    21 => Ok("true".to_string()),
+                    22 => Ok("null".to_string()),
+                    23 => Ok("undefined".to_string()),
+                    25 => Ok(format!("{:?}", from_f16(argument as u16))),
+                    26 => Ok(format!("{:?}", f32::from_bits(argument as u32))),
+                    27 => Ok(format!("{:?}", f64::from_bits(argument))),
+                    _  => Ok(format!("simple({})", argument)),
+                }
+            },
+        }
+    }
+
+    /// The name a map key gives its value.
+    fn key_name(context: Context) -> SimpleResult<String> {
+        match Header::read(context)?.major {
+            3 => Self::read_text(context),
+            _ => Self::display(context, 0),
+        }
+    }
+}
+
+impl H2TypeTrait for CBOR {
+    fn is_static(&self) -> bool {
+        false
+    }
+
+    fn actual_size(&self, offset: Offset) -> SimpleResult<u64> {
+        Ok(Self::walk(offset.get_dynamic()?, 0)?.0)
+    }
+
+    fn to_display(&self, offset: Offset) -> SimpleResult<String> {
+        match offset {
+            Offset::Static(_) => Ok("CBOR".to_string()),
+            Offset::Dynamic(context) | Offset::Bounded(context, ..) | Offset::Windowed(context, ..) => Self::display(context, 0),
+        }
+    }
+
+    fn children(&self, offset: Offset) -> SimpleResult<Vec<(Option<String>, H2Type)>> {
+        Ok(self.children_with_range(offset)?.into_iter().map(|(_, _, name, child)| {
+            (name, child)
+        }).collect())
+    }
+
+    /// We must implement this ourselves, because map keys and tag numbers
+    /// aren't children, so the children have gaps between them
+    fn children_with_range(&self, offset: Offset) -> SimpleResult<Vec<(Range<u64>, u8, Option<String>, H2Type)>> {
+        let context = match offset {
+            Offset::Static(_) => return Ok(vec![]),
+            Offset::Dynamic(context) | Offset::Bounded(context, ..) | Offset::Windowed(context, ..) => context,
+        };
+
+        Self::walk(context, 0)?.1.into_iter().map(|(key, value)| {
+            let name = match key {
+                Some(key) => Some(Self::key_name(context.at(key))?),
+                None      => None,
+            };
+            let child = CBOR::new();
+
+            Ok((child.aligned_range(offset.at(value))?, 0, name, child))
+        }).collect()
+    }
+
+    fn can_be_string(&self) -> bool {
+        true
+    }
+
+    fn to_string(&self, offset: Offset) -> SimpleResult<String> {
+        let context = offset.get_dynamic()?;
+
+        match Header::read(context)?.major {
+            3 => Self::read_text(context),
+            _ => bail!("CBOR item isn't a text string"),
+        }
+    }
+
+    fn can_be_u64(&self) -> bool {
+        true
+    }
+
+    fn to_u64(&self, offset: Offset) -> SimpleResult<u64> {
+        let header = Header::read(offset.get_dynamic()?)?;
+
+        match (header.major, header.argument) {
+            (0, Some(n)) => Ok(n),
+            _            => bail!("CBOR item isn't an unsigned integer"),
+        }
+    }
+
+    fn can_be_i64(&self) -> bool {
+        true
+    }
+
+    fn to_i64(&self, offset: Offset) -> SimpleResult<i64> {
+        let header = Header::read(offset.get_dynamic()?)?;
+
+        let value = match (header.major, header.argument) {
+            (0, Some(n)) => n as i128,
+            (1, Some(n)) => -1 - n as i128,
+            _            => bail!("CBOR item isn't an integer"),
+        };
+
+        if value < i64::MIN as i128 || value > i64::MAX as i128 {
+            bail!("CBOR integer {} doesn't fit in an i64", value);
+        }
+
+        Ok(value as i64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+    use sized_number::{Context, SizedDefinition, SizedDisplay};
+
+    use crate::composite::H2Struct;
+    use crate::simple::H2Number;
+
+    #[test]
+    fn test_simple_items() -> SimpleResult<()> {
+        // Examples from RFC 8949, appendix A
+        let tests: Vec<(Vec<u8>, &str)> = vec![
+            (b"\x00".to_vec(),                                 "0"),
+            (b"\x17".to_vec(),                                 "23"),
+            (b"\x18\x64".to_vec(),                             "100"),
+            (b"\x1b\x00\x00\x00\xe8\xd4\xa5\x10\x00".to_vec(), "1000000000000"),
+            (b"\x20".to_vec(),                                 "-1"),
+            (b"\x39\x03\xe7".to_vec(),                         "-1000"),
+            (b"\xf9\x3e\x00".to_vec(),                         "1.5"),
+            (b"\xf9\x7c\x00".to_vec(),                         "inf"),
+            (b"\xfa\x47\xc3\x50\x00".to_vec(),                 "100000.0"),
+            (b"\xfb\x3f\xf1\x99\x99\x99\x99\x99\x9a".to_vec(), "1.1"),
+            (b"\xf4".to_vec(),                                 "false"),
+            (b"\xf6".to_vec(),                                 "null"),
+            (b"\xf8\xff".to_vec(),                             "simple(255)"),
+            (b"\x44\x01\x02\x03\x04".to_vec(),                 "h'01020304'"),
+            (b"\x64\x49\x45\x54\x46".to_vec(),                 "\"IETF\""),
+            (b"\x5f\x42\x01\x02\x43\x03\x04\x05\xff".to_vec(), "h'0102030405'"),
+            (b"\x7f\x65strea\x64ming\xff".to_vec(),            "\"streaming\""),
+            (b"\xc1\x1a\x51\x4b\x67\xb0".to_vec(),             "1(1363896240)"),
+        ];
+
+        for (data, expected) in tests {
+            let offset = Offset::Dynamic(Context::new(&data));
+
+            assert_eq!(data.len() as u64, CBOR::new().actual_size(offset)?);
+            assert_eq!(expected, CBOR::new().to_display(offset)?);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_integers() -> SimpleResult<()> {
+        let data = b"\x18\x64\x39\x03\xe7\x64IETF".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        assert_eq!(100,   CBOR::new().to_u64(offset)?);
+        assert_eq!(-1000, CBOR::new().to_i64(offset.at(2))?);
+        assert!(CBOR::new().to_u64(offset.at(2)).is_err());
+        assert_eq!("IETF", CBOR::new().to_string(offset.at(5))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_collections() -> SimpleResult<()> {
+        // {"a": 1, "b": [2, 3]}
+        let data = b"\xa2\x61\x61\x01\x61\x62\x82\x02\x03".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = CBOR::new();
+        assert_eq!(9, t.actual_size(offset)?);
+        assert_eq!("{ \"a\": 1, \"b\": [ 2, 3 ] }", t.to_display(offset)?);
+
+        let r = t.resolve(offset, None)?;
+        assert_eq!(2, r.children.len());
+        assert_eq!(Some("a".to_string()), r.children[0].field_name);
+        assert_eq!(3..4, r.children[0].actual_range);
+        assert_eq!(Some(1), r.children[0].as_u64);
+        assert_eq!(Some("b".to_string()), r.children[1].field_name);
+        assert_eq!(6..9, r.children[1].actual_range);
+        assert_eq!(2, r.children[1].children.len());
+        assert_eq!("3", r.children[1].children[1].display);
+
+        // COSE-style integer keys, in an indefinite-length map
+        let data = b"\xbf\x01\x02\x20\x01\xff".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let r = t.resolve(offset, None)?;
+        assert_eq!(6, r.actual_size());
+        assert_eq!("{ 1: 2, -1: 1 }", r.display);
+        assert_eq!(Some("-1".to_string()), r.children[1].field_name);
+
+        // Empty collections, and an indefinite-length array
+        let data = b"\x80\xa0\x9f\x01\x9f\xff\xff".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+        assert_eq!("[]", t.to_display(offset)?);
+        assert_eq!("{}", t.to_display(offset.at(1))?);
+        assert_eq!("[ 1, [] ]", t.to_display(offset.at(2))?);
+        assert_eq!(5, t.actual_size(offset.at(2))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_in_struct() -> SimpleResult<()> {
+        let data = b"\x82\x01\x02\xff".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = H2Struct::new(vec![
+            ("payload".to_string(), CBOR::new()),
+            ("trailer".to_string(), H2Number::new(SizedDefinition::U8, SizedDisplay::Hex(Default::default()))),
+        ])?;
+
+        assert_eq!(4, t.actual_size(offset)?);
+        assert_eq!("{ payload: [ 1, 2 ], trailer: 0xff }", t.to_display(offset)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_errors() -> SimpleResult<()> {
+        // Truncated, reserved, a stray break, a bad chunk, and bad UTF-8
+        for data in vec![b"\x82\x01".to_vec(), b"\x1c".to_vec(), b"\xff".to_vec(), b"\x5f\x61\x00\xff".to_vec(), b"\x62\xc3\x28".to_vec()] {
+            let offset = Offset::Dynamic(Context::new(&data));
+
+            assert!(CBOR::new().to_display(offset).is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_deep_nesting() -> SimpleResult<()> {
+        // Arrays of one element, nested too deeply (and then very deeply)
+        for depth in vec![MAX_DEPTH as usize + 1, 1000000] {
+            let mut data = vec![0x81; depth];
+            data.push(0x00);
+            let offset = Offset::Dynamic(Context::new(&data));
+
+            assert!(CBOR::new().actual_size(offset).is_err());
+            assert!(CBOR::new().to_display(offset).is_err());
+        }
+
+        // Right at the limit is fine
+        let mut data = vec![0x81; MAX_DEPTH as usize];
+        data.push(0x00);
+        let offset = Offset::Dynamic(Context::new(&data));
+        assert_eq!(data.len() as u64, CBOR::new().actual_size(offset)?);
+
+        Ok(())
+    }
+}
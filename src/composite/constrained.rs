@@ -0,0 +1,432 @@
+#[cfg(feature = "serialize")]
+use serde::{Serialize, Deserialize};
+
+use regex::Regex;
+use simple_error::{bail, SimpleResult};
+use std::ops::{Range, RangeInclusive};
+use std::sync::{Arc, RwLock};
+
+use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset};
+
+/// A value for [`Constraint::Equals`] and [`Constraint::OneOf`] to compare
+/// against.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum ConstraintValue {
+    /// Compared against [`H2Type::to_u64`].
+    Integer(u64),
+
+    /// Compared against [`H2Type::to_string`].
+    String(String),
+
+    /// Compared against the raw bytes of the field.
+    Bytes(Vec<u8>),
+}
+
+impl ConstraintValue {
+    fn describe(&self) -> String {
+        match self {
+            Self::Integer(i) => i.to_string(),
+            Self::String(s)  => format!("{:?}", s),
+            Self::Bytes(b)   => format!("\"{}\"", b.iter().map(|b| format!("\\x{:02x}", b)).collect::<String>()),
+        }
+    }
+}
+
+/// A constraint that a [`Constrained`] value must satisfy.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum Constraint {
+    /// The value must equal this.
+    Equals(ConstraintValue),
+
+    /// The value must equal one of these.
+    OneOf(Vec<ConstraintValue>),
+
+    /// The value (from [`H2Type::to_i64`]) must be in this range.
+    Range(RangeInclusive<i64>),
+
+    /// The value (from [`H2Type::to_string`]) must match this regular
+    /// expression.
+    Regex(String),
+
+    /// The value (from [`H2Type::to_u64`]) must satisfy this function. This
+    /// can't be serialized.
+    #[cfg_attr(feature = "serialize", serde(skip))]
+    Predicate(fn(u64) -> bool),
+}
+
+/// What to do when a [`Constraint`] isn't satisfied.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum ConstraintAction {
+    /// Fail, which means the value (and anything containing it) can't be
+    /// displayed or resolved.
+    Fail,
+
+    /// Record a warning on the [`crate::ResolvedType`], and carry on.
+    Warn,
+}
+
+/// Wraps another type with a [`Constraint`], such as a magic number or a
+/// range of versions.
+///
+/// Other than checking the constraint, this acts exactly like the type it
+/// wraps. Whether the constraint is satisfied is available from
+/// [`H2TypeTrait::is_valid`] (and [`crate::ResolvedType::valid`]); when it's
+/// not, the `action` decides whether that's an error or a warning (in
+/// [`crate::ResolvedType::warnings`]). The wrapped type's own validity and
+/// warnings (for example, from a [`crate::simple::Checksum`]) are kept.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct Constrained {
+    inner: Box<H2Type>,
+    constraint: Constraint,
+    action: ConstraintAction,
+
+    /// The compiled [`Constraint::Regex`], so it isn't compiled for every
+    /// value. It's compiled again the first time it's needed after being
+    /// deserialized.
+    #[cfg_attr(feature = "serialize", serde(skip))]
+    regex: Arc<RwLock<Option<Regex>>>,
+}
+
+impl Constrained {
+    pub fn new_aligned(alignment: Alignment, inner: H2Type, constraint: Constraint, action: ConstraintAction) -> SimpleResult<H2Type> {
+        let regex = match &constraint {
+            Constraint::Range(_) if !inner.can_be_i64() => bail!("Range constraints need a type that can become an i64"),
+            Constraint::Predicate(_) if !inner.can_be_u64() => bail!("Predicate constraints need a type that can become a u64"),
+            Constraint::Regex(pattern) => {
+                if !inner.can_be_string() {
+                    bail!("Regex constraints need a type that can become a string");
+                }
+
+                match Regex::new(pattern) {
+                    Ok(r)  => Some(r),
+                    Err(e) => bail!("Invalid regex: {}", e),
+                }
+            },
+            _ => None,
+        };
+
+        Ok(H2Type::new(alignment, H2Types::Constrained(Self {
+            inner: Box::new(inner),
+            constraint: constraint,
+            action: action,
+            regex: Arc::new(RwLock::new(regex)),
+        })))
+    }
+
+    pub fn new(inner: H2Type, constraint: Constraint, action: ConstraintAction) -> SimpleResult<H2Type> {
+        Self::new_aligned(Alignment::None, inner, constraint, action)
+    }
+
+    /// Get the compiled regex, compiling it if it isn't cached.
+    fn regex(&self, pattern: &str) -> SimpleResult<Regex> {
+        if let Ok(cache) = self.regex.read() {
+            if let Some(regex) = cache.as_ref() {
+                return Ok(regex.clone());
+            }
+        }
+
+        let regex = match Regex::new(pattern) {
+            Ok(r)  => r,
+            Err(e) => bail!("Invalid regex: {}", e),
+        };
+
+        if let Ok(mut cache) = self.regex.write() {
+            *cache = Some(regex.clone());
+        }
+
+        Ok(regex)
+    }
+
+    fn read_bytes(&self, offset: Offset) -> SimpleResult<Vec<u8>> {
+        let context = offset.get_dynamic()?;
+        let range: Range<u64> = self.inner.actual_range(offset)?;
+
+        range.map(|i| context.at(i).read_u8()).collect()
+    }
+
+    fn matches(&self, offset: Offset, value: &ConstraintValue) -> SimpleResult<bool> {
+        match value {
+            ConstraintValue::Integer(i) => Ok(self.inner.to_u64(offset)? == *i),
+            ConstraintValue::String(s)  => Ok(&self.inner.to_string(offset)? == s),
+            ConstraintValue::Bytes(b)   => Ok(&self.read_bytes(offset)? == b),
+        }
+    }
+
+    /// Describe the actual value, in the same form as the expected value.
+    fn describe_actual(&self, offset: Offset, like: &ConstraintValue) -> SimpleResult<String> {
+        match like {
+            ConstraintValue::Integer(_) => Ok(ConstraintValue::Integer(self.inner.to_u64(offset)?).describe()),
+            ConstraintValue::String(_)  => Ok(ConstraintValue::String(self.inner.to_string(offset)?).describe()),
+            ConstraintValue::Bytes(_)   => Ok(ConstraintValue::Bytes(self.read_bytes(offset)?).describe()),
+        }
+    }
+
+    /// Check the constraint, returning a description of the problem if it
+    /// isn't satisfied.
+    fn check(&self, offset: Offset) -> SimpleResult<Option<String>> {
+        match &self.constraint {
+            Constraint::Equals(expected) => {
+                match self.matches(offset, expected)? {
+                    true  => Ok(None),
+                    false => Ok(Some(format!("expected {}, found {}", expected.describe(), self.describe_actual(offset, expected)?))),
+                }
+            },
+            Constraint::OneOf(options) => {
+                for option in options {
+                    if self.matches(offset, option)? {
+                        return Ok(None);
+                    }
+                }
+
+                let actual = match options.first() {
+                    Some(first) => self.describe_actual(offset, first)?,
+                    None        => self.inner.to_display(offset)?,
+                };
+
+                Ok(Some(format!("{} isn't one of [ {} ]", actual, options.iter().map(|o| o.describe()).collect::<Vec<String>>().join(", "))))
+            },
+            Constraint::Range(range) => {
+                let value = self.inner.to_i64(offset)?;
+
+                match range.contains(&value) {
+                    true  => Ok(None),
+                    false => Ok(Some(format!("{} isn't in the range {}..={}", value, range.start(), range.end()))),
+                }
+            },
+            Constraint::Regex(pattern) => {
+                let regex = self.regex(pattern)?;
+                let value = self.inner.to_string(offset)?;
+
+                match regex.is_match(&value) {
+                    true  => Ok(None),
+                    false => Ok(Some(format!("{:?} doesn't match /{}/", value, pattern))),
+                }
+            },
+            Constraint::Predicate(predicate) => {
+                let value = self.inner.to_u64(offset)?;
+
+                match predicate(value) {
+                    true  => Ok(None),
+                    false => Ok(Some(format!("{} doesn't satisfy the predicate", value))),
+                }
+            },
+        }
+    }
+}
+
+impl H2TypeTrait for Constrained {
+    fn is_static(&self) -> bool {
+        self.inner.is_static()
+    }
+
+    fn actual_size(&self, offset: Offset) -> SimpleResult<u64> {
+        self.inner.actual_size(offset)
+    }
+
+    fn is_bit_sized(&self) -> bool {
+        self.inner.is_bit_sized()
+    }
+
+    fn bit_size(&self, offset: Offset) -> SimpleResult<u64> {
+        self.inner.bit_size(offset)
+    }
+
+    fn bit_ranges(&self, offset: Offset) -> SimpleResult<Option<Vec<Range<u64>>>> {
+        self.inner.bit_ranges(offset)
+    }
+
+    fn to_display(&self, offset: Offset) -> SimpleResult<String> {
+        if let Offset::Dynamic(_) | Offset::Bounded(..) | Offset::Windowed(..) = offset {
+            if self.action == ConstraintAction::Fail {
+                if let Some(problem) = self.check(offset)? {
+                    bail!("Constraint failed: {}", problem);
+                }
+            }
+        }
+
+        self.inner.to_display(offset)
+    }
+
+    fn related(&self, offset: Offset) -> SimpleResult<Vec<(u64, H2Type)>> {
+        self.inner.related(offset)
+    }
+
+    fn children(&self, offset: Offset) -> SimpleResult<Vec<(Option<String>, H2Type)>> {
+        self.inner.children(offset)
+    }
+
+    fn children_with_range(&self, offset: Offset) -> SimpleResult<Vec<(Range<u64>, u8, Option<String>, H2Type)>> {
+        self.inner.children_with_range(offset)
+    }
+
+    fn is_valid(&self, offset: Offset) -> SimpleResult<bool> {
+        // The inner type might not have anything to validate
+        match self.inner.is_valid(offset) {
+            Ok(false) => Ok(false),
+            _         => Ok(self.check(offset)?.is_none()),
+        }
+    }
+
+    fn warnings(&self, offset: Offset) -> SimpleResult<Vec<String>> {
+        let mut warnings = self.inner.warnings(offset)?;
+
+        if let (ConstraintAction::Warn, Some(problem)) = (self.action, self.check(offset)?) {
+            warnings.push(format!("Constraint failed: {}", problem));
+        }
+
+        Ok(warnings)
+    }
+
+    fn can_be_char(&self) -> bool {
+        self.inner.can_be_char()
+    }
+
+    fn to_char(&self, offset: Offset) -> SimpleResult<char> {
+        self.inner.to_char(offset)
+    }
+
+    fn can_be_string(&self) -> bool {
+        self.inner.can_be_string()
+    }
+
+    fn to_string(&self, offset: Offset) -> SimpleResult<String> {
+        self.inner.to_string(offset)
+    }
+
+    fn can_be_u64(&self) -> bool {
+        self.inner.can_be_u64()
+    }
+
+    fn to_u64(&self, offset: Offset) -> SimpleResult<u64> {
+        self.inner.to_u64(offset)
+    }
+
+    fn can_be_i64(&self) -> bool {
+        self.inner.can_be_i64()
+    }
+
+    fn to_i64(&self, offset: Offset) -> SimpleResult<i64> {
+        self.inner.to_i64(offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+    use sized_number::{Context, Endian, SizedDefinition, SizedDisplay};
+
+    use crate::composite::H2Struct;
+    use crate::composite::string::H2String;
+    use crate::simple::{Checksum, ChecksumAlgorithm, ChecksumRange, H2Number};
+    use crate::simple::character::{ASCII, StrictASCII};
+
+    fn header(action: ConstraintAction) -> SimpleResult<H2Type> {
+        H2Struct::new(vec![
+            ("magic".to_string(), Constrained::new(
+                H2Number::new(SizedDefinition::U32(Endian::Big), SizedDisplay::Hex(Default::default())),
+                Constraint::Equals(ConstraintValue::Bytes(b"\x7fELF".to_vec())),
+                action,
+            )?),
+            ("version".to_string(), Constrained::new(
+                H2Number::new(SizedDefinition::U8, SizedDisplay::Decimal),
+                Constraint::Range(1..=3),
+                action,
+            )?),
+        ])
+    }
+
+    #[test]
+    fn test_fail() -> SimpleResult<()> {
+        let data = b"\x7fELF\x02\x7fELF\x04\x7fELG\x01".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+        let t = header(ConstraintAction::Fail)?;
+
+        let r = t.resolve(offset, None)?;
+        assert_eq!("{ magic: 0x7f454c46, version: 2 }", r.display);
+        assert_eq!(Some(true), r.children[0].valid);
+        assert_eq!(Some(true), r.children[1].valid);
+        assert_eq!(0, r.children[1].warnings.len());
+
+        // Bad version, then bad magic
+        assert!(t.resolve(offset.at(5), None).is_err());
+        assert!(t.resolve(offset.at(10), None).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_warn() -> SimpleResult<()> {
+        let data = b"\x7fELG\x04".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+        let t = header(ConstraintAction::Warn)?;
+
+        let r = t.resolve(offset, None)?;
+        assert_eq!("{ magic: 0x7f454c47, version: 4 }", r.display);
+
+        assert_eq!(Some(false), r.children[0].valid);
+        assert_eq!(vec!["Constraint failed: expected \"\\x7f\\x45\\x4c\\x46\", found \"\\x7f\\x45\\x4c\\x47\"".to_string()], r.children[0].warnings);
+
+        assert_eq!(Some(false), r.children[1].valid);
+        assert_eq!(vec!["Constraint failed: 4 isn't in the range 1..=3".to_string()], r.children[1].warnings);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_one_of_and_predicate() -> SimpleResult<()> {
+        let data = b"\x01\x02\x03".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+        let number = H2Number::new(SizedDefinition::U8, SizedDisplay::Decimal);
+
+        let t = Constrained::new(number.clone(), Constraint::OneOf(vec![ConstraintValue::Integer(1), ConstraintValue::Integer(3)]), ConstraintAction::Warn)?;
+        assert_eq!(Some(true),  t.resolve(offset.at(0), None)?.valid);
+        assert_eq!(Some(false), t.resolve(offset.at(1), None)?.valid);
+        assert_eq!(vec!["Constraint failed: 2 isn't one of [ 1, 3 ]".to_string()], t.resolve(offset.at(1), None)?.warnings);
+        assert_eq!(Some(true),  t.resolve(offset.at(2), None)?.valid);
+
+        let t = Constrained::new(number.clone(), Constraint::Predicate(|v| v % 2 == 1), ConstraintAction::Fail)?;
+        assert_eq!("1", t.to_display(offset.at(0))?);
+        assert!(t.to_display(offset.at(1)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_regex() -> SimpleResult<()> {
+        let data = b"v1.2xyz!".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+        let string = H2String::new(4, ASCII::new(StrictASCII::Strict))?;
+
+        let t = Constrained::new(string, Constraint::Regex("^v[0-9]+\\.[0-9]+$".to_string()), ConstraintAction::Warn)?;
+        assert_eq!(Some(true),  t.resolve(offset, None)?.valid);
+        assert_eq!(Some(false), t.resolve(offset.at(4), None)?.valid);
+        assert_eq!(vec!["Constraint failed: \"xyz!\" doesn't match /^v[0-9]+\\.[0-9]+$/".to_string()], t.resolve(offset.at(4), None)?.warnings);
+
+        // Bad regexes and types are caught early
+        assert!(Constrained::new(H2Number::new(SizedDefinition::U8, SizedDisplay::Decimal), Constraint::Regex(".*".to_string()), ConstraintAction::Warn).is_err());
+        assert!(Constrained::new(H2String::new(4, ASCII::new(StrictASCII::Strict))?, Constraint::Regex("(".to_string()), ConstraintAction::Warn).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_inner_validity() -> SimpleResult<()> {
+        // A CRC-32 of "123456789", then a bad one; the constraint is always
+        // satisfied, but the checksum isn't
+        let data = b"123456789\xcb\xf4\x39\x26\xcb\xf4\x39\x27".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let checksum = Checksum::new(SizedDefinition::U32(Endian::Big), ChecksumAlgorithm::CRC32, ChecksumRange::Absolute(0..9))?;
+        let t = Constrained::new(checksum, Constraint::Predicate(|_| true), ConstraintAction::Warn)?;
+
+        assert_eq!(Some(true), t.resolve(offset.at(9), None)?.valid);
+        assert_eq!(Some(false), t.resolve(offset.at(13), None)?.valid);
+
+        Ok(())
+    }
+}
@@ -44,6 +44,10 @@ impl H2TypeTrait for H2Array {
         self.field_type.is_static()
     }
 
+    fn is_bit_sized(&self) -> bool {
+        self.field_type.is_bit_sized()
+    }
+
     fn children(&self, _offset: Offset) -> SimpleResult<Vec<(Option<String>, H2Type)>> {
         // Just clone the child type over and over
         Ok((0..self.length).into_iter().map(|_index| {
@@ -54,8 +58,8 @@ impl H2TypeTrait for H2Array {
     fn to_display(&self, offset: Offset) -> SimpleResult<String> {
         // Because the collect() expects a result, this will end and bubble
         // up errors automatically!
-        let strings: Vec<String> = self.children_with_range(offset)?.iter().map(|(range, _name, child)| {
-            child.to_display(offset.at(range.start))
+        let strings: Vec<String> = self.children_with_range(offset)?.iter().map(|(range, bit, _name, child)| {
+            child.to_display(offset.at_bit(range.start, *bit))
         }).collect::<SimpleResult<Vec<String>>>()?;
 
         Ok(format!("[ {} ]", strings.join(", ")))
@@ -66,8 +70,9 @@ impl H2TypeTrait for H2Array {
 mod tests {
     use super::*;
     use simple_error::SimpleResult;
-    use sized_number::Context;
+    use sized_number::{Context, SizedDisplay};
 
+    use crate::simple::{BitNumber, BitOrder};
     use crate::simple::character::{ASCII, UTF8, StrictASCII};
 
     #[test]
@@ -304,4 +309,24 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_bit_array() -> SimpleResult<()> {
+        let data = b"\xe4\x80".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        // Five 2-bit elements; the last one is in the second byte
+        let a = H2Array::new(5, BitNumber::new(2, false, BitOrder::MSBFirst, SizedDisplay::Decimal)?)?;
+        assert!(a.is_bit_sized());
+        assert_eq!(10, a.bit_size(offset)?);
+        assert_eq!(2, a.actual_size(offset)?);
+        assert_eq!("[ 3, 2, 1, 0, 2 ]", a.to_display(offset)?);
+
+        let r = a.resolve(offset, None)?;
+        assert_eq!(Some(vec![0..10]), r.bit_ranges);
+        assert_eq!(1..2, r.children[4].actual_range);
+        assert_eq!(Some(vec![8..10]), r.children[4].bit_ranges);
+
+        Ok(())
+    }
 }
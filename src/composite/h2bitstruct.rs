@@ -0,0 +1,198 @@
+#[cfg(feature = "serialize")]
+use serde::{Serialize, Deserialize};
+
+use simple_error::{bail, SimpleResult};
+
+use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset};
+use crate::simple::BitNumber;
+
+/// Defines a struct of fields that are packed at the bit level, such as a
+/// 3-bit version followed by a 13-bit length.
+///
+/// Every field is a [`crate::simple::BitNumber`], and they're packed one
+/// after another with no regard for byte boundaries. Unlike a
+/// [`crate::composite::H2Struct`] of bit numbers, which can start and end
+/// partway through a byte, a bit struct is made of whole bytes: it always
+/// starts on a byte boundary, and if the fields don't add up to a multiple
+/// of 8 bits, the rest of the last byte is padding.
+///
+/// Fields that share a byte have overlapping (byte) ranges, so each child's
+/// [`crate::ResolvedType::bit_ranges`] is where to find its exact bits.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct H2BitStruct {
+    fields: Vec<(String, BitNumber)>,
+}
+
+impl H2BitStruct {
+    pub fn new_aligned(alignment: Alignment, fields: Vec<(String, H2Type)>) -> SimpleResult<H2Type> {
+        if fields.len() == 0 {
+            bail!("Bit structs must contain at least one field");
+        }
+
+        let fields = fields.into_iter().map(|(name, field_type)| {
+            if !matches!(field_type.alignment, Alignment::None) {
+                bail!("Field {} in a bit struct can't be aligned", name);
+            }
+
+            match field_type.field {
+                H2Types::BitNumber(n) => Ok((name, n)),
+                _ => bail!("Field {} in a bit struct must be a BitNumber", name),
+            }
+        }).collect::<SimpleResult<Vec<_>>>()?;
+
+        // Mixing orders doesn't make sense, since they number the bits
+        // differently
+        if fields.iter().any(|(_, n)| n.order() != fields[0].1.order()) {
+            bail!("Fields in a bit struct must all have the same bit order");
+        }
+
+        Ok(H2Type::new(alignment, H2Types::H2BitStruct(Self {
+            fields: fields,
+        })))
+    }
+
+    pub fn new(fields: Vec<(String, H2Type)>) -> SimpleResult<H2Type> {
+        Self::new_aligned(Alignment::None, fields)
+    }
+
+    fn total_bits(&self) -> u64 {
+        self.fields.iter().map(|(_, n)| n.width() as u64).sum()
+    }
+}
+
+impl H2TypeTrait for H2BitStruct {
+    fn is_static(&self) -> bool {
+        true
+    }
+
+    // Round the bits up to the next byte
+    fn actual_size(&self, _offset: Offset) -> SimpleResult<u64> {
+        Ok((self.total_bits() + 7) / 8)
+    }
+
+    fn children(&self, _offset: Offset) -> SimpleResult<Vec<(Option<String>, H2Type)>> {
+        Ok(self.fields.iter().map(|(name, field)| {
+            (Some(name.clone()), H2Type::new(Alignment::None, H2Types::BitNumber(field.clone())))
+        }).collect())
+    }
+
+    fn to_display(&self, offset: Offset) -> SimpleResult<String> {
+        let strings: Vec<String> = self.children_with_range(offset)?.iter().map(|(range, bit, name, child)| {
+            Ok(format!("{}: {}", name.clone().unwrap_or("<name unknown>".to_string()), child.to_display(offset.at_bit(range.start, *bit))?))
+        }).collect::<SimpleResult<Vec<String>>>()?;
+
+        Ok(format!("{{ {} }}", strings.join(", ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+    use sized_number::{Context, SizedDisplay};
+
+    use crate::composite::H2Struct;
+    use crate::simple::{BitOrder, H2Bytes, BytesLength, BytesDisplay};
+
+    #[test]
+    fn test_bit_struct() -> SimpleResult<()> {
+        // A 3-bit version (5) and a 13-bit length (0x1234)
+        let data = b"\xb2\x34".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = H2BitStruct::new(vec![
+            ("version".to_string(), BitNumber::new(3, false, BitOrder::MSBFirst, SizedDisplay::Decimal)?),
+            ("length".to_string(), BitNumber::new(13, false, BitOrder::MSBFirst, SizedDisplay::Decimal)?),
+        ])?;
+
+        assert_eq!(2, t.actual_size(offset)?);
+        assert_eq!("{ version: 5, length: 4660 }", t.to_display(offset)?);
+
+        let r = t.resolve(offset, None)?;
+        assert_eq!(0..2, r.actual_range);
+        assert_eq!(None, r.bit_ranges);
+
+        assert_eq!(0..1, r.children[0].actual_range);
+        assert_eq!(Some(vec![0..3]), r.children[0].bit_ranges);
+        assert_eq!(Some(5), r.children[0].as_u64);
+
+        assert_eq!(0..2, r.children[1].actual_range);
+        assert_eq!(Some(vec![3..16]), r.children[1].bit_ranges);
+        assert_eq!(Some(0x1234), r.children[1].as_u64);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_h264_nal_header() -> SimpleResult<()> {
+        // An H.264 NAL unit header (0x67 is a sequence parameter set),
+        // followed by the profile, in a regular struct
+        let data = b"\x67\x42".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = H2Struct::new(vec![
+            ("header".to_string(), H2BitStruct::new(vec![
+                ("forbidden_zero_bit".to_string(), BitNumber::new(1, false, BitOrder::MSBFirst, SizedDisplay::Decimal)?),
+                ("nal_ref_idc".to_string(), BitNumber::new(2, false, BitOrder::MSBFirst, SizedDisplay::Decimal)?),
+                ("nal_unit_type".to_string(), BitNumber::new(5, false, BitOrder::MSBFirst, SizedDisplay::Decimal)?),
+            ])?),
+            ("profile_idc".to_string(), H2Bytes::new(BytesLength::Fixed(1), BytesDisplay::Hex)?),
+        ])?;
+
+        let r = t.resolve(offset, None)?;
+        let header = &r.children[0];
+
+        assert_eq!(vec![Some(0), Some(3), Some(7)], header.children.iter().map(|c| c.as_u64).collect::<Vec<_>>());
+        assert_eq!(vec![Some(vec![0..1]), Some(vec![1..3]), Some(vec![3..8])], header.children.iter().map(|c| c.bit_ranges.clone()).collect::<Vec<_>>());
+        assert_eq!(1..2, r.children[1].actual_range);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lsb_first() -> SimpleResult<()> {
+        // A little-endian CAN signal layout: a 4-bit mode, a signed 12-bit
+        // value that crosses into the next byte, and a 1-bit flag; the last 7
+        // bits are padding
+        let data = b"\x3a\xff\x01".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = H2BitStruct::new(vec![
+            ("mode".to_string(), BitNumber::new(4, false, BitOrder::LSBFirst, SizedDisplay::Decimal)?),
+            ("value".to_string(), BitNumber::new(12, true, BitOrder::LSBFirst, SizedDisplay::Decimal)?),
+            ("flag".to_string(), BitNumber::new(1, false, BitOrder::LSBFirst, SizedDisplay::Decimal)?),
+        ])?;
+
+        assert_eq!(3, t.actual_size(offset)?);
+
+        // The bits are numbered from the most significant bit of each byte,
+        // like the MSB-first fields: the mode is the bottom half of the first
+        // byte, and the value is the top half and all of the second
+        let r = t.resolve(offset, None)?;
+        assert_eq!(Some(10), r.children[0].as_u64);
+        assert_eq!(Some(vec![4..8]), r.children[0].bit_ranges);
+        assert_eq!(Some(-13), r.children[1].as_i64);
+        assert_eq!(0..2, r.children[1].actual_range);
+        assert_eq!(Some(vec![0..4, 8..16]), r.children[1].bit_ranges);
+        assert_eq!(Some(1), r.children[2].as_u64);
+        assert_eq!(Some(vec![23..24]), r.children[2].bit_ranges);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_fields() -> SimpleResult<()> {
+        // Empty, not a bit number, and mixed orders
+        assert!(H2BitStruct::new(vec![]).is_err());
+        assert!(H2BitStruct::new(vec![
+            ("bytes".to_string(), H2Bytes::new(BytesLength::Fixed(1), BytesDisplay::Hex)?),
+        ]).is_err());
+        assert!(H2BitStruct::new(vec![
+            ("a".to_string(), BitNumber::new(4, false, BitOrder::MSBFirst, SizedDisplay::Decimal)?),
+            ("b".to_string(), BitNumber::new(4, false, BitOrder::LSBFirst, SizedDisplay::Decimal)?),
+        ]).is_err());
+
+        Ok(())
+    }
+}
@@ -55,6 +55,18 @@ impl H2TypeTrait for H2Enum {
         })
     }
 
+    // An enum of bit fields can start partway through a byte
+    fn is_bit_sized(&self) -> bool {
+        self.variants.iter().all(|(_, t)| t.is_bit_sized())
+    }
+
+    // The variants overlap, so it's the longest one
+    fn bit_size(&self, offset: Offset) -> SimpleResult<u64> {
+        self.variants.iter().try_fold(0, |size, (_, t)| {
+            Ok(cmp::max(t.bit_size(offset)?, size))
+        })
+    }
+
     fn children(&self, _offset: Offset) -> SimpleResult<Vec<(Option<String>, H2Type)>> {
         Ok(self.variants.iter().map(|(name, field_type)| {
             (Some(name.clone()), field_type.clone())
@@ -63,15 +75,15 @@ impl H2TypeTrait for H2Enum {
 
     /// We must implement this ourselves, because all children will start at the
     /// same offset (instead of being sequential)
-    fn children_with_range(&self, offset: Offset) -> SimpleResult<Vec<(Range<u64>, Option<String>, H2Type)>> {
+    fn children_with_range(&self, offset: Offset) -> SimpleResult<Vec<(Range<u64>, u8, Option<String>, H2Type)>> {
         self.variants.iter().map(|(name, field_type)| {
-            Ok((field_type.aligned_range(offset)?, Some(name.clone()), field_type.clone()))
+            Ok((field_type.aligned_range(offset)?, offset.bit(), Some(name.clone()), field_type.clone()))
         }).collect::<SimpleResult<Vec<_>>>()
     }
 
     fn to_display(&self, offset: Offset) -> SimpleResult<String> {
-        let strings: Vec<String> = self.children_with_range(offset)?.into_iter().map(|(range, name, child)| {
-            Ok(format!("{}: {}", name.unwrap_or("<name unknown>".to_string()), child.to_display(offset.at(range.start))?))
+        let strings: Vec<String> = self.children_with_range(offset)?.into_iter().map(|(range, bit, name, child)| {
+            Ok(format!("{}: {}", name.unwrap_or("<name unknown>".to_string()), child.to_display(offset.at_bit(range.start, bit))?))
         }).collect::<SimpleResult<Vec<String>>>()?;
 
         Ok(format!("{{ {} }}", strings.join(" | ")))
@@ -2,8 +2,10 @@
 use serde::{Serialize, Deserialize};
 
 use simple_error::{bail, SimpleResult};
+use std::ops::Range;
 
 use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset};
+use crate::h2typetrait::consecutive_children;
 
 /// Defines a struct.
 ///
@@ -29,6 +31,35 @@ impl H2Struct {
     pub fn new(fields: Vec<(String, H2Type)>) -> SimpleResult<H2Type> {
         Self::new_aligned(Alignment::None, fields)
     }
+
+    fn raw_children(&self) -> Vec<(Option<String>, H2Type)> {
+        self.fields.iter().map(|(name, field_type)| {
+            (Some(name.clone()), field_type.clone())
+        }).collect()
+    }
+
+    fn has_checksum(&self) -> bool {
+        self.fields.iter().any(|(_, t)| matches!(t.field, H2Types::Checksum(_)))
+    }
+
+    /// Lay out the fields, then point any [`crate::simple::Checksum`] over
+    /// other fields at where those fields ended up.
+    fn fields_with_range(&self, offset: Offset) -> SimpleResult<Vec<(Range<u64>, u8, Option<String>, H2Type)>> {
+        let fields = consecutive_children(offset, self.raw_children())?;
+
+        if !self.has_checksum() {
+            return Ok(fields);
+        }
+
+        let ranges: Vec<(Range<u64>, Option<String>)> = fields.iter().map(|(range, _, name, _)| (range.clone(), name.clone())).collect();
+
+        fields.into_iter().map(|(range, bit, name, t)| {
+            match &t.field {
+                H2Types::Checksum(c) => Ok((range, bit, name, H2Type::new(t.alignment, H2Types::Checksum(c.bind(&ranges)?)))),
+                _                    => Ok((range, bit, name, t)),
+            }
+        }).collect()
+    }
 }
 
 impl H2TypeTrait for H2Struct {
@@ -41,17 +72,31 @@ impl H2TypeTrait for H2Struct {
         }).is_none()
     }
 
-    fn children(&self, _offset: Offset) -> SimpleResult<Vec<(Option<String>, H2Type)>> {
-        Ok(self.fields.iter().map(|(name, field_type)| {
-            (Some(name.clone()), field_type.clone())
+    // A struct of bit fields can start partway through a byte
+    fn is_bit_sized(&self) -> bool {
+        self.fields.iter().all(|(_, t)| t.is_bit_sized())
+    }
+
+    fn children(&self, offset: Offset) -> SimpleResult<Vec<(Option<String>, H2Type)>> {
+        // Checksums need to know where the other fields are
+        if !self.has_checksum() {
+            return Ok(self.raw_children());
+        }
+
+        Ok(self.fields_with_range(offset)?.into_iter().map(|(_, _, name, field_type)| {
+            (name, field_type)
         }).collect())
     }
 
+    fn children_with_range(&self, offset: Offset) -> SimpleResult<Vec<(Range<u64>, u8, Option<String>, H2Type)>> {
+        self.fields_with_range(offset)
+    }
+
     fn to_display(&self, offset: Offset) -> SimpleResult<String> {
         // Because the collect() expects a result, this will end and bubble
         // up errors automatically!
-        let strings: Vec<String> = self.children_with_range(offset)?.iter().map(|(range, name, child)| {
-            Ok(format!("{}: {}", name.clone().unwrap_or("<name unknown>".to_string()), child.to_display(offset.at(range.start))?))
+        let strings: Vec<String> = self.children_with_range(offset)?.iter().map(|(range, bit, name, child)| {
+            Ok(format!("{}: {}", name.clone().unwrap_or("<name unknown>".to_string()), child.to_display(offset.at_bit(range.start, *bit))?))
         }).collect::<SimpleResult<Vec<String>>>()?;
 
         Ok(format!("{{ {} }}", strings.join(", ")))
@@ -64,10 +109,11 @@ mod tests {
     use simple_error::SimpleResult;
     use sized_number::{Context, SizedDefinition, SizedDisplay, Endian};
 
-    use crate::simple::H2Number;
+    use crate::simple::{BitNumber, BitOrder, BytesDisplay, BytesLength, H2Bytes, H2Number};
     use crate::simple::network::IPv4;
     use crate::simple::character::{ASCII, StrictASCII};
-    use crate::composite::H2Array;
+    use crate::composite::{H2Array, H2Enum};
+    use crate::composite::string::LPString;
 
     #[test]
     fn test_struct() -> SimpleResult<()> {
@@ -241,4 +287,80 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_bounded_struct() -> SimpleResult<()> {
+        // A record that's four bytes long, followed by the next record
+        let data = b"\x05hello\x01\x02".to_vec();
+        let offset = Offset::from(Context::new(&data));
+
+        let t = H2Struct::new(vec![
+            ("string".to_string(), LPString::new(H2Number::new(SizedDefinition::U8, SizedDisplay::Decimal), ASCII::new(StrictASCII::Permissive))?),
+        ])?;
+
+        // Unbounded, the string reads into the next record; bounded, that's
+        // an error
+        assert_eq!("hello", t.resolve(offset, None)?.children[0].as_string.clone().unwrap());
+        assert!(t.resolve(offset.bounded(0..4)?, None).is_err());
+        assert!(t.actual_size(offset.bounded(0..4)?).is_err());
+
+        // The rest of a bounded offset is the rest of the bounds
+        let t = H2Struct::new(vec![
+            ("length".to_string(), H2Number::new(SizedDefinition::U8, SizedDisplay::Decimal)),
+            ("rest".to_string(),   H2Bytes::new(BytesLength::Remaining, BytesDisplay::Hex)?),
+        ])?;
+
+        let r = t.resolve(offset.bounded(0..4)?, None)?;
+        assert_eq!(0..4, r.actual_range);
+        assert_eq!(1..4, r.children[1].actual_range);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bit_fields() -> SimpleResult<()> {
+        let data = b"\x12\x34\x56\x78".to_vec();
+        let offset = Offset::from(Context::new(&data));
+
+        let bits = |width| BitNumber::new(width, false, BitOrder::MSBFirst, SizedDisplay::Decimal);
+
+        // A byte, then bit fields that start partway through the second byte
+        // and carry on through a nested struct, then a byte that starts at
+        // the next byte boundary
+        let t = H2Struct::new(vec![
+            ("byte".to_string(), H2Number::new(SizedDefinition::U8, SizedDisplay::Decimal)),
+            ("high".to_string(), bits(3)?),
+            ("nested".to_string(), H2Struct::new(vec![
+                ("a".to_string(), bits(7)?),
+                ("b".to_string(), H2Enum::new(vec![
+                    ("two".to_string(), bits(2)?),
+                    ("one".to_string(), bits(1)?),
+                ])?),
+            ])?),
+            ("after".to_string(), H2Number::new(SizedDefinition::U8, SizedDisplay::Decimal)),
+        ])?;
+
+        assert!(!t.is_bit_sized());
+        assert_eq!(4, t.actual_size(offset)?);
+        assert_eq!("{ byte: 18, high: 1, nested: { a: 81, b: { two: 1 | one: 0 } }, after: 120 }", t.to_display(offset)?);
+
+        let r = t.resolve(offset, None)?;
+        assert_eq!(None, r.bit_ranges);
+        assert_eq!(1..2, r.children[1].actual_range);
+        assert_eq!(Some(vec![8..11]), r.children[1].bit_ranges);
+
+        // The nested struct covers the bits of its fields
+        let nested = &r.children[2];
+        assert_eq!(1..3, nested.actual_range);
+        assert_eq!(Some(vec![11..20]), nested.bit_ranges);
+        assert_eq!(Some(81), nested.children[0].as_u64);
+        assert_eq!(Some(vec![18..20]), nested.children[1].bit_ranges);
+        assert_eq!(Some(vec![18..20]), nested.children[1].children[0].bit_ranges);
+        assert_eq!(Some(vec![18..19]), nested.children[1].children[1].bit_ranges);
+
+        assert_eq!(3..4, r.children[3].actual_range);
+        assert_eq!(Some(0x78), r.children[3].as_u64);
+
+        Ok(())
+    }
 }
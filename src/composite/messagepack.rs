@@ -0,0 +1,434 @@
+#[cfg(feature = "serialize")]
+use serde::{Serialize, Deserialize};
+
+use simple_error::{bail, SimpleResult};
+use sized_number::{Context, Endian};
+use std::convert::TryInto;
+use std::ops::Range;
+
+use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset};
+use crate::bytes::{read_bytes, skip_bytes, to_hex};
+use crate::simple::character::common::{self, StringDisplay};
+use crate::simple::time::common::format_iso8601;
+
+/// What a MessagePack item is, along with its value or length.
+enum Kind {
+    Integer(i128),
+    Nil,
+    Boolean(bool),
+    Float32(f32),
+    Float64(f64),
+    String(u64),
+    Binary(u64),
+    Array(u64),
+    Map(u64),
+    Extension(i8, u64),
+}
+
+/// The format byte of a MessagePack item, plus any length, type, or value
+/// that follows it.
+struct Header {
+    kind: Kind,
+    size: u64,
+}
+
+impl Header {
+    fn read(context: Context) -> SimpleResult<Self> {
+        let b = context.read_u8()?;
+        let following = |n: u64| context.at(context.position() + n);
+
+        let (kind, size) = match b {
+            0x00..=0x7f => (Kind::Integer(b as i128), 1),
+            0x80..=0x8f => (Kind::Map((b & 0x0f) as u64), 1),
+            0x90..=0x9f => (Kind::Array((b & 0x0f) as u64), 1),
+            0xa0..=0xbf => (Kind::String((b & 0x1f) as u64), 1),
+            0xc0        => (Kind::Nil, 1),
+            0xc1        => bail!("Invalid MessagePack format: 0xc1"),
+            0xc2        => (Kind::Boolean(false), 1),
+            0xc3        => (Kind::Boolean(true), 1),
+            0xc4        => (Kind::Binary(following(1).read_u8()? as u64), 2),
+            0xc5        => (Kind::Binary(following(1).read_u16(Endian::Big)? as u64), 3),
+            0xc6        => (Kind::Binary(following(1).read_u32(Endian::Big)? as u64), 5),
+            0xc7        => (Kind::Extension(following(2).read_u8()? as i8, following(1).read_u8()? as u64), 3),
+            0xc8        => (Kind::Extension(following(3).read_u8()? as i8, following(1).read_u16(Endian::Big)? as u64), 4),
+            0xc9        => (Kind::Extension(following(5).read_u8()? as i8, following(1).read_u32(Endian::Big)? as u64), 6),
+            0xca        => (Kind::Float32(f32::from_bits(following(1).read_u32(Endian::Big)?)), 5),
+            0xcb        => (Kind::Float64(f64::from_bits(following(1).read_u64(Endian::Big)?)), 9),
+            0xcc        => (Kind::Integer(following(1).read_u8()? as i128), 2),
+            0xcd        => (Kind::Integer(following(1).read_u16(Endian::Big)? as i128), 3),
+            0xce        => (Kind::Integer(following(1).read_u32(Endian::Big)? as i128), 5),
+            0xcf        => (Kind::Integer(following(1).read_u64(Endian::Big)? as i128), 9),
+            0xd0        => (Kind::Integer(following(1).read_u8()? as i8 as i128), 2),
+            0xd1        => (Kind::Integer(following(1).read_u16(Endian::Big)? as i16 as i128), 3),
+            0xd2        => (Kind::Integer(following(1).read_u32(Endian::Big)? as i32 as i128), 5),
+            0xd3        => (Kind::Integer(following(1).read_u64(Endian::Big)? as i64 as i128), 9),
+            0xd4..=0xd8 => (Kind::Extension(following(1).read_u8()? as i8, 1 << (b - 0xd4)), 2),
+            0xd9        => (Kind::String(following(1).read_u8()? as u64), 2),
+            0xda        => (Kind::String(following(1).read_u16(Endian::Big)? as u64), 3),
+            0xdb        => (Kind::String(following(1).read_u32(Endian::Big)? as u64), 5),
+            0xdc        => (Kind::Array(following(1).read_u16(Endian::Big)? as u64), 3),
+            0xdd        => (Kind::Array(following(1).read_u32(Endian::Big)? as u64), 5),
+            0xde        => (Kind::Map(following(1).read_u16(Endian::Big)? as u64), 3),
+            0xdf        => (Kind::Map(following(1).read_u32(Endian::Big)? as u64), 5),
+            0xe0..=0xff => (Kind::Integer(b as i8 as i128), 1),
+        };
+
+        Ok(Self {
+            kind: kind,
+            size: size,
+        })
+    }
+}
+
+/// Items can only be nested this deep, so hostile data can't overflow the
+/// stack.
+const MAX_DEPTH: u64 = 128;
+
+/// Decode the timestamp extension (type -1), which comes in 32-, 64-, and
+/// 96-bit versions.
+fn decode_timestamp(data: &[u8]) -> Option<String> {
+    let (seconds, nanoseconds) = match data.len() {
+        4 => (u32::from_be_bytes(data.try_into().ok()?) as i64, 0),
+        8 => {
+            let value = u64::from_be_bytes(data.try_into().ok()?);
+
+            ((value & 0x3_ffff_ffff) as i64, (value >> 34) as u32)
+        },
+        12 => (i64::from_be_bytes(data[4..].try_into().ok()?), u32::from_be_bytes(data[..4].try_into().ok()?)),
+        _ => return None,
+    };
+
+    if nanoseconds >= 1_000_000_000 {
+        return None;
+    }
+
+    format_iso8601(seconds, nanoseconds)
+}
+
+/// Defines a single MessagePack item.
+///
+/// This works the same way as [`crate::composite::CBOR`]: arrays and maps
+/// have children, map values are named after their keys, and the display
+/// looks like `{ "a": [ 1, h'ff', ext(1, h'00') ] }`. The timestamp extension
+/// (type -1) is displayed as a date.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct MessagePack;
+
+impl MessagePack {
+    pub fn new_aligned(alignment: Alignment) -> H2Type {
+        H2Type::new(alignment, H2Types::MessagePack(Self))
+    }
+
+    pub fn new() -> H2Type {
+        Self::new_aligned(Alignment::None)
+    }
+
+    /// Walk the item, returning its size and the positions of its children -
+    /// the optional key position (for maps), and the value position.
+    ///
+    /// The `depth` is how deep the item is nested, starting at 0.
+    fn walk(context: Context, depth: u64) -> SimpleResult<(u64, Vec<(Option<u64>, u64)>)> {
+        if depth > MAX_DEPTH {
+            bail!("MessagePack items are nested too deeply");
+        }
+
+        let header = Header::read(context)?;
+        let mut position = context.position() + header.size;
+        let mut children = vec![];
+
+        match header.kind {
+            Kind::String(length) | Kind::Binary(length) | Kind::Extension(_, length) => {
+                position = skip_bytes(context, position, length)?;
+            },
+            Kind::Array(count) => {
+                for _ in 0..count {
+                    children.push((None, position));
+                    position = position + Self::walk(context.at(position), depth + 1)?.0;
+                }
+            },
+            Kind::Map(count) => {
+                for _ in 0..count {
+                    let key = position;
+                    position = position + Self::walk(context.at(position), depth + 1)?.0;
+
+                    children.push((Some(key), position));
+                    position = position + Self::walk(context.at(position), depth + 1)?.0;
+                }
+            },
+            _ => (),
+        };
+
+        Ok((position - context.position(), children))
+    }
+
+    fn read_text(context: Context) -> SimpleResult<String> {
+        let header = Header::read(context)?;
+
+        match header.kind {
+            Kind::String(length) => {
+                match String::from_utf8(read_bytes(context, context.position() + header.size, length)?) {
+                    Ok(s)  => Ok(s),
+                    Err(e) => bail!("Invalid UTF-8 in MessagePack string: {}", e),
+                }
+            },
+            _ => bail!("MessagePack item isn't a string"),
+        }
+    }
+
+    fn display(context: Context, depth: u64) -> SimpleResult<String> {
+        if depth > MAX_DEPTH {
+            bail!("MessagePack items are nested too deeply");
+        }
+
+        let header = Header::read(context)?;
+        let data = context.position() + header.size;
+
+        match header.kind {
+            Kind::Integer(n)      => Ok(n.to_string()),
+            Kind::Nil             => Ok("nil".to_string()),
+            Kind::Boolean(b)      => Ok(b.to_string()),
+            Kind::Float32(f)      => Ok(format!("{:?}", f)),
+            Kind::Float64(f)      => Ok(format!("{:?}", f)),
+            Kind::String(_)       => Ok(common::display_string(&Self::read_text(context)?, StringDisplay::default())),
+            Kind::Binary(length)  => Ok(format!("h'{}'", to_hex(&read_bytes(context, data, length)?, ""))),
+            Kind::Extension(extension_type, length) => {
+                let bytes = read_bytes(context, data, length)?;
+
+                match (extension_type, decode_timestamp(&bytes)) {
+                    (-1, Some(timestamp)) => Ok(format!("timestamp({})", timestamp)),
+                    _                     => Ok(format!("ext({}, h'{}')", extension_type, to_hex(&bytes, ""))),
+                }
+            },
+            Kind::Array(_) | Kind::Map(_) => {
+                let strings = Self::walk(context, depth)?.1.into_iter().map(|(key, value)| {
+                    let value = Self::display(context.at(value), depth + 1)?;
+
+                    match key {
+                        Some(key) => Ok(format!("{}: {}", Self::display(context.at(key), depth + 1)?, value)),
+                        None      => Ok(value),
+                    }
+                }).collect::<SimpleResult<Vec<String>>>()?;
+
+                let (open, close) = match header.kind {
+                    Kind::Array(_) => ("[", "]"),
+                    _              => ("{", "}"),
+                };
+
+                match strings.len() {
+                    0 => Ok(format!("{}{}", open, close)),
+                    _ => Ok(format!("{} {} {}", open, strings.join(", "), close)),
+                }
+            },
+        }
+    }
+
+    /// The name a map key gives its value.
+    fn key_name(context: Context) -> SimpleResult<String> {
+        match Header::read(context)?.kind {
+            Kind::String(_) => Self::read_text(context),
+            _               => Self::display(context, 0),
+        }
+    }
+
+    fn read_integer(offset: Offset) -> SimpleResult<i128> {
+        match Header::read(offset.get_dynamic()?)?.kind {
+            Kind::Integer(n) => Ok(n),
+            _                => bail!("MessagePack item isn't an integer"),
+        }
+    }
+}
+
+impl H2TypeTrait for MessagePack {
+    fn is_static(&self) -> bool {
+        false
+    }
+
+    fn actual_size(&self, offset: Offset) -> SimpleResult<u64> {
+        Ok(Self::walk(offset.get_dynamic()?, 0)?.0)
+    }
+
+    fn to_display(&self, offset: Offset) -> SimpleResult<String> {
+        match offset {
+            Offset::Static(_) => Ok("MessagePack".to_string()),
+            Offset::Dynamic(context) | Offset::Bounded(context, ..) | Offset::Windowed(context, ..) => Self::display(context, 0),
+        }
+    }
+
+    fn children(&self, offset: Offset) -> SimpleResult<Vec<(Option<String>, H2Type)>> {
+        Ok(self.children_with_range(offset)?.into_iter().map(|(_, _, name, child)| {
+            (name, child)
+        }).collect())
+    }
+
+    /// We must implement this ourselves, because map keys aren't children, so
+    /// the children have gaps between them
+    fn children_with_range(&self, offset: Offset) -> SimpleResult<Vec<(Range<u64>, u8, Option<String>, H2Type)>> {
+        let context = match offset {
+            Offset::Static(_) => return Ok(vec![]),
+            Offset::Dynamic(context) | Offset::Bounded(context, ..) | Offset::Windowed(context, ..) => context,
+        };
+
+        Self::walk(context, 0)?.1.into_iter().map(|(key, value)| {
+            let name = match key {
+                Some(key) => Some(Self::key_name(context.at(key))?),
+                None      => None,
+            };
+            let child = MessagePack::new();
+
+            Ok((child.aligned_range(offset.at(value))?, 0, name, child))
+        }).collect()
+    }
+
+    fn can_be_string(&self) -> bool {
+        true
+    }
+
+    fn to_string(&self, offset: Offset) -> SimpleResult<String> {
+        Self::read_text(offset.get_dynamic()?)
+    }
+
+    fn can_be_u64(&self) -> bool {
+        true
+    }
+
+    fn to_u64(&self, offset: Offset) -> SimpleResult<u64> {
+        let value = Self::read_integer(offset)?;
+
+        if value < 0 {
+            bail!("MessagePack integer {} doesn't fit in a u64", value);
+        }
+
+        Ok(value as u64)
+    }
+
+    fn can_be_i64(&self) -> bool {
+        true
+    }
+
+    fn to_i64(&self, offset: Offset) -> SimpleResult<i64> {
+        let value = Self::read_integer(offset)?;
+
+        if value > i64::MAX as i128 {
+            bail!("MessagePack integer {} doesn't fit in an i64", value);
+        }
+
+        Ok(value as i64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+    use sized_number::Context;
+
+    #[test]
+    fn test_simple_items() -> SimpleResult<()> {
+        let tests: Vec<(Vec<u8>, &str)> = vec![
+            (b"\x05".to_vec(),                                 "5"),
+            (b"\xff".to_vec(),                                 "-1"),
+            (b"\xcc\xff".to_vec(),                             "255"),
+            (b"\xcd\x01\x00".to_vec(),                         "256"),
+            (b"\xcf\xff\xff\xff\xff\xff\xff\xff\xff".to_vec(), "18446744073709551615"),
+            (b"\xd0\x80".to_vec(),                             "-128"),
+            (b"\xd3\xff\xff\xff\xff\xff\xff\xff\xfe".to_vec(), "-2"),
+            (b"\xc0".to_vec(),                                 "nil"),
+            (b"\xc3".to_vec(),                                 "true"),
+            (b"\xca\x3f\xc0\x00\x00".to_vec(),                 "1.5"),
+            (b"\xcb\x3f\xf1\x99\x99\x99\x99\x99\x9a".to_vec(), "1.1"),
+            (b"\xa3abc".to_vec(),                              "\"abc\""),
+            (b"\xd9\x01z".to_vec(),                            "\"z\""),
+            (b"\xc4\x02\x01\x02".to_vec(),                     "h'0102'"),
+            (b"\xd4\x01\xff".to_vec(),                         "ext(1, h'ff')"),
+            (b"\xc7\x03\x05abc".to_vec(),                      "ext(5, h'616263')"),
+            (b"\xd6\xff\x00\x00\x00\x00".to_vec(),             "timestamp(1970-01-01T00:00:00Z)"),
+            (b"\xd7\xff\x00\x00\x00\x04\x00\x00\x00\x01".to_vec(), "timestamp(1970-01-01T00:00:01.000000001Z)"),
+        ];
+
+        for (data, expected) in tests {
+            let offset = Offset::Dynamic(Context::new(&data));
+
+            assert_eq!(data.len() as u64, MessagePack::new().actual_size(offset)?);
+            assert_eq!(expected, MessagePack::new().to_display(offset)?);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_integers() -> SimpleResult<()> {
+        let data = b"\xcc\xff\xd0\x80\xa1x".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        assert_eq!(255,  MessagePack::new().to_u64(offset)?);
+        assert_eq!(-128, MessagePack::new().to_i64(offset.at(2))?);
+        assert!(MessagePack::new().to_u64(offset.at(2)).is_err());
+        assert_eq!("x", MessagePack::new().to_string(offset.at(4))?);
+        assert!(MessagePack::new().to_u64(offset.at(4)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_collections() -> SimpleResult<()> {
+        // {"a": 1, "b": [2, 3]}
+        let data = b"\x82\xa1a\x01\xa1b\x92\x02\x03".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = MessagePack::new();
+        assert_eq!(9, t.actual_size(offset)?);
+        assert_eq!("{ \"a\": 1, \"b\": [ 2, 3 ] }", t.to_display(offset)?);
+
+        let r = t.resolve(offset, None)?;
+        assert_eq!(2, r.children.len());
+        assert_eq!(Some("a".to_string()), r.children[0].field_name);
+        assert_eq!(3..4, r.children[0].actual_range);
+        assert_eq!(Some("b".to_string()), r.children[1].field_name);
+        assert_eq!(6..9, r.children[1].actual_range);
+        assert_eq!("[ 2, 3 ]", r.children[1].display);
+        assert_eq!(2, r.children[1].children.len());
+
+        // Integer keys, and empty collections
+        let data = b"\x81\x01\x90\x80".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+        assert_eq!("{ 1: [] }", t.to_display(offset)?);
+        assert_eq!(Some("1".to_string()), t.resolve(offset, None)?.children[0].field_name);
+        assert_eq!("{}", t.to_display(offset.at(3))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_errors() -> SimpleResult<()> {
+        // Reserved, truncated array, truncated string, and bad UTF-8
+        for data in vec![b"\xc1".to_vec(), b"\x92\x01".to_vec(), b"\xa3ab".to_vec(), b"\xa2\xc3\x28".to_vec()] {
+            let offset = Offset::Dynamic(Context::new(&data));
+
+            assert!(MessagePack::new().to_display(offset).is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_deep_nesting() -> SimpleResult<()> {
+        // Arrays of one element, nested too deeply (and then very deeply)
+        for depth in vec![MAX_DEPTH as usize + 1, 1000000] {
+            let mut data = vec![0x91; depth];
+            data.push(0x00);
+            let offset = Offset::Dynamic(Context::new(&data));
+
+            assert!(MessagePack::new().actual_size(offset).is_err());
+            assert!(MessagePack::new().to_display(offset).is_err());
+        }
+
+        // Right at the limit is fine
+        let mut data = vec![0x91; MAX_DEPTH as usize];
+        data.push(0x00);
+        let offset = Offset::Dynamic(Context::new(&data));
+        assert_eq!(data.len() as u64, MessagePack::new().actual_size(offset)?);
+
+        Ok(())
+    }
+}
@@ -12,4 +12,25 @@ pub use h2enum::*;
 mod h2struct;
 pub use h2struct::*;
 
+mod protobuf;
+pub use protobuf::*;
+
+mod cbor;
+pub use cbor::*;
+
+mod messagepack;
+pub use messagepack::*;
+
+mod asn1;
+pub use asn1::*;
+
+mod constrained;
+pub use constrained::*;
+
+mod transform;
+pub use transform::*;
+
+mod h2bitstruct;
+pub use h2bitstruct::*;
+
 pub mod string;
@@ -0,0 +1,701 @@
+#[cfg(feature = "serialize")]
+use serde::{Serialize, Deserialize};
+
+use simple_error::{bail, SimpleResult};
+use sized_number::{Endian, SizedDefinition, SizedDisplay};
+use std::collections::HashMap;
+
+use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset};
+use crate::composite::string::{LPString, LPStringOptions, LengthUnit};
+use crate::simple::{BytesDisplay, BytesLength, H2Bytes, H2Number, Varint, VarintFormat};
+use crate::simple::character::{UTF8, StringDisplay};
+
+/// Maps protobuf field numbers to a name and, optionally, a type.
+///
+/// The type replaces everything after the key, so for length-delimited fields
+/// it must include the length prefix - typically a nested [`Protobuf`] with a
+/// [`BytesLength::Prefixed`] varint length, or an [`LPString`] counted in
+/// bytes. Without a type, the field is decoded the same way as an unknown one.
+pub type ProtobufSchema = HashMap<u64, (String, Option<H2Type>)>;
+
+/// The wire type of a protobuf field (the low three bits of the key).
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum ProtobufWireType {
+    Varint,
+    Fixed64,
+    LengthDelimited,
+    StartGroup,
+    EndGroup,
+    Fixed32,
+}
+
+impl ProtobufWireType {
+    fn from_key(key: u64) -> SimpleResult<Self> {
+        match key & 7 {
+            0 => Ok(Self::Varint),
+            1 => Ok(Self::Fixed64),
+            2 => Ok(Self::LengthDelimited),
+            3 => Ok(Self::StartGroup),
+            4 => Ok(Self::EndGroup),
+            5 => Ok(Self::Fixed32),
+            n => bail!("Invalid protobuf wire type: {}", n),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Varint          => "varint",
+            Self::Fixed64         => "fixed64",
+            Self::LengthDelimited => "length-delimited",
+            Self::StartGroup      => "start group",
+            Self::EndGroup        => "end group",
+            Self::Fixed32         => "fixed32",
+        }
+    }
+}
+
+/// Nested messages are only guessed this deep, so hostile data can't
+/// overflow the stack; below that, length-delimited fields are strings or
+/// bytes.
+const MAX_DEPTH: u64 = 64;
+
+/// A field found by walking the wire format, before any types are chosen.
+struct WireField {
+    /// Where the key starts.
+    position: u64,
+    field_number: u64,
+    wire_type: ProtobufWireType,
+    value_start: u64,
+    end: u64,
+}
+
+/// Defines a protobuf message, decoded from the wire format.
+///
+/// Each field becomes a child, named from the `schema` or, for unknown fields,
+/// the field number. Each field's children are its key (showing the field
+/// number and wire type) and its value.
+///
+/// Without a schema, varints are unsigned and fixed-width values are unsigned
+/// and little endian. Length-delimited fields are displayed as a nested
+/// message if they parse as one, as a string if they're printable UTF-8, and
+/// as bytes otherwise. Short strings are often valid messages too (`"hi"` is
+/// field 13 with the value 105) - the wire format can't tell them apart, but a
+/// schema can. Groups are displayed as bytes.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct Protobuf {
+    kind: ProtobufKind,
+}
+
+/// A message's fields and keys are [`Protobuf`] types too, but they can only
+/// be created while parsing a message.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+enum ProtobufKind {
+    Message(ProtobufMessage),
+    Field(ProtobufField),
+    Key(ProtobufKey),
+}
+
+impl Protobuf {
+    pub fn new_aligned(alignment: Alignment, length: BytesLength, schema: ProtobufSchema) -> SimpleResult<H2Type> {
+        if let BytesLength::Prefixed(t) = &length {
+            if !t.can_be_u64() {
+                bail!("Length type can't become a u64");
+            }
+        }
+
+        Ok(Self::from_kind(alignment, ProtobufKind::Message(ProtobufMessage {
+            length: length,
+            schema: schema,
+            depth: 0,
+        })))
+    }
+
+    pub fn new(length: BytesLength, schema: ProtobufSchema) -> SimpleResult<H2Type> {
+        Self::new_aligned(Alignment::None, length, schema)
+    }
+
+    fn from_kind(alignment: Alignment, kind: ProtobufKind) -> H2Type {
+        H2Type::new(alignment, H2Types::Protobuf(Self {
+            kind: kind,
+        }))
+    }
+
+    fn kind(&self) -> &dyn H2TypeTrait {
+        match &self.kind {
+            ProtobufKind::Message(t) => t,
+            ProtobufKind::Field(t)   => t,
+            ProtobufKind::Key(t)     => t,
+        }
+    }
+}
+
+impl H2TypeTrait for Protobuf {
+    fn is_static(&self) -> bool {
+        self.kind().is_static()
+    }
+
+    fn actual_size(&self, offset: Offset) -> SimpleResult<u64> {
+        self.kind().actual_size(offset)
+    }
+
+    fn to_display(&self, offset: Offset) -> SimpleResult<String> {
+        self.kind().to_display(offset)
+    }
+
+    fn children(&self, offset: Offset) -> SimpleResult<Vec<(Option<String>, H2Type)>> {
+        self.kind().children(offset)
+    }
+
+    fn can_be_string(&self) -> bool {
+        self.kind().can_be_string()
+    }
+
+    fn to_string(&self, offset: Offset) -> SimpleResult<String> {
+        self.kind().to_string(offset)
+    }
+
+    fn can_be_u64(&self) -> bool {
+        self.kind().can_be_u64()
+    }
+
+    fn to_u64(&self, offset: Offset) -> SimpleResult<u64> {
+        self.kind().to_u64(offset)
+    }
+
+    fn can_be_i64(&self) -> bool {
+        self.kind().can_be_i64()
+    }
+
+    fn to_i64(&self, offset: Offset) -> SimpleResult<i64> {
+        self.kind().to_i64(offset)
+    }
+}
+
+/// A whole message - see [`Protobuf`].
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+struct ProtobufMessage {
+    length: BytesLength,
+    schema: ProtobufSchema,
+
+    /// How many messages this one is nested in, for guessed messages.
+    depth: u64,
+}
+
+impl ProtobufMessage {
+    /// Read a key, returning the field number, the wire type, and where the
+    /// value starts.
+    fn read_key(offset: Offset, position: u64) -> SimpleResult<(u64, ProtobufWireType, u64)> {
+        let key_type = Varint::new(VarintFormat::LEB128);
+        let key_offset = offset.at(position);
+        let key = key_type.to_u64(key_offset)?;
+
+        if key >> 3 == 0 {
+            bail!("Protobuf field numbers can't be 0");
+        }
+
+        Ok((key >> 3, ProtobufWireType::from_key(key)?, position + key_type.actual_size(key_offset)?))
+    }
+
+    /// Find the end of a value that isn't a group.
+    fn value_end(offset: Offset, wire_type: ProtobufWireType, value_start: u64) -> SimpleResult<u64> {
+        let value_offset = offset.at(value_start);
+
+        match wire_type {
+            ProtobufWireType::Varint  => Ok(value_start + Varint::new(VarintFormat::LEB128).actual_size(value_offset)?),
+            ProtobufWireType::Fixed64 => Ok(value_start + 8),
+            ProtobufWireType::Fixed32 => Ok(value_start + 4),
+            ProtobufWireType::LengthDelimited => {
+                let (start, length) = BytesLength::Prefixed(Box::new(Varint::new(VarintFormat::LEB128))).data_range(value_offset)?;
+
+                match start.checked_add(length) {
+                    Some(end) => Ok(end),
+                    None      => bail!("Protobuf length is too large"),
+                }
+            },
+            _ => bail!("Protobuf groups don't have a fixed size"),
+        }
+    }
+
+    /// Find the end of a group that starts at `start`, including its end
+    /// group key. Groups can nest, so this keeps a stack of the open ones
+    /// (rather than recursing).
+    fn group_end(offset: Offset, start: u64, end: u64, field_number: u64) -> SimpleResult<u64> {
+        let mut open = vec![field_number];
+        let mut position = start;
+
+        while position < end {
+            let (field_number, wire_type, value_start) = Self::read_key(offset, position)?;
+
+            position = match wire_type {
+                ProtobufWireType::StartGroup => {
+                    open.push(field_number);
+                    value_start
+                },
+                ProtobufWireType::EndGroup => {
+                    if open.pop() != Some(field_number) {
+                        bail!("Protobuf end group (field {}) doesn't match its start group", field_number);
+                    }
+
+                    if open.is_empty() {
+                        return Ok(value_start);
+                    }
+
+                    value_start
+                },
+                _ => Self::value_end(offset, wire_type, value_start)?,
+            };
+        }
+
+        bail!("Protobuf group (field {}) doesn't end", field_number);
+    }
+
+    /// Walk the fields in `start..end`, using only the wire format. Nothing
+    /// inside the fields is parsed, so this is cheap enough to use for
+    /// guessing whether some bytes are a message.
+    fn walk(offset: Offset, start: u64, end: u64) -> SimpleResult<Vec<WireField>> {
+        let mut position = start;
+        let mut fields = vec![];
+
+        while position < end {
+            let (field_number, wire_type, value_start) = Self::read_key(offset, position)?;
+
+            let field_end = match wire_type {
+                ProtobufWireType::StartGroup => Self::group_end(offset, value_start, end, field_number)?,
+                ProtobufWireType::EndGroup   => bail!("Protobuf end group (field {}) without a start group", field_number),
+                _                            => Self::value_end(offset, wire_type, value_start)?,
+            };
+
+            if field_end > end {
+                bail!("Protobuf field {} runs past the end of the message", field_number);
+            }
+
+            fields.push(WireField {
+                position: position,
+                field_number: field_number,
+                wire_type: wire_type,
+                value_start: value_start,
+                end: field_end,
+            });
+
+            position = field_end;
+        }
+
+        Ok(fields)
+    }
+
+    /// Choose a type for a field that isn't in the schema.
+    fn default_type(&self, offset: Offset, field: &WireField) -> SimpleResult<H2Type> {
+        match field.wire_type {
+            ProtobufWireType::Varint          => Ok(Varint::new(VarintFormat::LEB128)),
+            ProtobufWireType::Fixed64         => Ok(H2Number::new(SizedDefinition::U64(Endian::Little), SizedDisplay::Decimal)),
+            ProtobufWireType::Fixed32         => Ok(H2Number::new(SizedDefinition::U32(Endian::Little), SizedDisplay::Decimal)),
+            ProtobufWireType::LengthDelimited => self.guess_length_delimited(offset.at(field.value_start)),
+            _                                 => H2Bytes::new(BytesLength::Fixed(field.end - field.value_start), BytesDisplay::Hex),
+        }
+    }
+
+    fn guess_length_delimited(&self, offset: Offset) -> SimpleResult<H2Type> {
+        let prefix = Varint::new(VarintFormat::LEB128);
+        let (start, length) = BytesLength::Prefixed(Box::new(prefix.clone())).data_range(offset)?;
+        let end = match start.checked_add(length) {
+            Some(end) => end,
+            None      => bail!("Protobuf length is too large"),
+        };
+
+        // Try a nested message first. Only its top level has to parse, since
+        // anything inside it can fall back to a string or bytes, so this
+        // doesn't re-parse the whole subtree
+        if length > 0 && self.depth < MAX_DEPTH && Self::walk(offset, start, end).is_ok() {
+            return Ok(Protobuf::from_kind(Alignment::None, ProtobufKind::Message(Self {
+                length: BytesLength::Prefixed(Box::new(prefix)),
+                schema: ProtobufSchema::new(),
+                depth: self.depth + 1,
+            })));
+        }
+
+        let context = offset.get_dynamic()?;
+        let bytes = (start..end).map(|i| {
+            context.at(i).read_u8()
+        }).collect::<SimpleResult<Vec<u8>>>()?;
+
+        if let Ok(s) = std::str::from_utf8(&bytes) {
+            if s.chars().all(|c| !c.is_control() || c == '\n' || c == '\r' || c == '\t') {
+                return LPString::new_with_options(
+                    prefix,
+                    UTF8::new(),
+                    LPStringOptions { unit: LengthUnit::Bytes, ..Default::default() },
+                    StringDisplay::default(),
+                );
+            }
+        }
+
+        H2Bytes::new(BytesLength::Prefixed(Box::new(prefix)), BytesDisplay::Hex)
+    }
+
+    /// Parse the fields, not including the length prefix.
+    fn fields(&self, offset: Offset) -> SimpleResult<Vec<(Option<String>, H2Type)>> {
+        let (start, length) = self.length.data_range(offset)?;
+        let end = match start.checked_add(length) {
+            Some(end) => end,
+            None      => bail!("Protobuf length is too large"),
+        };
+
+        Self::walk(offset, start, end)?.iter().map(|field| {
+            let (name, value) = match self.schema.get(&field.field_number) {
+                Some((name, Some(t))) => (name.clone(), t.clone()),
+                Some((name, None))    => (name.clone(), self.default_type(offset, field)?),
+                None                  => (field.field_number.to_string(), self.default_type(offset, field)?),
+            };
+
+            // A schema type has to agree with the wire format about the size
+            let value = ProtobufField::new(field.wire_type, value);
+            if value.aligned_size(offset.at(field.position))? != field.end - field.position {
+                bail!("Protobuf field {} doesn't match its type in the schema", field.field_number);
+            }
+
+            Ok((Some(name), value))
+        }).collect()
+    }
+}
+
+impl H2TypeTrait for ProtobufMessage {
+    fn is_static(&self) -> bool {
+        false
+    }
+
+    fn actual_size(&self, offset: Offset) -> SimpleResult<u64> {
+        self.length.size(offset)
+    }
+
+    fn to_display(&self, offset: Offset) -> SimpleResult<String> {
+        if let Offset::Static(_) = offset {
+            return Ok("Protobuf".to_string());
+        }
+
+        // Skip the length prefix, if there is one
+        let skip = match self.length {
+            BytesLength::Prefixed(_) => 1,
+            _                        => 0,
+        };
+
+        let strings: Vec<String> = self.children_with_range(offset)?.iter().skip(skip).map(|(range, _, name, child)| {
+            Ok(format!("{}: {}", name.clone().unwrap_or("<name unknown>".to_string()), child.to_display(offset.at(range.start))?))
+        }).collect::<SimpleResult<Vec<String>>>()?;
+
+        match strings.len() {
+            0 => Ok("{}".to_string()),
+            _ => Ok(format!("{{ {} }}", strings.join(", "))),
+        }
+    }
+
+    fn children(&self, offset: Offset) -> SimpleResult<Vec<(Option<String>, H2Type)>> {
+        let mut children = vec![];
+
+        if let BytesLength::Prefixed(t) = &self.length {
+            children.push(( Some("size".to_string()), t.as_ref().clone() ));
+        }
+
+        children.append(&mut self.fields(offset)?);
+
+        Ok(children)
+    }
+}
+
+/// A single field in a [`Protobuf`] message: a key, then a value.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+struct ProtobufField {
+    wire_type: ProtobufWireType,
+    value: Box<H2Type>,
+}
+
+impl ProtobufField {
+    fn new(wire_type: ProtobufWireType, value: H2Type) -> H2Type {
+        Protobuf::from_kind(Alignment::None, ProtobufKind::Field(Self {
+            wire_type: wire_type,
+            value: Box::new(value),
+        }))
+    }
+
+    fn value_offset(&self, offset: Offset) -> SimpleResult<Offset> {
+        Ok(offset.at(offset.position() + Varint::new(VarintFormat::LEB128).actual_size(offset)?))
+    }
+}
+
+impl H2TypeTrait for ProtobufField {
+    fn is_static(&self) -> bool {
+        false
+    }
+
+    fn to_display(&self, offset: Offset) -> SimpleResult<String> {
+        match offset {
+            Offset::Static(_) => Ok(format!("Protobuf {} field", self.wire_type.name())),
+            Offset::Dynamic(_) | Offset::Bounded(..) | Offset::Windowed(..) => self.value.to_display(self.value_offset(offset)?),
+        }
+    }
+
+    fn children(&self, _offset: Offset) -> SimpleResult<Vec<(Option<String>, H2Type)>> {
+        Ok(vec![
+            ( Some("key".to_string()),   ProtobufKey::new() ),
+            ( Some("value".to_string()), self.value.as_ref().clone() ),
+        ])
+    }
+
+    fn can_be_string(&self) -> bool {
+        self.value.can_be_string()
+    }
+
+    fn to_string(&self, offset: Offset) -> SimpleResult<String> {
+        self.value.to_string(self.value_offset(offset)?)
+    }
+
+    fn can_be_u64(&self) -> bool {
+        self.value.can_be_u64()
+    }
+
+    fn to_u64(&self, offset: Offset) -> SimpleResult<u64> {
+        self.value.to_u64(self.value_offset(offset)?)
+    }
+
+    fn can_be_i64(&self) -> bool {
+        self.value.can_be_i64()
+    }
+
+    fn to_i64(&self, offset: Offset) -> SimpleResult<i64> {
+        self.value.to_i64(self.value_offset(offset)?)
+    }
+}
+
+/// The key of a [`ProtobufField`], displayed as its field number and wire
+/// type.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+struct ProtobufKey;
+
+impl ProtobufKey {
+    fn new() -> H2Type {
+        Protobuf::from_kind(Alignment::None, ProtobufKind::Key(Self))
+    }
+}
+
+impl H2TypeTrait for ProtobufKey {
+    fn is_static(&self) -> bool {
+        false
+    }
+
+    fn actual_size(&self, offset: Offset) -> SimpleResult<u64> {
+        Varint::new(VarintFormat::LEB128).actual_size(offset)
+    }
+
+    fn to_display(&self, offset: Offset) -> SimpleResult<String> {
+        match offset {
+            Offset::Static(_) => Ok("Protobuf key".to_string()),
+            Offset::Dynamic(_) | Offset::Bounded(..) | Offset::Windowed(..) => {
+                let key = self.to_u64(offset)?;
+
+                Ok(format!("field {}, {}", key >> 3, ProtobufWireType::from_key(key)?.name()))
+            },
+        }
+    }
+
+    fn can_be_u64(&self) -> bool {
+        true
+    }
+
+    fn to_u64(&self, offset: Offset) -> SimpleResult<u64> {
+        Varint::new(VarintFormat::LEB128).to_u64(offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+    use sized_number::Context;
+
+    #[test]
+    fn test_protobuf() -> SimpleResult<()> {
+        // The examples from the protobuf encoding documentation
+        let data = b"\x08\x96\x01\x12\x07testing\x1a\x03\x08\x96\x01".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = Protobuf::new(BytesLength::Remaining, ProtobufSchema::new())?;
+        assert_eq!(17, t.actual_size(offset)?);
+        assert_eq!("{ 1: 150, 2: \"testing\", 3: { 1: 150 } }", t.to_display(offset)?);
+
+        let r = t.resolve(offset, None)?;
+        assert_eq!(3, r.children.len());
+
+        assert_eq!(Some("1".to_string()), r.children[0].field_name);
+        assert_eq!(0..3, r.children[0].actual_range);
+        assert_eq!(Some(150), r.children[0].as_u64);
+        assert_eq!("field 1, varint", r.children[0].children[0].display);
+        assert_eq!("150", r.children[0].children[1].display);
+
+        assert_eq!(Some("testing".to_string()), r.children[1].as_string);
+        assert_eq!("field 2, length-delimited", r.children[1].children[0].display);
+
+        // The nested message has a size, then its field
+        let nested = &r.children[2].children[1];
+        assert_eq!(13..17, nested.actual_range);
+        assert_eq!(2, nested.children.len());
+        assert_eq!("3", nested.children[0].display);
+        assert_eq!("150", nested.children[1].display);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fixed_and_bytes() -> SimpleResult<()> {
+        let data = b"\x21\x01\x00\x00\x00\x00\x00\x00\x00\x2d\xff\xff\xff\xff\x32\x02\xff\xfe".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = Protobuf::new(BytesLength::Remaining, ProtobufSchema::new())?;
+        assert_eq!("{ 4: 1, 5: 4294967295, 6: fffe }", t.to_display(offset)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_schema() -> SimpleResult<()> {
+        let data = b"\x08\x03\x12\x05hello\x18\x01".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let mut schema = ProtobufSchema::new();
+        schema.insert(1, ("delta".to_string(), Some(Varint::new(VarintFormat::ZigZag))));
+        schema.insert(2, ("name".to_string(), None));
+
+        let t = Protobuf::new(BytesLength::Remaining, schema)?;
+        assert_eq!("{ delta: -2, name: \"hello\", 3: 1 }", t.to_display(offset)?);
+
+        // A schema type that doesn't match the wire format
+        let mut schema = ProtobufSchema::new();
+        schema.insert(1, ("delta".to_string(), Some(H2Number::new(SizedDefinition::U32(Endian::Little), SizedDisplay::Decimal))));
+
+        let t = Protobuf::new(BytesLength::Remaining, schema)?;
+        assert!(t.to_display(offset).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_prefixed_message() -> SimpleResult<()> {
+        // A length-prefixed message, then something else
+        let data = b"\x03\x08\x96\x01\xff".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = Protobuf::new(BytesLength::Prefixed(Box::new(Varint::new(VarintFormat::LEB128))), ProtobufSchema::new())?;
+        assert_eq!(4, t.actual_size(offset)?);
+        assert_eq!("{ 1: 150 }", t.to_display(offset)?);
+
+        // An empty message
+        let data = b"\x00".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+        assert_eq!(1, t.actual_size(offset)?);
+        assert_eq!("{}", t.to_display(offset)?);
+
+        // A length that runs past the end of the address space
+        let data = b"\xff\xff\xff\xff\xff\xff\xff\xff\xff\x01".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+        assert!(t.actual_size(offset).is_err());
+        assert!(t.resolve(offset, None).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_messages() -> SimpleResult<()> {
+        let t = Protobuf::new(BytesLength::Remaining, ProtobufSchema::new())?;
+
+        // Field 0, a bad wire type, a truncated varint, a field that's too
+        // long, an end group on its own, and a group that doesn't end
+        for data in vec![b"\x00\x01".to_vec(), b"\x0e\x00".to_vec(), b"\x08\x80".to_vec(), b"\x0a\x05hi".to_vec(), b"\x0c\x08\x01".to_vec(), b"\x0b\x08\x01".to_vec()] {
+            let offset = Offset::Dynamic(Context::new(&data));
+
+            assert!(t.to_display(offset).is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_groups() -> SimpleResult<()> {
+        // Field 1 is a group containing a varint and another group, then
+        // field 2 is a varint
+        let data = b"\x0b\x08\x01\x1b\x1c\x0c\x10\x02".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = Protobuf::new(BytesLength::Remaining, ProtobufSchema::new())?;
+        assert_eq!("{ 1: 08011b1c0c, 2: 2 }", t.to_display(offset)?);
+
+        let r = t.resolve(offset, None)?;
+        assert_eq!(0..6, r.children[0].actual_range);
+        assert_eq!("field 1, start group", r.children[0].children[0].display);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_nested_before_string() -> SimpleResult<()> {
+        // " A" is printable, but it's also field 4 with the value 65
+        let data = b"\x0a\x02 A".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = Protobuf::new(BytesLength::Remaining, ProtobufSchema::new())?;
+        assert_eq!("{ 1: { 4: 65 } }", t.to_display(offset)?);
+
+        // A schema can say it's a string
+        let mut schema = ProtobufSchema::new();
+        schema.insert(1, ("name".to_string(), Some(LPString::new_with_options(
+            Varint::new(VarintFormat::LEB128),
+            UTF8::new(),
+            LPStringOptions { unit: LengthUnit::Bytes, ..Default::default() },
+            StringDisplay::default(),
+        )?)));
+
+        let t = Protobuf::new(BytesLength::Remaining, schema)?;
+        assert_eq!("{ name: \" A\" }", t.to_display(offset)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_deep_nesting() -> SimpleResult<()> {
+        // Thousands of nested messages, each one field 1 containing the next
+        let mut data = b"\x08\x01".to_vec();
+        for _ in 0..5000 {
+            let mut length = data.len();
+            let mut prefix = vec![0x0a];
+
+            while length >= 0x80 {
+                prefix.push((length & 0x7f) as u8 | 0x80);
+                length = length >> 7;
+            }
+            prefix.push(length as u8);
+
+            prefix.append(&mut data);
+            data = prefix;
+        }
+
+        // Guessing stops at the maximum depth, and the rest is bytes
+        let offset = Offset::Dynamic(Context::new(&data));
+        let t = Protobuf::new(BytesLength::Remaining, ProtobufSchema::new())?;
+
+        // The top level has no size, but the nested messages do
+        let r = t.resolve(offset, None)?;
+        let mut message = r.children[0].children[1].clone();
+        for _ in 1..MAX_DEPTH {
+            assert!(message.display.starts_with("{ 1: "));
+            message = message.children[1].children[1].clone();
+        }
+
+        assert!(message.display.starts_with("{ 1: "));
+        assert!(!message.children[1].children[1].display.starts_with("{"));
+
+        Ok(())
+    }
+}
@@ -6,6 +6,7 @@ use std::iter::FromIterator;
 
 use crate::{H2Type, H2Types, H2TypeTrait, Offset, Alignment};
 use crate::composite::H2Array;
+use crate::simple::character::common::{self, StringDisplay};
 
 /// Defines a string with a configured length.
 ///
@@ -17,10 +18,19 @@ use crate::composite::H2Array;
 pub struct H2String {
     length: u64,
     character: Box<H2Type>,
+    display: StringDisplay,
 }
 
 impl H2String {
     pub fn new_aligned(alignment: Alignment, length_in_characters: u64, character: H2Type) -> SimpleResult<H2Type> {
+        Self::new_aligned_with_display(alignment, length_in_characters, character, StringDisplay::default())
+    }
+
+    pub fn new(length_in_characters: u64, character: H2Type) -> SimpleResult<H2Type> {
+        Self::new_aligned(Alignment::None, length_in_characters, character)
+    }
+
+    pub fn new_aligned_with_display(alignment: Alignment, length_in_characters: u64, character: H2Type, display: StringDisplay) -> SimpleResult<H2Type> {
         if length_in_characters == 0 {
             bail!("Length must be at least 1 character long");
         }
@@ -32,11 +42,12 @@ impl H2String {
         Ok(H2Type::new(alignment, H2Types::H2String(Self {
             length: length_in_characters,
             character: Box::new(character),
+            display: display,
         })))
     }
 
-    pub fn new(length_in_characters: u64, character: H2Type) -> SimpleResult<H2Type> {
-        Self::new_aligned(Alignment::None, length_in_characters, character)
+    pub fn new_with_display(length_in_characters: u64, character: H2Type, display: StringDisplay) -> SimpleResult<H2Type> {
+        Self::new_aligned_with_display(Alignment::None, length_in_characters, character, display)
     }
 
 
@@ -79,7 +90,7 @@ impl H2TypeTrait for H2String {
     }
 
     fn to_display(&self, offset: Offset) -> SimpleResult<String> {
-        Ok(format!("\"{}\"", self.to_string(offset)?))
+        Ok(common::display_string(&self.to_string(offset)?, self.display))
     }
 
     fn children(&self, _offset: Offset) -> SimpleResult<Vec<(Option<String>, H2Type)>> {
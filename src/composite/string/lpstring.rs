@@ -6,22 +6,104 @@ use std::iter::FromIterator;
 
 use crate::{H2Type, H2Types, H2TypeTrait, Offset, Alignment};
 use crate::composite::H2Array;
+use crate::simple::character::common::{self, StringDisplay};
+
+/// The unit that the length prefix of an [`LPString`] is counted in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum LengthUnit {
+    /// The length is a number of characters, regardless of their size.
+    Characters,
+
+    /// The length is a number of bytes (BSTR, Delphi, Go, protobuf, etc).
+    Bytes,
+
+    /// The length is a number of fixed-size code units, with the size given
+    /// in bytes - for example, `CodeUnits(2)` for UTF-16.
+    CodeUnits(u64),
+}
+
+/// An adjustment to the value of the length prefix of an [`LPString`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum LengthAdjustment {
+    /// The length is exactly the length of the data.
+    None,
+
+    /// The length includes the size of the length prefix itself. Can't be
+    /// used with [`LengthUnit::Characters`].
+    IncludesHeader,
+
+    /// The length is one more than the data - typically because it counts a
+    /// terminator.
+    MinusOne,
+}
+
+/// Configures how the length prefix of an [`LPString`] is interpreted.
+///
+/// The default is a length in characters, with no adjustment and no
+/// terminator.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct LPStringOptions {
+    /// The unit that the length is counted in.
+    pub unit: LengthUnit,
+
+    /// How to get from the length prefix to the length of the data.
+    pub adjustment: LengthAdjustment,
+
+    /// If set, a NUL character follows the counted data (and isn't counted).
+    pub terminator: bool,
+}
+
+impl Default for LPStringOptions {
+    fn default() -> Self {
+        Self {
+            unit: LengthUnit::Characters,
+            adjustment: LengthAdjustment::None,
+            terminator: false,
+        }
+    }
+}
 
 /// Defines a length-prefixed string.
 ///
 /// This is a string with a numerical prefix that denotes the length of the
-/// string (in *characters*). The length is any numerical value as defined in
+/// string. The length is any numerical value as defined in
 /// [`crate::simple::H2Number`] (or other numeric types if we add any), and
 /// the character type is any type defined in [`crate::simple::Character`].
+///
+/// By default, the length is in *characters*; use
+/// [`LPString::new_with_options`] with [`LPStringOptions`] for strings that
+/// count bytes or code units, that include the prefix in the length, or that
+/// have a terminator.
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct LPString {
     length: Box<H2Type>,
     character: Box<H2Type>,
+    options: LPStringOptions,
+    display: StringDisplay,
 }
 
 impl LPString {
     pub fn new_aligned(alignment: Alignment, length: H2Type, character: H2Type) -> SimpleResult<H2Type> {
+        Self::new_aligned_with_display(alignment, length, character, StringDisplay::default())
+    }
+
+    pub fn new(length: H2Type, character: H2Type) -> SimpleResult<H2Type> {
+        Self::new_aligned(Alignment::None, length, character)
+    }
+
+    pub fn new_aligned_with_display(alignment: Alignment, length: H2Type, character: H2Type, display: StringDisplay) -> SimpleResult<H2Type> {
+        Self::new_aligned_with_options(alignment, length, character, LPStringOptions::default(), display)
+    }
+
+    pub fn new_with_display(length: H2Type, character: H2Type, display: StringDisplay) -> SimpleResult<H2Type> {
+        Self::new_aligned_with_display(Alignment::None, length, character, display)
+    }
+
+    pub fn new_aligned_with_options(alignment: Alignment, length: H2Type, character: H2Type, options: LPStringOptions, display: StringDisplay) -> SimpleResult<H2Type> {
         if !length.can_be_u64() {
             bail!("Length type can't become a u64");
         }
@@ -30,29 +112,109 @@ impl LPString {
             bail!("Character type can't become a character");
         }
 
+        if options.unit == LengthUnit::Characters && options.adjustment == LengthAdjustment::IncludesHeader {
+            bail!("A length in characters can't include the header");
+        }
+
+        if options.unit == LengthUnit::CodeUnits(0) {
+            bail!("Code units must be at least one byte long");
+        }
+
         Ok(H2Type::new(alignment, H2Types::LPString(Self {
             length: Box::new(length),
             character: Box::new(character),
+            options: options,
+            display: display,
         })))
     }
 
-    pub fn new(length: H2Type, character: H2Type) -> SimpleResult<H2Type> {
-        Self::new_aligned(Alignment::None, length, character)
+    pub fn new_with_options(length: H2Type, character: H2Type, options: LPStringOptions, display: StringDisplay) -> SimpleResult<H2Type> {
+        Self::new_aligned_with_options(Alignment::None, length, character, options, display)
     }
 
-    fn analyze(&self, offset: Offset) -> SimpleResult<(u64, Vec<char>)> {
+    /// Read the length prefix and apply the adjustment, returning the header
+    /// size and the length of the data (in [`LengthUnit`]s).
+    fn read_length(&self, offset: Offset) -> SimpleResult<(u64, u64)> {
+        let header_size = self.length.aligned_size(offset)?;
         let length = self.length.to_u64(offset)?;
 
-        let mut position = offset.position() + self.length.aligned_size(offset)?;
+        let length = match self.options.adjustment {
+            LengthAdjustment::None           => Some(length),
+            LengthAdjustment::IncludesHeader => match self.options.unit {
+                LengthUnit::CodeUnits(n) => {
+                    if header_size % n != 0 {
+                        bail!("Length prefix ({} bytes) isn't a whole number of {}-byte code units", header_size, n);
+                    }
+
+                    length.checked_sub(header_size / n)
+                },
+                _ => length.checked_sub(header_size),
+            },
+            LengthAdjustment::MinusOne       => length.checked_sub(1),
+        };
+
+        match length {
+            Some(length) => Ok((header_size, length)),
+            None         => bail!("Length prefix is too small for its adjustment"),
+        }
+    }
+
+    /// Read the string, returning the total size and the characters (not
+    /// including the terminator, if there is one).
+    fn analyze(&self, offset: Offset) -> SimpleResult<(u64, Vec<char>)> {
+        let (header_size, length) = self.read_length(offset)?;
+
+        let start = offset.position() + header_size;
+        let mut position = start;
 
         let mut result = Vec::new();
-        for _ in 0..length {
+        match self.options.unit {
+            LengthUnit::Characters => {
+                for _ in 0..length {
+                    let this_offset = offset.at(position);
+                    let this_size = self.character.actual_size(this_offset)?;
+                    let this_character = self.character.to_char(this_offset)?;
+
+                    result.push(this_character);
+                    position = position + this_size;
+                }
+            },
+            LengthUnit::Bytes | LengthUnit::CodeUnits(_) => {
+                let unit_size = match self.options.unit {
+                    LengthUnit::CodeUnits(n) => n,
+                    _                        => 1,
+                };
+
+                // Bound the characters to the counted bytes, so a multi-byte
+                // character can't run past the end
+                let end = match length.checked_mul(unit_size).and_then(|size| start.checked_add(size)) {
+                    Some(end) => end,
+                    None      => bail!("String length is too large: {}", length),
+                };
+                let data = offset.bounded(start..end)?;
+
+                while position < end {
+                    let this_offset = data.at(position);
+                    let this_size = self.character.actual_size(this_offset)?;
+                    let this_character = self.character.to_char(this_offset)?;
+
+                    result.push(this_character);
+                    position = position + this_size;
+                }
+
+                if position != end {
+                    bail!("String length doesn't end on a character boundary");
+                }
+            },
+        }
+
+        if self.options.terminator {
             let this_offset = offset.at(position);
-            let this_size = self.character.actual_size(this_offset)?;
-            let this_character = self.character.to_char(this_offset)?;
+            if self.character.to_char(this_offset)? != '\0' {
+                bail!("String is missing its terminator");
+            }
 
-            result.push(this_character);
-            position = position + this_size;
+            position = position + self.character.actual_size(this_offset)?;
         }
 
         Ok((position - offset.position(), result))
@@ -81,19 +243,30 @@ impl H2TypeTrait for LPString {
     }
 
     fn to_display(&self, offset: Offset) -> SimpleResult<String> {
-        Ok(format!("\"{}\"", self.to_string(offset)?))
+        Ok(common::display_string(&self.to_string(offset)?, self.display))
     }
 
     fn children(&self, offset: Offset) -> SimpleResult<Vec<(Option<String>, H2Type)>> {
-        let length = self.length.to_u64(offset)?;
+        // We want the number of characters, not the value of the length field
+        let (_, characters) = self.analyze(offset)?;
 
-        Ok(vec![
-            // The size field
+        // The size field
+        let mut children = vec![
             ( Some("size".to_string()), self.length.as_ref().clone() ),
+        ];
+
+        // The data field (arrays can't be empty, so leave it off if there's
+        // no data)
+        if characters.len() > 0 {
+            children.push(( None, H2Array::new(characters.len() as u64, self.character.as_ref().clone())? ));
+        }
 
-            // The data field
-            ( None,                     H2Array::new(length, self.character.as_ref().clone())? ),
-        ])
+        // The terminator, if there is one
+        if self.options.terminator {
+            children.push(( Some("terminator".to_string()), self.character.as_ref().clone() ));
+        }
+
+        Ok(children)
     }
 }
 
@@ -104,7 +277,7 @@ mod tests {
     use sized_number::{Context, SizedDefinition, SizedDisplay, Endian};
     use crate::simple::H2Number;
     use crate::simple::network::IPv4;
-    use crate::simple::character::{UTF8, ASCII, StrictASCII};
+    use crate::simple::character::{UTF8, UTF16, ASCII, StrictASCII, StringDisplay};
     use crate::Alignment;
 
     #[test]
@@ -208,4 +381,142 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_bstr() -> SimpleResult<()> {
+        // A BSTR is a 32-bit length in bytes, then UTF-16, then a NUL that
+        // isn't counted
+        let data = b"\x06\x00\x00\x00h\x00i\x00!\x00\x00\x00".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = LPString::new_with_options(
+            H2Number::new(SizedDefinition::U32(Endian::Little), SizedDisplay::Decimal),
+            UTF16::new(Endian::Little),
+            LPStringOptions { unit: LengthUnit::Bytes, adjustment: LengthAdjustment::None, terminator: true },
+            StringDisplay::default(),
+        )?;
+
+        assert_eq!(12, t.actual_size(offset)?);
+        assert_eq!("\"hi!\"", t.to_display(offset)?);
+
+        let r = t.resolve(offset, None)?;
+        assert_eq!(3, r.children.len());
+        assert_eq!("6", r.children[0].display);
+        assert_eq!("[ 'h', 'i', '!' ]", r.children[1].display);
+        assert_eq!("terminator", r.children[2].field_name.as_ref().unwrap());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_code_units() -> SimpleResult<()> {
+        // One code unit for 'A', then two for a surrogate pair
+        let data = b"\x03\x00\x41\xD8\x34\xDD\x1E".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = LPString::new_with_options(
+            H2Number::new(SizedDefinition::U8, SizedDisplay::Decimal),
+            UTF16::new(Endian::Big),
+            LPStringOptions { unit: LengthUnit::CodeUnits(2), adjustment: LengthAdjustment::None, terminator: false },
+            StringDisplay::default(),
+        )?;
+
+        assert_eq!(7, t.actual_size(offset)?);
+        assert_eq!("\"A𝄞\"", t.to_display(offset)?);
+
+        // A length that overflows once it's converted to bytes
+        let data = b"\xff\xff\xff\xff\xff\xff\xff\xff\x00\x41".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = LPString::new_with_options(
+            H2Number::new(SizedDefinition::U64(Endian::Big), SizedDisplay::Decimal),
+            UTF16::new(Endian::Big),
+            LPStringOptions { unit: LengthUnit::CodeUnits(2), adjustment: LengthAdjustment::None, terminator: false },
+            StringDisplay::default(),
+        )?;
+
+        assert!(t.actual_size(offset).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_includes_header() -> SimpleResult<()> {
+        let data = b"\x00\x05abc".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = LPString::new_with_options(
+            H2Number::new(SizedDefinition::U16(Endian::Big), SizedDisplay::Decimal),
+            ASCII::new(StrictASCII::Strict),
+            LPStringOptions { unit: LengthUnit::Bytes, adjustment: LengthAdjustment::IncludesHeader, terminator: false },
+            StringDisplay::default(),
+        )?;
+
+        assert_eq!(5, t.actual_size(offset)?);
+        assert_eq!("\"abc\"", t.to_display(offset)?);
+
+        // A one-byte header can't be taken off a length in two-byte code
+        // units
+        let data = b"\x03\x00A".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = LPString::new_with_options(
+            H2Number::new(SizedDefinition::U8, SizedDisplay::Decimal),
+            UTF16::new(Endian::Big),
+            LPStringOptions { unit: LengthUnit::CodeUnits(2), adjustment: LengthAdjustment::IncludesHeader, terminator: false },
+            StringDisplay::default(),
+        )?;
+
+        assert!(t.to_display(offset).is_err());
+
+        // A length in characters can't include the header
+        assert!(LPString::new_with_options(
+            H2Number::new(SizedDefinition::U16(Endian::Big), SizedDisplay::Decimal),
+            ASCII::new(StrictASCII::Strict),
+            LPStringOptions { unit: LengthUnit::Characters, adjustment: LengthAdjustment::IncludesHeader, terminator: false },
+            StringDisplay::default(),
+        ).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_minus_one() -> SimpleResult<()> {
+        // The length counts the terminator, which is still there
+        let data = b"\x04abc\x00\x00".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = LPString::new_with_options(
+            H2Number::new(SizedDefinition::U8, SizedDisplay::Decimal),
+            ASCII::new(StrictASCII::Strict),
+            LPStringOptions { unit: LengthUnit::Characters, adjustment: LengthAdjustment::MinusOne, terminator: true },
+            StringDisplay::default(),
+        )?;
+
+        assert_eq!(5, t.actual_size(offset)?);
+        assert_eq!("\"abc\"", t.to_display(offset)?);
+
+        // A length of zero can't have one subtracted
+        assert!(t.to_display(offset.at(5)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bytes_not_on_boundary() -> SimpleResult<()> {
+        // 3 bytes doesn't end on a UTF-16 character
+        let data = b"\x03\x00A\x00B".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = LPString::new_with_options(
+            H2Number::new(SizedDefinition::U8, SizedDisplay::Decimal),
+            UTF16::new(Endian::Big),
+            LPStringOptions { unit: LengthUnit::Bytes, adjustment: LengthAdjustment::None, terminator: false },
+            StringDisplay::default(),
+        )?;
+
+        assert!(t.to_display(offset).is_err());
+
+        Ok(())
+    }
 }
@@ -6,6 +6,7 @@ use std::iter::FromIterator;
 
 use crate::{H2Type, H2Types, H2TypeTrait, Offset, Alignment};
 use crate::composite::H2Array;
+use crate::simple::character::common::{self, StringDisplay};
 
 /// Defines a null-terminated string.
 ///
@@ -15,21 +16,31 @@ use crate::composite::H2Array;
 #[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct NTString {
     character: Box<H2Type>,
+    display: StringDisplay,
 }
 
 impl NTString {
     pub fn new_aligned(alignment: Alignment, character: H2Type) -> SimpleResult<H2Type> {
+        Self::new_aligned_with_display(alignment, character, StringDisplay::default())
+    }
+
+    pub fn new(character: H2Type) -> SimpleResult<H2Type> {
+        Self::new_aligned(Alignment::None, character)
+    }
+
+    pub fn new_aligned_with_display(alignment: Alignment, character: H2Type, display: StringDisplay) -> SimpleResult<H2Type> {
         if !character.can_be_char() {
             bail!("Character type can't become a character");
         }
 
         Ok(H2Type::new(alignment, H2Types::NTString(Self {
             character: Box::new(character),
+            display: display,
         })))
     }
 
-    pub fn new(character: H2Type) -> SimpleResult<H2Type> {
-        Self::new_aligned(Alignment::None, character)
+    pub fn new_with_display(character: H2Type, display: StringDisplay) -> SimpleResult<H2Type> {
+        Self::new_aligned_with_display(Alignment::None, character, display)
     }
 
     fn analyze(&self, offset: Offset) -> SimpleResult<(u64, Vec<char>)> {
@@ -75,7 +86,7 @@ impl H2TypeTrait for NTString {
     }
 
     fn to_display(&self, offset: Offset) -> SimpleResult<String> {
-        Ok(format!("\"{}\"", self.to_string(offset)?))
+        Ok(common::display_string(&self.to_string(offset)?, self.display))
     }
 
     fn children(&self, offset: Offset) -> SimpleResult<Vec<(Option<String>, H2Type)>> {
@@ -94,7 +105,7 @@ mod tests {
     use simple_error::SimpleResult;
     use sized_number::{Context, Endian};
     use crate::simple::network::IPv4;
-    use crate::simple::character::{UTF8, ASCII, StrictASCII};
+    use crate::simple::character::{UTF8, ASCII, StrictASCII, EscapeStyle, StringDisplay};
     use crate::Alignment;
 
     #[test]
@@ -200,4 +211,29 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_escaped_display() -> SimpleResult<()> {
+        let data = b"a\"b\nc\x1bd\0".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = NTString::new_with_display(ASCII::new(StrictASCII::Strict), StringDisplay { escape: EscapeStyle::C, max_length: None })?;
+        assert_eq!("\"a\\\"b\\nc\\x1bd\"", t.to_display(offset)?);
+
+        let t = NTString::new_with_display(ASCII::new(StrictASCII::Strict), StringDisplay { escape: EscapeStyle::Rust, max_length: None })?;
+        assert_eq!("\"a\\\"b\\nc\\u{1b}d\"", t.to_display(offset)?);
+
+        let t = NTString::new_with_display(ASCII::new(StrictASCII::Strict), StringDisplay { escape: EscapeStyle::JSON, max_length: None })?;
+        assert_eq!("\"a\\\"b\\nc\\u001bd\"", t.to_display(offset)?);
+
+        let t = NTString::new_with_display(ASCII::new(StrictASCII::Strict), StringDisplay { escape: EscapeStyle::Raw, max_length: None })?;
+        assert_eq!("\"a\"b\nc\x1bd\"", t.to_display(offset)?);
+
+        // The string itself is never escaped or truncated
+        let t = NTString::new_with_display(ASCII::new(StrictASCII::Strict), StringDisplay { escape: EscapeStyle::C, max_length: Some(3) })?;
+        assert_eq!("\"a\\\"b\"...", t.to_display(offset)?);
+        assert_eq!("a\"b\nc\x1bd", t.to_string(offset)?);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,459 @@
+#[cfg(feature = "serialize")]
+use serde::{Serialize, Deserialize};
+
+use simple_error::{bail, try_with, SimpleResult};
+use sized_number::Context;
+use std::io::Read;
+use std::sync::{Arc, RwLock};
+
+use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset, ResolvedType};
+use crate::simple::BytesLength;
+
+/// The most data that a [`Transform`] will decode, so a small compressed
+/// field can't expand to fill up memory.
+const MAX_DECODED_SIZE: u64 = 64 * 1024 * 1024;
+
+/// A writer that fails instead of growing past a limit, for decoders that
+/// write their output rather than being read like [`Decoder::read_all`].
+struct LimitedWriter {
+    data: Vec<u8>,
+    limit: u64,
+}
+
+impl std::io::Write for LimitedWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.data.len() as u64 + buf.len() as u64 > self.limit {
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, "decoded data is too big"));
+        }
+
+        self.data.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// How the data in a [`Transform`] is decoded.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum Decoder {
+    /// zlib (RFC 1950) - deflate with a two-byte header and an Adler-32.
+    Zlib,
+
+    /// Raw deflate (RFC 1951), with no header or checksum.
+    Deflate,
+
+    /// gzip (RFC 1952).
+    Gzip,
+
+    /// An LZ4 frame, which starts with the magic number `04 22 4d 18`.
+    LZ4Frame,
+
+    /// A raw LZ4 block, with no header; the decompressed size has to be
+    /// known ahead of time.
+    LZ4Block(u64),
+
+    /// An LZMA stream in the "alone" (`.lzma`) format, with a 13-byte
+    /// header.
+    LZMA,
+
+    /// Base64, with the standard alphabet. Whitespace is ignored and the
+    /// padding is optional.
+    Base64,
+
+    /// Hex-encoded ASCII, like `68656c6c6f`. Whitespace is ignored.
+    Hex,
+
+    /// XOR with a repeating key.
+    XOR(Vec<u8>),
+}
+
+impl Decoder {
+    /// Read a decompressor to the end, up to [`MAX_DECODED_SIZE`].
+    fn read_all(reader: impl Read, name: &str) -> SimpleResult<Vec<u8>> {
+        let mut decoded = Vec::new();
+        try_with!(reader.take(MAX_DECODED_SIZE + 1).read_to_end(&mut decoded), "Couldn't decode {} data", name);
+
+        if decoded.len() as u64 > MAX_DECODED_SIZE {
+            bail!("Decoded {} data is too big", name);
+        }
+
+        Ok(decoded)
+    }
+
+    fn base64(data: &[u8]) -> SimpleResult<Vec<u8>> {
+        let mut decoded = Vec::new();
+        let mut bits: u32 = 0;
+        let mut count = 0;
+
+        for &c in data.iter().filter(|c| !c.is_ascii_whitespace()).take_while(|&&c| c != b'=') {
+            let value = match c {
+                b'A'..=b'Z' => c - b'A',
+                b'a'..=b'z' => c - b'a' + 26,
+                b'0'..=b'9' => c - b'0' + 52,
+                b'+'        => 62,
+                b'/'        => 63,
+                _           => bail!("Invalid base64 character: {:?}", c as char),
+            };
+
+            bits = (bits << 6) | value as u32;
+            count += 6;
+
+            if count >= 8 {
+                count -= 8;
+                decoded.push((bits >> count) as u8);
+            }
+        }
+
+        Ok(decoded)
+    }
+
+    fn hex(data: &[u8]) -> SimpleResult<Vec<u8>> {
+        let digits = data.iter().filter(|c| !c.is_ascii_whitespace()).map(|&c| {
+            match (c as char).to_digit(16) {
+                Some(d) => Ok(d as u8),
+                None    => bail!("Invalid hex character: {:?}", c as char),
+            }
+        }).collect::<SimpleResult<Vec<u8>>>()?;
+
+        if digits.len() % 2 != 0 {
+            bail!("Hex data has an odd number of digits");
+        }
+
+        Ok(digits.chunks(2).map(|pair| (pair[0] << 4) | pair[1]).collect())
+    }
+
+    /// Decode the data.
+    pub fn decode(&self, data: &[u8]) -> SimpleResult<Vec<u8>> {
+        match self {
+            Self::Zlib     => Self::read_all(flate2::read::ZlibDecoder::new(data), "zlib"),
+            Self::Deflate  => Self::read_all(flate2::read::DeflateDecoder::new(data), "deflate"),
+            Self::Gzip     => Self::read_all(flate2::read::GzDecoder::new(data), "gzip"),
+            Self::LZ4Frame => Self::read_all(lz4_flex::frame::FrameDecoder::new(data), "LZ4"),
+            Self::LZ4Block(size) => {
+                if *size > MAX_DECODED_SIZE {
+                    bail!("Decoded LZ4 data is too big");
+                }
+
+                Ok(try_with!(lz4_flex::block::decompress(data, *size as usize), "Couldn't decode LZ4 data"))
+            },
+            Self::LZMA => {
+                let mut input = data;
+                let mut decoded = LimitedWriter { data: Vec::new(), limit: MAX_DECODED_SIZE };
+                try_with!(lzma_rs::lzma_decompress(&mut input, &mut decoded), "Couldn't decode LZMA data");
+
+                Ok(decoded.data)
+            },
+            Self::Base64   => Self::base64(data),
+            Self::Hex      => Self::hex(data),
+            Self::XOR(key) => Ok(data.iter().zip(key.iter().cycle()).map(|(d, k)| d ^ k).collect()),
+        }
+    }
+}
+
+/// Defines data that has to be decoded before it can be parsed - for
+/// example, a compressed or base64-encoded field.
+///
+/// The encoded data is a range of bytes, defined by a [`BytesLength`] like
+/// [`crate::simple::H2Bytes`]. It's decoded with a [`Decoder`], and the
+/// `inner` type is read from the start of the decoded data.
+///
+/// When it's resolved, the [`ResolvedType`] covers the encoded bytes in the
+/// original buffer, and has a single child: the `inner` type, resolved
+/// against the decoded data. That child (and everything under it) has ranges
+/// in the decoded data, not the original buffer, and is marked with
+/// [`ResolvedType::in_decoded`]; the decoded data is kept in
+/// [`ResolvedType::decoded`] so they can be looked up.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct Transform {
+    length: BytesLength,
+    decoder: Decoder,
+    inner: Box<H2Type>,
+
+    /// The last encoded data that was decoded, and what it decoded to, so
+    /// the display, the value, and so on don't each decode it again. It's
+    /// empty after being deserialized.
+    #[cfg_attr(feature = "serialize", serde(skip))]
+    cache: Arc<RwLock<Option<(Vec<u8>, Arc<Vec<u8>>)>>>,
+}
+
+impl Transform {
+    pub fn new_aligned(alignment: Alignment, length: BytesLength, decoder: Decoder, inner: H2Type) -> SimpleResult<H2Type> {
+        if let BytesLength::Prefixed(t) = &length {
+            if !t.can_be_u64() {
+                bail!("Length type can't become a u64");
+            }
+        }
+
+        if let Decoder::XOR(key) = &decoder {
+            if key.is_empty() {
+                bail!("XOR key can't be empty");
+            }
+        }
+
+        Ok(H2Type::new(alignment, H2Types::Transform(Self {
+            length: length,
+            decoder: decoder,
+            inner: Box::new(inner),
+            cache: Arc::new(RwLock::new(None)),
+        })))
+    }
+
+    pub fn new(length: BytesLength, decoder: Decoder, inner: H2Type) -> SimpleResult<H2Type> {
+        Self::new_aligned(Alignment::None, length, decoder, inner)
+    }
+
+    /// Read the encoded data, and decode it (unless it was the last data
+    /// decoded).
+    fn decode(&self, offset: Offset) -> SimpleResult<Arc<Vec<u8>>> {
+        let (_, _, encoded) = self.length.read(offset, u64::MAX)?;
+
+        if let Ok(cache) = self.cache.read() {
+            if let Some((cached, decoded)) = cache.as_ref() {
+                if cached == &encoded {
+                    return Ok(decoded.clone());
+                }
+            }
+        }
+
+        let decoded = Arc::new(self.decoder.decode(&encoded)?);
+
+        if let Ok(mut cache) = self.cache.write() {
+            *cache = Some((encoded, decoded.clone()));
+        }
+
+        Ok(decoded)
+    }
+}
+
+/// Mark a [`ResolvedType`] and everything under it as being in decoded data.
+fn mark_decoded(resolved: &mut ResolvedType) {
+    resolved.in_decoded = true;
+
+    for child in resolved.children.iter_mut() {
+        mark_decoded(child);
+    }
+}
+
+impl H2TypeTrait for Transform {
+    fn is_static(&self) -> bool {
+        match self.length {
+            BytesLength::Fixed(_) => true,
+            _                     => false,
+        }
+    }
+
+    fn actual_size(&self, offset: Offset) -> SimpleResult<u64> {
+        self.length.size(offset)
+    }
+
+    fn to_display(&self, offset: Offset) -> SimpleResult<String> {
+        if let Offset::Static(_) = offset {
+            return Ok("Transformed data".to_string());
+        }
+
+        let decoded = self.decode(offset)?;
+        self.inner.to_display(Offset::from(Context::new(&decoded)))
+    }
+
+    fn can_be_char(&self) -> bool {
+        self.inner.can_be_char()
+    }
+
+    fn to_char(&self, offset: Offset) -> SimpleResult<char> {
+        let decoded = self.decode(offset)?;
+        self.inner.to_char(Offset::from(Context::new(&decoded)))
+    }
+
+    fn can_be_string(&self) -> bool {
+        self.inner.can_be_string()
+    }
+
+    fn to_string(&self, offset: Offset) -> SimpleResult<String> {
+        let decoded = self.decode(offset)?;
+        self.inner.to_string(Offset::from(Context::new(&decoded)))
+    }
+
+    fn can_be_u64(&self) -> bool {
+        self.inner.can_be_u64()
+    }
+
+    fn to_u64(&self, offset: Offset) -> SimpleResult<u64> {
+        let decoded = self.decode(offset)?;
+        self.inner.to_u64(Offset::from(Context::new(&decoded)))
+    }
+
+    fn can_be_i64(&self) -> bool {
+        self.inner.can_be_i64()
+    }
+
+    fn to_i64(&self, offset: Offset) -> SimpleResult<i64> {
+        let decoded = self.decode(offset)?;
+        self.inner.to_i64(Offset::from(Context::new(&decoded)))
+    }
+
+    fn resolve(&self, offset: Offset, alignment: Alignment, field_name: Option<String>) -> SimpleResult<ResolvedType> {
+        let decoded = self.decode(offset)?;
+
+        // The inner type is resolved against the decoded data, so its ranges
+        // are relative to that
+        let mut inner = self.inner.resolve(Offset::from(Context::new(&decoded)), None)?;
+        mark_decoded(&mut inner);
+
+        Ok(ResolvedType {
+            actual_range: self.range(offset, Alignment::None)?,
+            aligned_range: self.range(offset, alignment)?,
+            bit_ranges: None,
+
+            field_name: field_name,
+            display: inner.display.clone(),
+
+            related: vec![],
+
+            as_char:   inner.as_char,
+            as_string: inner.as_string.clone(),
+            as_u64:    inner.as_u64,
+            as_i64:    inner.as_i64,
+
+            valid: inner.valid,
+            warnings: vec![],
+
+            children: vec![inner],
+            decoded: Some(decoded.as_ref().clone()),
+            in_decoded: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+    use sized_number::{Context, SizedDefinition, SizedDisplay};
+
+    use crate::composite::H2Struct;
+    use crate::composite::string::LPString;
+    use crate::simple::H2Number;
+    use crate::simple::character::{ASCII, StrictASCII};
+
+    /// A length-prefixed "hello".
+    fn hello() -> SimpleResult<H2Type> {
+        LPString::new(
+            H2Number::new(SizedDefinition::U8, SizedDisplay::Decimal),
+            ASCII::new(StrictASCII::Permissive),
+        )
+    }
+
+    #[test]
+    fn test_decoders() -> SimpleResult<()> {
+        let tests: Vec<(Decoder, &[u8])> = vec![
+            (Decoder::Zlib,        b"\x78\x9c\xcb\x48\xcd\xc9\xc9\x07\x00\x06\x2c\x02\x15"),
+            (Decoder::Deflate,     b"\xcb\x48\xcd\xc9\xc9\x07\x00"),
+            (Decoder::LZ4Block(5), b"\x50hello"),
+            (Decoder::LZMA,        b"\x5d\x00\x00\x80\x00\xff\xff\xff\xff\xff\xff\xff\xff\x00\x34\x19\x49\xee\x8e\x68\x21\xff\xff\xff\xb9\xe0\x00\x00"),
+            (Decoder::Base64,      b"aGVs\nbG8"),
+            (Decoder::Hex,         b"68 65 6C 6c 6f"),
+            (Decoder::XOR(b"\x01\x02".to_vec()), b"igmnn"),
+        ];
+
+        for (decoder, data) in tests {
+            assert_eq!(b"hello".to_vec(), decoder.decode(data)?);
+        }
+
+        assert!(Decoder::Zlib.decode(b"hello").is_err());
+        assert!(Decoder::Base64.decode(b"aGV*").is_err());
+        assert!(Decoder::Hex.decode(b"686").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_limited_writer() -> SimpleResult<()> {
+        use std::io::Write;
+
+        let mut writer = LimitedWriter { data: Vec::new(), limit: 4 };
+        assert!(writer.write_all(b"abc").is_ok());
+        assert!(writer.write_all(b"d").is_ok());
+        assert!(writer.write_all(b"e").is_err());
+        assert_eq!(b"abcd".to_vec(), writer.data);
+
+        // LZMA output goes through it, so it's capped too
+        let mut input: &[u8] = b"\x5d\x00\x00\x80\x00\xff\xff\xff\xff\xff\xff\xff\xff\x00\x34\x19\x49\xee\x8e\x68\x21\xff\xff\xff\xb9\xe0\x00\x00";
+        let mut writer = LimitedWriter { data: Vec::new(), limit: 4 };
+        assert!(lzma_rs::lzma_decompress(&mut input, &mut writer).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_transform() -> SimpleResult<()> {
+        // A length, then a base64-encoded, length-prefixed "hello"
+        let data = b"\x08BWhlbGxv".to_vec();
+        let offset = Offset::from(Context::new(&data));
+
+        let t = H2Struct::new(vec![
+            ("length".to_string(), H2Number::new(SizedDefinition::U8, SizedDisplay::Decimal)),
+            ("config".to_string(), Transform::new(BytesLength::Fixed(8), Decoder::Base64, hello()?)?),
+        ])?;
+
+        let r = t.resolve(offset, None)?;
+        assert_eq!(0..9, r.actual_range);
+
+        // The transform covers the original bytes...
+        let config = &r.children[1];
+        assert_eq!(1..9, config.actual_range);
+        assert_eq!("\"hello\"", config.display);
+        assert_eq!(Some("hello".to_string()), config.as_string);
+        assert_eq!(Some(b"\x05hello".to_vec()), config.decoded);
+        assert!(!config.in_decoded);
+
+        // ...and what's inside it covers the decoded bytes
+        let inner = &config.children[0];
+        assert_eq!(0..6, inner.actual_range);
+        assert_eq!(0..1, inner.children[0].actual_range);
+        assert_eq!(None, inner.decoded);
+        assert!(inner.in_decoded);
+        assert!(inner.children[0].in_decoded);
+        assert!(!r.children[0].in_decoded);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_prefixed() -> SimpleResult<()> {
+        // A length-prefixed, XORed, length-prefixed "hello", then junk
+        let data = b"\x06\x04\x69\x64\x6d\x6d\x6ezzz".to_vec();
+        let offset = Offset::from(Context::new(&data));
+
+        let t = Transform::new(
+            BytesLength::Prefixed(Box::new(H2Number::new(SizedDefinition::U8, SizedDisplay::Decimal))),
+            Decoder::XOR(b"\x01".to_vec()),
+            hello()?,
+        )?;
+
+        assert_eq!(7, t.actual_size(offset)?);
+        assert_eq!("hello", t.to_string(offset)?);
+        assert_eq!("Transformed data", t.to_display(Offset::Static(0))?);
+
+        // The same type on different data isn't fooled by the cached data
+        let data = b"\x06\x04\x69\x64\x6d\x6d\x6c".to_vec();
+        assert_eq!("hellm", t.to_string(Offset::from(Context::new(&data)))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad() -> SimpleResult<()> {
+        assert!(Transform::new(BytesLength::Fixed(1), Decoder::XOR(vec![]), hello()?).is_err());
+
+        // The decoded data is too short for the inner type
+        let data = b"BWhl".to_vec();
+        let t = Transform::new(BytesLength::Fixed(4), Decoder::Base64, hello()?)?;
+        assert!(t.resolve(Offset::from(Context::new(&data)), None).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,48 @@
+//! Helpers shared by the format definitions.
+
+use simple_error::{bail, SimpleResult};
+use sized_number::{SizedDefinition, SizedDisplay};
+
+use crate::{H2Type, ResolvedType};
+use crate::composite::{Constrained, Constraint, ConstraintAction, ConstraintValue};
+use crate::simple::{BytesDisplay, BytesLength, H2Bytes, H2Number};
+
+/// A number displayed in decimal.
+pub fn number(definition: SizedDefinition) -> H2Type {
+    H2Number::new(definition, SizedDisplay::Decimal)
+}
+
+/// A number displayed in hex.
+pub fn hex(definition: SizedDefinition) -> H2Type {
+    H2Number::new(definition, SizedDisplay::Hex(Default::default()))
+}
+
+/// Magic bytes, which fail to resolve if they don't match.
+pub fn magic(value: &[u8]) -> SimpleResult<H2Type> {
+    Constrained::new(
+        H2Bytes::new(BytesLength::Fixed(value.len() as u64), BytesDisplay::Hex)?,
+        Constraint::Equals(ConstraintValue::Bytes(value.to_vec())),
+        ConstraintAction::Fail,
+    )
+}
+
+/// A number that should be one of `values`, with a warning if it isn't.
+pub fn one_of(t: H2Type, values: Vec<u64>) -> SimpleResult<H2Type> {
+    Constrained::new(t, Constraint::OneOf(values.into_iter().map(ConstraintValue::Integer).collect()), ConstraintAction::Warn)
+}
+
+/// A number that should be `value`, with a warning if it isn't.
+pub fn equals(t: H2Type, value: u64) -> SimpleResult<H2Type> {
+    Constrained::new(t, Constraint::Equals(ConstraintValue::Integer(value)), ConstraintAction::Warn)
+}
+
+/// Get a numeric field from a resolved struct, by name.
+pub fn field(resolved: &ResolvedType, name: &str) -> SimpleResult<u64> {
+    match resolved.children.iter().find(|c| c.field_name.as_deref() == Some(name)) {
+        Some(c) => match c.as_u64 {
+            Some(v) => Ok(v),
+            None    => bail!("Field {} isn't numeric", name),
+        },
+        None => bail!("Field {} not found", name),
+    }
+}
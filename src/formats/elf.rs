@@ -0,0 +1,429 @@
+//! Definitions for ELF (Executable and Linkable Format) files.
+//!
+//! ELF comes in two classes (32- and 64-bit) and two byte orders, both of
+//! which are set in the identification bytes (`e_ident`) at the start of the
+//! file. The individual structures are built with [`ElfClass`] and an
+//! [`Endian`]; [`elf`] reads those from a buffer and builds the whole header,
+//! with pointers to the program and section header tables, and
+//! [`elf_tables`] finds the symbol and dynamic tables through the section
+//! headers.
+//!
+//! Field names follow the ELF specification (`e_phoff`, `sh_type`, etc.).
+//!
+//! Offsets in an ELF file are from the start of the file, so the file must
+//! start at the beginning of the buffer for the pointers to work.
+
+#[cfg(feature = "serialize")]
+use serde::{Serialize, Deserialize};
+
+use simple_error::SimpleResult;
+use sized_number::{Context, Endian, SizedDefinition, SizedDisplay};
+
+use crate::{H2Type, Offset};
+use crate::composite::{Constrained, Constraint, ConstraintAction, ConstraintValue, H2Array, H2Struct};
+use crate::formats::common::{field, hex, magic, number};
+use crate::simple::{BytesDisplay, BytesLength, H2Bytes, H2Pointer};
+
+// Section types (`sh_type`) that [`elf_tables`] knows how to read
+/// The magic bytes at the start of every ELF file.
+pub const MAGIC: &[u8] = b"\x7fELF";
+
+const SHT_SYMTAB:  u64 = 2;
+const SHT_DYNAMIC: u64 = 6;
+const SHT_DYNSYM:  u64 = 11;
+
+/// The class of an ELF file, from `e_ident[EI_CLASS]`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum ElfClass {
+    /// `ELFCLASS32`
+    Elf32,
+
+    /// `ELFCLASS64`
+    Elf64,
+}
+
+impl ElfClass {
+    /// The definition of an address, offset, or native-sized word.
+    fn word(self, endian: Endian) -> SizedDefinition {
+        match self {
+            Self::Elf32 => SizedDefinition::U32(endian),
+            Self::Elf64 => SizedDefinition::U64(endian),
+        }
+    }
+
+    /// The definition of a signed native-sized word.
+    fn sword(self, endian: Endian) -> SizedDefinition {
+        match self {
+            Self::Elf32 => SizedDefinition::I32(endian),
+            Self::Elf64 => SizedDefinition::I64(endian),
+        }
+    }
+}
+
+/// The identification bytes (`e_ident`), which are the same for every class
+/// and byte order.
+///
+/// The magic, class, byte order, and version are checked.
+pub fn ident() -> SimpleResult<H2Type> {
+    H2Struct::new(vec![
+        ("EI_MAG".to_string(), magic(MAGIC)?),
+        ("EI_CLASS".to_string(), Constrained::new(
+            number(SizedDefinition::U8),
+            Constraint::Range(1..=2),
+            ConstraintAction::Fail,
+        )?),
+        ("EI_DATA".to_string(), Constrained::new(
+            number(SizedDefinition::U8),
+            Constraint::Range(1..=2),
+            ConstraintAction::Fail,
+        )?),
+        ("EI_VERSION".to_string(), Constrained::new(
+            number(SizedDefinition::U8),
+            Constraint::Equals(ConstraintValue::Integer(1)),
+            ConstraintAction::Warn,
+        )?),
+        ("EI_OSABI".to_string(),      number(SizedDefinition::U8)),
+        ("EI_ABIVERSION".to_string(), number(SizedDefinition::U8)),
+        ("EI_PAD".to_string(),        H2Bytes::new(BytesLength::Fixed(7), BytesDisplay::Hex)?),
+    ])
+}
+
+/// Read the class and byte order from the identification bytes at the
+/// [`Context`]'s position.
+pub fn read_ident(context: Context) -> SimpleResult<(ElfClass, Endian)> {
+    let resolved = ident()?.resolve(Offset::from(context), None)?;
+
+    let class = match field(&resolved, "EI_CLASS")? {
+        1 => ElfClass::Elf32,
+        _ => ElfClass::Elf64,
+    };
+
+    let endian = match field(&resolved, "EI_DATA")? {
+        1 => Endian::Little,
+        _ => Endian::Big,
+    };
+
+    Ok((class, endian))
+}
+
+/// The ELF header (`Elf32_Ehdr` / `Elf64_Ehdr`).
+///
+/// If `phnum` or `shnum` are non-zero, `e_phoff` and `e_shoff` are pointers
+/// to arrays of that many program and section headers; otherwise, they're
+/// just numbers.
+pub fn elf_header(class: ElfClass, endian: Endian, phnum: u64, shnum: u64) -> SimpleResult<H2Type> {
+    let e_phoff = match phnum {
+        0 => hex(class.word(endian)),
+        n => H2Pointer::new(class.word(endian), SizedDisplay::Hex(Default::default()), H2Array::new(n, program_header(class, endian)?)?),
+    };
+
+    let e_shoff = match shnum {
+        0 => hex(class.word(endian)),
+        n => H2Pointer::new(class.word(endian), SizedDisplay::Hex(Default::default()), H2Array::new(n, section_header(class, endian)?)?),
+    };
+
+    H2Struct::new(vec![
+        ("e_ident".to_string(),     ident()?),
+        ("e_type".to_string(),      number(SizedDefinition::U16(endian))),
+        ("e_machine".to_string(),   number(SizedDefinition::U16(endian))),
+        ("e_version".to_string(),   number(SizedDefinition::U32(endian))),
+        ("e_entry".to_string(),     hex(class.word(endian))),
+        ("e_phoff".to_string(),     e_phoff),
+        ("e_shoff".to_string(),     e_shoff),
+        ("e_flags".to_string(),     hex(SizedDefinition::U32(endian))),
+        ("e_ehsize".to_string(),    number(SizedDefinition::U16(endian))),
+        ("e_phentsize".to_string(), number(SizedDefinition::U16(endian))),
+        ("e_phnum".to_string(),     number(SizedDefinition::U16(endian))),
+        ("e_shentsize".to_string(), number(SizedDefinition::U16(endian))),
+        ("e_shnum".to_string(),     number(SizedDefinition::U16(endian))),
+        ("e_shstrndx".to_string(),  number(SizedDefinition::U16(endian))),
+    ])
+}
+
+/// A program header (`Elf32_Phdr` / `Elf64_Phdr`).
+///
+/// The 64-bit version moves `p_flags` up, to keep the larger fields aligned.
+pub fn program_header(class: ElfClass, endian: Endian) -> SimpleResult<H2Type> {
+    let word = class.word(endian);
+
+    let mut fields = vec![
+        ("p_type".to_string(),   hex(SizedDefinition::U32(endian))),
+        ("p_offset".to_string(), hex(word)),
+        ("p_vaddr".to_string(),  hex(word)),
+        ("p_paddr".to_string(),  hex(word)),
+        ("p_filesz".to_string(), number(word)),
+        ("p_memsz".to_string(),  number(word)),
+        ("p_align".to_string(),  number(word)),
+    ];
+
+    let p_flags = ("p_flags".to_string(), hex(SizedDefinition::U32(endian)));
+    match class {
+        ElfClass::Elf32 => fields.insert(6, p_flags),
+        ElfClass::Elf64 => fields.insert(1, p_flags),
+    };
+
+    H2Struct::new(fields)
+}
+
+/// A section header (`Elf32_Shdr` / `Elf64_Shdr`).
+pub fn section_header(class: ElfClass, endian: Endian) -> SimpleResult<H2Type> {
+    let word = class.word(endian);
+
+    H2Struct::new(vec![
+        ("sh_name".to_string(),      number(SizedDefinition::U32(endian))),
+        ("sh_type".to_string(),      number(SizedDefinition::U32(endian))),
+        ("sh_flags".to_string(),     hex(word)),
+        ("sh_addr".to_string(),      hex(word)),
+        ("sh_offset".to_string(),    hex(word)),
+        ("sh_size".to_string(),      number(word)),
+        ("sh_link".to_string(),      number(SizedDefinition::U32(endian))),
+        ("sh_info".to_string(),      number(SizedDefinition::U32(endian))),
+        ("sh_addralign".to_string(), number(word)),
+        ("sh_entsize".to_string(),   number(word)),
+    ])
+}
+
+/// A symbol table entry (`Elf32_Sym` / `Elf64_Sym`).
+///
+/// Like the program header, the 64-bit version is reordered to keep the
+/// larger fields aligned.
+pub fn symbol(class: ElfClass, endian: Endian) -> SimpleResult<H2Type> {
+    let word = class.word(endian);
+
+    let st_name  = ("st_name".to_string(),  number(SizedDefinition::U32(endian)));
+    let st_value = ("st_value".to_string(), hex(word));
+    let st_size  = ("st_size".to_string(),  number(word));
+    let st_info  = ("st_info".to_string(),  hex(SizedDefinition::U8));
+    let st_other = ("st_other".to_string(), hex(SizedDefinition::U8));
+    let st_shndx = ("st_shndx".to_string(), number(SizedDefinition::U16(endian)));
+
+    match class {
+        ElfClass::Elf32 => H2Struct::new(vec![st_name, st_value, st_size, st_info, st_other, st_shndx]),
+        ElfClass::Elf64 => H2Struct::new(vec![st_name, st_info, st_other, st_shndx, st_value, st_size]),
+    }
+}
+
+/// A dynamic section entry (`Elf32_Dyn` / `Elf64_Dyn`).
+pub fn dynamic(class: ElfClass, endian: Endian) -> SimpleResult<H2Type> {
+    H2Struct::new(vec![
+        ("d_tag".to_string(), number(class.sword(endian))),
+        ("d_val".to_string(), hex(class.word(endian))),
+    ])
+}
+
+/// Read the ELF header at the [`Context`]'s position, and build its
+/// definition.
+///
+/// The class and byte order come from `e_ident`, and the number of program
+/// and section headers come from `e_phnum` and `e_shnum`.
+pub fn elf(context: Context) -> SimpleResult<H2Type> {
+    let (class, endian) = read_ident(context)?;
+
+    // Read the counts using a version of the header without tables
+    let resolved = elf_header(class, endian, 0, 0)?.resolve(Offset::from(context), None)?;
+
+    elf_header(class, endian, field(&resolved, "e_phnum")?, field(&resolved, "e_shnum")?)
+}
+
+/// Find the symbol tables (`SHT_SYMTAB` and `SHT_DYNSYM`) and dynamic tables
+/// (`SHT_DYNAMIC`) through the section headers.
+///
+/// Each one is returned as its offset and an array of [`symbol`] or
+/// [`dynamic`] entries, similar to [`crate::analyze::find_strings`].
+pub fn elf_tables(context: Context) -> SimpleResult<Vec<(u64, H2Type)>> {
+    let (class, endian) = read_ident(context)?;
+    let offset = Offset::from(context);
+
+    let header = elf_header(class, endian, 0, 0)?.resolve(offset, None)?;
+    let shoff = field(&header, "e_shoff")?;
+    let shnum = field(&header, "e_shnum")?;
+
+    if shnum == 0 {
+        return Ok(vec![]);
+    }
+
+    let sections = H2Array::new(shnum, section_header(class, endian)?)?.resolve(offset.at(shoff), None)?;
+
+    let mut tables = Vec::new();
+    for section in sections.children.iter() {
+        let entry = match field(section, "sh_type")? {
+            SHT_SYMTAB | SHT_DYNSYM => symbol(class, endian)?,
+            SHT_DYNAMIC             => dynamic(class, endian)?,
+            _                       => continue,
+        };
+
+        let count = field(section, "sh_size")? / entry.actual_size(offset)?;
+        if count == 0 {
+            continue;
+        }
+
+        tables.push((field(section, "sh_offset")?, H2Array::new(count, entry)?));
+    }
+
+    Ok(tables)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+    use sized_number::Context;
+
+    /// A tiny 64-bit little-endian executable: the header, one program
+    /// header, two section headers (the null section and a symbol table),
+    /// and two symbols.
+    fn elf64() -> Vec<u8> {
+        let mut data = Vec::new();
+
+        // e_ident
+        data.extend_from_slice(b"\x7fELF\x02\x01\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00");
+
+        data.extend_from_slice(&2u16.to_le_bytes());        // e_type
+        data.extend_from_slice(&0x3eu16.to_le_bytes());     // e_machine
+        data.extend_from_slice(&1u32.to_le_bytes());        // e_version
+        data.extend_from_slice(&0x401000u64.to_le_bytes()); // e_entry
+        data.extend_from_slice(&64u64.to_le_bytes());       // e_phoff
+        data.extend_from_slice(&120u64.to_le_bytes());      // e_shoff
+        data.extend_from_slice(&0u32.to_le_bytes());        // e_flags
+        data.extend_from_slice(&64u16.to_le_bytes());       // e_ehsize
+        data.extend_from_slice(&56u16.to_le_bytes());       // e_phentsize
+        data.extend_from_slice(&1u16.to_le_bytes());        // e_phnum
+        data.extend_from_slice(&64u16.to_le_bytes());       // e_shentsize
+        data.extend_from_slice(&2u16.to_le_bytes());        // e_shnum
+        data.extend_from_slice(&0u16.to_le_bytes());        // e_shstrndx
+
+        // Program header
+        data.extend_from_slice(&1u32.to_le_bytes());        // p_type
+        data.extend_from_slice(&5u32.to_le_bytes());        // p_flags
+        data.extend_from_slice(&0u64.to_le_bytes());        // p_offset
+        data.extend_from_slice(&0x400000u64.to_le_bytes()); // p_vaddr
+        data.extend_from_slice(&0x400000u64.to_le_bytes()); // p_paddr
+        data.extend_from_slice(&0x1000u64.to_le_bytes());   // p_filesz
+        data.extend_from_slice(&0x1000u64.to_le_bytes());   // p_memsz
+        data.extend_from_slice(&0x1000u64.to_le_bytes());   // p_align
+
+        // Section headers
+        data.extend_from_slice(&[0; 64]);
+        data.extend_from_slice(&0u32.to_le_bytes());        // sh_name
+        data.extend_from_slice(&2u32.to_le_bytes());        // sh_type
+        data.extend_from_slice(&0u64.to_le_bytes());        // sh_flags
+        data.extend_from_slice(&0u64.to_le_bytes());        // sh_addr
+        data.extend_from_slice(&248u64.to_le_bytes());      // sh_offset
+        data.extend_from_slice(&48u64.to_le_bytes());       // sh_size
+        data.extend_from_slice(&0u32.to_le_bytes());        // sh_link
+        data.extend_from_slice(&0u32.to_le_bytes());        // sh_info
+        data.extend_from_slice(&8u64.to_le_bytes());        // sh_addralign
+        data.extend_from_slice(&24u64.to_le_bytes());       // sh_entsize
+
+        // Symbols
+        data.extend_from_slice(&[0; 24]);
+        data.extend_from_slice(&1u32.to_le_bytes());        // st_name
+        data.extend_from_slice(&[0x12, 0x00]);              // st_info, st_other
+        data.extend_from_slice(&1u16.to_le_bytes());        // st_shndx
+        data.extend_from_slice(&0x401000u64.to_le_bytes()); // st_value
+        data.extend_from_slice(&16u64.to_le_bytes());       // st_size
+
+        data
+    }
+
+    #[test]
+    fn test_sizes() -> SimpleResult<()> {
+        let offset = Offset::Static(0);
+
+        let tests = vec![
+            (ElfClass::Elf32, 52, 32, 40, 16, 8),
+            (ElfClass::Elf64, 64, 56, 64, 24, 16),
+        ];
+
+        for (class, ehdr, phdr, shdr, sym, dyn_entry) in tests {
+            for endian in vec![Endian::Little, Endian::Big] {
+                assert_eq!(ehdr,      elf_header(class, endian, 1, 1)?.actual_size(offset)?);
+                assert_eq!(phdr,      program_header(class, endian)?.actual_size(offset)?);
+                assert_eq!(shdr,      section_header(class, endian)?.actual_size(offset)?);
+                assert_eq!(sym,       symbol(class, endian)?.actual_size(offset)?);
+                assert_eq!(dyn_entry, dynamic(class, endian)?.actual_size(offset)?);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_elf64() -> SimpleResult<()> {
+        let data = elf64();
+        let context = Context::new(&data);
+        let offset = Offset::from(context);
+
+        let (class, endian) = read_ident(context)?;
+        assert_eq!(ElfClass::Elf64, class);
+        assert!(matches!(endian, Endian::Little));
+
+        let r = elf(context)?.resolve(offset, None)?;
+        assert_eq!(0..64, r.actual_range);
+        assert_eq!(0x401000, field(&r, "e_entry")?);
+
+        // The program and section headers are found through pointers
+        let phdrs = &r.children[5];
+        assert_eq!(Some("e_phoff".to_string()), phdrs.field_name);
+        assert_eq!(64, phdrs.related[0].0);
+
+        let phdrs = phdrs.related[0].1.resolve(offset.at(64), None)?;
+        assert_eq!(1, phdrs.children.len());
+        assert_eq!(5, field(&phdrs.children[0], "p_flags")?);
+        assert_eq!(0x400000, field(&phdrs.children[0], "p_vaddr")?);
+
+        let shdrs = &r.children[6];
+        assert_eq!(120, shdrs.related[0].0);
+        let shdrs = shdrs.related[0].1.resolve(offset.at(120), None)?;
+        assert_eq!(2, shdrs.children.len());
+        assert_eq!(2, field(&shdrs.children[1], "sh_type")?);
+
+        // The symbol table is found through the section headers
+        let tables = elf_tables(context)?;
+        assert_eq!(1, tables.len());
+        assert_eq!(248, tables[0].0);
+
+        let symbols = tables[0].1.resolve(offset.at(248), None)?;
+        assert_eq!(2, symbols.children.len());
+        assert_eq!(0x401000, field(&symbols.children[1], "st_value")?);
+        assert_eq!(16, field(&symbols.children[1], "st_size")?);
+        assert_eq!(0x12, field(&symbols.children[1], "st_info")?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_elf32_big_endian() -> SimpleResult<()> {
+        // Just a header, with no tables
+        let mut data = b"\x7fELF\x01\x02\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00".to_vec();
+        data.extend_from_slice(&[0x00, 0x02, 0x00, 0x08]); // e_type, e_machine
+        data.extend_from_slice(&[0x00, 0x00, 0x00, 0x01]); // e_version
+        data.extend_from_slice(&[0x00, 0x40, 0x00, 0x00]); // e_entry
+        data.extend_from_slice(&[0; 24]);
+
+        let context = Context::new(&data);
+        let (class, endian) = read_ident(context)?;
+        assert_eq!(ElfClass::Elf32, class);
+        assert!(matches!(endian, Endian::Big));
+
+        let r = elf(context)?.resolve(Offset::from(context), None)?;
+        assert_eq!(0..52, r.actual_range);
+        assert_eq!(8, field(&r, "e_machine")?);
+        assert_eq!(0x400000, field(&r, "e_entry")?);
+        assert_eq!(0, elf_tables(context)?.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_ident() -> SimpleResult<()> {
+        let data = b"\x7fELG\x02\x01\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00".to_vec();
+        assert!(read_ident(Context::new(&data)).is_err());
+
+        let data = b"\x7fELF\x03\x01\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00".to_vec();
+        assert!(read_ident(Context::new(&data)).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,182 @@
+//! Definitions for gzip files (RFC 1952).
+//!
+//! A gzip file is a header, the compressed data, and a trailer with the CRC-32
+//! and size of the uncompressed data. The header has a fixed part, followed
+//! by optional fields that are present depending on its flags: extra data
+//! (`FEXTRA`), the original file name (`FNAME`), a comment (`FCOMMENT`), and
+//! a header CRC (`FHCRC`).
+//!
+//! The compressed data isn't decompressed, so the trailer's CRC can't be
+//! checked.
+
+use simple_error::{bail, SimpleResult};
+use sized_number::{Context, Endian, SizedDefinition};
+
+use crate::{H2Type, Offset};
+use crate::composite::{Constrained, Constraint, ConstraintAction, H2Struct};
+use crate::composite::string::NTString;
+use crate::formats::common::{equals, hex, magic, number};
+use crate::offset::buffer_end;
+use crate::simple::{BytesDisplay, BytesLength, H2Bytes};
+use crate::simple::character::{ASCII, StrictASCII};
+use crate::simple::time::{Timestamp, TimestampFormat};
+
+/// The magic bytes at the start of every gzip file.
+pub const MAGIC: &[u8] = b"\x1f\x8b";
+
+/// The header has a CRC-16.
+pub const FHCRC: u8 = 0x02;
+
+/// The header has extra data.
+pub const FEXTRA: u8 = 0x04;
+
+/// The header has the original file name.
+pub const FNAME: u8 = 0x08;
+
+/// The header has a comment.
+pub const FCOMMENT: u8 = 0x10;
+
+/// The only compression method, deflate.
+const METHOD_DEFLATE: u64 = 8;
+
+/// The flags that are reserved, and must be clear.
+const FRESERVED: u64 = 0xe0;
+
+const U16: SizedDefinition = SizedDefinition::U16(Endian::Little);
+const U32: SizedDefinition = SizedDefinition::U32(Endian::Little);
+
+/// The size of the trailer.
+const TRAILER_SIZE: u64 = 8;
+
+/// The header, including the optional fields that `flags` enables.
+///
+/// The method must be deflate, and the reserved flags must be clear, or
+/// there's a warning.
+///
+/// The file name and comment are ISO 8859-1 (Latin-1), NUL-terminated.
+pub fn header(flags: u8) -> SimpleResult<H2Type> {
+    let mut fields = vec![
+        ("magic".to_string(),  magic(MAGIC)?),
+        ("method".to_string(), equals(number(SizedDefinition::U8), METHOD_DEFLATE)?),
+        ("flags".to_string(),  Constrained::new(hex(SizedDefinition::U8), Constraint::Predicate(|flags| flags & FRESERVED == 0), ConstraintAction::Warn)?),
+        ("mtime".to_string(),  Timestamp::new(U32, TimestampFormat::UnixSeconds)?),
+        ("xfl".to_string(),    hex(SizedDefinition::U8)),
+        ("os".to_string(),     number(SizedDefinition::U8)),
+    ];
+
+    if flags & FEXTRA != 0 {
+        fields.push(("extra".to_string(), H2Bytes::new(BytesLength::Prefixed(Box::new(number(U16))), BytesDisplay::Hex)?));
+    }
+
+    if flags & FNAME != 0 {
+        fields.push(("name".to_string(), NTString::new(ASCII::new(StrictASCII::Permissive))?));
+    }
+
+    if flags & FCOMMENT != 0 {
+        fields.push(("comment".to_string(), NTString::new(ASCII::new(StrictASCII::Permissive))?));
+    }
+
+    if flags & FHCRC != 0 {
+        fields.push(("header_crc".to_string(), hex(U16)));
+    }
+
+    H2Struct::new(fields)
+}
+
+/// The trailer: the CRC-32 and size (modulo 2^32) of the uncompressed data.
+pub fn trailer() -> SimpleResult<H2Type> {
+    H2Struct::new(vec![
+        ("crc32".to_string(), hex(U32)),
+        ("isize".to_string(), number(U32)),
+    ])
+}
+
+/// Read the gzip file at the [`Context`]'s position, which is assumed to run
+/// to the end of the buffer, and build the definition: the header, the
+/// compressed data, and the trailer.
+pub fn gzip(context: Context) -> SimpleResult<H2Type> {
+    let offset = Offset::from(context);
+
+    magic(MAGIC)?.resolve(offset, None)?;
+    let flags = context.at(offset.position() + 3).read_u8()?;
+
+    let header = header(flags)?;
+    let header_size = header.actual_size(offset)?;
+
+    let end = buffer_end(context);
+    let data_start = offset.position() + header_size;
+
+    if data_start + TRAILER_SIZE > end {
+        bail!("gzip file is too short");
+    }
+
+    let mut fields = vec![
+        ("header".to_string(), header),
+    ];
+
+    // Empty input still produces some compressed data, but don't count on it
+    if data_start + TRAILER_SIZE < end {
+        fields.push(("data".to_string(), H2Bytes::new(BytesLength::Fixed(end - data_start - TRAILER_SIZE), BytesDisplay::Preview(16))?));
+    }
+
+    fields.push(("trailer".to_string(), trailer()?));
+
+    H2Struct::new(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+    use sized_number::Context;
+
+    use crate::formats::common::field;
+
+    #[test]
+    fn test_gzip() -> SimpleResult<()> {
+        // "hello", with extra data, a name, and a comment
+        let data = b"\x1f\x8b\x08\x1c\x00\x10\x5e\x5f\x02\x03\x04\x00\x41\x42\x01\x02hello.txt\x00hi\x00\xcb\x48\xcd\xc9\xc9\x07\x00\x86\xa6\x10\x36\x05\x00\x00\x00".to_vec();
+        let context = Context::new(&data);
+
+        let r = gzip(context)?.resolve(Offset::from(context), None)?;
+        assert_eq!(0..(data.len() as u64), r.actual_range);
+
+        let header = &r.children[0];
+        assert_eq!(0..29, header.actual_range);
+        assert_eq!("2020-09-13T12:26:40Z", header.children[3].display);
+        assert_eq!(Some("extra".to_string()), header.children[6].field_name);
+        assert_eq!(Some("hello.txt".to_string()), header.children[7].as_string);
+        assert_eq!(Some("hi".to_string()), header.children[8].as_string);
+
+        assert_eq!(29..36, r.children[1].actual_range);
+
+        let trailer = &r.children[2];
+        assert_eq!(0x3610a686, field(trailer, "crc32")?);
+        assert_eq!(5, field(trailer, "isize")?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_header_flags() -> SimpleResult<()> {
+        let offset = Offset::Static(0);
+
+        assert_eq!(10, header(0)?.actual_size(offset)?);
+        assert_eq!(12, header(FHCRC)?.actual_size(offset)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad() -> SimpleResult<()> {
+        // Too short for a trailer
+        let data = b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x00\x03\x00\x00".to_vec();
+        assert!(gzip(Context::new(&data)).is_err());
+
+        // Not gzip
+        let data = b"\x1f\x8c\x08\x00\x00\x00\x00\x00\x00\x03\x00\x00\x00\x00\x00\x00\x00\x00".to_vec();
+        assert!(gzip(Context::new(&data)).is_err());
+
+        Ok(())
+    }
+}
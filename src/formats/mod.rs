@@ -0,0 +1,24 @@
+//! Ready-made definitions for common file formats.
+//!
+//! Most formats can't be described by a single fixed type - the layout
+//! depends on values in the header, such as the byte order or the number of
+//! entries in a table. So each format has functions that build the individual
+//! structures from the parameters they depend on, and a function that reads
+//! those parameters from a buffer and builds the whole thing.
+//!
+//! Some layouts can't be expressed with the composite types at all: PNG's
+//! chunks repeat until an `IEND` chunk, and ZIP's end of central directory
+//! record is found by scanning backwards from the end of the buffer. There's
+//! no generic "repeat until" or "scan from the end" type for those - [`png`]
+//! and [`zip`] are ad-hoc walkers, written in Rust, that read the buffer and
+//! then build a fixed definition of what they found. That definition only
+//! describes the buffer it was built from.
+
+pub mod common;
+pub mod elf;
+pub mod gzip;
+pub mod net;
+pub mod pcap;
+pub mod pe;
+pub mod png;
+pub mod zip;
@@ -0,0 +1,711 @@
+//! Definitions for network packets.
+//!
+//! Each layer has a builder for its header: an Ethernet II frame (with any
+//! number of VLAN tags), IPv4 (with options), IPv6 (with extension headers),
+//! TCP (with options), UDP, ICMP, and DNS messages.
+//!
+//! Since each layer picks the next one - the Ethernet frame by its
+//! ethertype, IP by its protocol, and UDP by its port - [`ethernet`] and
+//! [`ip`] walk a packet to build the full definition. Each layer is a field
+//! named after it (`ethernet`, `ipv4`, `tcp`, ...), and whatever isn't
+//! understood is left as `payload` at the end.
+//!
+//! Everything is big endian ("network order"). TCP's data offset and flags
+//! are bit fields ([`crate::simple::BitNumber`]s); the other flags and
+//! bitfields are displayed in hex.
+
+use simple_error::{bail, SimpleResult};
+use sized_number::{Context, Endian, SizedDefinition, SizedDisplay};
+
+use crate::{H2Type, Offset};
+use crate::composite::H2Struct;
+use crate::formats::common::{field, hex, number};
+use crate::offset::buffer_end;
+use crate::simple::{BitNumber, BitOrder, BytesDisplay, BytesLength, Checksum, ChecksumAlgorithm, ChecksumRange, H2Bytes};
+use crate::simple::network::{DNSName, IPv4, IPv6, MacAddress};
+
+// Ethertypes
+pub const ETHERTYPE_IPV4: u64 = 0x0800;
+pub const ETHERTYPE_IPV6: u64 = 0x86dd;
+pub const ETHERTYPE_VLAN: u64 = 0x8100;
+pub const ETHERTYPE_QINQ: u64 = 0x88a8;
+
+// IP protocols (including IPv6 extension headers)
+pub const PROTOCOL_HOP_BY_HOP: u64 = 0;
+pub const PROTOCOL_ICMP: u64 = 1;
+pub const PROTOCOL_TCP: u64 = 6;
+pub const PROTOCOL_UDP: u64 = 17;
+pub const PROTOCOL_ROUTING: u64 = 43;
+pub const PROTOCOL_FRAGMENT: u64 = 44;
+pub const PROTOCOL_AUTHENTICATION: u64 = 51;
+pub const PROTOCOL_ICMPV6: u64 = 58;
+pub const PROTOCOL_DESTINATION_OPTIONS: u64 = 60;
+
+/// The UDP port for DNS.
+pub const DNS_PORT: u64 = 53;
+
+const U16: SizedDefinition = SizedDefinition::U16(Endian::Big);
+const U32: SizedDefinition = SizedDefinition::U32(Endian::Big);
+
+const ETHERNET_HEADER_SIZE: u64 = 14;
+const VLAN_TAG_SIZE: u64 = 4;
+const IPV4_HEADER_SIZE: u64 = 20;
+const IPV6_HEADER_SIZE: u64 = 40;
+const TCP_HEADER_SIZE: u64 = 20;
+const UDP_HEADER_SIZE: u64 = 8;
+const DNS_HEADER_SIZE: u64 = 12;
+
+/// A big-endian bit field that's `width` bits wide.
+fn bits(width: u8) -> SimpleResult<H2Type> {
+    BitNumber::new(width, false, BitOrder::MSBFirst, SizedDisplay::Decimal)
+}
+
+/// An 802.1Q VLAN tag: the tag protocol identifier (`0x8100`, or `0x88a8`
+/// for an outer QinQ tag), then the priority, drop eligible bit, and VLAN id.
+pub fn vlan_tag() -> SimpleResult<H2Type> {
+    H2Struct::new(vec![
+        ("tpid".to_string(), hex(U16)),
+        ("tci".to_string(),  hex(U16)),
+    ])
+}
+
+/// An Ethernet II header with `vlan_tags` VLAN tags between the addresses
+/// and the ethertype.
+pub fn ethernet_header(vlan_tags: u64) -> SimpleResult<H2Type> {
+    let mut fields = vec![
+        ("destination".to_string(), MacAddress::new()),
+        ("source".to_string(),      MacAddress::new()),
+    ];
+
+    for _ in 0..vlan_tags {
+        fields.push(("vlan".to_string(), vlan_tag()?));
+    }
+
+    fields.push(("ethertype".to_string(), hex(U16)));
+
+    H2Struct::new(fields)
+}
+
+/// An IPv4 header, with `options` after the fixed part if it's set.
+///
+/// The checksum covers the whole header, including the options.
+pub fn ipv4_header(options: Option<H2Type>) -> SimpleResult<H2Type> {
+    let mut fields = vec![
+        ("version_ihl".to_string(),     hex(SizedDefinition::U8)),
+        ("dscp_ecn".to_string(),        hex(SizedDefinition::U8)),
+        ("total_length".to_string(),    number(U16)),
+        ("identification".to_string(),  hex(U16)),
+        ("flags_fragment".to_string(),  hex(U16)),
+        ("ttl".to_string(),             number(SizedDefinition::U8)),
+        ("protocol".to_string(),        number(SizedDefinition::U8)),
+        ("checksum".to_string(),        Checksum::new(U16, ChecksumAlgorithm::Internet, ChecksumRange::Struct)?),
+        ("source".to_string(),          IPv4::new(Endian::Big)),
+        ("destination".to_string(),     IPv4::new(Endian::Big)),
+    ];
+
+    if let Some(options) = options {
+        fields.push(("options".to_string(), options));
+    }
+
+    H2Struct::new(fields)
+}
+
+/// The fixed IPv6 header.
+pub fn ipv6_header() -> SimpleResult<H2Type> {
+    H2Struct::new(vec![
+        ("version_class_flow".to_string(), hex(U32)),
+        ("payload_length".to_string(),     number(U16)),
+        ("next_header".to_string(),        number(SizedDefinition::U8)),
+        ("hop_limit".to_string(),          number(SizedDefinition::U8)),
+        ("source".to_string(),             IPv6::new(Endian::Big)),
+        ("destination".to_string(),        IPv6::new(Endian::Big)),
+    ])
+}
+
+/// An IPv6 extension header that's `length` bytes long, including the next
+/// header and length fields.
+pub fn ipv6_extension_header(length: u64) -> SimpleResult<H2Type> {
+    if length < 8 {
+        bail!("IPv6 extension headers are at least 8 bytes");
+    }
+
+    H2Struct::new(vec![
+        ("next_header".to_string(), number(SizedDefinition::U8)),
+        ("length".to_string(),      number(SizedDefinition::U8)),
+        ("data".to_string(),        H2Bytes::new(BytesLength::Fixed(length - 2), BytesDisplay::Hex)?),
+    ])
+}
+
+/// A TCP header, with `options` after the fixed part if it's set.
+///
+/// The data offset (in 32-bit words) and each of the flags, including NS,
+/// are bit fields.
+pub fn tcp_header(options: Option<H2Type>) -> SimpleResult<H2Type> {
+    let mut fields = vec![
+        ("source_port".to_string(),      number(U16)),
+        ("destination_port".to_string(), number(U16)),
+        ("sequence".to_string(),         number(U32)),
+        ("acknowledgment".to_string(),   number(U32)),
+        ("data_offset".to_string(),      bits(4)?),
+        ("reserved".to_string(),         bits(3)?),
+        ("ns".to_string(),               bits(1)?),
+        ("cwr".to_string(),              bits(1)?),
+        ("ece".to_string(),              bits(1)?),
+        ("urg".to_string(),              bits(1)?),
+        ("ack".to_string(),              bits(1)?),
+        ("psh".to_string(),              bits(1)?),
+        ("rst".to_string(),              bits(1)?),
+        ("syn".to_string(),              bits(1)?),
+        ("fin".to_string(),              bits(1)?),
+        ("window".to_string(),           number(U16)),
+        ("checksum".to_string(),         hex(U16)),
+        ("urgent_pointer".to_string(),   number(U16)),
+    ];
+
+    if let Some(options) = options {
+        fields.push(("options".to_string(), options));
+    }
+
+    H2Struct::new(fields)
+}
+
+/// A TCP option with the given kind and length (including the kind and
+/// length fields), and its name.
+///
+/// The end-of-options and no-op options are a single byte; the rest have a
+/// length, and the well-known ones have their values broken out.
+pub fn tcp_option(kind: u8, length: u64) -> SimpleResult<(String, H2Type)> {
+    let (name, values) = match (kind, length) {
+        (0, _)  => return Ok(("eol".to_string(), number(SizedDefinition::U8))),
+        (1, _)  => return Ok(("nop".to_string(), number(SizedDefinition::U8))),
+        (2, 4)  => ("mss", vec![("value".to_string(), number(U16))]),
+        (3, 3)  => ("window_scale", vec![("shift".to_string(), number(SizedDefinition::U8))]),
+        (4, 2)  => ("sack_permitted", vec![]),
+        (8, 10) => ("timestamps", vec![("value".to_string(), number(U32)), ("echo".to_string(), number(U32))]),
+        (_, 0..2) => bail!("TCP option {} has a bad length: {}", kind, length),
+        (5, _)  => ("sack", vec![("blocks".to_string(), H2Bytes::new(BytesLength::Fixed(length - 2), BytesDisplay::Hex)?)]),
+        (_, 2)  => ("option", vec![]),
+        (_, _)  => ("option", vec![("data".to_string(), H2Bytes::new(BytesLength::Fixed(length - 2), BytesDisplay::Hex)?)]),
+    };
+
+    let mut fields = vec![
+        ("kind".to_string(),   number(SizedDefinition::U8)),
+        ("length".to_string(), number(SizedDefinition::U8)),
+    ];
+    fields.extend(values);
+
+    Ok((name.to_string(), H2Struct::new(fields)?))
+}
+
+/// Walk `length` bytes of TCP options at the [`Context`]'s position. Anything
+/// after the end-of-options option is `padding`.
+pub fn tcp_options(context: Context, length: u64) -> SimpleResult<H2Type> {
+    let start = context.position();
+    let end = start + length;
+
+    let mut fields = vec![];
+    let mut position = start;
+
+    while position < end {
+        let kind = context.at(position).read_u8()?;
+        let option_length = match kind {
+            0 | 1 => 1,
+            _     => context.at(position + 1).read_u8()? as u64,
+        };
+
+        let (name, t) = tcp_option(kind, option_length)?;
+        fields.push((name, t));
+        position += option_length;
+
+        if kind == 0 && position < end {
+            fields.push(("padding".to_string(), H2Bytes::new(BytesLength::Fixed(end - position), BytesDisplay::Hex)?));
+            position = end;
+        }
+    }
+
+    if position != end {
+        bail!("TCP options run past the end of the header");
+    }
+
+    H2Struct::new(fields)
+}
+
+/// A UDP header.
+pub fn udp_header() -> SimpleResult<H2Type> {
+    H2Struct::new(vec![
+        ("source_port".to_string(),      number(U16)),
+        ("destination_port".to_string(), number(U16)),
+        ("length".to_string(),           number(U16)),
+        ("checksum".to_string(),         hex(U16)),
+    ])
+}
+
+/// An ICMP (or ICMPv6) header.
+///
+/// If `message_length` is set, the checksum is verified over that many bytes
+/// from the start of the header. ICMPv6 checksums include the IPv6
+/// pseudo-header, so they can't be verified here.
+pub fn icmp_header(message_length: Option<u64>) -> SimpleResult<H2Type> {
+    let checksum = match message_length {
+        Some(length) => Checksum::new(U16, ChecksumAlgorithm::Internet, ChecksumRange::Relative(-2..(length as i64 - 2)))?,
+        None         => hex(U16),
+    };
+
+    H2Struct::new(vec![
+        ("type".to_string(),           number(SizedDefinition::U8)),
+        ("code".to_string(),           number(SizedDefinition::U8)),
+        ("checksum".to_string(),       checksum),
+        ("rest_of_header".to_string(), hex(U32)),
+    ])
+}
+
+/// A DNS message header.
+pub fn dns_header() -> SimpleResult<H2Type> {
+    H2Struct::new(vec![
+        ("id".to_string(),               hex(U16)),
+        ("flags".to_string(),            hex(U16)),
+        ("question_count".to_string(),   number(U16)),
+        ("answer_count".to_string(),     number(U16)),
+        ("authority_count".to_string(),  number(U16)),
+        ("additional_count".to_string(), number(U16)),
+    ])
+}
+
+/// A question in a DNS message that starts at `message_start`.
+pub fn dns_question(message_start: u64) -> SimpleResult<H2Type> {
+    H2Struct::new(vec![
+        ("name".to_string(),  DNSName::new(message_start)),
+        ("type".to_string(),  number(U16)),
+        ("class".to_string(), number(U16)),
+    ])
+}
+
+/// A resource record in a DNS message that starts at `message_start`, with
+/// `rdata` as its data.
+pub fn dns_resource_record(message_start: u64, rdata: H2Type) -> SimpleResult<H2Type> {
+    H2Struct::new(vec![
+        ("name".to_string(),     DNSName::new(message_start)),
+        ("type".to_string(),     number(U16)),
+        ("class".to_string(),    number(U16)),
+        ("ttl".to_string(),      number(U32)),
+        ("rdlength".to_string(), number(U16)),
+        ("rdata".to_string(),    rdata),
+    ])
+}
+
+/// The data for a resource record of the given type and length: addresses
+/// for `A` and `AAAA`, names for `NS`, `CNAME`, and `PTR`, and bytes for
+/// everything else.
+fn dns_rdata(message_start: u64, record_type: u64, length: u64) -> SimpleResult<H2Type> {
+    Ok(match (record_type, length) {
+        (1, 4)          => IPv4::new(Endian::Big),
+        (28, 16)        => IPv6::new(Endian::Big),
+        (2, _) | (5, _) | (12, _) => DNSName::new(message_start),
+        (_, _)          => H2Bytes::new(BytesLength::Fixed(length), BytesDisplay::Hex)?,
+    })
+}
+
+/// Read the DNS message at the [`Context`]'s position, and build the
+/// definition: the header, then the questions, answers, authority records,
+/// and additional records, each grouped into a struct (and left out if
+/// there are none). Each question and record is named by its domain name.
+pub fn dns(context: Context) -> SimpleResult<H2Type> {
+    let offset = Offset::from(context);
+    let message_start = offset.position();
+
+    let header = dns_header()?.resolve(offset, None)?;
+    let mut fields = vec![
+        ("header".to_string(), dns_header()?),
+    ];
+
+    let mut position = message_start + DNS_HEADER_SIZE;
+
+    let mut questions = vec![];
+    for _ in 0..field(&header, "question_count")? {
+        let t = dns_question(message_start)?;
+        let name = DNSName::new(message_start).to_string(offset.at(position))?;
+
+        position += t.actual_size(offset.at(position))?;
+        questions.push((name, t));
+    }
+
+    if !questions.is_empty() {
+        fields.push(("questions".to_string(), H2Struct::new(questions)?));
+    }
+
+    for (group, count) in &[("answers", "answer_count"), ("authority", "authority_count"), ("additional", "additional_count")] {
+        let mut records = vec![];
+
+        for _ in 0..field(&header, count)? {
+            let name_type = DNSName::new(message_start);
+            let name = name_type.to_string(offset.at(position))?;
+            let fixed = position + name_type.actual_size(offset.at(position))?;
+
+            let record_type = number(U16).to_u64(offset.at(fixed))?;
+            let length = number(U16).to_u64(offset.at(fixed + 8))?;
+
+            let t = dns_resource_record(message_start, dns_rdata(message_start, record_type, length)?)?;
+
+            // The rdata has to fill rdlength exactly
+            let end = fixed + 10 + length;
+            if position + t.actual_size(offset.at(position))? != end {
+                bail!("DNS record {} doesn't match its rdlength", name);
+            }
+
+            position = end;
+            records.push((name, t));
+        }
+
+        if !records.is_empty() {
+            fields.push((group.to_string(), H2Struct::new(records)?));
+        }
+    }
+
+    H2Struct::new(fields)
+}
+
+/// The layers found while walking a packet.
+type Layers = Vec<(String, H2Type)>;
+
+/// Add whatever's left between `position` and `end` as the payload.
+fn payload(layers: &mut Layers, position: u64, end: u64) -> SimpleResult<()> {
+    if position < end {
+        layers.push(("payload".to_string(), H2Bytes::new(BytesLength::Fixed(end - position), BytesDisplay::Preview(16))?));
+    }
+
+    Ok(())
+}
+
+/// Walk the layer after an Ethernet header, by its ethertype.
+fn walk_ethertype(layers: &mut Layers, offset: Offset, ethertype: u64, end: u64) -> SimpleResult<()> {
+    match ethertype {
+        ETHERTYPE_IPV4 => walk_ipv4(layers, offset, end),
+        ETHERTYPE_IPV6 => walk_ipv6(layers, offset, end),
+        _              => payload(layers, offset.position(), end),
+    }
+}
+
+/// Walk the IP layer, by its version.
+fn walk_ip(layers: &mut Layers, offset: Offset, end: u64) -> SimpleResult<()> {
+    match offset.get_dynamic()?.read_u8()? >> 4 {
+        4 => walk_ipv4(layers, offset, end),
+        6 => walk_ipv6(layers, offset, end),
+        v => bail!("Unknown IP version: {}", v),
+    }
+}
+
+fn walk_ipv4(layers: &mut Layers, offset: Offset, end: u64) -> SimpleResult<()> {
+    let start = offset.position();
+    let context = offset.get_dynamic()?;
+
+    let header_length = (context.read_u8()? & 0x0f) as u64 * 4;
+    if header_length < IPV4_HEADER_SIZE {
+        bail!("IPv4 header is too short: {} bytes", header_length);
+    }
+
+    let options = match header_length - IPV4_HEADER_SIZE {
+        0 => None,
+        n => Some(H2Bytes::new(BytesLength::Fixed(n), BytesDisplay::Hex)?),
+    };
+
+    let t = ipv4_header(options)?;
+    let header = t.resolve(offset, None)?;
+
+    // The total length bounds the packet, but the capture might be shorter
+    let total_length = field(&header, "total_length")?;
+    if total_length < header_length {
+        bail!("IPv4 total length is shorter than the header");
+    }
+    let end = std::cmp::min(end, start + total_length);
+
+    layers.push(("ipv4".to_string(), t));
+
+    // Only the first fragment has the next header
+    match field(&header, "flags_fragment")? & 0x1fff {
+        0 => walk_protocol(layers, offset.at(start + header_length), field(&header, "protocol")?, end),
+        _ => payload(layers, start + header_length, end),
+    }
+}
+
+fn walk_ipv6(layers: &mut Layers, offset: Offset, end: u64) -> SimpleResult<()> {
+    let start = offset.position();
+
+    let t = ipv6_header()?;
+    let header = t.resolve(offset, None)?;
+    let end = std::cmp::min(end, start + IPV6_HEADER_SIZE + field(&header, "payload_length")?);
+    layers.push(("ipv6".to_string(), t));
+
+    let mut next_header = field(&header, "next_header")?;
+    let mut position = start + IPV6_HEADER_SIZE;
+
+    loop {
+        let name = match next_header {
+            PROTOCOL_HOP_BY_HOP          => "hop_by_hop",
+            PROTOCOL_ROUTING             => "routing",
+            PROTOCOL_FRAGMENT            => "fragment",
+            PROTOCOL_AUTHENTICATION      => "authentication",
+            PROTOCOL_DESTINATION_OPTIONS => "destination_options",
+            _                            => break,
+        };
+
+        let context = offset.get_dynamic()?.at(position);
+        let length = context.at(position + 1).read_u8()? as u64;
+        let length = match next_header {
+            PROTOCOL_FRAGMENT       => 8,
+            PROTOCOL_AUTHENTICATION => (length + 2) * 4,
+            _                       => (length + 1) * 8,
+        };
+
+        if position + length > end {
+            bail!("IPv6 extension header runs past the end of the packet");
+        }
+
+        layers.push((name.to_string(), ipv6_extension_header(length)?));
+
+        // Only the first fragment has the next header
+        if next_header == PROTOCOL_FRAGMENT && number(U16).to_u64(offset.at(position + 2))? & 0xfff8 != 0 {
+            return payload(layers, position + length, end);
+        }
+
+        next_header = context.read_u8()? as u64;
+        position += length;
+    }
+
+    walk_protocol(layers, offset.at(position), next_header, end)
+}
+
+/// Walk the layer after an IP header, by its protocol.
+fn walk_protocol(layers: &mut Layers, offset: Offset, protocol: u64, end: u64) -> SimpleResult<()> {
+    let start = offset.position();
+
+    match protocol {
+        PROTOCOL_TCP => {
+            let data_offset = (offset.get_dynamic()?.at(start + 12).read_u8()? >> 4) as u64 * 4;
+            let options = match data_offset {
+                0..TCP_HEADER_SIZE => bail!("TCP header is too short: {} bytes", data_offset),
+                TCP_HEADER_SIZE    => None,
+                n                  => Some(tcp_options(offset.get_dynamic()?.at(start + TCP_HEADER_SIZE), n - TCP_HEADER_SIZE)?),
+            };
+
+            layers.push(("tcp".to_string(), tcp_header(options)?));
+            payload(layers, start + data_offset, end)
+        },
+        PROTOCOL_UDP => {
+            let t = udp_header()?;
+            let header = t.resolve(offset, None)?;
+            layers.push(("udp".to_string(), t));
+
+            let position = start + UDP_HEADER_SIZE;
+            if position < end && (field(&header, "source_port")? == DNS_PORT || field(&header, "destination_port")? == DNS_PORT) {
+                let message = dns(offset.get_dynamic()?.at(position))?;
+                let size = message.actual_size(offset.at(position))?;
+
+                layers.push(("dns".to_string(), message));
+                payload(layers, position + size, end)
+            } else {
+                payload(layers, position, end)
+            }
+        },
+        PROTOCOL_ICMP => {
+            layers.push(("icmp".to_string(), icmp_header(Some(end.saturating_sub(start)))?));
+            payload(layers, start + 8, end)
+        },
+        PROTOCOL_ICMPV6 => {
+            layers.push(("icmpv6".to_string(), icmp_header(None)?));
+            payload(layers, start + 8, end)
+        },
+        _ => payload(layers, start, end),
+    }
+}
+
+/// Where the packet at the [`Offset`] ends: the end of the bounds if it's
+/// bounded, or the end of the buffer.
+fn packet_end(offset: Offset) -> SimpleResult<u64> {
+    match offset.bounds() {
+        Some(bounds) => Ok(bounds.end),
+        None         => Ok(buffer_end(offset.get_dynamic()?)),
+    }
+}
+
+/// Walk the Ethernet II frame at the [`Context`]'s position, which is
+/// assumed to run to the end of the buffer, and build the definition: one
+/// field per layer, ending with any payload that isn't understood.
+pub fn ethernet(context: Context) -> SimpleResult<H2Type> {
+    ethernet_at(Offset::from(context))
+}
+
+/// Like [`ethernet`], but if the [`Offset`] is bounded (such as a packet in
+/// a capture), the frame ends at the end of the bounds.
+pub fn ethernet_at(offset: Offset) -> SimpleResult<H2Type> {
+    let start = offset.position();
+
+    // Count the VLAN tags, which go where the ethertype normally is
+    let mut vlan_tags = 0;
+    let ethertype = loop {
+        let ethertype = number(U16).to_u64(offset.at(start + 12 + vlan_tags * VLAN_TAG_SIZE))?;
+
+        match ethertype {
+            ETHERTYPE_VLAN | ETHERTYPE_QINQ => vlan_tags += 1,
+            _                               => break ethertype,
+        }
+    };
+
+    let mut layers = vec![
+        ("ethernet".to_string(), ethernet_header(vlan_tags)?),
+    ];
+
+    let position = start + ETHERNET_HEADER_SIZE + vlan_tags * VLAN_TAG_SIZE;
+    walk_ethertype(&mut layers, offset.at(position), ethertype, packet_end(offset)?)?;
+
+    H2Struct::new(layers)
+}
+
+/// Walk the raw IP (version 4 or 6) packet at the [`Context`]'s position,
+/// which is assumed to run to the end of the buffer, and build the
+/// definition like [`ethernet`].
+pub fn ip(context: Context) -> SimpleResult<H2Type> {
+    ip_at(Offset::from(context))
+}
+
+/// Like [`ip`], but if the [`Offset`] is bounded, the packet ends at the end
+/// of the bounds.
+pub fn ip_at(offset: Offset) -> SimpleResult<H2Type> {
+    let mut layers = vec![];
+    walk_ip(&mut layers, offset, packet_end(offset)?)?;
+
+    H2Struct::new(layers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+    use sized_number::Context;
+
+    use crate::ResolvedType;
+
+    fn names(r: &ResolvedType) -> Vec<String> {
+        r.children.iter().map(|c| c.field_name.clone().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_dns_over_udp() -> SimpleResult<()> {
+        // A VLAN-tagged DNS response for example.com, with an A record and a
+        // compressed CNAME
+        let data = b"\x00\x11\x22\x33\x44\x55\x66\x77\x88\x99\xaa\xbb\x81\x00\x00\x64\x08\x00\x45\x00\x00\x5b\x11\x11\x40\x00\x40\x11\x57\xc7\x08\x08\x08\x08\xc0\xa8\x01\x02\x00\x35\x9c\x40\x00\x47\x00\x00\x12\x34\x81\x80\x00\x01\x00\x02\x00\x00\x00\x00\x07\x65\x78\x61\x6d\x70\x6c\x65\x03\x63\x6f\x6d\x00\x00\x01\x00\x01\xc0\x0c\x00\x01\x00\x01\x00\x00\x01\x2c\x00\x04\x5d\xb8\xd8\x22\x03\x77\x77\x77\xc0\x0c\x00\x05\x00\x01\x00\x00\x00\x3c\x00\x02\xc0\x0c".to_vec();
+        let context = Context::new(&data);
+
+        let r = ethernet(context)?.resolve(Offset::from(context), None)?;
+        assert_eq!(0..(data.len() as u64), r.actual_range);
+        assert_eq!(vec!["ethernet", "ipv4", "udp", "dns"], names(&r));
+
+        let ethernet = &r.children[0];
+        assert_eq!(vec!["destination", "source", "vlan", "ethertype"], names(ethernet));
+        assert_eq!(ETHERTYPE_IPV4, field(ethernet, "ethertype")?);
+
+        let ipv4 = &r.children[1];
+        assert_eq!(18..38, ipv4.actual_range);
+        assert_eq!(Some(true), ipv4.children[7].valid);
+        assert_eq!("8.8.8.8", ipv4.children[8].display);
+
+        let dns = &r.children[3];
+        assert_eq!(46..109, dns.actual_range);
+        assert_eq!(vec!["header", "questions", "answers"], names(dns));
+        assert_eq!(vec!["example.com"], names(&dns.children[1]));
+        assert_eq!(vec!["example.com", "www.example.com"], names(&dns.children[2]));
+
+        // The A record's address, and the CNAME's target
+        assert_eq!("93.184.216.34", dns.children[2].children[0].children[5].display);
+        assert_eq!(Some("example.com".to_string()), dns.children[2].children[1].children[5].as_string);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tcp_over_ipv6() -> SimpleResult<()> {
+        // A SYN with options, after a hop-by-hop extension header
+        let data = b"\x00\x11\x22\x33\x44\x55\x66\x77\x88\x99\xaa\xbb\x86\xdd\x60\x00\x00\x00\x00\x32\x00\x40\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x02\x06\x00\x01\x04\x00\x00\x00\x00\x9c\x40\x01\xbb\x00\x00\x03\xe8\x00\x00\x00\x00\xa0\x02\xff\xff\x00\x00\x00\x00\x02\x04\x05\xb4\x04\x02\x08\x0a\x00\x00\x00\x01\x00\x00\x00\x00\x01\x03\x03\x07\x68\x69".to_vec();
+        let context = Context::new(&data);
+
+        let r = ethernet(context)?.resolve(Offset::from(context), None)?;
+        assert_eq!(vec!["ethernet", "ipv6", "hop_by_hop", "tcp", "payload"], names(&r));
+
+        let tcp = &r.children[3];
+        assert_eq!(62..102, tcp.actual_range);
+        assert_eq!(443, field(tcp, "destination_port")?);
+        assert_eq!(10, field(tcp, "data_offset")?);
+        assert_eq!(1, field(tcp, "syn")?);
+        assert_eq!(0, field(tcp, "ack")?);
+        assert_eq!(0, field(tcp, "ns")?);
+
+        // NS is the last bit of the data offset's byte, and FIN is the last
+        // bit of the next byte
+        assert_eq!(74..75, tcp.children[6].actual_range);
+        assert_eq!(Some(vec![599..600]), tcp.children[6].bit_ranges);
+        assert_eq!(75..76, tcp.children[14].actual_range);
+        assert_eq!(Some(vec![607..608]), tcp.children[14].bit_ranges);
+        assert_eq!(76..78, tcp.children[15].actual_range);
+
+        let options = &tcp.children[18];
+        assert_eq!(vec!["mss", "sack_permitted", "timestamps", "nop", "window_scale"], names(options));
+        assert_eq!(1460, field(&options.children[0], "value")?);
+        assert_eq!(7, field(&options.children[4], "shift")?);
+
+        assert_eq!(102..104, r.children[4].actual_range);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_icmp() -> SimpleResult<()> {
+        // A raw IPv4 echo request, with four bytes of options
+        let data = b"\x46\x00\x00\x24\x22\x22\x00\x00\x40\x01\x41\xb4\x0a\x00\x00\x01\x0a\x00\x00\x02\x01\x01\x01\x00\x08\x00\x19\x2c\x00\x01\x00\x02\x70\x69\x6e\x67".to_vec();
+        let context = Context::new(&data);
+
+        let r = ip(context)?.resolve(Offset::from(context), None)?;
+        assert_eq!(vec!["ipv4", "icmp", "payload"], names(&r));
+
+        // Both checksums are good
+        assert_eq!(0..24, r.children[0].actual_range);
+        assert_eq!(Some(true), r.children[0].children[7].valid);
+        assert_eq!(Some(true), r.children[1].children[2].valid);
+
+        // Break the ICMP checksum
+        let mut data = data;
+        data[30] = 0xff;
+        let context = Context::new(&data);
+
+        let r = ip(context)?.resolve(Offset::from(context), None)?;
+        assert_eq!(Some(true), r.children[0].children[7].valid);
+        assert_eq!(Some(false), r.children[1].children[2].valid);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad() -> SimpleResult<()> {
+        // IHL is too short
+        let data = b"\x44\x00\x00\x14\x00\x00\x00\x00\x40\x01\x00\x00\x0a\x00\x00\x01\x0a\x00\x00\x02".to_vec();
+        assert!(ip(Context::new(&data)).is_err());
+
+        // Not IP at all
+        let data = b"\x00\x00".to_vec();
+        assert!(ip(Context::new(&data)).is_err());
+
+        // An IPv6 extension header that runs past the payload length: the
+        // hop-by-hop header says it's 16 bytes, but the payload is 8
+        let data = b"\x60\x00\x00\x00\x00\x08\x00\x40\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x02\x3b\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00".to_vec();
+        assert!(ip(Context::new(&data)).is_err());
+
+        // The same header is fine with the right payload length
+        let mut data = data;
+        data[5] = 0x10;
+        assert!(ip(Context::new(&data)).is_ok());
+
+        // A DNS name that points to itself
+        let data = b"\x00\x00\x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\xc0\x0c\x00\x01\x00\x01".to_vec();
+        assert!(dns(Context::new(&data)).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,720 @@
+//! Definitions for packet captures, in the classic pcap and pcapng formats.
+//!
+//! A classic pcap file is a header, followed by records that each have a
+//! header and the captured bytes of one packet. The magic number tells the
+//! byte order, and whether the timestamps are in microseconds or
+//! nanoseconds.
+//!
+//! A pcapng file is a series of blocks, each with a type and length (at the
+//! start and the end). A section header block (SHB) starts each section and
+//! sets its byte order, interface description blocks (IDB) describe the
+//! interfaces that packets were captured on, and enhanced packet blocks (EPB)
+//! hold the packets. Most blocks end with a list of options.
+//!
+//! [`pcap`] and [`pcapng`] build the definition of the whole file, with the
+//! packet data as bytes. To look inside the packets, [`packets`] (or
+//! [`read_file`]) returns each one as a [`Packet`], which can be resolved
+//! against the link-layer definition for its interface - for example,
+//! [`crate::formats::net::ethernet`]. A packet is just where it is in the
+//! file, so resolving it needs the file, and the ranges are file offsets.
+//!
+//! A capture that was interrupted often ends partway through a record. That
+//! isn't an error: the complete packets are still read, and the truncated
+//! record is reported (and shown as bytes at the end of the definition).
+
+use std::path::Path;
+
+use simple_error::{bail, try_with, SimpleResult};
+use sized_number::{Context, Endian, SizedDefinition};
+
+use crate::{H2Type, Offset, ResolvedType};
+use crate::composite::H2Struct;
+use crate::composite::string::H2String;
+use crate::formats::common::{hex, number};
+use crate::formats::net;
+use crate::offset::buffer_end;
+use crate::simple::{BytesDisplay, BytesLength, H2Bytes};
+use crate::simple::character::{ASCII, StrictASCII};
+use crate::simple::time::{Timestamp, TimestampFormat};
+
+// Classic pcap magic numbers, as read in little endian
+const MAGIC_MICROSECONDS: u32 = 0xa1b2c3d4;
+const MAGIC_NANOSECONDS: u32 = 0xa1b23c4d;
+const MAGIC_MICROSECONDS_SWAPPED: u32 = 0xd4c3b2a1;
+const MAGIC_NANOSECONDS_SWAPPED: u32 = 0x4d3cb2a1;
+
+// pcapng block types
+pub const BLOCK_SECTION_HEADER: u32 = 0x0a0d0d0a;
+pub const BLOCK_INTERFACE_DESCRIPTION: u32 = 0x00000001;
+pub const BLOCK_ENHANCED_PACKET: u32 = 0x00000006;
+
+/// The pcapng byte-order magic, in the section header block.
+const BYTE_ORDER_MAGIC: u32 = 0x1a2b3c4d;
+
+// Link types
+pub const LINKTYPE_ETHERNET: u64 = 1;
+pub const LINKTYPE_RAW: u64 = 101;
+pub const LINKTYPE_IPV4: u64 = 228;
+pub const LINKTYPE_IPV6: u64 = 229;
+
+/// The interface option with the timestamp resolution.
+const OPTION_IF_TSRESOL: u16 = 9;
+
+const PCAP_HEADER_SIZE: u64 = 24;
+const PCAP_RECORD_HEADER_SIZE: u64 = 16;
+
+/// A packet from a capture.
+#[derive(Debug, Clone)]
+pub struct Packet {
+    /// Where the packet's data starts in the file.
+    pub position: u64,
+
+    /// The length of the captured data.
+    pub captured_length: u64,
+
+    /// The link type of the interface it was captured on.
+    pub linktype: u64,
+
+    /// The capture time, in nanoseconds since the Unix epoch.
+    pub timestamp: u64,
+
+    /// The length of the packet on the wire, which can be longer than the
+    /// captured data.
+    pub original_length: u64,
+}
+
+impl Packet {
+    /// The packet's data in the file that it was read from, bounded to the
+    /// captured length so nothing can read past it.
+    pub fn offset<'a>(&self, file: Context<'a>) -> SimpleResult<Offset<'a>> {
+        Offset::from(file.at(self.position)).bounded(self.position..(self.position + self.captured_length))
+    }
+
+    /// The definition of the packet, based on its link type.
+    pub fn link_layer(&self, file: Context) -> SimpleResult<H2Type> {
+        link_layer(self.linktype, self.offset(file)?)
+    }
+
+    /// Resolve the packet against its [`Packet::link_layer`] definition.
+    pub fn resolve(&self, file: Context) -> SimpleResult<ResolvedType> {
+        self.link_layer(file)?.resolve(self.offset(file)?, None)
+    }
+}
+
+/// The packets read from a capture.
+#[derive(Debug, Clone)]
+pub struct Packets {
+    /// The complete packets.
+    pub packets: Vec<Packet>,
+
+    /// Where the last record starts, if the file ends partway through it.
+    pub truncated: Option<u64>,
+}
+
+/// The definition of the packet at the [`Offset`], for the given link type.
+/// Ethernet and raw IP are walked with [`crate::formats::net`], and anything
+/// else is just bytes. If the offset is bounded, the packet ends at the end
+/// of the bounds.
+pub fn link_layer(linktype: u64, offset: Offset) -> SimpleResult<H2Type> {
+    match linktype {
+        LINKTYPE_ETHERNET                            => net::ethernet_at(offset),
+        LINKTYPE_RAW | LINKTYPE_IPV4 | LINKTYPE_IPV6 => net::ip_at(offset),
+        _                                            => H2Bytes::new(BytesLength::Remaining, BytesDisplay::Preview(16)),
+    }
+}
+
+/// Read the byte order and whether the timestamps are in nanoseconds from a
+/// classic pcap magic number.
+fn read_magic(context: Context) -> SimpleResult<(Endian, bool)> {
+    match context.read_u32(Endian::Little)? {
+        MAGIC_MICROSECONDS         => Ok((Endian::Little, false)),
+        MAGIC_NANOSECONDS          => Ok((Endian::Little, true)),
+        MAGIC_MICROSECONDS_SWAPPED => Ok((Endian::Big, false)),
+        MAGIC_NANOSECONDS_SWAPPED  => Ok((Endian::Big, true)),
+        magic                      => bail!("Not a pcap file: 0x{:08x}", magic),
+    }
+}
+
+/// The classic pcap file header.
+pub fn pcap_header(endian: Endian) -> SimpleResult<H2Type> {
+    H2Struct::new(vec![
+        ("magic".to_string(),         hex(SizedDefinition::U32(endian))),
+        ("version_major".to_string(), number(SizedDefinition::U16(endian))),
+        ("version_minor".to_string(), number(SizedDefinition::U16(endian))),
+        ("thiszone".to_string(),      number(SizedDefinition::I32(endian))),
+        ("sigfigs".to_string(),       number(SizedDefinition::U32(endian))),
+        ("snaplen".to_string(),       number(SizedDefinition::U32(endian))),
+        ("linktype".to_string(),      number(SizedDefinition::U32(endian))),
+    ])
+}
+
+/// A classic pcap record, with `captured_length` bytes of data.
+pub fn pcap_record(endian: Endian, nanoseconds: bool, captured_length: u64) -> SimpleResult<H2Type> {
+    let mut fields = vec![
+        ("ts_sec".to_string(), Timestamp::new(SizedDefinition::U32(endian), TimestampFormat::UnixSeconds)?),
+        (match nanoseconds { true => "ts_nsec", false => "ts_usec" }.to_string(), number(SizedDefinition::U32(endian))),
+        ("incl_len".to_string(), number(SizedDefinition::U32(endian))),
+        ("orig_len".to_string(), number(SizedDefinition::U32(endian))),
+    ];
+
+    if captured_length > 0 {
+        fields.push(("data".to_string(), H2Bytes::new(BytesLength::Fixed(captured_length), BytesDisplay::Preview(16))?));
+    }
+
+    H2Struct::new(fields)
+}
+
+/// The definition of a file, and the packets in it.
+type Capture = (Vec<(String, H2Type)>, Packets);
+
+/// The rest of the buffer, after a truncated record.
+fn truncated_record(length: u64) -> SimpleResult<(String, H2Type)> {
+    Ok(("truncated".to_string(), H2Bytes::new(BytesLength::Fixed(length), BytesDisplay::Preview(16))?))
+}
+
+fn walk_pcap(context: Context) -> SimpleResult<Capture> {
+    let offset = Offset::from(context);
+    let start = offset.position();
+    let end = buffer_end(context);
+
+    let (endian, nanoseconds) = read_magic(context)?;
+    let linktype = context.at(start + 20).read_u32(endian)? as u64;
+
+    let mut fields = vec![
+        ("header".to_string(), pcap_header(endian)?),
+    ];
+    let mut packets = vec![];
+
+    let mut position = start + PCAP_HEADER_SIZE;
+    while position < end {
+        // A cut-off header or data ends the capture
+        if position + PCAP_RECORD_HEADER_SIZE > end {
+            break;
+        }
+
+        let seconds = context.at(position).read_u32(endian)? as u64;
+        let fraction = context.at(position + 4).read_u32(endian)? as u64;
+        let captured_length = context.at(position + 8).read_u32(endian)? as u64;
+        let original_length = context.at(position + 12).read_u32(endian)? as u64;
+
+        let data_position = position + PCAP_RECORD_HEADER_SIZE;
+        if data_position + captured_length > end {
+            break;
+        }
+
+        packets.push(Packet {
+            position: data_position,
+            captured_length: captured_length,
+            linktype: linktype,
+            timestamp: seconds * 1_000_000_000 + match nanoseconds {
+                true  => fraction,
+                false => fraction * 1_000,
+            },
+            original_length: original_length,
+        });
+
+        fields.push(("record".to_string(), pcap_record(endian, nanoseconds, captured_length)?));
+        position = data_position + captured_length;
+    }
+
+    let truncated = match position < end {
+        true => {
+            fields.push(truncated_record(end - position)?);
+            Some(position)
+        },
+        false => None,
+    };
+
+    Ok((fields, Packets { packets: packets, truncated: truncated }))
+}
+
+/// Read the classic pcap file at the [`Context`]'s position, which is
+/// assumed to run to the end of the buffer, and build the definition: the
+/// header, then each record (and the truncated record, if there is one).
+pub fn pcap(context: Context) -> SimpleResult<H2Type> {
+    H2Struct::new(walk_pcap(context)?.0)
+}
+
+/// The name of a pcapng option, which depends on the type of block it's in.
+fn option_name(block_type: u32, code: u16) -> &'static str {
+    match (block_type, code) {
+        (_, 0) => "end_of_options",
+        (_, 1) => "comment",
+
+        (BLOCK_SECTION_HEADER, 2) => "hardware",
+        (BLOCK_SECTION_HEADER, 3) => "os",
+        (BLOCK_SECTION_HEADER, 4) => "userappl",
+
+        (BLOCK_INTERFACE_DESCRIPTION, 2)  => "name",
+        (BLOCK_INTERFACE_DESCRIPTION, 3)  => "description",
+        (BLOCK_INTERFACE_DESCRIPTION, 4)  => "ipv4addr",
+        (BLOCK_INTERFACE_DESCRIPTION, 5)  => "ipv6addr",
+        (BLOCK_INTERFACE_DESCRIPTION, 6)  => "macaddr",
+        (BLOCK_INTERFACE_DESCRIPTION, 7)  => "euiaddr",
+        (BLOCK_INTERFACE_DESCRIPTION, 8)  => "speed",
+        (BLOCK_INTERFACE_DESCRIPTION, 9)  => "tsresol",
+        (BLOCK_INTERFACE_DESCRIPTION, 10) => "tzone",
+        (BLOCK_INTERFACE_DESCRIPTION, 11) => "filter",
+        (BLOCK_INTERFACE_DESCRIPTION, 12) => "os",
+        (BLOCK_INTERFACE_DESCRIPTION, 13) => "fcslen",
+        (BLOCK_INTERFACE_DESCRIPTION, 14) => "tsoffset",
+
+        (BLOCK_ENHANCED_PACKET, 2) => "flags",
+        (BLOCK_ENHANCED_PACKET, 3) => "hash",
+        (BLOCK_ENHANCED_PACKET, 4) => "dropcount",
+
+        (_, _) => "option",
+    }
+}
+
+/// A pcapng option with a `length`-byte value, in a block of the given type.
+///
+/// The value is a string for the options that are defined as UTF-8 (and
+/// displayed as ASCII), or bytes otherwise, and is padded to four bytes.
+pub fn pcapng_option(endian: Endian, block_type: u32, code: u16, length: u64) -> SimpleResult<H2Type> {
+    let mut fields = vec![
+        ("code".to_string(),   number(SizedDefinition::U16(endian))),
+        ("length".to_string(), number(SizedDefinition::U16(endian))),
+    ];
+
+    let is_string = match (block_type, code) {
+        (_, 1)                                    => true,
+        (BLOCK_SECTION_HEADER, 2..=4)             => true,
+        (BLOCK_INTERFACE_DESCRIPTION, 2 | 3 | 12) => true,
+        (_, _)                                    => false,
+    };
+
+    if length > 0 {
+        fields.push(("value".to_string(), match is_string {
+            true  => H2String::new(length, ASCII::new(StrictASCII::Permissive))?,
+            false => H2Bytes::new(BytesLength::Fixed(length), BytesDisplay::Hex)?,
+        }));
+    }
+
+    if length % 4 != 0 {
+        fields.push(("padding".to_string(), H2Bytes::new(BytesLength::Fixed(4 - length % 4), BytesDisplay::Hex)?));
+    }
+
+    H2Struct::new(fields)
+}
+
+/// Walk the options between the [`Context`]'s position and `end`, and build
+/// the definition (or `None` if there are none). Also returns the interface
+/// timestamp resolution, if it's one of the options.
+fn walk_options(context: Context, endian: Endian, block_type: u32, end: u64) -> SimpleResult<(Option<H2Type>, Option<u8>)> {
+    let mut fields = vec![];
+    let mut resolution = None;
+
+    let mut position = context.position();
+    while position < end {
+        let code = context.at(position).read_u16(endian)?;
+        let length = context.at(position + 2).read_u16(endian)? as u64;
+
+        if code == OPTION_IF_TSRESOL && block_type == BLOCK_INTERFACE_DESCRIPTION && length == 1 {
+            resolution = Some(context.at(position + 4).read_u8()?);
+        }
+
+        let t = pcapng_option(endian, block_type, code, length)?;
+        position += t.actual_size(Offset::Static(position))?;
+        fields.push((option_name(block_type, code).to_string(), t));
+
+        if code == 0 {
+            break;
+        }
+    }
+
+    if position > end {
+        bail!("pcapng options run past the end of the block");
+    }
+
+    match fields.len() {
+        0 => Ok((None, resolution)),
+        _ => Ok((Some(H2Struct::new(fields)?), resolution)),
+    }
+}
+
+/// A pcapng block with the given body (which is everything after the type
+/// and length, up to the trailing length).
+pub fn pcapng_block(endian: Endian, body: Vec<(String, H2Type)>) -> SimpleResult<H2Type> {
+    let mut fields = vec![
+        ("block_type".to_string(),   hex(SizedDefinition::U32(endian))),
+        ("block_length".to_string(), number(SizedDefinition::U32(endian))),
+    ];
+
+    fields.extend(body);
+    fields.push(("block_length_trailer".to_string(), number(SizedDefinition::U32(endian))));
+
+    H2Struct::new(fields)
+}
+
+/// A block that's `length` bytes long, with its body as bytes.
+fn unknown_block(endian: Endian, length: u64) -> SimpleResult<H2Type> {
+    pcapng_block(endian, vec![
+        ("body".to_string(), H2Bytes::new(BytesLength::Fixed(length - 12), BytesDisplay::Preview(16))?),
+    ])
+}
+
+/// A section header block, with `options` at the end if they're set.
+pub fn section_header_block(endian: Endian, options: Option<H2Type>) -> SimpleResult<H2Type> {
+    let mut body = vec![
+        ("byte_order_magic".to_string(), hex(SizedDefinition::U32(endian))),
+        ("major_version".to_string(),    number(SizedDefinition::U16(endian))),
+        ("minor_version".to_string(),    number(SizedDefinition::U16(endian))),
+        ("section_length".to_string(),   number(SizedDefinition::I64(endian))),
+    ];
+
+    if let Some(options) = options {
+        body.push(("options".to_string(), options));
+    }
+
+    pcapng_block(endian, body)
+}
+
+/// An interface description block, with `options` at the end if they're
+/// set.
+pub fn interface_description_block(endian: Endian, options: Option<H2Type>) -> SimpleResult<H2Type> {
+    let mut body = vec![
+        ("linktype".to_string(), number(SizedDefinition::U16(endian))),
+        ("reserved".to_string(), hex(SizedDefinition::U16(endian))),
+        ("snaplen".to_string(),  number(SizedDefinition::U32(endian))),
+    ];
+
+    if let Some(options) = options {
+        body.push(("options".to_string(), options));
+    }
+
+    pcapng_block(endian, body)
+}
+
+/// An enhanced packet block with `captured_length` bytes of data (padded to
+/// four bytes), and `options` at the end if they're set.
+pub fn enhanced_packet_block(endian: Endian, captured_length: u64, options: Option<H2Type>) -> SimpleResult<H2Type> {
+    let mut body = vec![
+        ("interface_id".to_string(),    number(SizedDefinition::U32(endian))),
+        ("timestamp_high".to_string(),  number(SizedDefinition::U32(endian))),
+        ("timestamp_low".to_string(),   number(SizedDefinition::U32(endian))),
+        ("captured_length".to_string(), number(SizedDefinition::U32(endian))),
+        ("original_length".to_string(), number(SizedDefinition::U32(endian))),
+    ];
+
+    if captured_length > 0 {
+        body.push(("data".to_string(), H2Bytes::new(BytesLength::Fixed(captured_length), BytesDisplay::Preview(16))?));
+    }
+
+    if captured_length % 4 != 0 {
+        body.push(("padding".to_string(), H2Bytes::new(BytesLength::Fixed(4 - captured_length % 4), BytesDisplay::Hex)?));
+    }
+
+    if let Some(options) = options {
+        body.push(("options".to_string(), options));
+    }
+
+    pcapng_block(endian, body)
+}
+
+/// Convert a pcapng timestamp to nanoseconds, using the interface's
+/// `if_tsresol` (the default is microseconds): a negative power of ten, or
+/// of two if the top bit is set.
+fn to_nanoseconds(timestamp: u64, resolution: u8) -> u64 {
+    let timestamp = timestamp as u128;
+    let exponent = (resolution & 0x7f) as u32;
+
+    let nanoseconds = match (resolution & 0x80, exponent) {
+        (0, 0..=9) => timestamp * 10u128.pow(9 - exponent),
+        (0, _)     => timestamp / 10u128.checked_pow(exponent - 9).unwrap_or(u128::MAX),
+        (_, _)     => (timestamp * 1_000_000_000) >> exponent,
+    };
+
+    nanoseconds as u64
+}
+
+fn walk_pcapng(context: Context) -> SimpleResult<Capture> {
+    let offset = Offset::from(context);
+    let end = buffer_end(context);
+
+    let mut fields = vec![];
+    let mut packets = vec![];
+
+    // The byte order and interfaces are set by each section
+    let mut endian = Endian::Little;
+    let mut interfaces: Vec<(u64, u8)> = vec![];
+
+    let mut position = offset.position();
+    while position < end {
+        // A cut-off block ends the capture (as long as there's a section)
+        if position + 8 > end && !fields.is_empty() {
+            break;
+        }
+
+        let block_type = context.at(position).read_u32(endian)?;
+
+        if block_type == BLOCK_SECTION_HEADER {
+            endian = match context.at(position + 8).read_u32(Endian::Little)? {
+                BYTE_ORDER_MAGIC                        => Endian::Little,
+                m if m.swap_bytes() == BYTE_ORDER_MAGIC => Endian::Big,
+                m                                       => bail!("Bad pcapng byte-order magic: 0x{:08x}", m),
+            };
+            interfaces.clear();
+        } else if fields.is_empty() {
+            bail!("Not a pcapng file: 0x{:08x}", block_type);
+        }
+
+        let length = context.at(position + 4).read_u32(endian)? as u64;
+        if length < 12 || length % 4 != 0 {
+            bail!("Bad pcapng block length at {}: {}", position, length);
+        }
+
+        let block_end = position + length;
+        if block_end > end && !fields.is_empty() {
+            break;
+        } else if block_end > end {
+            bail!("pcapng block at {} runs off the end of the buffer", position);
+        }
+
+        if context.at(block_end - 4).read_u32(endian)? as u64 != length {
+            bail!("pcapng block at {} has mismatched lengths", position);
+        }
+
+        let options_end = block_end - 4;
+        let (name, t) = match block_type {
+            BLOCK_SECTION_HEADER => {
+                let (options, _) = walk_options(context.at(position + 24), endian, block_type, options_end)?;
+                ("section_header", section_header_block(endian, options)?)
+            },
+            BLOCK_INTERFACE_DESCRIPTION => {
+                let (options, resolution) = walk_options(context.at(position + 16), endian, block_type, options_end)?;
+                interfaces.push((context.at(position + 8).read_u16(endian)? as u64, resolution.unwrap_or(6)));
+
+                ("interface_description", interface_description_block(endian, options)?)
+            },
+            BLOCK_ENHANCED_PACKET => {
+                let interface = context.at(position + 8).read_u32(endian)? as usize;
+                let (linktype, resolution) = match interfaces.get(interface) {
+                    Some(i) => *i,
+                    None    => bail!("pcapng packet at {} is for unknown interface {}", position, interface),
+                };
+
+                let timestamp = (context.at(position + 12).read_u32(endian)? as u64) << 32 | context.at(position + 16).read_u32(endian)? as u64;
+                let captured_length = context.at(position + 20).read_u32(endian)? as u64;
+                let original_length = context.at(position + 24).read_u32(endian)? as u64;
+
+                let data_position = position + 28;
+                let options_start = data_position + (captured_length + 3) / 4 * 4;
+                if options_start > options_end {
+                    bail!("pcapng packet at {} is longer than its block", position);
+                }
+
+                packets.push(Packet {
+                    position: data_position,
+                    captured_length: captured_length,
+                    linktype: linktype,
+                    timestamp: to_nanoseconds(timestamp, resolution),
+                    original_length: original_length,
+                });
+
+                let (options, _) = walk_options(context.at(options_start), endian, block_type, options_end)?;
+                ("enhanced_packet", enhanced_packet_block(endian, captured_length, options)?)
+            },
+            _ => ("block", unknown_block(endian, length)?),
+        };
+
+        // If the block has more than we understand, fall back to bytes so
+        // the rest of the file still lines up
+        match t.actual_size(offset.at(position))? == length {
+            true  => fields.push((name.to_string(), t)),
+            false => fields.push((name.to_string(), unknown_block(endian, length)?)),
+        }
+
+        position = block_end;
+    }
+
+    let truncated = match position < end {
+        true => {
+            fields.push(truncated_record(end - position)?);
+            Some(position)
+        },
+        false => None,
+    };
+
+    Ok((fields, Packets { packets: packets, truncated: truncated }))
+}
+
+/// Read the pcapng file at the [`Context`]'s position, which is assumed to
+/// run to the end of the buffer, and build the definition: one field per
+/// block, named after the type of block (and the truncated block, if there
+/// is one).
+pub fn pcapng(context: Context) -> SimpleResult<H2Type> {
+    H2Struct::new(walk_pcapng(context)?.0)
+}
+
+/// Read the packets from the pcap or pcapng file at the [`Context`]'s
+/// position, which is assumed to run to the end of the buffer.
+pub fn packets(context: Context) -> SimpleResult<Packets> {
+    match context.read_u32(Endian::Little)? {
+        BLOCK_SECTION_HEADER => Ok(walk_pcapng(context)?.1),
+        _                    => Ok(walk_pcap(context)?.1),
+    }
+}
+
+/// Read a pcap or pcapng file from disk, and return its contents (which the
+/// packets are resolved against) and its packets.
+pub fn read_file(path: &Path) -> SimpleResult<(Vec<u8>, Packets)> {
+    let data = try_with!(std::fs::read(path), "Couldn't read {}", path.display());
+    let packets = packets(Context::new(&data))?;
+
+    Ok((data, packets))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+    use sized_number::Context;
+
+    use crate::ResolvedType;
+
+    fn names(r: &ResolvedType) -> Vec<String> {
+        r.children.iter().map(|c| c.field_name.clone().unwrap()).collect()
+    }
+
+    /// A big-endian, nanosecond pcap with raw IP packets: an ICMP echo
+    /// request, and the same packet cut off after four bytes.
+    fn classic() -> Vec<u8> {
+        b"\xa1\xb2\x3c\x4d\x00\x02\x00\x04\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\xff\xff\x00\x00\x00\x65\x5f\x5e\x10\x00\x00\x00\x00\x05\x00\x00\x00\x24\x00\x00\x00\x24\x46\x00\x00\x24\x22\x22\x00\x00\x40\x01\x41\xb4\x0a\x00\x00\x01\x0a\x00\x00\x02\x01\x01\x01\x00\x08\x00\x19\x2c\x00\x01\x00\x02\x70\x69\x6e\x67\x5f\x5e\x10\x01\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x64\x46\x00\x00\x24".to_vec()
+    }
+
+    #[test]
+    fn test_pcap() -> SimpleResult<()> {
+        let data = classic();
+        let context = Context::new(&data);
+
+        let r = pcap(context)?.resolve(Offset::from(context), None)?;
+        assert_eq!(0..96, r.actual_range);
+        assert_eq!(vec!["header", "record", "record"], names(&r));
+        assert_eq!(Some("ts_nsec".to_string()), r.children[1].children[1].field_name);
+
+        let packets = packets(context)?;
+        assert_eq!(None, packets.truncated);
+        let packets = packets.packets;
+        assert_eq!(2, packets.len());
+
+        // The packet is resolved in place, so the ranges are in the file
+        assert_eq!(40, packets[0].offset(context)?.position());
+        assert_eq!(1_600_000_000_000_000_005, packets[0].timestamp);
+
+        let r = packets[0].resolve(context)?;
+        assert_eq!(vec!["ipv4", "icmp", "payload"], names(&r));
+        assert_eq!(40..76, r.actual_range);
+        assert_eq!(40..64, r.children[0].actual_range);
+
+        // The second packet is cut off, and can't be read past its captured
+        // length
+        assert_eq!(100, packets[1].original_length);
+        assert_eq!(4, packets[1].captured_length);
+        assert_eq!(Some(92..96), packets[1].offset(context)?.bounds());
+        assert!(packets[1].resolve(context).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pcapng() -> SimpleResult<()> {
+        // A section with a comment, a raw IP interface with nanosecond
+        // timestamps, another interface with an unknown link type, an ICMP
+        // packet, an unknown block, and a padded packet with a comment
+        let data = b"\x0a\x0d\x0d\x0a\x2c\x00\x00\x00\x4d\x3c\x2b\x1a\x01\x00\x00\x00\xff\xff\xff\xff\xff\xff\xff\xff\x01\x00\x05\x00\x68\x65\x6c\x6c\x6f\x00\x00\x00\x00\x00\x00\x00\x2c\x00\x00\x00\x01\x00\x00\x00\x20\x00\x00\x00\x65\x00\x00\x00\xff\xff\x00\x00\x09\x00\x01\x00\x09\x00\x00\x00\x00\x00\x00\x00\x20\x00\x00\x00\x01\x00\x00\x00\x14\x00\x00\x00\x93\x00\x00\x00\xff\xff\x00\x00\x14\x00\x00\x00\x06\x00\x00\x00\x44\x00\x00\x00\x00\x00\x00\x00\x85\x57\x34\x16\x07\x00\xa0\xd8\x24\x00\x00\x00\x24\x00\x00\x00\x46\x00\x00\x24\x22\x22\x00\x00\x40\x01\x41\xb4\x0a\x00\x00\x01\x0a\x00\x00\x02\x01\x01\x01\x00\x08\x00\x19\x2c\x00\x01\x00\x02\x70\x69\x6e\x67\x44\x00\x00\x00\xad\x0b\x00\x00\x10\x00\x00\x00\x01\x02\x03\x04\x10\x00\x00\x00\x06\x00\x00\x00\x34\x00\x00\x00\x01\x00\x00\x00\x31\xaf\x05\x00\x01\x00\xa4\x07\x05\x00\x00\x00\x05\x00\x00\x00\x61\x62\x63\x64\x65\x00\x00\x00\x01\x00\x01\x00\x78\x00\x00\x00\x00\x00\x00\x00\x34\x00\x00\x00".to_vec();
+        let context = Context::new(&data);
+
+        let r = pcapng(context)?.resolve(Offset::from(context), None)?;
+        assert_eq!(0..232, r.actual_range);
+        assert_eq!(vec!["section_header", "interface_description", "interface_description", "enhanced_packet", "block", "enhanced_packet"], names(&r));
+
+        let options = &r.children[0].children[6];
+        assert_eq!(vec!["comment", "end_of_options"], names(options));
+        assert_eq!(Some("hello".to_string()), options.children[0].children[2].as_string);
+
+        // The padding and options after the second packet
+        assert_eq!(vec!["block_type", "block_length", "interface_id", "timestamp_high", "timestamp_low", "captured_length", "original_length", "data", "padding", "options", "block_length_trailer"], names(&r.children[5]));
+
+        let packets = packets(context)?.packets;
+        assert_eq!(2, packets.len());
+
+        assert_eq!(LINKTYPE_RAW, packets[0].linktype);
+        assert_eq!(1_600_000_000_000_000_007, packets[0].timestamp);
+        assert_eq!(vec!["ipv4", "icmp", "payload"], names(&packets[0].resolve(context)?));
+
+        // The default resolution is microseconds, and an unknown link type
+        // is just the captured bytes
+        assert_eq!(147, packets[1].linktype);
+        assert_eq!(1_600_000_000_000_001_000, packets[1].timestamp);
+        assert_eq!(208, packets[1].position);
+        assert_eq!(208..213, packets[1].resolve(context)?.actual_range);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_file() -> SimpleResult<()> {
+        let path = std::env::temp_dir().join(format!("h2datatype-test-{}.pcap", std::process::id()));
+        try_with!(std::fs::write(&path, classic()), "Couldn't write {}", path.display());
+
+        let file = read_file(&path);
+        try_with!(std::fs::remove_file(&path), "Couldn't remove {}", path.display());
+
+        let (data, packets) = file?;
+        assert_eq!(classic(), data);
+        assert_eq!(2, packets.packets.len());
+        assert_eq!(vec!["ipv4", "icmp", "payload"], names(&packets.packets[0].resolve(Context::new(&data))?));
+
+        assert!(read_file(&path).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_nanoseconds() -> SimpleResult<()> {
+        assert_eq!(5_000, to_nanoseconds(5, 6));
+        assert_eq!(5, to_nanoseconds(5, 9));
+        assert_eq!(5, to_nanoseconds(5_000, 12));
+        assert_eq!(1_000_000_000, to_nanoseconds(1024, 0x8a));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_truncated() -> SimpleResult<()> {
+        // The last record's data is cut off, so only the first packet is
+        // read and the rest is reported
+        let data = classic();
+        let data = data[0..(data.len() - 1)].to_vec();
+        let context = Context::new(&data);
+
+        let p = packets(context)?;
+        assert_eq!(1, p.packets.len());
+        assert_eq!(Some(76), p.truncated);
+
+        let r = pcap(context)?.resolve(Offset::from(context), None)?;
+        assert_eq!(vec!["header", "record", "truncated"], names(&r));
+        assert_eq!(76..95, r.children[2].actual_range);
+
+        // Same with a cut-off record header
+        let data = classic();
+        let data = data[0..80].to_vec();
+        assert_eq!(Some(76), packets(Context::new(&data))?.truncated);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad() -> SimpleResult<()> {
+        // Not a capture
+        let data = b"\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00".to_vec();
+        assert!(packets(Context::new(&data)).is_err());
+
+        // A block whose lengths don't match
+        let data = b"\x0a\x0d\x0d\x0a\x1c\x00\x00\x00\x4d\x3c\x2b\x1a\x01\x00\x00\x00\xff\xff\xff\xff\xff\xff\xff\xff\x20\x00\x00\x00".to_vec();
+        assert!(pcapng(Context::new(&data)).is_err());
+
+        Ok(())
+    }
+}
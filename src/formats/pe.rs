@@ -0,0 +1,1059 @@
+//! Definitions for PE (Portable Executable) files, as used by Windows.
+//!
+//! A PE file starts with an `IMAGE_DOS_HEADER`, whose `e_lfanew` field
+//! points to the `IMAGE_NT_HEADERS`: a signature, the COFF file header, and
+//! the optional header (which is really not optional). The optional header
+//! comes in two versions, PE32 and PE32+, selected by its `Magic` field, and
+//! ends with the data directories; the section table follows it.
+//!
+//! Most addresses in a PE file are RVAs (relative virtual addresses) - that
+//! is, relative to where the image is loaded in memory, not to the start of
+//! the file. They're converted to file offsets through the section table
+//! with [`rva_to_offset`], and RVA fields are pointers that are adjusted the
+//! same way (see [`crate::simple::H2Pointer::new_adjusted`]). [`pe`] builds
+//! the headers, and [`pe_tables`] finds the export, import, and debug
+//! directories, which point to the export tables, imported functions, and
+//! CodeView records.
+//!
+//! Flags, such as `Characteristics`, are [`H2BitStruct`]s with a field for
+//! each flag.
+//!
+//! Field names follow the Windows headers (`e_lfanew`, `NumberOfSections`,
+//! etc.), and everything is little endian. Like ELF, the file must start at
+//! the beginning of the buffer for the pointers to work.
+
+#[cfg(feature = "serialize")]
+use serde::{Serialize, Deserialize};
+
+use simple_error::{bail, SimpleResult};
+use sized_number::{Context, Endian, SizedDefinition, SizedDisplay};
+
+use crate::{H2Type, Offset};
+use crate::composite::{Constrained, Constraint, ConstraintAction, ConstraintValue, H2Array, H2BitStruct, H2Struct};
+use crate::composite::string::{H2String, NTString};
+use crate::formats::common::{field, hex, magic, number};
+use crate::simple::{BitNumber, BitOrder, BytesDisplay, BytesLength, H2Bytes, H2Pointer};
+use crate::simple::character::{ASCII, StrictASCII};
+use crate::simple::network::{GUID, GUIDLayout};
+use crate::simple::time::{Timestamp, TimestampFormat};
+
+/// The magic bytes at the start of the DOS header.
+pub const DOS_MAGIC: &[u8] = b"MZ";
+
+/// The signature at the start of the NT headers.
+pub const SIGNATURE: &[u8] = b"PE\x00\x00";
+
+const U16: SizedDefinition = SizedDefinition::U16(Endian::Little);
+const U32: SizedDefinition = SizedDefinition::U32(Endian::Little);
+const U64: SizedDefinition = SizedDefinition::U64(Endian::Little);
+
+/// The names of the data directories, in order.
+const DATA_DIRECTORIES: [&str; 16] = [
+    "Export", "Import", "Resource", "Exception", "Security", "BaseReloc",
+    "Debug", "Architecture", "GlobalPtr", "TLS", "LoadConfig", "BoundImport",
+    "IAT", "DelayImport", "CLRRuntime", "Reserved",
+];
+
+// Data directory indexes, and the one debug type we understand
+const DIRECTORY_EXPORT: usize = 0;
+const DIRECTORY_IMPORT: usize = 1;
+const DIRECTORY_DEBUG:  usize = 6;
+const DEBUG_TYPE_CODEVIEW: u64 = 2;
+
+/// The names of the debug types, by number.
+const DEBUG_TYPES: [&str; 17] = [
+    "Unknown", "COFF", "CodeView", "FPO", "Misc", "Exception", "Fixup",
+    "OmapToSrc", "OmapFromSrc", "Borland", "Reserved10", "CLSID",
+    "VCFeature", "POGO", "ILTCG", "MPX", "Repro",
+];
+
+/// The version of the optional header, from its `Magic` field.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum PEFormat {
+    /// 32-bit (`0x10b`).
+    PE32,
+
+    /// 64-bit (`0x20b`).
+    PE32Plus,
+}
+
+impl PEFormat {
+    fn magic(self) -> u64 {
+        match self {
+            Self::PE32     => 0x10b,
+            Self::PE32Plus => 0x20b,
+        }
+    }
+
+    /// The definition of fields that are 32 bits in PE32, and 64 bits in
+    /// PE32+.
+    fn word(self) -> SizedDefinition {
+        match self {
+            Self::PE32     => U32,
+            Self::PE32Plus => U64,
+        }
+    }
+}
+
+/// The flags in the file header's `Characteristics`, from bit 0 up.
+const FILE_CHARACTERISTICS: [(&str, u8); 16] = [
+    ("IMAGE_FILE_RELOCS_STRIPPED", 1),
+    ("IMAGE_FILE_EXECUTABLE_IMAGE", 1),
+    ("IMAGE_FILE_LINE_NUMS_STRIPPED", 1),
+    ("IMAGE_FILE_LOCAL_SYMS_STRIPPED", 1),
+    ("IMAGE_FILE_AGGRESSIVE_WS_TRIM", 1),
+    ("IMAGE_FILE_LARGE_ADDRESS_AWARE", 1),
+    ("Reserved", 1),
+    ("IMAGE_FILE_BYTES_REVERSED_LO", 1),
+    ("IMAGE_FILE_32BIT_MACHINE", 1),
+    ("IMAGE_FILE_DEBUG_STRIPPED", 1),
+    ("IMAGE_FILE_REMOVABLE_RUN_FROM_SWAP", 1),
+    ("IMAGE_FILE_NET_RUN_FROM_SWAP", 1),
+    ("IMAGE_FILE_SYSTEM", 1),
+    ("IMAGE_FILE_DLL", 1),
+    ("IMAGE_FILE_UP_SYSTEM_ONLY", 1),
+    ("IMAGE_FILE_BYTES_REVERSED_HI", 1),
+];
+
+/// The flags in the optional header's `DllCharacteristics`.
+const DLL_CHARACTERISTICS: [(&str, u8); 12] = [
+    ("Reserved", 5),
+    ("IMAGE_DLLCHARACTERISTICS_HIGH_ENTROPY_VA", 1),
+    ("IMAGE_DLLCHARACTERISTICS_DYNAMIC_BASE", 1),
+    ("IMAGE_DLLCHARACTERISTICS_FORCE_INTEGRITY", 1),
+    ("IMAGE_DLLCHARACTERISTICS_NX_COMPAT", 1),
+    ("IMAGE_DLLCHARACTERISTICS_NO_ISOLATION", 1),
+    ("IMAGE_DLLCHARACTERISTICS_NO_SEH", 1),
+    ("IMAGE_DLLCHARACTERISTICS_NO_BIND", 1),
+    ("IMAGE_DLLCHARACTERISTICS_APPCONTAINER", 1),
+    ("IMAGE_DLLCHARACTERISTICS_WDM_DRIVER", 1),
+    ("IMAGE_DLLCHARACTERISTICS_GUARD_CF", 1),
+    ("IMAGE_DLLCHARACTERISTICS_TERMINAL_SERVER_AWARE", 1),
+];
+
+/// The flags in a section header's `Characteristics`; the alignment is a
+/// 4-bit number in the middle.
+const SECTION_CHARACTERISTICS: [(&str, u8); 26] = [
+    ("Reserved", 3),
+    ("IMAGE_SCN_TYPE_NO_PAD", 1),
+    ("Reserved", 1),
+    ("IMAGE_SCN_CNT_CODE", 1),
+    ("IMAGE_SCN_CNT_INITIALIZED_DATA", 1),
+    ("IMAGE_SCN_CNT_UNINITIALIZED_DATA", 1),
+    ("IMAGE_SCN_LNK_OTHER", 1),
+    ("IMAGE_SCN_LNK_INFO", 1),
+    ("Reserved", 1),
+    ("IMAGE_SCN_LNK_REMOVE", 1),
+    ("IMAGE_SCN_LNK_COMDAT", 1),
+    ("Reserved", 2),
+    ("IMAGE_SCN_GPREL", 1),
+    ("Reserved", 1),
+    ("IMAGE_SCN_MEM_PURGEABLE", 1),
+    ("IMAGE_SCN_MEM_LOCKED", 1),
+    ("IMAGE_SCN_MEM_PRELOAD", 1),
+    ("IMAGE_SCN_ALIGN", 4),
+    ("IMAGE_SCN_LNK_NRELOC_OVFL", 1),
+    ("IMAGE_SCN_MEM_DISCARDABLE", 1),
+    ("IMAGE_SCN_MEM_NOT_CACHED", 1),
+    ("IMAGE_SCN_MEM_NOT_PAGED", 1),
+    ("IMAGE_SCN_MEM_SHARED", 1),
+    ("IMAGE_SCN_MEM_EXECUTE", 1),
+    ("IMAGE_SCN_MEM_READ", 1),
+    ("IMAGE_SCN_MEM_WRITE", 1),
+];
+
+fn timestamp() -> SimpleResult<H2Type> {
+    Timestamp::new(U32, TimestampFormat::UnixSeconds)
+}
+
+/// Little-endian flags, as a bit struct of `fields` (a name and a width,
+/// starting at the lowest bit).
+fn flags(fields: &[(&str, u8)]) -> SimpleResult<H2Type> {
+    H2BitStruct::new(fields.iter().map(|&(name, width)| {
+        Ok((name.to_string(), BitNumber::new(width, false, BitOrder::LSBFirst, SizedDisplay::Decimal)?))
+    }).collect::<SimpleResult<Vec<_>>>()?)
+}
+
+fn ascii() -> H2Type {
+    ASCII::new(StrictASCII::Permissive)
+}
+
+/// The DOS header (`IMAGE_DOS_HEADER`).
+///
+/// If `nt_headers` is set, `e_lfanew` is a pointer to it; otherwise, it's
+/// just a number.
+pub fn dos_header(nt_headers: Option<H2Type>) -> SimpleResult<H2Type> {
+    let e_lfanew = match nt_headers {
+        Some(t) => H2Pointer::new(U32, SizedDisplay::Hex(Default::default()), t),
+        None    => hex(U32),
+    };
+
+    H2Struct::new(vec![
+        ("e_magic".to_string(),    magic(DOS_MAGIC)?),
+        ("e_cblp".to_string(),     number(U16)),
+        ("e_cp".to_string(),       number(U16)),
+        ("e_crlc".to_string(),     number(U16)),
+        ("e_cparhdr".to_string(),  number(U16)),
+        ("e_minalloc".to_string(), number(U16)),
+        ("e_maxalloc".to_string(), number(U16)),
+        ("e_ss".to_string(),       hex(U16)),
+        ("e_sp".to_string(),       hex(U16)),
+        ("e_csum".to_string(),     hex(U16)),
+        ("e_ip".to_string(),       hex(U16)),
+        ("e_cs".to_string(),       hex(U16)),
+        ("e_lfarlc".to_string(),   hex(U16)),
+        ("e_ovno".to_string(),     number(U16)),
+        ("e_res".to_string(),      H2Array::new(4, hex(U16))?),
+        ("e_oemid".to_string(),    hex(U16)),
+        ("e_oeminfo".to_string(),  hex(U16)),
+        ("e_res2".to_string(),     H2Array::new(10, hex(U16))?),
+        ("e_lfanew".to_string(),   e_lfanew),
+    ])
+}
+
+/// The COFF file header (`IMAGE_FILE_HEADER`).
+pub fn file_header() -> SimpleResult<H2Type> {
+    H2Struct::new(vec![
+        ("Machine".to_string(),              hex(U16)),
+        ("NumberOfSections".to_string(),     number(U16)),
+        ("TimeDateStamp".to_string(),        timestamp()?),
+        ("PointerToSymbolTable".to_string(), hex(U32)),
+        ("NumberOfSymbols".to_string(),      number(U32)),
+        ("SizeOfOptionalHeader".to_string(), number(U16)),
+        ("Characteristics".to_string(),      flags(&FILE_CHARACTERISTICS)?),
+    ])
+}
+
+/// A data directory (`IMAGE_DATA_DIRECTORY`).
+pub fn data_directory() -> SimpleResult<H2Type> {
+    H2Struct::new(vec![
+        ("VirtualAddress".to_string(), hex(U32)),
+        ("Size".to_string(),           number(U32)),
+    ])
+}
+
+/// The optional header (`IMAGE_OPTIONAL_HEADER32` / `IMAGE_OPTIONAL_HEADER64`),
+/// with `directories` data directories (normally 16) named after what they
+/// point to.
+pub fn optional_header(format: PEFormat, directories: u64) -> SimpleResult<H2Type> {
+    if directories as usize > DATA_DIRECTORIES.len() {
+        bail!("Too many data directories: {}", directories);
+    }
+
+    let mut fields = vec![
+        ("Magic".to_string(), Constrained::new(
+            hex(U16),
+            Constraint::Equals(ConstraintValue::Integer(format.magic())),
+            ConstraintAction::Fail,
+        )?),
+        ("MajorLinkerVersion".to_string(),          number(SizedDefinition::U8)),
+        ("MinorLinkerVersion".to_string(),          number(SizedDefinition::U8)),
+        ("SizeOfCode".to_string(),                  number(U32)),
+        ("SizeOfInitializedData".to_string(),       number(U32)),
+        ("SizeOfUninitializedData".to_string(),     number(U32)),
+        ("AddressOfEntryPoint".to_string(),         hex(U32)),
+        ("BaseOfCode".to_string(),                  hex(U32)),
+    ];
+
+    // PE32+ drops BaseOfData, to make room for the larger ImageBase
+    if format == PEFormat::PE32 {
+        fields.push(("BaseOfData".to_string(), hex(U32)));
+    }
+
+    fields.append(&mut vec![
+        ("ImageBase".to_string(),                   hex(format.word())),
+        ("SectionAlignment".to_string(),            hex(U32)),
+        ("FileAlignment".to_string(),               hex(U32)),
+        ("MajorOperatingSystemVersion".to_string(), number(U16)),
+        ("MinorOperatingSystemVersion".to_string(), number(U16)),
+        ("MajorImageVersion".to_string(),           number(U16)),
+        ("MinorImageVersion".to_string(),           number(U16)),
+        ("MajorSubsystemVersion".to_string(),       number(U16)),
+        ("MinorSubsystemVersion".to_string(),       number(U16)),
+        ("Win32VersionValue".to_string(),           number(U32)),
+        ("SizeOfImage".to_string(),                 hex(U32)),
+        ("SizeOfHeaders".to_string(),               hex(U32)),
+        ("CheckSum".to_string(),                    hex(U32)),
+        ("Subsystem".to_string(),                   number(U16)),
+        ("DllCharacteristics".to_string(),          flags(&DLL_CHARACTERISTICS)?),
+        ("SizeOfStackReserve".to_string(),          hex(format.word())),
+        ("SizeOfStackCommit".to_string(),           hex(format.word())),
+        ("SizeOfHeapReserve".to_string(),           hex(format.word())),
+        ("SizeOfHeapCommit".to_string(),            hex(format.word())),
+        ("LoaderFlags".to_string(),                 hex(U32)),
+        ("NumberOfRvaAndSizes".to_string(),         number(U32)),
+    ]);
+
+    if directories > 0 {
+        fields.push(("DataDirectory".to_string(), H2Struct::new(
+            DATA_DIRECTORIES.iter().take(directories as usize).map(|name| {
+                Ok((name.to_string(), data_directory()?))
+            }).collect::<SimpleResult<Vec<_>>>()?
+        )?));
+    }
+
+    H2Struct::new(fields)
+}
+
+/// A section header (`IMAGE_SECTION_HEADER`).
+pub fn section_header() -> SimpleResult<H2Type> {
+    H2Struct::new(vec![
+        ("Name".to_string(),                 H2String::new(8, ascii())?),
+        ("VirtualSize".to_string(),          hex(U32)),
+        ("VirtualAddress".to_string(),       hex(U32)),
+        ("SizeOfRawData".to_string(),        hex(U32)),
+        ("PointerToRawData".to_string(),     hex(U32)),
+        ("PointerToRelocations".to_string(), hex(U32)),
+        ("PointerToLinenumbers".to_string(), hex(U32)),
+        ("NumberOfRelocations".to_string(),  number(U16)),
+        ("NumberOfLinenumbers".to_string(),  number(U16)),
+        ("Characteristics".to_string(),      flags(&SECTION_CHARACTERISTICS)?),
+    ])
+}
+
+/// The NT headers (`IMAGE_NT_HEADERS32` / `IMAGE_NT_HEADERS64`), followed by
+/// the section table if there are any `sections`.
+///
+/// The section table starts `optional_size` bytes (`SizeOfOptionalHeader`)
+/// after the optional header, like the loader expects; if that's past the
+/// data directories, the gap is padding.
+pub fn nt_headers(format: PEFormat, directories: u64, optional_size: u64, sections: u64) -> SimpleResult<H2Type> {
+    let optional = optional_header(format, directories)?;
+    let size = optional.actual_size(Offset::Static(0))?;
+    if size > optional_size {
+        bail!("Optional header ({} bytes) doesn't fit in SizeOfOptionalHeader ({})", size, optional_size);
+    }
+
+    let mut fields = vec![
+        ("Signature".to_string(),      magic(SIGNATURE)?),
+        ("FileHeader".to_string(),     file_header()?),
+        ("OptionalHeader".to_string(), optional),
+    ];
+
+    if optional_size > size {
+        fields.push(("Padding".to_string(), H2Bytes::new(BytesLength::Fixed(optional_size - size), BytesDisplay::Hex)?));
+    }
+
+    if sections > 0 {
+        fields.push(("SectionHeaders".to_string(), H2Array::new(sections, section_header()?)?));
+    }
+
+    H2Struct::new(fields)
+}
+
+/// Use `pointer` for a field if it's set, or a number otherwise.
+fn pointer_or_hex(pointer: Option<H2Type>) -> H2Type {
+    pointer.unwrap_or_else(|| hex(U32))
+}
+
+/// An import directory entry (`IMAGE_IMPORT_DESCRIPTOR`). The table ends
+/// with an entry that's all zeroes.
+///
+/// If `original_first_thunk`, `name`, or `first_thunk` are set, they're used
+/// for those fields, which are normally pointers to the import lookup table,
+/// the DLL name, and the import address table (see [`pe_tables`]);
+/// otherwise, they're just numbers.
+pub fn import_descriptor(original_first_thunk: Option<H2Type>, name: Option<H2Type>, first_thunk: Option<H2Type>) -> SimpleResult<H2Type> {
+    H2Struct::new(vec![
+        ("OriginalFirstThunk".to_string(), pointer_or_hex(original_first_thunk)),
+        ("TimeDateStamp".to_string(),      timestamp()?),
+        ("ForwarderChain".to_string(),     hex(U32)),
+        ("Name".to_string(),               pointer_or_hex(name)),
+        ("FirstThunk".to_string(),         pointer_or_hex(first_thunk)),
+    ])
+}
+
+/// An import by name (`IMAGE_IMPORT_BY_NAME`), which an import lookup table
+/// entry points to.
+pub fn hint_name() -> SimpleResult<H2Type> {
+    H2Struct::new(vec![
+        ("Hint".to_string(), number(U16)),
+        ("Name".to_string(), NTString::new(ascii())?),
+    ])
+}
+
+/// The export directory (`IMAGE_EXPORT_DIRECTORY`).
+///
+/// If `name`, `functions`, `names`, or `ordinals` are set, they're used for
+/// the `Name`, `AddressOfFunctions`, `AddressOfNames`, and
+/// `AddressOfNameOrdinals` fields, which are normally pointers (see
+/// [`pe_tables`]); otherwise, they're just numbers.
+pub fn export_directory(name: Option<H2Type>, functions: Option<H2Type>, names: Option<H2Type>, ordinals: Option<H2Type>) -> SimpleResult<H2Type> {
+    H2Struct::new(vec![
+        ("Characteristics".to_string(),       hex(U32)),
+        ("TimeDateStamp".to_string(),         timestamp()?),
+        ("MajorVersion".to_string(),          number(U16)),
+        ("MinorVersion".to_string(),          number(U16)),
+        ("Name".to_string(),                  pointer_or_hex(name)),
+        ("Base".to_string(),                  number(U32)),
+        ("NumberOfFunctions".to_string(),     number(U32)),
+        ("NumberOfNames".to_string(),         number(U32)),
+        ("AddressOfFunctions".to_string(),    pointer_or_hex(functions)),
+        ("AddressOfNames".to_string(),        pointer_or_hex(names)),
+        ("AddressOfNameOrdinals".to_string(), pointer_or_hex(ordinals)),
+    ])
+}
+
+/// A debug directory entry (`IMAGE_DEBUG_DIRECTORY`).
+///
+/// If `raw_data` is set, it's used for `PointerToRawData` (which is a file
+/// offset, not an RVA), normally a pointer to a [`codeview`] record;
+/// otherwise, it's just a number.
+pub fn debug_directory(raw_data: Option<H2Type>) -> SimpleResult<H2Type> {
+    H2Struct::new(vec![
+        ("Characteristics".to_string(),  hex(U32)),
+        ("TimeDateStamp".to_string(),    timestamp()?),
+        ("MajorVersion".to_string(),     number(U16)),
+        ("MinorVersion".to_string(),     number(U16)),
+        ("Type".to_string(),             number(U32)),
+        ("SizeOfData".to_string(),       number(U32)),
+        ("AddressOfRawData".to_string(), hex(U32)),
+        ("PointerToRawData".to_string(), pointer_or_hex(raw_data)),
+    ])
+}
+
+/// A CodeView (`RSDS`) debug record, which identifies the PDB file.
+pub fn codeview() -> SimpleResult<H2Type> {
+    H2Struct::new(vec![
+        ("Signature".to_string(), magic(b"RSDS")?),
+        ("Guid".to_string(),      GUID::new(GUIDLayout::Microsoft, false)),
+        ("Age".to_string(),       number(U32)),
+        ("PdbFileName".to_string(), NTString::new(ascii())?),
+    ])
+}
+
+/// A section, as far as mapping RVAs goes.
+struct Section {
+    virtual_address: u64,
+    virtual_size: u64,
+    raw_offset: u64,
+    raw_size: u64,
+}
+
+/// The parts of the headers that the rest of the file depends on.
+struct Layout {
+    format: PEFormat,
+    nt_offset: u64,
+    optional_size: u64,
+    directories: Vec<(u64, u64)>,
+    sections: Vec<Section>,
+}
+
+impl Layout {
+    fn read(context: Context) -> SimpleResult<Self> {
+        let offset = Offset::from(context);
+
+        let dos = dos_header(None)?.resolve(offset, None)?;
+        let nt_offset = field(&dos, "e_lfanew")?;
+
+        // The signature, then the file header
+        magic(SIGNATURE)?.resolve(offset.at(nt_offset), None)?;
+        let file = file_header()?.resolve(offset.at(nt_offset + 4), None)?;
+
+        let optional_offset = nt_offset + 24;
+        let format = match number(U16).to_u64(offset.at(optional_offset))? {
+            0x10b => PEFormat::PE32,
+            0x20b => PEFormat::PE32Plus,
+            m     => bail!("Unknown optional header magic: 0x{:x}", m),
+        };
+
+        // Read the optional header without directories, then with them; like
+        // the loader, ignore any directories past the 16 that are defined
+        let optional = optional_header(format, 0)?.resolve(offset.at(optional_offset), None)?;
+        let optional_size = field(&file, "SizeOfOptionalHeader")?;
+        if optional_size < optional.actual_size() {
+            bail!("Optional header is too small: {} bytes", optional_size);
+        }
+
+        let count = std::cmp::min(field(&optional, "NumberOfRvaAndSizes")?, DATA_DIRECTORIES.len() as u64);
+        let optional = optional_header(format, count)?.resolve(offset.at(optional_offset), None)?;
+
+        let directories = match optional.children.last() {
+            Some(d) if count > 0 => d.children.iter().map(|d| {
+                Ok((field(d, "VirtualAddress")?, field(d, "Size")?))
+            }).collect::<SimpleResult<Vec<_>>>()?,
+            _ => vec![],
+        };
+
+        let section_count = field(&file, "NumberOfSections")?;
+        let sections = match section_count {
+            0 => vec![],
+            n => H2Array::new(n, section_header()?)?.resolve(offset.at(optional_offset + optional_size), None)?.children.iter().map(|s| {
+                Ok(Section {
+                    virtual_address: field(s, "VirtualAddress")?,
+                    virtual_size:    field(s, "VirtualSize")?,
+                    raw_offset:      field(s, "PointerToRawData")?,
+                    raw_size:        field(s, "SizeOfRawData")?,
+                })
+            }).collect::<SimpleResult<Vec<_>>>()?,
+        };
+
+        Ok(Self {
+            format: format,
+            nt_offset: nt_offset,
+            optional_size: optional_size,
+            directories: directories,
+            sections: sections,
+        })
+    }
+
+    fn rva_to_offset(&self, rva: u64) -> SimpleResult<u64> {
+        for section in self.sections.iter() {
+            // Sections are padded in memory, so use the larger of the sizes
+            let size = std::cmp::max(section.virtual_size, section.raw_size);
+
+            if rva >= section.virtual_address && rva < section.virtual_address + size {
+                let delta = rva - section.virtual_address;
+                if delta >= section.raw_size {
+                    bail!("RVA 0x{:x} isn't backed by the file", rva);
+                }
+
+                return Ok(section.raw_offset + delta);
+            }
+        }
+
+        // Before the first section, the headers are mapped as-is
+        match self.sections.iter().map(|s| s.virtual_address).min() {
+            Some(first) if rva < first => Ok(rva),
+            _ => bail!("RVA 0x{:x} isn't in any section", rva),
+        }
+    }
+
+    /// A pointer (read as `definition`) to the `target` at `rva`, adjusted so
+    /// it points to the target's file offset.
+    fn pointer(&self, definition: SizedDefinition, rva: u64, target: H2Type) -> SimpleResult<H2Type> {
+        let adjustment = self.rva_to_offset(rva)? as i64 - rva as i64;
+
+        Ok(H2Pointer::new_adjusted(definition, SizedDisplay::Hex(Default::default()), adjustment, target))
+    }
+
+    /// Walk the import lookup table (or import address table) at `rva`, and
+    /// build it: each entry is an ordinal, or a pointer to an
+    /// [`hint_name`] named after the import, and the table ends with a null
+    /// entry. Also returns the number of entries, including the null one.
+    fn thunks(&self, offset: Offset, rva: u64) -> SimpleResult<(H2Type, u64)> {
+        let word = self.format.word();
+        let position = self.rva_to_offset(rva)?;
+
+        // The top bit means the import is by ordinal
+        let ordinal_flag = 1u64 << (word.size() * 8 - 1);
+
+        let mut entries = Vec::new();
+        loop {
+            let value = number(word).to_u64(offset.at(position + word.size() * entries.len() as u64))?;
+
+            if value == 0 {
+                entries.push(("Null".to_string(), hex(word)));
+                break;
+            }
+
+            if value & ordinal_flag != 0 {
+                entries.push((format!("Ordinal {}", value & 0xffff), hex(word)));
+            } else {
+                let name = NTString::new(ascii())?.to_string(offset.at(self.rva_to_offset(value)? + 2))?;
+                entries.push((name, self.pointer(word, value, hint_name()?)?));
+            }
+        }
+
+        let count = entries.len() as u64;
+        Ok((H2Struct::new(entries)?, count))
+    }
+
+    /// Get the file offset and size of a data directory, if it's present.
+    fn directory(&self, index: usize) -> SimpleResult<Option<(u64, u64)>> {
+        match self.directories.get(index) {
+            Some(&(rva, size)) if rva != 0 && size != 0 => Ok(Some((self.rva_to_offset(rva)?, size))),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Convert an RVA (relative virtual address) to a file offset, using the
+/// section table of the PE file at the start of the buffer.
+pub fn rva_to_offset(context: Context, rva: u64) -> SimpleResult<u64> {
+    Layout::read(context)?.rva_to_offset(rva)
+}
+
+/// Read the headers at the start of the buffer and build the DOS header, with
+/// `e_lfanew` pointing to the NT headers and section table.
+pub fn pe(context: Context) -> SimpleResult<H2Type> {
+    let layout = Layout::read(context)?;
+
+    // If the section table starts before the end of the directories, only
+    // show the directories that come before it
+    let base = optional_header(layout.format, 0)?.actual_size(Offset::Static(0))?;
+    let entry = data_directory()?.actual_size(Offset::Static(0))?;
+    let directories = std::cmp::min(layout.directories.len() as u64, (layout.optional_size - base) / entry);
+
+    dos_header(Some(nt_headers(layout.format, directories, layout.optional_size, layout.sections.len() as u64)?))
+}
+
+/// Find the export, import, and debug directories by following the data
+/// directories' RVAs through the section table.
+///
+/// Each one is returned as its file offset and type, similar to
+/// [`crate::analyze::find_strings`]. The tables they refer to are reached
+/// through [`H2Pointer`]s, adjusted to turn the RVAs into file offsets:
+///
+/// * The export directory points to the DLL name, the functions, the names
+///   (each a pointer to a string), and the ordinals.
+/// * The import directory is an entry for each DLL, named after it, which
+///   points to the name and to the import lookup and address tables; each
+///   of those points to the [`hint_name`] entries. If an import is bound,
+///   the address table holds addresses instead, so it's just numbers.
+/// * The debug directory is an entry for each record, named after its type,
+///   and CodeView entries point to their [`codeview`] record.
+pub fn pe_tables(context: Context) -> SimpleResult<Vec<(u64, H2Type)>> {
+    let layout = Layout::read(context)?;
+    let offset = Offset::from(context);
+
+    let mut tables = Vec::new();
+
+    if let Some((position, _)) = layout.directory(DIRECTORY_EXPORT)? {
+        let resolved = export_directory(None, None, None, None)?.resolve(offset.at(position), None)?;
+
+        let name = match field(&resolved, "Name")? {
+            0   => None,
+            rva => Some(layout.pointer(U32, rva, NTString::new(ascii())?)?),
+        };
+
+        // The functions are RVAs, indexed by ordinal (minus Base)
+        let functions = match field(&resolved, "NumberOfFunctions")? {
+            0 => None,
+            n => Some(layout.pointer(U32, field(&resolved, "AddressOfFunctions")?, H2Array::new(n, hex(U32))?)?),
+        };
+
+        // The names and ordinals are parallel arrays, and each name is an RVA
+        // to a string
+        let (names, ordinals) = match field(&resolved, "NumberOfNames")? {
+            0 => (None, None),
+            n => {
+                let names_rva = field(&resolved, "AddressOfNames")?;
+                let name_rvas = H2Array::new(n, hex(U32))?.resolve(offset.at(layout.rva_to_offset(names_rva)?), None)?;
+
+                let names = name_rvas.children.iter().map(|rva| {
+                    let rva = match rva.as_u64 {
+                        Some(rva) => rva,
+                        None      => bail!("Export name RVA isn't a number"),
+                    };
+
+                    let name = NTString::new(ascii())?;
+                    Ok((name.to_string(offset.at(layout.rva_to_offset(rva)?))?, layout.pointer(U32, rva, name)?))
+                }).collect::<SimpleResult<Vec<_>>>()?;
+
+                (
+                    Some(layout.pointer(U32, names_rva, H2Struct::new(names)?)?),
+                    Some(layout.pointer(U32, field(&resolved, "AddressOfNameOrdinals")?, H2Array::new(n, number(U16))?)?),
+                )
+            },
+        };
+
+        tables.push((position, export_directory(name, functions, names, ordinals)?));
+    }
+
+    if let Some((position, _)) = layout.directory(DIRECTORY_IMPORT)? {
+        // Each entry is named after its DLL, up to the terminating entry,
+        // which has no name
+        let descriptor = import_descriptor(None, None, None)?;
+        let size = descriptor.actual_size(offset)?;
+
+        let mut entries = Vec::new();
+        loop {
+            let entry = descriptor.resolve(offset.at(position + size * entries.len() as u64), None)?;
+
+            let name = match field(&entry, "Name")? {
+                0 => {
+                    entries.push(("Null".to_string(), descriptor.clone()));
+                    break;
+                },
+                rva => rva,
+            };
+
+            let dll = NTString::new(ascii())?;
+            let dll_name = dll.to_string(offset.at(layout.rva_to_offset(name)?))?;
+
+            // Old linkers leave out the lookup table, and use the address
+            // table for the names
+            let lookup = field(&entry, "OriginalFirstThunk")?;
+            let address = field(&entry, "FirstThunk")?;
+            let (lookup_table, count) = layout.thunks(offset, if lookup != 0 { lookup } else { address })?;
+
+            let address_table = match field(&entry, "TimeDateStamp")? {
+                0 => layout.thunks(offset, address)?.0,
+                _ => H2Array::new(count, hex(layout.format.word()))?,
+            };
+
+            entries.push((dll_name, import_descriptor(
+                match lookup {
+                    0 => None,
+                    _ => Some(layout.pointer(U32, lookup, lookup_table)?),
+                },
+                Some(layout.pointer(U32, name, dll)?),
+                Some(layout.pointer(U32, address, address_table)?),
+            )?));
+        }
+
+        if entries.len() > 1 {
+            tables.push((position, H2Struct::new(entries)?));
+        }
+    }
+
+    if let Some((position, size)) = layout.directory(DIRECTORY_DEBUG)? {
+        let entry = debug_directory(None)?;
+        let count = size / entry.actual_size(offset)?;
+
+        if count > 0 {
+            let resolved = H2Array::new(count, entry)?.resolve(offset.at(position), None)?;
+
+            let entries = resolved.children.iter().map(|entry| {
+                let debug_type = field(entry, "Type")?;
+                let name = DEBUG_TYPES.get(debug_type as usize).unwrap_or(&"Unknown").to_string();
+
+                match debug_type {
+                    DEBUG_TYPE_CODEVIEW => Ok((name, debug_directory(Some(H2Pointer::new(U32, SizedDisplay::Hex(Default::default()), codeview()?)))?)),
+                    _                   => Ok((name, debug_directory(None)?)),
+                }
+            }).collect::<SimpleResult<Vec<_>>>()?;
+
+            tables.push((position, H2Struct::new(entries)?));
+        }
+    }
+
+    Ok(tables)
+}
+
+/// Get the file offset of the NT headers (the value of `e_lfanew`).
+pub fn nt_headers_offset(context: Context) -> SimpleResult<u64> {
+    Ok(Layout::read(context)?.nt_offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+    use sized_number::Context;
+
+    fn put(data: &mut Vec<u8>, position: usize, value: &[u8]) {
+        data[position..(position + value.len())].copy_from_slice(value);
+    }
+
+    /// A small PE32+ file, with one section (`.rdata`) that holds an import
+    /// table, a debug directory, a CodeView record, and a DLL name.
+    fn pe64() -> Vec<u8> {
+        let mut data = vec![0; 0x400];
+
+        // DOS header
+        put(&mut data, 0x00, b"MZ");
+        put(&mut data, 0x3c, &0x40u32.to_le_bytes());
+
+        // NT headers: signature and file header
+        put(&mut data, 0x40, b"PE\x00\x00");
+        put(&mut data, 0x44, &0x8664u16.to_le_bytes());     // Machine
+        put(&mut data, 0x46, &1u16.to_le_bytes());          // NumberOfSections
+        put(&mut data, 0x48, &0x5f5e1000u32.to_le_bytes()); // TimeDateStamp
+        put(&mut data, 0x54, &240u16.to_le_bytes());        // SizeOfOptionalHeader
+        put(&mut data, 0x56, &0x22u16.to_le_bytes());       // Characteristics
+
+        // Optional header
+        put(&mut data, 0x58,       &0x20bu16.to_le_bytes());      // Magic
+        put(&mut data, 0x58 + 16,  &0x1000u32.to_le_bytes());     // AddressOfEntryPoint
+        put(&mut data, 0x58 + 24,  &0x140000000u64.to_le_bytes()); // ImageBase
+        put(&mut data, 0x58 + 108, &16u32.to_le_bytes());         // NumberOfRvaAndSizes
+
+        // Data directories: import and debug
+        put(&mut data, 0x58 + 112 + 8,  &0x1000u32.to_le_bytes());
+        put(&mut data, 0x58 + 112 + 12, &40u32.to_le_bytes());
+        put(&mut data, 0x58 + 112 + 48, &0x1028u32.to_le_bytes());
+        put(&mut data, 0x58 + 112 + 52, &28u32.to_le_bytes());
+
+        // Section table, right after the optional header
+        put(&mut data, 0x148,      b".rdata\x00\x00");
+        put(&mut data, 0x148 + 8,  &0x200u32.to_le_bytes());  // VirtualSize
+        put(&mut data, 0x148 + 12, &0x1000u32.to_le_bytes()); // VirtualAddress
+        put(&mut data, 0x148 + 16, &0x200u32.to_le_bytes());  // SizeOfRawData
+        put(&mut data, 0x148 + 20, &0x200u32.to_le_bytes());  // PointerToRawData
+
+        // Import descriptor (RVA 0x1000), then an empty one
+        put(&mut data, 0x200,      &0x1098u32.to_le_bytes()); // OriginalFirstThunk
+        put(&mut data, 0x200 + 12, &0x1080u32.to_le_bytes()); // Name
+        put(&mut data, 0x200 + 16, &0x10b0u32.to_le_bytes()); // FirstThunk
+
+        // Debug directory (RVA 0x1028)
+        put(&mut data, 0x228 + 12, &2u32.to_le_bytes());      // Type
+        put(&mut data, 0x228 + 16, &33u32.to_le_bytes());     // SizeOfData
+        put(&mut data, 0x228 + 20, &0x1044u32.to_le_bytes()); // AddressOfRawData
+        put(&mut data, 0x228 + 24, &0x244u32.to_le_bytes());  // PointerToRawData
+
+        // CodeView record
+        put(&mut data, 0x244, b"RSDS\x33\x22\x11\x00\x55\x44\x77\x66\x88\x99\xaa\xbb\xcc\xdd\xee\xff\x01\x00\x00\x00test.pdb\x00");
+
+        // DLL name (RVA 0x1080)
+        put(&mut data, 0x280, b"KERNEL32.dll\x00");
+
+        // Import lookup table (RVA 0x1098) and import address table (RVA
+        // 0x10b0): a name, an ordinal, and the null entry
+        for position in [0x298, 0x2b0].iter() {
+            put(&mut data, *position,     &0x10d0u64.to_le_bytes());
+            put(&mut data, *position + 8, &0x8000000000000005u64.to_le_bytes());
+        }
+
+        // Hint/name (RVA 0x10d0)
+        put(&mut data, 0x2d0, b"\x01\x00ExitProcess\x00");
+
+        data
+    }
+
+    #[test]
+    fn test_sizes() -> SimpleResult<()> {
+        let offset = Offset::Static(0);
+
+        assert_eq!(64,  dos_header(None)?.actual_size(offset)?);
+        assert_eq!(20,  file_header()?.actual_size(offset)?);
+        assert_eq!(224, optional_header(PEFormat::PE32, 16)?.actual_size(offset)?);
+        assert_eq!(240, optional_header(PEFormat::PE32Plus, 16)?.actual_size(offset)?);
+        assert_eq!(40,  section_header()?.actual_size(offset)?);
+        assert_eq!(20,  import_descriptor(None, None, None)?.actual_size(offset)?);
+        assert_eq!(40,  export_directory(None, None, None, None)?.actual_size(offset)?);
+        assert_eq!(28,  debug_directory(None)?.actual_size(offset)?);
+        assert!(optional_header(PEFormat::PE32, 17).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_headers() -> SimpleResult<()> {
+        let data = pe64();
+        let context = Context::new(&data);
+        let offset = Offset::from(context);
+
+        assert_eq!(0x40, nt_headers_offset(context)?);
+
+        let r = pe(context)?.resolve(offset, None)?;
+        assert_eq!(0..64, r.actual_range);
+
+        // e_lfanew points to the NT headers
+        let e_lfanew = r.children.last().unwrap();
+        assert_eq!(0x40, e_lfanew.related[0].0);
+
+        let nt = e_lfanew.related[0].1.resolve(offset.at(0x40), None)?;
+        assert_eq!(0x40..0x170, nt.actual_range);
+
+        let file = &nt.children[1];
+        assert_eq!(0x8664, field(file, "Machine")?);
+        assert_eq!("2020-09-13T12:26:40Z", file.children[2].display);
+
+        // Characteristics is 0x22
+        let characteristics = &file.children[6];
+        assert_eq!(16, characteristics.children.len());
+        assert_eq!(1, field(characteristics, "IMAGE_FILE_EXECUTABLE_IMAGE")?);
+        assert_eq!(1, field(characteristics, "IMAGE_FILE_LARGE_ADDRESS_AWARE")?);
+        assert_eq!(0, field(characteristics, "IMAGE_FILE_DLL")?);
+
+        let optional = &nt.children[2];
+        assert_eq!(0x140000000, field(optional, "ImageBase")?);
+        let directories = optional.children.last().unwrap();
+        assert_eq!(16, directories.children.len());
+        assert_eq!(Some("Debug".to_string()), directories.children[6].field_name);
+        assert_eq!(0x1028, field(&directories.children[6], "VirtualAddress")?);
+
+        let sections = &nt.children[3];
+        assert_eq!(1, sections.children.len());
+        assert_eq!(Some(".rdata\0\0".to_string()), sections.children[0].children[0].as_string);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tables() -> SimpleResult<()> {
+        let data = pe64();
+        let context = Context::new(&data);
+        let offset = Offset::from(context);
+
+        assert_eq!(0x200, rva_to_offset(context, 0x1000)?);
+        assert_eq!(0x280, rva_to_offset(context, 0x1080)?);
+        assert_eq!(0x10, rva_to_offset(context, 0x10)?);
+        assert!(rva_to_offset(context, 0x5000).is_err());
+
+        let tables = pe_tables(context)?;
+        assert_eq!(2, tables.len());
+
+        // The import table, with an entry for KERNEL32.dll and the null entry
+        assert_eq!(0x200, tables[0].0);
+        let imports = tables[0].1.resolve(offset.at(0x200), None)?;
+        assert_eq!(vec![Some("KERNEL32.dll".to_string()), Some("Null".to_string())], imports.children.iter().map(|c| c.field_name.clone()).collect::<Vec<_>>());
+
+        let kernel32 = &imports.children[0];
+        assert_eq!(0x280, kernel32.children[3].related[0].0);
+        assert_eq!(Some("KERNEL32.dll".to_string()), kernel32.children[3].related[0].1.resolve(offset.at(0x280), None)?.as_string);
+
+        // The lookup and address tables, through their RVAs
+        assert_eq!(0x298, kernel32.children[0].related[0].0);
+        assert_eq!(0x2b0, kernel32.children[4].related[0].0);
+
+        let lookup = kernel32.children[0].related[0].1.resolve(offset.at(0x298), None)?;
+        assert_eq!(0x298..0x2b0, lookup.actual_range);
+        assert_eq!(vec![Some("ExitProcess".to_string()), Some("Ordinal 5".to_string()), Some("Null".to_string())], lookup.children.iter().map(|c| c.field_name.clone()).collect::<Vec<_>>());
+
+        // The hint/name, through the lookup table
+        assert_eq!(0x2d0, lookup.children[0].related[0].0);
+        let hint_name = lookup.children[0].related[0].1.resolve(offset.at(0x2d0), None)?;
+        assert_eq!(Some(1), hint_name.children[0].as_u64);
+        assert_eq!(Some("ExitProcess".to_string()), hint_name.children[1].as_string);
+
+        // The debug directory and the CodeView record
+        assert_eq!(0x228, tables[1].0);
+        let debug = tables[1].1.resolve(offset.at(0x228), None)?;
+        assert_eq!(Some("CodeView".to_string()), debug.children[0].field_name);
+        assert_eq!(0x244, debug.children[0].children[7].related[0].0);
+
+        let codeview = debug.children[0].children[7].related[0].1.resolve(offset.at(0x244), None)?;
+        assert_eq!("{00112233-4455-6677-8899-aabbccddeeff}", codeview.children[1].display);
+        assert_eq!(Some("test.pdb".to_string()), codeview.children[3].as_string);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bound_imports() -> SimpleResult<()> {
+        // A bound import's address table holds addresses, not RVAs
+        let mut data = pe64();
+        put(&mut data, 0x200 + 4, &0xffffffffu32.to_le_bytes());
+        put(&mut data, 0x2b0, &0x7ff812345678u64.to_le_bytes());
+        put(&mut data, 0x2b8, &0x7ff812340000u64.to_le_bytes());
+
+        let context = Context::new(&data);
+        let offset = Offset::from(context);
+
+        let tables = pe_tables(context)?;
+        let imports = tables[0].1.resolve(offset.at(0x200), None)?;
+
+        let address = imports.children[0].children[4].related[0].1.resolve(offset.at(0x2b0), None)?;
+        assert_eq!(vec![Some(0x7ff812345678), Some(0x7ff812340000), Some(0)], address.children.iter().map(|c| c.as_u64).collect::<Vec<_>>());
+
+        // The names still come from the lookup table
+        let lookup = imports.children[0].children[0].related[0].1.resolve(offset.at(0x298), None)?;
+        assert_eq!(Some("ExitProcess".to_string()), lookup.children[0].field_name);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_exports() -> SimpleResult<()> {
+        let mut data = pe64();
+
+        // Export directory (RVA 0x1100), with two functions and one name
+        put(&mut data, 0x58 + 112,      &0x1100u32.to_le_bytes());
+        put(&mut data, 0x58 + 112 + 4,  &40u32.to_le_bytes());
+        put(&mut data, 0x300 + 12, &0x1140u32.to_le_bytes()); // Name
+        put(&mut data, 0x300 + 16, &1u32.to_le_bytes());      // Base
+        put(&mut data, 0x300 + 20, &2u32.to_le_bytes());      // NumberOfFunctions
+        put(&mut data, 0x300 + 24, &1u32.to_le_bytes());      // NumberOfNames
+        put(&mut data, 0x300 + 28, &0x1150u32.to_le_bytes()); // AddressOfFunctions
+        put(&mut data, 0x300 + 32, &0x1160u32.to_le_bytes()); // AddressOfNames
+        put(&mut data, 0x300 + 36, &0x1164u32.to_le_bytes()); // AddressOfNameOrdinals
+
+        put(&mut data, 0x340, b"test.dll\x00");
+        put(&mut data, 0x350, &0x2000u32.to_le_bytes());
+        put(&mut data, 0x354, &0x2010u32.to_le_bytes());
+        put(&mut data, 0x360, &0x1170u32.to_le_bytes());
+        put(&mut data, 0x364, &1u16.to_le_bytes());
+        put(&mut data, 0x370, b"Hello\x00");
+
+        let context = Context::new(&data);
+        let offset = Offset::from(context);
+
+        // The export directory comes before the import and debug tables
+        let tables = pe_tables(context)?;
+        assert_eq!(3, tables.len());
+        assert_eq!(0x300, tables[0].0);
+
+        let exports = tables[0].1.resolve(offset.at(0x300), None)?;
+        assert_eq!(0x340, exports.children[4].related[0].0);
+        assert_eq!(Some("test.dll".to_string()), exports.children[4].related[0].1.resolve(offset.at(0x340), None)?.as_string);
+
+        assert_eq!(0x350, exports.children[8].related[0].0);
+        let functions = exports.children[8].related[0].1.resolve(offset.at(0x350), None)?;
+        assert_eq!(vec![Some(0x2000), Some(0x2010)], functions.children.iter().map(|c| c.as_u64).collect::<Vec<_>>());
+
+        // Each name is a pointer to the string, named after it
+        assert_eq!(0x360, exports.children[9].related[0].0);
+        let names = exports.children[9].related[0].1.resolve(offset.at(0x360), None)?;
+        assert_eq!(Some("Hello".to_string()), names.children[0].field_name);
+        assert_eq!(0x370, names.children[0].related[0].0);
+
+        assert_eq!(0x364, exports.children[10].related[0].0);
+        let ordinals = exports.children[10].related[0].1.resolve(offset.at(0x364), None)?;
+        assert_eq!(Some(1), ordinals.children[0].as_u64);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_nonstandard_optional_header() -> SimpleResult<()> {
+        // A larger SizeOfOptionalHeader moves the section table back, and
+        // extra directories are ignored
+        let mut data = pe64();
+        let sections = data[0x148..0x170].to_vec();
+        put(&mut data, 0x148, &[0; 16]);
+        put(&mut data, 0x158, &sections);
+        put(&mut data, 0x54, &256u16.to_le_bytes());
+        put(&mut data, 0x58 + 108, &20u32.to_le_bytes());
+
+        let context = Context::new(&data);
+        let offset = Offset::from(context);
+
+        assert_eq!(0x200, rva_to_offset(context, 0x1000)?);
+        assert_eq!(2, pe_tables(context)?.len());
+
+        let r = pe(context)?.resolve(offset, None)?;
+        let nt = r.children.last().unwrap().related[0].1.resolve(offset.at(0x40), None)?;
+        assert_eq!(0x40..0x180, nt.actual_range);
+        assert_eq!(16, nt.children[2].children.last().unwrap().children.len());
+        assert_eq!(Some("Padding".to_string()), nt.children[3].field_name);
+        assert_eq!(0x148..0x158, nt.children[3].actual_range);
+        assert_eq!(Some(".rdata\0\0".to_string()), nt.children[4].children[0].children[0].as_string);
+
+        // A smaller one puts the section table on top of the last two
+        // directories, which aren't shown
+        let mut data = pe64();
+        let sections = data[0x148..0x170].to_vec();
+        put(&mut data, 0x138, &sections);
+        put(&mut data, 0x54, &224u16.to_le_bytes());
+
+        let context = Context::new(&data);
+        let offset = Offset::from(context);
+
+        assert_eq!(0x200, rva_to_offset(context, 0x1000)?);
+
+        let r = pe(context)?.resolve(offset, None)?;
+        let nt = r.children.last().unwrap().related[0].1.resolve(offset.at(0x40), None)?;
+        assert_eq!(0x40..0x160, nt.actual_range);
+        assert_eq!(14, nt.children[2].children.last().unwrap().children.len());
+        assert_eq!(0x138..0x160, nt.children[3].actual_range);
+
+        // Too small to hold the fixed fields
+        put(&mut data, 0x54, &100u16.to_le_bytes());
+        assert!(pe(Context::new(&data)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_not_pe() -> SimpleResult<()> {
+        let data = b"MZ".to_vec();
+        assert!(pe(Context::new(&data)).is_err());
+
+        // Bad NT signature
+        let mut data = pe64();
+        put(&mut data, 0x40, b"NE\x00\x00");
+        assert!(pe(Context::new(&data)).is_err());
+
+        // Bad optional header magic
+        let mut data = pe64();
+        put(&mut data, 0x58, &0x107u16.to_le_bytes());
+        assert!(pe(Context::new(&data)).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,229 @@
+//! Definitions for PNG images.
+//!
+//! A PNG file is an eight-byte signature, followed by a series of chunks.
+//! Each chunk has a length, a four-character type, that many bytes of data,
+//! and a CRC-32 of the type and data. The first chunk is always `IHDR`, and
+//! the last is always `IEND`.
+//!
+//! Since the chunks have different sizes and continue until `IEND`, [`png`]
+//! walks them to build the full definition (there's no type that repeats
+//! until a condition, so this is done by hand). The chunk type is the field
+//! name, so repeated chunks (such as `IDAT`) have the same name.
+
+use simple_error::{bail, SimpleResult};
+use sized_number::{Context, Endian, SizedDefinition};
+
+use crate::{H2Type, Offset};
+use crate::composite::H2Struct;
+use crate::composite::string::H2String;
+use crate::formats::common::{equals, field, magic, number, one_of};
+use crate::simple::{BytesDisplay, BytesLength, Checksum, ChecksumAlgorithm, ChecksumRange, H2Bytes};
+use crate::simple::character::{ASCII, StrictASCII};
+
+/// The signature at the start of every PNG file.
+pub const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+const U32: SizedDefinition = SizedDefinition::U32(Endian::Big);
+
+/// The length of an `IHDR` chunk's data.
+pub const IHDR_LENGTH: u64 = 13;
+
+/// The data in an `IHDR` chunk.
+///
+/// The bit depth, color type, and methods are checked, with a warning if
+/// they're not values the spec allows.
+pub fn ihdr() -> SimpleResult<H2Type> {
+    H2Struct::new(vec![
+        ("width".to_string(),              number(U32)),
+        ("height".to_string(),             number(U32)),
+        ("bit_depth".to_string(),          one_of(number(SizedDefinition::U8), vec![1, 2, 4, 8, 16])?),
+        ("color_type".to_string(),         one_of(number(SizedDefinition::U8), vec![0, 2, 3, 4, 6])?),
+        ("compression_method".to_string(), equals(number(SizedDefinition::U8), 0)?),
+        ("filter_method".to_string(),      equals(number(SizedDefinition::U8), 0)?),
+        ("interlace_method".to_string(),   one_of(number(SizedDefinition::U8), vec![0, 1])?),
+    ])
+}
+
+/// A chunk with `length` bytes of data.
+///
+/// The data is `data` if it's set, or bytes otherwise (a chunk with no data
+/// has no data field). The CRC is a [`Checksum`] over the type and data.
+pub fn chunk(length: u64, data: Option<H2Type>) -> SimpleResult<H2Type> {
+    let mut fields = vec![
+        ("length".to_string(), number(U32)),
+        ("type".to_string(),   H2String::new(4, ASCII::new(StrictASCII::Permissive))?),
+    ];
+
+    match (length, data) {
+        (_, Some(data)) => fields.push(("data".to_string(), data)),
+        (0, None)       => (),
+        (n, None)       => fields.push(("data".to_string(), H2Bytes::new(BytesLength::Fixed(n), BytesDisplay::Preview(16))?)),
+    };
+
+    let covered = fields.iter().skip(1).map(|(name, _)| name.clone()).collect();
+    fields.push(("crc".to_string(), Checksum::new(U32, ChecksumAlgorithm::CRC32, ChecksumRange::Fields(covered))?));
+
+    H2Struct::new(fields)
+}
+
+/// Read the chunk type at the given offset.
+fn chunk_type(offset: Offset) -> SimpleResult<String> {
+    H2String::new(4, ASCII::new(StrictASCII::Permissive))?.to_string(offset)
+}
+
+/// Walk the chunks of the PNG at the [`Context`]'s position, and build the
+/// definition: the signature, then each chunk up to and including `IEND`.
+pub fn png(context: Context) -> SimpleResult<H2Type> {
+    let offset = Offset::from(context);
+
+    magic(SIGNATURE)?.resolve(offset, None)?;
+
+    let mut fields = vec![
+        ("signature".to_string(), magic(SIGNATURE)?),
+    ];
+
+    let mut position = offset.position() + SIGNATURE.len() as u64;
+    loop {
+        let length = number(U32).to_u64(offset.at(position))?;
+        let name = chunk_type(offset.at(position + 4))?;
+
+        // A malformed IHDR is shown as bytes, so the chunk's size still
+        // matches its length
+        let data = match (name.as_str(), length) {
+            ("IHDR", IHDR_LENGTH) => Some(ihdr()?),
+            _                     => None,
+        };
+
+        let t = chunk(length, data)?;
+        position += t.actual_size(offset.at(position))?;
+
+        // Make sure the chunk is actually there, since the length could be
+        // anything
+        if context.at(position - 1).read_u8().is_err() {
+            bail!("{} chunk runs off the end of the buffer", name);
+        }
+
+        fields.push((name.clone(), t));
+
+        if name == "IEND" {
+            break;
+        }
+    }
+
+    H2Struct::new(fields)
+}
+
+/// Read the width and height from the `IHDR` chunk of the PNG at the
+/// [`Context`]'s position.
+pub fn dimensions(context: Context) -> SimpleResult<(u64, u64)> {
+    let offset = Offset::from(context);
+    let position = offset.position() + SIGNATURE.len() as u64;
+
+    if chunk_type(offset.at(position + 4))? != "IHDR" {
+        bail!("The first chunk isn't IHDR");
+    }
+
+    let length = number(U32).to_u64(offset.at(position))?;
+    if length != IHDR_LENGTH {
+        bail!("IHDR chunk has the wrong length: {}", length);
+    }
+
+    let header = ihdr()?.resolve(offset.at(position + 8), None)?;
+
+    Ok((field(&header, "width")?, field(&header, "height")?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+    use sized_number::Context;
+
+    /// A 1x1 image with a comment.
+    fn image() -> Vec<u8> {
+        let mut data = SIGNATURE.to_vec();
+        data.extend_from_slice(b"\x00\x00\x00\x0dIHDR\x00\x00\x00\x01\x00\x00\x00\x01\x08\x06\x00\x00\x00\x1f\x15\xc4\x89");
+        data.extend_from_slice(b"\x00\x00\x00\x0atEXtComment\x00hi\xa2\xa2\x58\x66");
+        data.extend_from_slice(b"\x00\x00\x00\x09IDAT\x78\x9c\x63\x00\x00\x00\x01\x00\x01\x5e\xff\x7d\xf9");
+        data.extend_from_slice(b"\x00\x00\x00\x00IEND\xae\x42\x60\x82");
+
+        data
+    }
+
+    #[test]
+    fn test_png() -> SimpleResult<()> {
+        let data = image();
+        let context = Context::new(&data);
+
+        let r = png(context)?.resolve(Offset::from(context), None)?;
+        assert_eq!(0..(data.len() as u64), r.actual_range);
+
+        let names: Vec<String> = r.children.iter().map(|c| c.field_name.clone().unwrap()).collect();
+        assert_eq!(vec!["signature", "IHDR", "tEXt", "IDAT", "IEND"], names);
+
+        // The IHDR is parsed, and every CRC is good
+        let header = &r.children[1].children[2];
+        assert_eq!(1, field(header, "width")?);
+        assert_eq!(8, field(header, "bit_depth")?);
+
+        for chunk in &r.children[1..] {
+            assert_eq!(Some(true), chunk.children.last().unwrap().valid);
+        }
+
+        // IEND has no data
+        assert_eq!(3, r.children[4].children.len());
+
+        assert_eq!((1, 1), dimensions(context)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_crc() -> SimpleResult<()> {
+        let mut data = image();
+        data[37] = b'X';
+
+        let r = png(Context::new(&data))?.resolve(Offset::from(Context::new(&data)), None)?;
+        assert_eq!(Some(false), r.children[2].children.last().unwrap().valid);
+
+        // The bad CRC makes the chunk, and the image, invalid
+        assert_eq!(Some(false), r.children[2].valid);
+        assert_eq!(Some(false), r.valid);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_ihdr_length() -> SimpleResult<()> {
+        // An IHDR with an extra byte of data is just bytes, and the chunks
+        // after it are still in the right place
+        let mut data = SIGNATURE.to_vec();
+        data.extend_from_slice(b"\x00\x00\x00\x0eIHDR\x00\x00\x00\x01\x00\x00\x00\x01\x08\x06\x00\x00\x00\x00\x00\x00\x00\x00");
+        data.extend_from_slice(b"\x00\x00\x00\x00IEND\xae\x42\x60\x82");
+        let context = Context::new(&data);
+
+        let r = png(context)?.resolve(Offset::from(context), None)?;
+        assert_eq!(0..(data.len() as u64), r.actual_range);
+        assert_eq!(8..34, r.children[1].actual_range);
+        assert_eq!(Some("IEND".to_string()), r.children[2].field_name);
+        assert!(r.children[1].children[2].children.is_empty());
+
+        assert!(dimensions(context).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_truncated() -> SimpleResult<()> {
+        // No IEND
+        let data = image();
+        let data = data[0..(data.len() - 12)].to_vec();
+        assert!(png(Context::new(&data)).is_err());
+
+        // Not a PNG
+        let data = b"GIF89a".to_vec();
+        assert!(png(Context::new(&data)).is_err());
+
+        Ok(())
+    }
+}
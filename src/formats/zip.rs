@@ -0,0 +1,321 @@
+//! Definitions for ZIP archives.
+//!
+//! A ZIP archive is a series of local files (each a header followed by the
+//! file's data), then the central directory (a header for each file, which
+//! points back to the local file), then the end of central directory record,
+//! which points to the central directory.
+//!
+//! The only reliable way to read a ZIP is from the end: the end of central
+//! directory record is found by scanning backwards from the end of the buffer
+//! (it ends with a variable-length comment), and everything else is found
+//! through it. [`zip`] does that, and builds the whole archive with pointers
+//! from the end record, to the central directory, to each local file.
+//!
+//! Offsets in a ZIP archive are from the start of the archive, so it must
+//! start at the beginning of the buffer for the pointers to work. Data
+//! descriptors (which follow the data when bit 3 of the flags is set) and
+//! ZIP64 records aren't handled.
+
+use simple_error::{bail, SimpleResult};
+use sized_number::{Context, Endian, SizedDefinition, SizedDisplay};
+
+use crate::{H2Type, Offset};
+use crate::composite::H2Struct;
+use crate::composite::string::H2String;
+use crate::formats::common::{field, hex, magic, number, one_of};
+use crate::offset::buffer_end;
+use crate::simple::{BytesDisplay, BytesLength, H2Bytes, H2Pointer};
+use crate::simple::character::{ASCII, StrictASCII};
+use crate::simple::time::{Timestamp, TimestampFormat};
+
+/// The signature of a local file header.
+pub const LOCAL_FILE_SIGNATURE: &[u8] = b"PK\x03\x04";
+
+/// The signature of a central directory header.
+pub const CENTRAL_DIRECTORY_SIGNATURE: &[u8] = b"PK\x01\x02";
+
+/// The signature of the end of central directory record.
+pub const END_OF_CENTRAL_DIRECTORY_SIGNATURE: &[u8] = b"PK\x05\x06";
+
+/// The compression methods that are defined (stored, deflate, bzip2, LZMA,
+/// and so on).
+const COMPRESSION_METHODS: [u64; 11] = [0, 1, 6, 8, 9, 12, 14, 93, 95, 98, 99];
+
+const U16: SizedDefinition = SizedDefinition::U16(Endian::Little);
+const U32: SizedDefinition = SizedDefinition::U32(Endian::Little);
+
+// The sizes of the fixed parts of the records
+const LOCAL_FILE_SIZE: u64 = 30;
+const CENTRAL_DIRECTORY_SIZE: u64 = 46;
+const END_OF_CENTRAL_DIRECTORY_SIZE: u64 = 22;
+
+/// A string of `length` characters; names and comments are officially CP437
+/// (or UTF-8, if bit 11 of the flags is set), but are almost always ASCII.
+fn string(length: u64) -> SimpleResult<H2Type> {
+    H2String::new(length, ASCII::new(StrictASCII::Permissive))
+}
+
+/// Add the variable-length fields at the end of a record, skipping any that
+/// are empty.
+fn variable_fields(fields: &mut Vec<(String, H2Type)>, name_length: u64, extra_length: u64, comment_length: Option<u64>) -> SimpleResult<()> {
+    if name_length > 0 {
+        fields.push(("name".to_string(), string(name_length)?));
+    }
+
+    if extra_length > 0 {
+        fields.push(("extra".to_string(), H2Bytes::new(BytesLength::Fixed(extra_length), BytesDisplay::Hex)?));
+    }
+
+    match comment_length {
+        Some(0) | None => (),
+        Some(n) => fields.push(("comment".to_string(), string(n)?)),
+    };
+
+    Ok(())
+}
+
+/// A local file: the header (with a `name_length`-character name and
+/// `extra_length` bytes of extra data), then `data_length` bytes of data.
+pub fn local_file(name_length: u64, extra_length: u64, data_length: u64) -> SimpleResult<H2Type> {
+    let mut fields = vec![
+        ("signature".to_string(),         magic(LOCAL_FILE_SIGNATURE)?),
+        ("version_needed".to_string(),    number(U16)),
+        ("flags".to_string(),             hex(U16)),
+        ("compression".to_string(),       one_of(number(U16), COMPRESSION_METHODS.to_vec())?),
+        ("modified".to_string(),          Timestamp::new(U32, TimestampFormat::DOS)?),
+        ("crc32".to_string(),             hex(U32)),
+        ("compressed_size".to_string(),   number(U32)),
+        ("uncompressed_size".to_string(), number(U32)),
+        ("name_length".to_string(),       number(U16)),
+        ("extra_length".to_string(),      number(U16)),
+    ];
+
+    variable_fields(&mut fields, name_length, extra_length, None)?;
+
+    if data_length > 0 {
+        fields.push(("data".to_string(), H2Bytes::new(BytesLength::Fixed(data_length), BytesDisplay::Preview(16))?));
+    }
+
+    H2Struct::new(fields)
+}
+
+/// A central directory header, with a `name_length`-character name,
+/// `extra_length` bytes of extra data, and a `comment_length`-character
+/// comment.
+///
+/// If `local_file` is set, `local_header_offset` is a pointer to it;
+/// otherwise, it's just a number.
+pub fn central_directory_header(name_length: u64, extra_length: u64, comment_length: u64, local_file: Option<H2Type>) -> SimpleResult<H2Type> {
+    let local_header_offset = match local_file {
+        Some(t) => H2Pointer::new(U32, SizedDisplay::Hex(Default::default()), t),
+        None    => hex(U32),
+    };
+
+    let mut fields = vec![
+        ("signature".to_string(),           magic(CENTRAL_DIRECTORY_SIGNATURE)?),
+        ("version_made_by".to_string(),     hex(U16)),
+        ("version_needed".to_string(),      number(U16)),
+        ("flags".to_string(),               hex(U16)),
+        ("compression".to_string(),         one_of(number(U16), COMPRESSION_METHODS.to_vec())?),
+        ("modified".to_string(),            Timestamp::new(U32, TimestampFormat::DOS)?),
+        ("crc32".to_string(),               hex(U32)),
+        ("compressed_size".to_string(),     number(U32)),
+        ("uncompressed_size".to_string(),   number(U32)),
+        ("name_length".to_string(),         number(U16)),
+        ("extra_length".to_string(),        number(U16)),
+        ("comment_length".to_string(),      number(U16)),
+        ("disk_start".to_string(),          number(U16)),
+        ("internal_attributes".to_string(), hex(U16)),
+        ("external_attributes".to_string(), hex(U32)),
+        ("local_header_offset".to_string(), local_header_offset),
+    ];
+
+    variable_fields(&mut fields, name_length, extra_length, Some(comment_length))?;
+
+    H2Struct::new(fields)
+}
+
+/// The end of central directory record, with a `comment_length`-character
+/// comment.
+///
+/// If `central_directory` is set, `central_directory_offset` is a pointer to
+/// it; otherwise, it's just a number.
+pub fn end_of_central_directory(comment_length: u64, central_directory: Option<H2Type>) -> SimpleResult<H2Type> {
+    let central_directory_offset = match central_directory {
+        Some(t) => H2Pointer::new(U32, SizedDisplay::Hex(Default::default()), t),
+        None    => hex(U32),
+    };
+
+    let mut fields = vec![
+        ("signature".to_string(),                magic(END_OF_CENTRAL_DIRECTORY_SIGNATURE)?),
+        ("disk_number".to_string(),              number(U16)),
+        ("central_directory_disk".to_string(),   number(U16)),
+        ("disk_entries".to_string(),             number(U16)),
+        ("total_entries".to_string(),            number(U16)),
+        ("central_directory_size".to_string(),   number(U32)),
+        ("central_directory_offset".to_string(), central_directory_offset),
+        ("comment_length".to_string(),           number(U16)),
+    ];
+
+    variable_fields(&mut fields, 0, 0, Some(comment_length))?;
+
+    H2Struct::new(fields)
+}
+
+/// Find the end of central directory record, by scanning backwards from the
+/// end of the buffer.
+///
+/// To avoid false positives in the comment, the record's comment length has
+/// to reach exactly to the end of the buffer.
+pub fn find_end_of_central_directory(context: Context) -> SimpleResult<u64> {
+    let offset = Offset::from(context);
+    let end = buffer_end(context);
+
+    if end < offset.position() + END_OF_CENTRAL_DIRECTORY_SIZE {
+        bail!("Buffer is too short to be a ZIP archive");
+    }
+
+    // The comment is at most 65535 bytes
+    let last = end - END_OF_CENTRAL_DIRECTORY_SIZE;
+    let first = std::cmp::max(offset.position(), last.saturating_sub(0xffff));
+
+    for position in (first..=last).rev() {
+        if magic(END_OF_CENTRAL_DIRECTORY_SIGNATURE)?.resolve(offset.at(position), None).is_err() {
+            continue;
+        }
+
+        if number(U16).to_u64(offset.at(position + 20))? == end - position - END_OF_CENTRAL_DIRECTORY_SIZE {
+            return Ok(position);
+        }
+    }
+
+    bail!("Couldn't find the end of central directory record");
+}
+
+/// Read the ZIP archive in the buffer, and build the end of central directory
+/// record, which points to the central directory, which points to each local
+/// file.
+///
+/// Since the record is at the end of the archive, it's returned along with
+/// its offset. The central directory is a struct, with each entry named after
+/// its file.
+pub fn zip(context: Context) -> SimpleResult<(u64, H2Type)> {
+    let offset = Offset::from(context);
+
+    let end_position = find_end_of_central_directory(context)?;
+    let end = end_of_central_directory(0, None)?.resolve(offset.at(end_position), None)?;
+
+    let total_entries = field(&end, "total_entries")?;
+    let mut position = field(&end, "central_directory_offset")?;
+
+    let mut entries = Vec::new();
+    for _ in 0..total_entries {
+        let header = central_directory_header(0, 0, 0, None)?.resolve(offset.at(position), None)?;
+        let name_length = field(&header, "name_length")?;
+        let extra_length = field(&header, "extra_length")?;
+        let comment_length = field(&header, "comment_length")?;
+        let name = string(name_length)?.to_string(offset.at(position + CENTRAL_DIRECTORY_SIZE))?;
+
+        // The local header has its own name and extra lengths, which don't
+        // necessarily match
+        let local_position = field(&header, "local_header_offset")?;
+        let local = local_file(0, 0, 0)?.resolve(offset.at(local_position), None)?;
+        let local = local_file(field(&local, "name_length")?, field(&local, "extra_length")?, field(&header, "compressed_size")?)?;
+
+        let entry = central_directory_header(name_length, extra_length, comment_length, Some(local))?;
+        position += entry.actual_size(offset.at(position))?;
+
+        entries.push((name, entry));
+    }
+
+    let central_directory = match entries.len() {
+        0 => None,
+        _ => Some(H2Struct::new(entries)?),
+    };
+
+    let comment_length = field(&end, "comment_length")?;
+
+    Ok((end_position, end_of_central_directory(comment_length, central_directory)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+    use sized_number::Context;
+
+    /// An archive with two stored files ("a.txt" and "dir/b.txt") and a
+    /// comment ("zc").
+    fn archive() -> Vec<u8> {
+        b"\x50\x4b\x03\x04\x14\x00\x00\x00\x00\x00\x54\x63\x2d\x51\x86\xa6\x10\x36\x05\x00\x00\x00\x05\x00\x00\x00\x05\x00\x00\x00\x61\x2e\x74\x78\x74\x68\x65\x6c\x6c\x6f\
+          \x50\x4b\x03\x04\x14\x00\x00\x00\x00\x00\x00\x00\x21\x00\xe8\x98\x84\x71\x06\x00\x00\x00\x06\x00\x00\x00\x09\x00\x00\x00\x64\x69\x72\x2f\x62\x2e\x74\x78\x74\x77\x6f\x72\x6c\x64\x21\
+          \x50\x4b\x01\x02\x14\x03\x14\x00\x00\x00\x00\x00\x54\x63\x2d\x51\x86\xa6\x10\x36\x05\x00\x00\x00\x05\x00\x00\x00\x05\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x80\x01\x00\x00\x00\x00\x61\x2e\x74\x78\x74\
+          \x50\x4b\x01\x02\x14\x03\x14\x00\x00\x00\x00\x00\x00\x00\x21\x00\xe8\x98\x84\x71\x06\x00\x00\x00\x06\x00\x00\x00\x09\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x80\x01\x28\x00\x00\x00\x64\x69\x72\x2f\x62\x2e\x74\x78\x74\
+          \x50\x4b\x05\x06\x00\x00\x00\x00\x02\x00\x02\x00\x6a\x00\x00\x00\x55\x00\x00\x00\x02\x00\x7a\x63".to_vec()
+    }
+
+    #[test]
+    fn test_zip() -> SimpleResult<()> {
+        let data = archive();
+        let context = Context::new(&data);
+        let offset = Offset::from(context);
+
+        assert_eq!(191, find_end_of_central_directory(context)?);
+
+        let (position, t) = zip(context)?;
+        assert_eq!(191, position);
+
+        let end = t.resolve(offset.at(position), None)?;
+        assert_eq!(191..215, end.actual_range);
+        assert_eq!(Some("zc".to_string()), end.children.last().unwrap().as_string);
+
+        // The central directory, through the pointer
+        let pointer = &end.children[6];
+        assert_eq!(85, pointer.related[0].0);
+        let directory = pointer.related[0].1.resolve(offset.at(85), None)?;
+        assert_eq!(85..191, directory.actual_range);
+
+        let names: Vec<String> = directory.children.iter().map(|c| c.field_name.clone().unwrap()).collect();
+        assert_eq!(vec!["a.txt", "dir/b.txt"], names);
+
+        // The second file, through the central directory
+        let entry = &directory.children[1];
+        assert_eq!("1980-01-01T00:00:00Z", entry.children[5].display);
+        assert_eq!(40, entry.children[15].related[0].0);
+
+        let local = entry.children[15].related[0].1.resolve(offset.at(40), None)?;
+        assert_eq!(40..85, local.actual_range);
+        assert_eq!(Some("dir/b.txt".to_string()), local.children[10].as_string);
+        assert_eq!("77 6f 72 6c 64 21", local.children[11].display);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_trailing_garbage() -> SimpleResult<()> {
+        // A signature inside the comment shouldn't confuse it
+        let mut data = archive();
+        data[211] = 0x06;
+        data.extend_from_slice(b"PK\x05\x06");
+        assert_eq!(191, find_end_of_central_directory(Context::new(&data))?);
+
+        // But anything after the comment means it's not found
+        let mut data = archive();
+        data.push(0);
+        assert!(find_end_of_central_directory(Context::new(&data)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sizes() -> SimpleResult<()> {
+        let offset = Offset::Static(0);
+
+        assert_eq!(LOCAL_FILE_SIZE,               local_file(0, 0, 0)?.actual_size(offset)?);
+        assert_eq!(CENTRAL_DIRECTORY_SIZE,        central_directory_header(0, 0, 0, None)?.actual_size(offset)?);
+        assert_eq!(END_OF_CENTRAL_DIRECTORY_SIZE, end_of_central_directory(0, None)?.actual_size(offset)?);
+        assert_eq!(LOCAL_FILE_SIZE + 9,           local_file(2, 3, 4)?.actual_size(offset)?);
+
+        Ok(())
+    }
+}
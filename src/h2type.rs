@@ -1,13 +1,14 @@
 #[cfg(feature = "serialize")]
 use serde::{Serialize, Deserialize};
 
-use simple_error::SimpleResult;
+use simple_error::{bail, SimpleResult};
 use std::ops::Range;
 
 use crate::{Alignment, H2TypeTrait, Offset, ResolvedType};
 use crate::simple::*;
 use crate::simple::character::*;
 use crate::simple::network::*;
+use crate::simple::time::*;
 use crate::composite::*;
 use crate::composite::string::*;
 
@@ -20,12 +21,23 @@ pub enum H2Types {
     // Simple
     H2Number(H2Number),
     H2Pointer(H2Pointer),
+    H2Bytes(H2Bytes),
+    Varint(Varint),
+    FixedPoint(FixedPoint),
+    BCD(BCD),
+    Checksum(Checksum),
+    BitNumber(BitNumber),
 
     // Netework
     IPv4(IPv4),
     IPv6(IPv6),
     MacAddress(MacAddress),
     MacAddress8(MacAddress8),
+    GUID(GUID),
+    DNSName(DNSName),
+
+    // Time
+    Timestamp(Timestamp),
 
     // Characters
     ASCII(ASCII),
@@ -37,6 +49,13 @@ pub enum H2Types {
     H2Array(H2Array),
     H2Enum(H2Enum),
     H2Struct(H2Struct),
+    Protobuf(Protobuf),
+    CBOR(CBOR),
+    MessagePack(MessagePack),
+    ASN1(ASN1),
+    Constrained(Constrained),
+    Transform(Transform),
+    H2BitStruct(H2BitStruct),
 
     // Strings
     H2String(H2String),
@@ -78,14 +97,25 @@ impl H2Type {
     fn field_type(&self) -> &dyn H2TypeTrait {
         match &self.field {
             // Simple
-            H2Types::H2Number(t)  => t,
-            H2Types::H2Pointer(t) => t,
+            H2Types::H2Number(t)   => t,
+            H2Types::H2Pointer(t)  => t,
+            H2Types::H2Bytes(t)    => t,
+            H2Types::Varint(t)     => t,
+            H2Types::FixedPoint(t) => t,
+            H2Types::BCD(t)        => t,
+            H2Types::Checksum(t)   => t,
+            H2Types::BitNumber(t)  => t,
 
             // Network
             H2Types::IPv4(t)        => t,
             H2Types::IPv6(t)        => t,
             H2Types::MacAddress(t)  => t,
             H2Types::MacAddress8(t) => t,
+            H2Types::GUID(t)        => t,
+            H2Types::DNSName(t)     => t,
+
+            // Time
+            H2Types::Timestamp(t) => t,
 
             // Characters
             H2Types::ASCII(t) => t,
@@ -94,9 +124,16 @@ impl H2Type {
             H2Types::UTF32(t) => t,
 
             // Complex
-            H2Types::H2Array(t)   => t,
-            H2Types::H2Enum(t)    => t,
-            H2Types::H2Struct(t)  => t,
+            H2Types::H2Array(t)       => t,
+            H2Types::H2Enum(t)        => t,
+            H2Types::H2Struct(t)      => t,
+            H2Types::Protobuf(t)      => t,
+            H2Types::CBOR(t)          => t,
+            H2Types::MessagePack(t)   => t,
+            H2Types::ASN1(t)          => t,
+            H2Types::Constrained(t)   => t,
+            H2Types::Transform(t)     => t,
+            H2Types::H2BitStruct(t)   => t,
 
             // Strings
             H2Types::H2String(t)   => t,
@@ -105,38 +142,101 @@ impl H2Type {
         }
     }
 
+    /// Types that are made of whole bytes can't start partway through one, so
+    /// if the [`Offset`] has a bit, they start at the next byte.
+    fn byte_offset<'a>(&self, offset: Offset<'a>) -> SimpleResult<Offset<'a>> {
+        if offset.bit() == 0 || self.is_bit_sized() {
+            return Ok(offset);
+        }
+
+        match offset.position().checked_add(1) {
+            Some(position) => Ok(offset.at(position)),
+            None => bail!("Offset is too large: {}", offset.position()),
+        }
+    }
+
+    /// If the [`Offset`] is bounded, make sure the whole field fits inside the
+    /// bounds before anything is read from it. Types read through the
+    /// [`sized_number::Context`] directly, which can see the whole buffer, so
+    /// this is what stops a value from including bytes past the end.
+    fn check_bounds(&self, offset: Offset) -> SimpleResult<()> {
+        if offset.bounds().is_some() {
+            self.actual_size(offset)?;
+        }
+
+        Ok(())
+    }
+
     /// Is the size known ahead of time?
     pub fn is_static(&self) -> bool {
         self.field_type().is_static()
     }
 
+    /// Is the field a number of bits, rather than bytes? If it is, it can
+    /// start partway through a byte. Aligned fields always start on a byte.
+    pub fn is_bit_sized(&self) -> bool {
+        matches!(self.alignment, Alignment::None) && self.field_type().is_bit_sized()
+    }
+
+    /// Get the size of the field in bits, if it [`H2Type::is_bit_sized`].
+    pub fn bit_size(&self, offset: Offset) -> SimpleResult<u64> {
+        if !self.is_bit_sized() {
+            bail!("This type is a number of bytes, not bits");
+        }
+
+        self.field_type().bit_size(offset)
+    }
+
     /// Get the size of just the field - no alignment included.
     ///
     /// Note that if the type has children (such as a
     /// [`crate::composite::H2Array`], the alignment on THAT is included
     /// since that's part of the actual object.
+    ///
+    /// If the [`Offset`] is bounded, the field has to fit inside the bounds.
     pub fn actual_size(&self, offset: Offset) -> SimpleResult<u64> {
-        self.field_type().actual_size(offset)
+        let offset = self.byte_offset(offset)?;
+        let size = self.field_type().actual_size(offset)?;
+
+        let end = match offset.position().checked_add(size) {
+            Some(end) => end,
+            None      => bail!("Size is too large: {}", size),
+        };
+        offset.check_range(&(offset.position()..end))?;
+
+        Ok(size)
     }
 
     /// Get the size of the field, including the alignment.
     pub fn aligned_size(&self, offset: Offset) -> SimpleResult<u64> {
+        let offset = self.byte_offset(offset)?;
         self.field_type().aligned_size(offset, self.alignment)
     }
 
     /// Get the [`Range<u64>`] that the type will cover, starting at the
     /// given [`Offset`], if it can be known, without adding padding.
     pub fn actual_range(&self, offset: Offset) -> SimpleResult<Range<u64>> {
+        let offset = self.byte_offset(offset)?;
         self.field_type().range(offset, Alignment::None)
     }
 
     /// Get the [`Range<u64`] that the type will cover, with padding.
     pub fn aligned_range(&self, offset: Offset) -> SimpleResult<Range<u64>> {
+        let offset = self.byte_offset(offset)?;
         self.field_type().range(offset, self.alignment)
     }
 
+    /// Get the exact bits that the type covers, if it isn't a whole number of
+    /// bytes.
+    pub fn bit_ranges(&self, offset: Offset) -> SimpleResult<Option<Vec<Range<u64>>>> {
+        let offset = self.byte_offset(offset)?;
+        self.field_type().bit_ranges(offset)
+    }
+
     /// Get *related* nodes - ie, other fields that a pointer points to
     pub fn related(&self, offset: Offset) -> SimpleResult<Vec<(u64, H2Type)>> {
+        let offset = self.byte_offset(offset)?;
+        self.check_bounds(offset)?;
         self.field_type().related(offset)
     }
 
@@ -146,20 +246,31 @@ impl H2Type {
     /// (such as [`H2Array`] and
     /// [`NTString`]) do.
     pub fn children(&self, offset: Offset) -> SimpleResult<Vec<(Option<String>, H2Type)>> {
+        let offset = self.byte_offset(offset)?;
         self.field_type().children(offset)
     }
 
+    /// Get the types that make up the given type, along with the range that
+    /// each one covers and the bit in the first byte that it starts at.
+    pub fn children_with_range(&self, offset: Offset) -> SimpleResult<Vec<(Range<u64>, u8, Option<String>, H2Type)>> {
+        let offset = self.byte_offset(offset)?;
+        self.field_type().children_with_range(offset)
+    }
+
     /// Resolve this type into a concrete type.
     ///
     /// Once a type is resolved, the size, range, data, string value, and so on
     /// are "written in stone", so to speak, which means they no longer need to
     /// be calculated.
     pub fn resolve(&self, offset: Offset, name: Option<String>) -> SimpleResult<ResolvedType> {
+        let offset = self.byte_offset(offset)?;
         self.field_type().resolve(offset, self.alignment, name)
     }
 
     /// Get a user-consumeable string
     pub fn to_display(&self, offset: Offset) -> SimpleResult<String> {
+        let offset = self.byte_offset(offset)?;
+        self.check_bounds(offset)?;
         self.field_type().to_display(offset)
     }
 
@@ -170,6 +281,8 @@ impl H2Type {
 
     /// Try to convert to a [`char`].
     pub fn to_char(&self, offset: Offset) -> SimpleResult<char> {
+        let offset = self.byte_offset(offset)?;
+        self.check_bounds(offset)?;
         self.field_type().to_char(offset)
     }
 
@@ -180,6 +293,8 @@ impl H2Type {
 
     /// Try to convert to a [`String`].
     pub fn to_string(&self, offset: Offset) -> SimpleResult<String> {
+        let offset = self.byte_offset(offset)?;
+        self.check_bounds(offset)?;
         self.field_type().to_string(offset)
     }
 
@@ -190,11 +305,15 @@ impl H2Type {
 
     /// Try to convert to a [`u64`]?
     pub fn to_u64(&self, offset: Offset) -> SimpleResult<u64> {
+        let offset = self.byte_offset(offset)?;
+        self.check_bounds(offset)?;
         self.field_type().to_u64(offset)
     }
 
     /// Can this value represent a [`i64`]?
     pub fn to_i64(&self, offset: Offset) -> SimpleResult<i64> {
+        let offset = self.byte_offset(offset)?;
+        self.check_bounds(offset)?;
         self.field_type().to_i64(offset)
     }
 
@@ -202,4 +321,19 @@ impl H2Type {
     pub fn can_be_i64(&self) -> bool {
         self.field_type().can_be_i64()
     }
+
+    /// Check whether the value is valid (such as a checksum).
+    pub fn is_valid(&self, offset: Offset) -> SimpleResult<bool> {
+        let offset = self.byte_offset(offset)?;
+        self.check_bounds(offset)?;
+        self.field_type().is_valid(offset)
+    }
+
+    /// Get any warnings about the value (such as a failed
+    /// [`crate::composite::Constrained`]).
+    pub fn warnings(&self, offset: Offset) -> SimpleResult<Vec<String>> {
+        let offset = self.byte_offset(offset)?;
+        self.check_bounds(offset)?;
+        self.field_type().warnings(offset)
+    }
 }
@@ -44,19 +44,56 @@ pub trait H2TypeTrait {
         let children = self.children_with_range(offset)?;
 
         let first_range = match children.first() {
-            Some((r, _, _)) => r,
+            Some((r, _, _, _)) => r,
             None => bail!("Can't calculate size with no child types"),
         };
 
         // This should never trigger, but just in case...
         let last_range = match children.last() {
-            Some((r, _, _)) => r,
+            Some((r, _, _, _)) => r,
             None => bail!("Can't calculate size with no child types"),
         };
 
         Ok(last_range.end - first_range.start)
     }
 
+    /// Is the type a number of bits, rather than bytes? Types like that (such
+    /// as a [`crate::simple::BitNumber`]) can start partway through a byte,
+    /// at the bit in the [`Offset`], and the next field carries on from the
+    /// bit after it. Anything else starts at the next whole byte, so a type
+    /// made of bytes after some bits is aligned back to a byte boundary.
+    ///
+    /// Composite types are made of bits if all of their children are.
+    fn is_bit_sized(&self) -> bool {
+        false
+    }
+
+    /// The size, in bits, of a type that [`#is_bit_sized`].
+    ///
+    /// By default, this is the number of bits from the start of the type to
+    /// the end of its last child, so only simple types need to implement
+    /// it.
+    fn bit_size(&self, offset: Offset) -> SimpleResult<u64> {
+        let children = self.children_with_range(offset)?;
+
+        let (range, bit, _, child) = match children.last() {
+            Some(c) => c,
+            None => bail!("Can't calculate size with no child types"),
+        };
+
+        // The whole bytes before the last child, plus the bits in it
+        let child_bits = child.bit_size(offset.at_bit(range.start, *bit))?;
+        let size = (range.start - offset.position()).checked_mul(8)
+            .and_then(|bits| bits.checked_add(*bit as u64))
+            .and_then(|bits| bits.checked_add(child_bits))
+            .and_then(|bits| bits.checked_sub(offset.bit() as u64));
+
+        match size {
+            Some(size) => Ok(size),
+            None => bail!("Size in bits is too large"),
+        }
+    }
+
     /// Get the aligned size.
     ///
     /// The default implementation is very likely fine for this.
@@ -77,10 +114,47 @@ pub trait H2TypeTrait {
         let start = offset.position();
         let end   = start + self.actual_size(offset)?;
 
+        // If the offset is bounded, the type has to fit (the alignment
+        // padding isn't read, so it's allowed to go past)
+        offset.check_range(&(start..end))?;
+
         // Do the rounding
         alignment.align(start..end)
     }
 
+    /// Get the exact bits that the type covers, for types that aren't a whole
+    /// number of bytes (such as a [`crate::simple::BitNumber`]).
+    ///
+    /// Bits are numbered from the start of the buffer, the same way no matter
+    /// how the type packs them: bit `n` is in byte `n / 8`, and bit 0 of each
+    /// byte is the most significant (like [`Offset::bit`]). Bits that are
+    /// packed least significant first aren't consecutive in that numbering
+    /// when they cross a byte, so there can be more than one range.
+    ///
+    /// Most types are made of whole bytes, so this is `None`. By default,
+    /// types that [`#is_bit_sized`] cover their children's bits.
+    fn bit_ranges(&self, offset: Offset) -> SimpleResult<Option<Vec<Range<u64>>>> {
+        if !self.is_bit_sized() {
+            return Ok(None);
+        }
+
+        let mut ranges: Vec<Range<u64>> = vec![];
+        for (range, bit, _, child) in self.children_with_range(offset)? {
+            for child_range in child.bit_ranges(offset.at_bit(range.start, bit))?.unwrap_or(vec![]) {
+                // Join ranges that touch or overlap (children of an enum
+                // overlap)
+                match ranges.last_mut() {
+                    Some(previous) if previous.start <= child_range.start && child_range.start <= previous.end => {
+                        previous.end = std::cmp::max(previous.end, child_range.end);
+                    },
+                    _ => ranges.push(child_range),
+                }
+            }
+        }
+
+        Ok(Some(ranges))
+    }
+
     /// Convert to a String.
     ///
     /// This String value is ultimately what is displayed by users, and should
@@ -118,22 +192,18 @@ pub trait H2TypeTrait {
         Ok(vec![])
     }
 
-    /// Get a list of children with their associated (aligned) ranges.
+    /// Get a list of children with their associated (aligned) ranges, and
+    /// the bit in the first byte that each one starts at (which is 0 unless
+    /// it [`#is_bit_sized`]). The child's [`Offset`] is
+    /// `offset.at_bit(range.start, bit)`.
     ///
     /// As notes in [`#children`], the default implementation assumes that the
     /// children are consecutive, adjacent, and make up the full parent type.
     /// As long as that's the case, the default implementation will work just
-    /// fine.
-    fn children_with_range(&self, offset: Offset) -> SimpleResult<Vec<(Range<u64>, Option<String>, H2Type)>> {
-        let mut child_offset = offset;
-
-        self.children(offset)?.into_iter().map(|(name, child)| {
-            let range = child.aligned_range(child_offset)?;
-
-            child_offset = offset.at(range.end);
-
-            Ok((range, name, child.clone()))
-        }).collect::<SimpleResult<Vec<_>>>()
+    /// fine. Children that are made of bits are packed one after another, and
+    /// anything else starts at the next whole byte.
+    fn children_with_range(&self, offset: Offset) -> SimpleResult<Vec<(Range<u64>, u8, Option<String>, H2Type)>> {
+        consecutive_children(offset, self.children(offset)?)
     }
 
     /// Create a [`ResolvedType`] from this [`H2Type`] and context.
@@ -141,18 +211,30 @@ pub trait H2TypeTrait {
     /// A resolved type has all the values calculated, and is therefore very
     /// quick to use.
     fn resolve(&self, offset: Offset, alignment: Alignment, field_name: Option<String>) -> SimpleResult<ResolvedType> {
+        let actual_range = self.range(offset, Alignment::None)?;
+
+        // Children can't go outside of their parent
+        let child_offset = offset.bounded(actual_range.clone())?;
+
+        // Resolve the children here and now
+        let children = self.children_with_range(offset)?.into_iter().map(|(range, bit, name, child)| {
+            // Errors here will be handled by the collect
+            child.resolve(child_offset.at_bit(range.start, bit), name)
+        }).collect::<SimpleResult<Vec<ResolvedType>>>()?;
+
+        // Anything invalid inside the value makes the value invalid (false
+        // is the minimum)
+        let valid = self.is_valid(offset).ok().into_iter().chain(children.iter().filter_map(|c| c.valid)).min();
+
         Ok(ResolvedType {
-            actual_range: self.range(offset, Alignment::None)?,
+            actual_range: actual_range,
             aligned_range: self.range(offset, alignment)?,
+            bit_ranges: self.bit_ranges(offset)?,
 
             field_name: field_name,
             display: self.to_display(offset)?,
 
-            // Resolve the children here and now
-            children: self.children_with_range(offset)?.into_iter().map(|(range, name, child)| {
-                // Errors here will be handled by the collect
-                child.resolve(offset.at(range.start), name)
-            }).collect::<SimpleResult<Vec<ResolvedType>>>()?,
+            children: children,
 
             related: self.related(offset)?,
 
@@ -160,6 +242,12 @@ pub trait H2TypeTrait {
             as_string: self.to_string(offset).ok(),
             as_u64:    self.to_u64(offset).ok(),
             as_i64:    self.to_i64(offset).ok(),
+
+            valid: valid,
+            warnings: self.warnings(offset)?,
+
+            decoded: None,
+            in_decoded: false,
         })
     }
 
@@ -216,4 +304,65 @@ pub trait H2TypeTrait {
     fn to_i64(&self, _offset: Offset) -> SimpleResult<i64> {
         bail!("This type cannot be converted to a i64");
     }
+
+    /// Check whether the value is valid - for example, whether a
+    /// [`crate::simple::Checksum`] matches the data it covers.
+    ///
+    /// Most types have nothing to check, and return an error.
+    fn is_valid(&self, _offset: Offset) -> SimpleResult<bool> {
+        bail!("This type cannot be validated");
+    }
+
+    /// Get any warnings about the value - for example, a
+    /// [`crate::composite::Constrained`] value that doesn't satisfy its
+    /// constraint, but isn't set to fail.
+    ///
+    /// Most types have no warnings.
+    fn warnings(&self, _offset: Offset) -> SimpleResult<Vec<String>> {
+        Ok(vec![])
+    }
+}
+
+/// Lay out `children` one after another, starting at `offset`, like the
+/// default [`H2TypeTrait::children_with_range`].
+pub(crate) fn consecutive_children(offset: Offset, children: Vec<(Option<String>, H2Type)>) -> SimpleResult<Vec<(Range<u64>, u8, Option<String>, H2Type)>> {
+    let mut position = offset.position();
+    let mut bit = offset.bit() as u64;
+
+    children.into_iter().map(|(name, child)| {
+        if child.is_bit_sized() {
+            let start = position;
+            let start_bit = bit;
+
+            let end_bit = match bit.checked_add(child.bit_size(offset.at_bit(start, start_bit as u8))?) {
+                Some(end_bit) => end_bit,
+                None => bail!("Field is too large"),
+            };
+
+            // The range covers the byte with the last bit, even if
+            // the next field starts in it too
+            position = match start.checked_add(end_bit / 8) {
+                Some(position) => position,
+                None => bail!("Field is too large"),
+            };
+            bit = end_bit % 8;
+
+            let end = match bit {
+                0 => position,
+                _ => position + 1,
+            };
+
+            Ok((start..end, start_bit as u8, name, child))
+        } else {
+            if bit != 0 {
+                position += 1;
+                bit = 0;
+            }
+
+            let range = child.aligned_range(offset.at(position))?;
+            position = range.end;
+
+            Ok((range, 0, name, child))
+        }
+    }).collect::<SimpleResult<Vec<_>>>()
 }
@@ -49,12 +49,36 @@
 //! recursive - an array can contain a struct which can contain an array and so
 //! on, for as long as you like.
 //!
+//! Fields that aren't a whole number of bytes, such as a 3-bit version, are
+//! [`simple::BitNumber`]s. An [`Offset`] can be partway through a byte, so a
+//! bit field can follow another one mid-byte, in a struct, an array, or an
+//! enum; the next field that's made of bytes starts at the next byte. Bit
+//! fields have [`ResolvedType::bit_ranges`] with their exact bits. A
+//! [`composite::H2BitStruct`] packs bit fields into whole bytes.
+//!
 //! ### String types
 //!
 //! A string type, which are defined in [`composite::strings`], are a special
 //! composite type. They're really just arrays of a value that can consume a
 //! character type in some way to become a String.
 //!
+//! ## Analyzing
+//!
+//! When the layout of a buffer isn't known, the tools in [`analyze`] can find
+//! types in it - for example, [`analyze::find_strings`] works like
+//! `strings(1)`, and returns each string it finds as an [`H2Type`].
+//!
+//! ## Formats
+//!
+//! Definitions for common file formats, such as [`formats::elf`], are
+//! included so they don't need to be typed out every time.
+//!
+//! ## Data sources
+//!
+//! Data that's too big to load into memory - a disk image or a core dump -
+//! can be read through a [`source::DataSource`], such as a memory-mapped
+//! file. [`source::resolve_from`] reads just the part that a type needs.
+//!
 //! ## Alignment
 //!
 //! All [`H2Type`] values can be aligned. In the standard case, which is
@@ -206,6 +230,8 @@ pub use resolved_type::ResolvedType;
 mod offset;
 pub use offset::Offset;
 
+mod bytes;
+
 mod h2typetrait;
 pub use h2typetrait::H2TypeTrait;
 
@@ -214,3 +240,6 @@ pub use h2type::{H2Types, H2Type};
 
 pub mod simple;
 pub mod composite;
+pub mod analyze;
+pub mod formats;
+pub mod source;
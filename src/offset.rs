@@ -1,5 +1,6 @@
 use simple_error::{bail, SimpleResult};
 use sized_number::Context;
+use std::ops::Range;
 
 /// Represents data that is being parsed.
 ///
@@ -10,10 +11,44 @@ use sized_number::Context;
 /// To actually read and parse data, a [`Context`] is required. A [`Context`] is
 /// basically a [`std::io::Cursor`] - a pointer to a buffer of data and a
 /// position.
+///
+/// An [`Offset::Bounded`] is a [`Context`] that's restricted to part of the
+/// buffer - for example, the body of a length-prefixed record. Any type
+/// that's resolved against it has to fit inside the bounds, so a type that
+/// would read past the end of its record fails instead of quietly reading
+/// the next one. See [`Offset::bounded`].
+///
+/// The bounds are checked, not enforced by the [`Context`]: the [`Context`]
+/// from [`Offset::get_dynamic`] can still read the whole buffer. A type that
+/// has to scan to find its size - such as a
+/// [`crate::composite::string::NTString`] looking for its terminator - can
+/// read past the end of the bounds, and is only rejected once its size is
+/// known.
+///
+/// An [`Offset::Windowed`] is a bounded [`Context`] over part of a bigger
+/// buffer (a [`crate::source::Window`]). Positions are relative to the
+/// window, but values in the data (such as pointers) are positions in the
+/// full buffer, so they're converted with [`Offset::dereference`].
+///
+/// A bounded or windowed offset can also be partway through a byte, for
+/// fields that aren't a whole number of bytes (such as a
+/// [`crate::simple::BitNumber`]). The position is the byte, and the bit
+/// (0 - 7, where 0 is the most significant bit) is how far into it the
+/// field starts - see [`Offset::at_bit`]. Types that are made of whole bytes
+/// start at the next byte instead.
 #[derive(Debug, Clone, Copy)]
 pub enum Offset<'a> {
     Static(u64),
     Dynamic(Context<'a>),
+
+    /// A [`Context`], the start and end (exclusive) that it's restricted to,
+    /// and the bit.
+    Bounded(Context<'a>, u64, u64, u8),
+
+    /// A [`Context`] over a window, the position in the full buffer that the
+    /// window starts at, the start and end (exclusive, and relative to the
+    /// window) that it's restricted to, and the bit.
+    Windowed(Context<'a>, u64, u64, u64, u8),
 }
 
 impl<'a> From<u64> for Offset<'a> {
@@ -33,21 +68,281 @@ impl<'a> Offset<'a> {
         match self {
             Self::Static(n) => n,
             Self::Dynamic(c) => c.position(),
+            Self::Bounded(c, ..) => c.position(),
+            Self::Windowed(c, ..) => c.position(),
+        }
+    }
+
+    /// The bit within the byte at [`Offset::position`], where 0 is the most
+    /// significant bit.
+    pub fn bit(self) -> u8 {
+        match self {
+            Self::Static(_) | Self::Dynamic(_) => 0,
+            Self::Bounded(_, _, _, bit) | Self::Windowed(_, _, _, _, bit) => bit,
         }
     }
 
+    /// Move to a different position, at the start of the byte. A bounded
+    /// offset keeps its bounds, even if the new position is outside of them.
     pub fn at(self, offset: u64) -> Offset<'a> {
         match self {
             Self::Static(_) => Self::Static(offset),
             Self::Dynamic(c) => Self::Dynamic(c.at(offset)),
+            Self::Bounded(c, start, end, _) => Self::Bounded(c.at(offset), start, end, 0),
+            Self::Windowed(c, base, start, end, _) => Self::Windowed(c.at(offset), base, start, end, 0),
         }
     }
 
+    /// Move to a bit partway through a byte. Bits past the end of the byte
+    /// (8 and up) carry into the following bytes.
+    ///
+    /// A [`Offset::Dynamic`] has nowhere to keep the bit, so it becomes an
+    /// [`Offset::Bounded`] over the whole buffer. A [`Offset::Static`] has no
+    /// data to read, so it's only used to work out sizes; it keeps just the
+    /// byte, and types that pack bits work out their layout in bits.
+    pub fn at_bit(self, offset: u64, bit: u8) -> Offset<'a> {
+        let offset = offset + (bit / 8) as u64;
+        let bit = bit % 8;
+
+        match self {
+            Self::Static(_) => Self::Static(offset),
+            Self::Dynamic(c) => Self::Bounded(c.at(offset), 0, u64::MAX, bit),
+            Self::Bounded(c, start, end, _) => Self::Bounded(c.at(offset), start, end, bit),
+            Self::Windowed(c, base, start, end, _) => Self::Windowed(c.at(offset), base, start, end, bit),
+        }
+    }
+
+    /// Get the [`Context`], to read data.
+    ///
+    /// For a bounded offset, the position has to be inside the bounds (or
+    /// right at the end). The [`Context`] itself can read anywhere, which
+    /// types that follow offsets (such as compression pointers) rely on;
+    /// [`crate::H2Type`] checks that a field fits inside the bounds (with
+    /// [`Offset::check_range`]) before it reads a value from it, so an
+    /// overread is an error rather than part of the value.
     pub fn get_dynamic(self) -> SimpleResult<Context<'a>> {
         match self {
             Self::Static(_) => bail!("This operation cannot be performed on a static context"),
             Self::Dynamic(c) => Ok(c),
+            Self::Bounded(c, start, end, _) | Self::Windowed(c, _, start, end, _) => {
+                if c.position() < start || c.position() > end {
+                    bail!("Offset {} is outside of the bounds {}..{}", c.position(), start, end);
+                }
+
+                Ok(c)
+            },
+        }
+    }
+
+    /// Get an [`Offset`] for an address that was read from the data, such
+    /// as a pointer's target. The target isn't part of whatever the offset
+    /// was bounded to, so the new offset isn't bounded - except that in a
+    /// window, it can only be somewhere in the window.
+    pub fn dereference(self, address: u64) -> SimpleResult<Offset<'a>> {
+        match self {
+            Self::Static(_) => Ok(Self::Static(address)),
+            Self::Dynamic(c) | Self::Bounded(c, ..) => Ok(Self::Dynamic(c.at(address))),
+            Self::Windowed(c, base, ..) => {
+                let end = base + buffer_end(c.at(0));
+                if address < base || address > end {
+                    bail!("Address {} is outside of the window {}..{}", address, base, end);
+                }
+
+                Ok(Self::Windowed(c.at(address - base), base, 0, end - base, 0))
+            },
+        }
+    }
+
+    /// Restrict the offset to the given range of the buffer.
+    ///
+    /// A static offset has nothing to read, so it's returned as-is. An
+    /// offset that's already bounded can only be narrowed - the range has to
+    /// be inside the existing bounds.
+    pub fn bounded(self, range: Range<u64>) -> SimpleResult<Offset<'a>> {
+        if range.start > range.end {
+            bail!("Invalid bounds: {}..{}", range.start, range.end);
+        }
+
+        match self {
+            Self::Static(_) => Ok(self),
+            Self::Dynamic(c) => Ok(Self::Bounded(c, range.start, range.end, 0)),
+            Self::Bounded(c, start, end, bit) => {
+                if range.start < start || range.end > end {
+                    bail!("Bounds {}..{} are outside of the bounds {}..{}", range.start, range.end, start, end);
+                }
+
+                Ok(Self::Bounded(c, range.start, range.end, bit))
+            },
+            Self::Windowed(c, base, start, end, bit) => {
+                if range.start < start || range.end > end {
+                    bail!("Bounds {}..{} are outside of the bounds {}..{}", range.start, range.end, start, end);
+                }
+
+                Ok(Self::Windowed(c, base, range.start, range.end, bit))
+            },
+        }
+    }
+
+    /// Get the bounds, if the offset is bounded.
+    pub fn bounds(self) -> Option<Range<u64>> {
+        match self {
+            Self::Bounded(_, start, end, _) | Self::Windowed(_, _, start, end, _) => Some(start..end),
+            _ => None,
+        }
+    }
+
+    /// Make sure that a range fits inside the bounds (if there are any).
+    pub fn check_range(self, range: &Range<u64>) -> SimpleResult<()> {
+        if let Self::Bounded(_, start, end, _) | Self::Windowed(_, _, start, end, _) = self {
+            if range.start < start || range.end > end {
+                bail!("Range {}..{} is outside of the bounds {}..{}", range.start, range.end, start, end);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Find the end of the buffer that a [`Context`] reads, without reading every
+/// byte along the way.
+pub(crate) fn buffer_end(context: Context) -> u64 {
+    // Double until we're past the end...
+    let mut low = context.position();
+    let mut step = 1;
+    while context.at(low + step - 1).read_u8().is_ok() {
+        low = low + step;
+        step = step * 2;
+    }
+
+    // ...then binary search for the first position we can't read
+    let mut high = low + step - 1;
+    while low < high {
+        let middle = low + (high - low) / 2;
+
+        match context.at(middle).read_u8().is_ok() {
+            true  => low = middle + 1,
+            false => high = middle,
         }
     }
+
+    low
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+    use sized_number::{Context, Endian, SizedDefinition, SizedDisplay};
+
+    use crate::simple::H2Number;
+
+    #[test]
+    fn test_bounded() -> SimpleResult<()> {
+        let data = b"\x00\x01\x02\x03\x04\x05\x06\x07".to_vec();
+        let offset = Offset::from(Context::new(&data)).bounded(2..6)?;
+
+        assert_eq!(Some(2..6), offset.bounds());
+        assert_eq!(Some(2..6), offset.at(7).bounds());
+
+        // The position has to be inside
+        assert_eq!(3, offset.at(3).get_dynamic()?.read_u8()?);
+        assert!(offset.at(6).get_dynamic().is_ok());
+        assert!(offset.at(1).get_dynamic().is_err());
+        assert!(offset.at(7).get_dynamic().is_err());
+
+        // Ranges have to fit
+        assert!(offset.check_range(&(2..6)).is_ok());
+        assert!(offset.check_range(&(5..7)).is_err());
+        assert!(offset.check_range(&(1..3)).is_err());
+
+        // Bounds can be narrowed, but not widened
+        assert_eq!(Some(3..4), offset.bounded(3..4)?.bounds());
+        assert!(offset.bounded(3..7).is_err());
+
+        // Static and unbounded offsets don't care
+        assert!(Offset::Static(0).bounded(2..6)?.check_range(&(0..100)).is_ok());
+        assert!(Offset::from(Context::new(&data)).check_range(&(0..100)).is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_windowed() -> SimpleResult<()> {
+        // A window of 4 bytes, starting at 100 in the full data
+        let data = b"\x00\x01\x02\x03".to_vec();
+        let offset = Offset::Windowed(Context::new(&data), 100, 0, 4, 0);
+
+        assert_eq!(Some(0..4), offset.at(1).bounds());
+        assert_eq!(Some(1..2), offset.bounded(1..2)?.bounds());
+
+        // Addresses from the data are in the full data
+        assert_eq!(2, offset.dereference(102)?.get_dynamic()?.read_u8()?);
+        assert!(offset.dereference(99).is_err());
+        assert!(offset.dereference(105).is_err());
+
+        // Anywhere else, they're used as-is
+        assert_eq!(2, Offset::from(Context::new(&data)).dereference(2)?.position());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bits() -> SimpleResult<()> {
+        let data = b"\x00\x01\x02\x03".to_vec();
+        let offset = Offset::from(Context::new(&data));
+        assert_eq!(0, offset.bit());
+
+        // A dynamic offset becomes bounded (over everything) to keep the bit
+        let bits = offset.at_bit(1, 3);
+        assert_eq!((1, 3), (bits.position(), bits.bit()));
+        assert_eq!(1, bits.get_dynamic()?.read_u8()?);
+
+        // Bits past the end of the byte carry over
+        let bits = offset.at_bit(1, 19);
+        assert_eq!((3, 3), (bits.position(), bits.bit()));
+
+        // Bounds keep the bit, and moving to a byte drops it
+        let bits = bits.bounded(2..4)?;
+        assert_eq!((Some(2..4), 3), (bits.bounds(), bits.bit()));
+        assert_eq!(0, bits.at(2).bit());
+
+        // A static offset only keeps the byte
+        assert_eq!((3, 0), (Offset::Static(0).at_bit(1, 19).position(), Offset::Static(0).at_bit(1, 19).bit()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bounded_reads() -> SimpleResult<()> {
+        let data = b"\x00\x01\x02\x03\x04\x05\x06\x07".to_vec();
+        let offset = Offset::from(Context::new(&data)).bounded(2..4)?;
+
+        // A u16 fits, but a u32 would read two bytes past the end
+        let t = H2Number::new(SizedDefinition::U16(Endian::Big), SizedDisplay::Hex(Default::default()));
+        assert_eq!("0x0203", t.to_display(offset.at(2))?);
+        assert_eq!(0x0203, t.to_u64(offset.at(2))?);
+
+        let t = H2Number::new(SizedDefinition::U32(Endian::Big), SizedDisplay::Hex(Default::default()));
+        assert!(t.to_display(offset.at(2)).is_err());
+        assert!(t.to_u64(offset.at(2)).is_err());
+        assert!(t.to_display(offset.at(3)).is_err());
+
+        // Without bounds, it reads whatever's there
+        assert_eq!("0x02030405", t.to_display(Offset::from(Context::new(&data)).at(2))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_buffer_end() -> SimpleResult<()> {
+        for &length in &[0, 1, 2, 3, 100, 1000] {
+            let data: Vec<u8> = vec![0; length];
+            let context = Context::new(&data);
+
+            assert_eq!(length as u64, buffer_end(context));
+            assert_eq!(length as u64, buffer_end(context.at(length as u64 / 2)));
+        }
+
+        Ok(())
+    }
+}
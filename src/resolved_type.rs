@@ -17,6 +17,10 @@ pub struct ResolvedType {
     pub actual_range: Range<u64>,
     pub aligned_range: Range<u64>,
 
+    /// The exact bits that the value covers, if it isn't a whole number of
+    /// bytes. See [`crate::H2TypeTrait::bit_ranges`].
+    pub bit_ranges: Option<Vec<Range<u64>>>,
+
     pub field_name: Option<String>,
     pub display: String,
 
@@ -27,6 +31,25 @@ pub struct ResolvedType {
     pub as_string: Option<String>,
     pub as_u64:    Option<u64>,
     pub as_i64:    Option<i64>,
+
+    /// Whether the value passed validation (such as a checksum), or `None`
+    /// if the type has nothing to validate. A value is invalid if anything
+    /// in it is, so a struct with a bad checksum is invalid too.
+    pub valid: Option<bool>,
+
+    /// Problems with the value that weren't serious enough to fail (such as
+    /// an unexpected magic number).
+    pub warnings: Vec<String>,
+
+    /// For a [`crate::composite::Transform`], the decoded data. The children
+    /// are resolved against this, so their ranges are offsets into it rather
+    /// than the original buffer.
+    pub decoded: Option<Vec<u8>>,
+
+    /// Whether this is inside a [`crate::composite::Transform`], which means
+    /// its ranges are offsets into the nearest ancestor's
+    /// [`ResolvedType::decoded`] data, not the original buffer.
+    pub in_decoded: bool,
 }
 
 impl ResolvedType {
@@ -0,0 +1,253 @@
+#[cfg(feature = "serialize")]
+use serde::{Serialize, Deserialize};
+
+use simple_error::{bail, SimpleResult};
+use sized_number::Context;
+
+use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset};
+
+/// How the digits of a [`BCD`] number are stored.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum BCDFormat {
+    /// Two digits per byte, most significant first. When signed, the last
+    /// nibble is the sign instead of a digit (COBOL's `COMP-3`).
+    Packed,
+
+    /// One digit per byte, in the low nibble; the high nibble is the "zone"
+    /// (`0xF` in EBCDIC, `0x3` in ASCII). When signed, the last byte's zone is
+    /// the sign.
+    Zoned,
+}
+
+/// Defines a binary-coded decimal number.
+///
+/// The number is `length` bytes long, and is displayed in decimal with
+/// `decimal_places` implied digits after the decimal point (COBOL's `V`). The
+/// raw, unscaled value is available with [`H2TypeTrait::to_i64`].
+///
+/// Sign nibbles `0xB` and `0xD` are negative; anything else is positive. A
+/// digit nibble above `9` is an error.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct BCD {
+    format: BCDFormat,
+    length: u64,
+    signed: bool,
+    decimal_places: u64,
+}
+
+impl BCD {
+    pub fn new_aligned(alignment: Alignment, format: BCDFormat, length: u64, signed: bool, decimal_places: u64) -> SimpleResult<H2Type> {
+        if length == 0 {
+            bail!("BCD numbers must be at least one byte long");
+        }
+
+        Ok(H2Type::new(alignment, H2Types::BCD(Self {
+            format: format,
+            length: length,
+            signed: signed,
+            decimal_places: decimal_places,
+        })))
+    }
+
+    pub fn new(format: BCDFormat, length: u64, signed: bool, decimal_places: u64) -> SimpleResult<H2Type> {
+        Self::new_aligned(Alignment::None, format, length, signed, decimal_places)
+    }
+
+    fn is_negative(sign: u8) -> bool {
+        sign == 0xb || sign == 0xd
+    }
+
+    fn to_digit(nibble: u8, position: u64) -> SimpleResult<char> {
+        if nibble > 9 {
+            bail!("Invalid BCD nibble 0x{:x} at offset {}", nibble, position);
+        }
+
+        Ok((b'0' + nibble) as char)
+    }
+
+    /// Read the digits, returning whether it's negative and the digits
+    /// (including leading zeroes).
+    fn read(&self, context: Context) -> SimpleResult<(bool, String)> {
+        let mut negative = false;
+        let mut digits = String::new();
+
+        for i in 0..self.length {
+            let position = context.position() + i;
+            let b = context.at(position).read_u8()?;
+            let last = i + 1 == self.length;
+
+            match self.format {
+                BCDFormat::Packed => {
+                    digits.push(Self::to_digit(b >> 4, position)?);
+
+                    match self.signed && last {
+                        true  => negative = Self::is_negative(b & 0x0f),
+                        false => digits.push(Self::to_digit(b & 0x0f, position)?),
+                    }
+                },
+                BCDFormat::Zoned => {
+                    digits.push(Self::to_digit(b & 0x0f, position)?);
+
+                    if self.signed && last {
+                        negative = Self::is_negative(b >> 4);
+                    }
+                },
+            }
+        }
+
+        Ok((negative, digits))
+    }
+}
+
+impl H2TypeTrait for BCD {
+    fn is_static(&self) -> bool {
+        true
+    }
+
+    fn actual_size(&self, _offset: Offset) -> SimpleResult<u64> {
+        Ok(self.length)
+    }
+
+    fn to_display(&self, offset: Offset) -> SimpleResult<String> {
+        let context = match offset {
+            Offset::Static(_) => return Ok("BCD".to_string()),
+            Offset::Dynamic(context) | Offset::Bounded(context, ..) | Offset::Windowed(context, ..) => context,
+        };
+
+        let (negative, digits) = self.read(context)?;
+
+        // Pad so there's always a digit before the decimal point
+        let places = self.decimal_places as usize;
+        let digits = format!("{:0>width$}", digits, width = places + 1);
+        let (integer, fraction) = digits.split_at(digits.len() - places);
+
+        // Strip leading zeroes, but leave at least one
+        let integer = match integer.trim_start_matches('0') {
+            "" => "0",
+            i  => i,
+        };
+
+        let sign = if negative { "-" } else { "" };
+        match places {
+            0 => Ok(format!("{}{}", sign, integer)),
+            _ => Ok(format!("{}{}.{}", sign, integer, fraction)),
+        }
+    }
+
+    fn can_be_i64(&self) -> bool {
+        true
+    }
+
+    fn to_i64(&self, offset: Offset) -> SimpleResult<i64> {
+        let (negative, digits) = self.read(offset.get_dynamic()?)?;
+
+        let value = match digits.parse::<i64>() {
+            Ok(v) => v,
+            Err(_) => bail!("BCD value {} doesn't fit in an i64", digits),
+        };
+
+        match negative {
+            true  => Ok(-value),
+            false => Ok(value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+    use sized_number::Context;
+
+    #[test]
+    fn test_packed() -> SimpleResult<()> {
+        let data = b"\x12\x34\x56\x78\x00\x01".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = BCD::new(BCDFormat::Packed, 4, false, 0)?;
+        assert_eq!(4, t.actual_size(offset)?);
+        assert_eq!("12345678", t.to_display(offset)?);
+        assert_eq!(12345678,   t.to_i64(offset)?);
+
+        let t = BCD::new(BCDFormat::Packed, 2, false, 0)?;
+        assert_eq!("1", t.to_display(offset.at(4))?);
+
+        let t = BCD::new(BCDFormat::Packed, 4, false, 2)?;
+        assert_eq!("123456.78", t.to_display(offset)?);
+        assert_eq!(12345678,    t.to_i64(offset)?);
+
+        let t = BCD::new(BCDFormat::Packed, 2, false, 3)?;
+        assert_eq!("0.001", t.to_display(offset.at(4))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_packed_signed() -> SimpleResult<()> {
+        // COMP-3: +12345, -12345, and unsigned (F) 12345
+        let data = b"\x12\x34\x5c\x12\x34\x5d\x12\x34\x5f".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = BCD::new(BCDFormat::Packed, 3, true, 2)?;
+        assert_eq!("123.45",  t.to_display(offset.at(0))?);
+        assert_eq!("-123.45", t.to_display(offset.at(3))?);
+        assert_eq!("123.45",  t.to_display(offset.at(6))?);
+
+        assert_eq!(12345,  t.to_i64(offset.at(0))?);
+        assert_eq!(-12345, t.to_i64(offset.at(3))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_zoned() -> SimpleResult<()> {
+        // ASCII "0042", then EBCDIC +123 and -123
+        let data = b"\x30\x30\x34\x32\xf1\xf2\xc3\xf1\xf2\xd3".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = BCD::new(BCDFormat::Zoned, 4, false, 0)?;
+        assert_eq!("42", t.to_display(offset)?);
+        assert_eq!(42,   t.to_i64(offset)?);
+
+        let t = BCD::new(BCDFormat::Zoned, 3, true, 0)?;
+        assert_eq!("123",  t.to_display(offset.at(4))?);
+        assert_eq!("-123", t.to_display(offset.at(7))?);
+        assert_eq!(-123,   t.to_i64(offset.at(7))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_nibble() -> SimpleResult<()> {
+        let data = b"\x12\x3a\x4f".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = BCD::new(BCDFormat::Packed, 2, false, 0)?;
+        assert!(t.to_display(offset).is_err());
+        assert!(t.to_i64(offset).is_err());
+
+        // The sign nibble isn't a digit, so it's allowed
+        let t = BCD::new(BCDFormat::Packed, 1, true, 0)?;
+        assert_eq!("4", t.to_display(offset.at(2))?);
+
+        // Too short
+        let t = BCD::new(BCDFormat::Packed, 4, false, 0)?;
+        assert!(t.to_display(offset).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_too_big() -> SimpleResult<()> {
+        let data = b"\x99\x99\x99\x99\x99\x99\x99\x99\x99\x99".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = BCD::new(BCDFormat::Packed, 10, false, 0)?;
+        assert_eq!("99999999999999999999", t.to_display(offset)?);
+        assert!(t.to_i64(offset).is_err());
+
+        Ok(())
+    }
+}
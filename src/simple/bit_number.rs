@@ -0,0 +1,317 @@
+#[cfg(feature = "serialize")]
+use serde::{Serialize, Deserialize};
+
+use simple_error::{bail, SimpleResult};
+use sized_number::{Context, Endian, SizedDefinition, SizedDisplay};
+use std::ops::Range;
+
+use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset};
+
+/// The order that bits are packed into bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum BitOrder {
+    /// The most significant bit of each byte comes first, and a value that
+    /// crosses a byte boundary continues in the next byte's top bits. This is
+    /// how most network protocols and codecs (such as H.264) pack fields.
+    MSBFirst,
+
+    /// The least significant bit of each byte comes first, and a value that
+    /// crosses a byte boundary continues in the next byte's bottom bits. This
+    /// is how DEFLATE and little-endian (Intel) CAN signals pack fields.
+    LSBFirst,
+}
+
+/// Defines an integer that's a number of bits, rather than bytes - a `u1`
+/// through a `u64`.
+///
+/// A bit number starts at the bit in the [`Offset`] (see
+/// [`Offset::at_bit`]), which can be partway through a byte, and takes up as
+/// many bytes as its bits touch (so a `u12` at the start of a byte is two
+/// bytes, with four bits left for the next field). In a
+/// [`crate::composite::H2Struct`], each field after a bit number starts at
+/// the bit after it, or at the next byte if it's made of bytes.
+///
+/// The resolved value has [`crate::ResolvedType::bit_ranges`] with the exact
+/// bits that it covers.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct BitNumber {
+    width: u8,
+    signed: bool,
+    order: BitOrder,
+    display: SizedDisplay,
+}
+
+impl BitNumber {
+    pub fn new_aligned(alignment: Alignment, width: u8, signed: bool, order: BitOrder, display: SizedDisplay) -> SimpleResult<H2Type> {
+        if width == 0 || width > 64 {
+            bail!("Bit numbers must be 1 to 64 bits, not {}", width);
+        }
+
+        Ok(H2Type::new(alignment, H2Types::BitNumber(Self {
+            width: width,
+            signed: signed,
+            order: order,
+            display: display,
+        })))
+    }
+
+    pub fn new(width: u8, signed: bool, order: BitOrder, display: SizedDisplay) -> SimpleResult<H2Type> {
+        Self::new_aligned(Alignment::None, width, signed, order, display)
+    }
+
+    pub(crate) fn width(&self) -> u8 {
+        self.width
+    }
+
+    pub(crate) fn order(&self) -> BitOrder {
+        self.order
+    }
+
+    /// The number of bytes that the bits touch.
+    fn byte_count(&self, offset: Offset) -> u64 {
+        (offset.bit() as u64 + self.width as u64 + 7) / 8
+    }
+
+    /// Read the value. It's an [`i128`], so it can hold any [`u64`] or
+    /// [`i64`].
+    fn read(&self, offset: Offset) -> SimpleResult<i128> {
+        let context = offset.get_dynamic()?;
+        let byte_count = self.byte_count(offset);
+
+        // At most 7 + 64 bits, which is 9 bytes
+        let mut bits: u128 = 0;
+        for i in 0..byte_count {
+            let b = context.at(context.position() + i).read_u8()? as u128;
+
+            bits = match self.order {
+                BitOrder::MSBFirst => (bits << 8) | b,
+                BitOrder::LSBFirst => bits | (b << (i * 8)),
+            };
+        }
+
+        let shift = match self.order {
+            BitOrder::MSBFirst => byte_count * 8 - offset.bit() as u64 - self.width as u64,
+            BitOrder::LSBFirst => offset.bit() as u64,
+        };
+
+        let value = (bits >> shift) & ((1u128 << self.width) - 1);
+
+        // Sign extend if the top bit is set
+        match self.signed && (value >> (self.width - 1)) & 1 == 1 {
+            true  => Ok(value as i128 - (1i128 << self.width)),
+            false => Ok(value as i128),
+        }
+    }
+}
+
+impl H2TypeTrait for BitNumber {
+    fn is_static(&self) -> bool {
+        true
+    }
+
+    fn actual_size(&self, offset: Offset) -> SimpleResult<u64> {
+        Ok(self.byte_count(offset))
+    }
+
+    fn is_bit_sized(&self) -> bool {
+        true
+    }
+
+    fn bit_size(&self, _offset: Offset) -> SimpleResult<u64> {
+        Ok(self.width as u64)
+    }
+
+    fn bit_ranges(&self, offset: Offset) -> SimpleResult<Option<Vec<Range<u64>>>> {
+        // The last bit of the last byte has to fit in a u64
+        let start = match offset.position().checked_add(self.byte_count(offset)).and_then(|end| end.checked_mul(8)) {
+            Some(_) => offset.position() * 8,
+            None => bail!("Offset is too large to number its bits: {}", offset.position()),
+        };
+        let first = offset.bit() as u64;
+        let last = first + self.width as u64;
+
+        match self.order {
+            BitOrder::MSBFirst => Ok(Some(vec![(start + first)..(start + last)])),
+            BitOrder::LSBFirst => {
+                // Flip the part of each byte over, since the ranges count
+                // from the most significant bit
+                let mut ranges: Vec<Range<u64>> = vec![];
+
+                for byte in 0..self.byte_count(offset) {
+                    let low = std::cmp::max(first, byte * 8) - byte * 8;
+                    let high = std::cmp::min(last, byte * 8 + 8) - byte * 8;
+                    let range = (start + byte * 8 + 8 - high)..(start + byte * 8 + 8 - low);
+
+                    match ranges.last_mut() {
+                        Some(previous) if previous.end == range.start => previous.end = range.end,
+                        _ => ranges.push(range),
+                    }
+                }
+
+                Ok(Some(ranges))
+            },
+        }
+    }
+
+    fn to_display(&self, offset: Offset) -> SimpleResult<String> {
+        match offset {
+            Offset::Static(_) => Ok(format!("{}{}", if self.signed { "i" } else { "u" }, self.width)),
+            Offset::Dynamic(_) | Offset::Bounded(..) | Offset::Windowed(..) => {
+                // Put the value in the smallest buffer that holds it, so it
+                // can be displayed the same way as any other number
+                let definition = match (self.width, self.signed) {
+                    (1..=8,   false) => SizedDefinition::U8,
+                    (9..=16,  false) => SizedDefinition::U16(Endian::Big),
+                    (17..=32, false) => SizedDefinition::U32(Endian::Big),
+                    (_,       false) => SizedDefinition::U64(Endian::Big),
+                    (1..=8,   true)  => SizedDefinition::I8,
+                    (9..=16,  true)  => SizedDefinition::I16(Endian::Big),
+                    (17..=32, true)  => SizedDefinition::I32(Endian::Big),
+                    (_,       true)  => SizedDefinition::I64(Endian::Big),
+                };
+
+                let value = (self.read(offset)? as u64).to_be_bytes();
+                let buffer = value[(8 - definition.size() as usize)..].to_vec();
+
+                definition.to_string(Context::new(&buffer), self.display)
+            }
+        }
+    }
+
+    fn can_be_u64(&self) -> bool {
+        !self.signed
+    }
+
+    fn to_u64(&self, offset: Offset) -> SimpleResult<u64> {
+        let value = self.read(offset)?;
+
+        if value < 0 {
+            bail!("Bit number value {} doesn't fit in a u64", value);
+        }
+
+        Ok(value as u64)
+    }
+
+    fn can_be_i64(&self) -> bool {
+        self.signed || self.width < 64
+    }
+
+    fn to_i64(&self, offset: Offset) -> SimpleResult<i64> {
+        let value = self.read(offset)?;
+
+        if value > i64::MAX as i128 {
+            bail!("Bit number value {} doesn't fit in an i64", value);
+        }
+
+        Ok(value as i64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+    use sized_number::Context;
+
+    #[test]
+    fn test_bit_number() -> SimpleResult<()> {
+        let data = b"\xa5\x0f\x80".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        // A u12 takes up two bytes; MSB-first is the top 12 bits of 0xa50f,
+        // and LSB-first is the bottom 12 bits of 0x0fa5
+        let t = BitNumber::new(12, false, BitOrder::MSBFirst, SizedDisplay::Hex(Default::default()))?;
+        assert_eq!(2, t.actual_size(offset)?);
+        assert_eq!(0xa50, t.to_u64(offset)?);
+        assert_eq!("0x0a50", t.to_display(offset)?);
+
+        let t = BitNumber::new(12, false, BitOrder::LSBFirst, SizedDisplay::Decimal)?;
+        assert_eq!(0xfa5, t.to_u64(offset)?);
+        assert_eq!("4005", t.to_display(offset)?);
+
+        // A single bit
+        let t = BitNumber::new(1, false, BitOrder::MSBFirst, SizedDisplay::Decimal)?;
+        assert_eq!(1, t.actual_size(offset)?);
+        assert_eq!(1, t.to_u64(offset)?);
+
+        // A full 64 bits still works
+        let data = b"\xff\xff\xff\xff\xff\xff\xff\xfe".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+        let t = BitNumber::new(64, false, BitOrder::LSBFirst, SizedDisplay::Decimal)?;
+        assert_eq!(0xfeffffffffffffff, t.to_u64(offset)?);
+
+        // Too small and too big
+        assert!(BitNumber::new(0, false, BitOrder::MSBFirst, SizedDisplay::Decimal).is_err());
+        assert!(BitNumber::new(65, false, BitOrder::MSBFirst, SizedDisplay::Decimal).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_at_bit() -> SimpleResult<()> {
+        let data = b"\xa5\x0f".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data)).at_bit(0, 4);
+
+        // A u8 that starts halfway through a byte touches two bytes
+        let t = BitNumber::new(8, false, BitOrder::MSBFirst, SizedDisplay::Hex(Default::default()))?;
+        assert_eq!(2, t.actual_size(offset)?);
+        assert_eq!(8, t.bit_size(offset)?);
+        assert_eq!(0x50, t.to_u64(offset)?);
+        assert_eq!(Some(vec![4..12]), t.bit_ranges(offset)?);
+
+        // LSB-first, it's the top half of the first byte and the bottom half
+        // of the second
+        let t = BitNumber::new(8, false, BitOrder::LSBFirst, SizedDisplay::Hex(Default::default()))?;
+        assert_eq!(0xfa, t.to_u64(offset)?);
+        assert_eq!(Some(vec![0..4, 12..16]), t.bit_ranges(offset)?);
+
+        // Aligned, it's made of bytes, so it starts at the next one
+        let t = BitNumber::new_aligned(Alignment::Loose(1), 8, false, BitOrder::MSBFirst, SizedDisplay::Hex(Default::default()))?;
+        assert!(!t.is_bit_sized());
+        assert_eq!(0x0f, t.to_u64(offset)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_signed() -> SimpleResult<()> {
+        let data = b"\xe0".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        // The top three bits are 0b111, which is -1
+        let t = BitNumber::new(3, true, BitOrder::MSBFirst, SizedDisplay::Decimal)?;
+        assert_eq!(-1, t.to_i64(offset)?);
+        assert_eq!("-1", t.to_display(offset)?);
+        assert!(t.to_u64(offset).is_err());
+
+        // The bottom three bits are 0, which is 0 either way
+        let t = BitNumber::new(3, true, BitOrder::LSBFirst, SizedDisplay::Decimal)?;
+        assert_eq!(0, t.to_i64(offset)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve() -> SimpleResult<()> {
+        let data = b"\x00\x00\xff\xff".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = BitNumber::new(10, false, BitOrder::MSBFirst, SizedDisplay::Decimal)?;
+        let r = t.resolve(offset.at(2), None)?;
+
+        assert_eq!(2..4, r.actual_range);
+        assert_eq!(Some(vec![16..26]), r.bit_ranges);
+        assert_eq!(Some(1023), r.as_u64);
+
+        // The same bits, packed the other way, are the bottom two bits of
+        // the second byte and all of the first
+        let t = BitNumber::new(10, false, BitOrder::LSBFirst, SizedDisplay::Decimal)?;
+        let r = t.resolve(offset.at(2), None)?;
+        assert_eq!(Some(vec![16..24, 30..32]), r.bit_ranges);
+        assert_eq!(Some(1023), r.as_u64);
+
+        Ok(())
+    }
+}
@@ -5,7 +5,7 @@ use simple_error::{bail, SimpleResult};
 use sized_number::Context;
 
 use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset};
-use crate::simple::character::common;
+use crate::simple::character::common::{self, EscapeStyle};
 
 /// Configuration options for ASCII characters.
 #[derive(Debug, Clone, Copy)]
@@ -27,19 +27,29 @@ pub enum StrictASCII {
 #[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct ASCII {
     strict: StrictASCII,
+    escape: EscapeStyle,
 }
 
 impl ASCII {
     pub fn new_aligned(alignment: Alignment, strict: StrictASCII) -> H2Type {
-        H2Type::new(alignment, H2Types::ASCII(Self {
-            strict: strict
-        }))
+        Self::new_aligned_with_escape(alignment, strict, EscapeStyle::C)
     }
 
     pub fn new(strict: StrictASCII) -> H2Type {
         Self::new_aligned(Alignment::None, strict)
     }
 
+    pub fn new_aligned_with_escape(alignment: Alignment, strict: StrictASCII, escape: EscapeStyle) -> H2Type {
+        H2Type::new(alignment, H2Types::ASCII(Self {
+            strict: strict,
+            escape: escape,
+        }))
+    }
+
+    pub fn new_with_escape(strict: StrictASCII, escape: EscapeStyle) -> H2Type {
+        Self::new_aligned_with_escape(Alignment::None, strict, escape)
+    }
+
     fn read_ascii_strict(context: Context) -> SimpleResult<char> {
         let number = context.read_u8()?;
 
@@ -69,7 +79,7 @@ impl H2TypeTrait for ASCII {
     }
 
     fn to_display(&self, offset: Offset) -> SimpleResult<String> {
-        Ok(common::display_character(self.to_char(offset)?))
+        Ok(common::display_character(self.to_char(offset)?, self.escape))
     }
 
     fn can_be_char(&self) -> bool {
@@ -1,16 +1,161 @@
-pub fn display_character(c: char) -> String {
-    match c as u32 {
-        0x00        => "'\\0'".to_string(),
-        0x01..=0x06 => format!("'\\x{:02x}'", c as u32),
-        0x07        => "'\\a'".to_string(),
-        0x08        => "'\\b'".to_string(),
-        0x09        => "'\\t'".to_string(),
-        0x0a        => "'\\n'".to_string(),
-        0x0b        => "'\\v'".to_string(),
-        0x0c        => "'\\f'".to_string(),
-        0x0d        => "'\\r'".to_string(),
-        0x0e..=0x1f => format!("'\\x{:02x}'", c as u32),
-
-        _ => format!("'{}'", c)
+#[cfg(feature = "serialize")]
+use serde::{Serialize, Deserialize};
+
+/// How special characters are escaped when a character or string is displayed.
+///
+/// Characters use [`EscapeStyle::C`] unless they're created with a
+/// `new_with_escape` constructor, such as
+/// [`crate::simple::character::ASCII::new_with_escape`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum EscapeStyle {
+    /// C-style escapes, like `\n`, `\0`, and `\x1b`.
+    C,
+
+    /// Rust's [`char::escape_debug`], like `\n`, `\0`, and `\u{1b}`.
+    Rust,
+
+    /// JSON string escapes, like `\n` and `\u001b`.
+    JSON,
+
+    /// No escaping at all - what you see is what's in memory.
+    Raw,
+}
+
+impl Default for EscapeStyle {
+    fn default() -> Self {
+        Self::C
+    }
+}
+
+/// Configures how a string is displayed.
+///
+/// Strings use the default unless they're created with a `new_with_display`
+/// constructor, such as
+/// [`crate::composite::string::NTString::new_with_display`].
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct StringDisplay {
+    /// How special characters are escaped.
+    pub escape: EscapeStyle,
+
+    /// The maximum number of characters to display. Longer strings are
+    /// truncated and followed by an ellipsis.
+    pub max_length: Option<u64>,
+}
+
+/// Escape a single character, with `quote` being the character that wraps it
+/// (and therefore must be escaped as well).
+fn escape_character(c: char, quote: char, style: EscapeStyle) -> String {
+    match style {
+        EscapeStyle::C => match c as u32 {
+            0x00        => "\\0".to_string(),
+            0x01..=0x06 => format!("\\x{:02x}", c as u32),
+            0x07        => "\\a".to_string(),
+            0x08        => "\\b".to_string(),
+            0x09        => "\\t".to_string(),
+            0x0a        => "\\n".to_string(),
+            0x0b        => "\\v".to_string(),
+            0x0c        => "\\f".to_string(),
+            0x0d        => "\\r".to_string(),
+            0x0e..=0x1f => format!("\\x{:02x}", c as u32),
+            0x7f        => "\\x7f".to_string(),
+            _ if c == '\\' || c == quote => format!("\\{}", c),
+
+            _ => c.to_string(),
+        },
+
+        EscapeStyle::Rust => c.escape_debug().to_string(),
+
+        EscapeStyle::JSON => match c as u32 {
+            0x08        => "\\b".to_string(),
+            0x09        => "\\t".to_string(),
+            0x0a        => "\\n".to_string(),
+            0x0c        => "\\f".to_string(),
+            0x0d        => "\\r".to_string(),
+            0x00..=0x1f => format!("\\u{:04x}", c as u32),
+            0x7f        => "\\u007f".to_string(),
+            _ if c == '\\' || c == '"' => format!("\\{}", c),
+
+            _ => c.to_string(),
+        },
+
+        EscapeStyle::Raw => c.to_string(),
+    }
+}
+
+/// Display a single character, with quotes and escaping.
+///
+/// JSON doesn't have a character type, so those are displayed as a
+/// one-character string.
+pub fn display_character(c: char, style: EscapeStyle) -> String {
+    match style {
+        EscapeStyle::JSON => format!("\"{}\"", escape_character(c, '"', style)),
+        _                 => format!("'{}'", escape_character(c, '\'', style)),
+    }
+}
+
+/// Display a string, with quotes, escaping, and (if needed) truncation.
+///
+/// If the string is truncated, the ellipsis goes outside of the quotes so it
+/// can't be confused for the string's content.
+pub fn display_string(s: &str, display: StringDisplay) -> String {
+    let (s, truncated): (String, bool) = match display.max_length {
+        Some(max) if s.chars().count() as u64 > max => (s.chars().take(max as usize).collect(), true),
+        _ => (s.to_string(), false),
+    };
+
+    let escaped: String = s.chars().map(|c| escape_character(c, '"', display.escape)).collect();
+
+    match truncated {
+        true  => format!("\"{}\"...", escaped),
+        false => format!("\"{}\"", escaped),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_character() {
+        let tests: Vec<(char, EscapeStyle, &str)> = vec![
+            //  char  style               expected
+            (   'A',  EscapeStyle::C,     "'A'"),
+            (  '\0',  EscapeStyle::C,     "'\\0'"),
+            ('\x1b',  EscapeStyle::C,     "'\\x1b'"),
+            (  '\'',  EscapeStyle::C,     "'\\''"),
+            (   '"',  EscapeStyle::C,     "'\"'"),
+            (  '\n',  EscapeStyle::Rust,  "'\\n'"),
+            ('\x1b',  EscapeStyle::Rust,  "'\\u{1b}'"),
+            (  '\n',  EscapeStyle::JSON,  "\"\\n\""),
+            ('\x1b',  EscapeStyle::JSON,  "\"\\u001b\""),
+            (   '"',  EscapeStyle::JSON,  "\"\\\"\""),
+            (  '\n',  EscapeStyle::Raw,   "'\n'"),
+        ];
+
+        for (c, style, expected) in tests {
+            assert_eq!(expected, display_character(c, style));
+        }
+    }
+
+    #[test]
+    fn test_display_string() {
+        let s = "a\"b\\c\nd";
+
+        assert_eq!("\"a\\\"b\\\\c\\nd\"", display_string(s, StringDisplay { escape: EscapeStyle::C,    max_length: None }));
+        assert_eq!("\"a\\\"b\\\\c\\nd\"", display_string(s, StringDisplay { escape: EscapeStyle::Rust, max_length: None }));
+        assert_eq!("\"a\\\"b\\\\c\\nd\"", display_string(s, StringDisplay { escape: EscapeStyle::JSON, max_length: None }));
+        assert_eq!("\"a\"b\\c\nd\"",      display_string(s, StringDisplay { escape: EscapeStyle::Raw,  max_length: None }));
+    }
+
+    #[test]
+    fn test_display_string_truncated() {
+        let display = StringDisplay { escape: EscapeStyle::C, max_length: Some(4) };
+
+        assert_eq!("\"abc\"",     display_string("abc",     display));
+        assert_eq!("\"abcd\"",    display_string("abcd",    display));
+        assert_eq!("\"abcd\"...", display_string("abcdefg", display));
+        assert_eq!("\"❄☢𝄞😈\"...", display_string("❄☢𝄞😈÷", display));
     }
 }
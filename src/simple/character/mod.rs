@@ -16,3 +16,4 @@ mod utf32;
 pub use utf32::*;
 
 pub mod common;
+pub use common::{EscapeStyle, StringDisplay};
@@ -5,7 +5,7 @@ use simple_error::SimpleResult;
 use sized_number::{Endian, Context};
 
 use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset};
-use crate::simple::character::common;
+use crate::simple::character::common::{self, EscapeStyle};
 
 /// Defines a UTF-16 character.
 ///
@@ -14,19 +14,29 @@ use crate::simple::character::common;
 #[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct UTF16 {
     endian: Endian,
+    escape: EscapeStyle,
 }
 
 impl UTF16 {
     pub fn new_aligned(alignment: Alignment, endian: Endian) -> H2Type {
-        H2Type::new(alignment, H2Types::UTF16(Self {
-            endian: endian
-        }))
+        Self::new_aligned_with_escape(alignment, endian, EscapeStyle::C)
     }
 
     pub fn new(endian: Endian) -> H2Type {
         Self::new_aligned(Alignment::None, endian)
     }
 
+    pub fn new_aligned_with_escape(alignment: Alignment, endian: Endian, escape: EscapeStyle) -> H2Type {
+        H2Type::new(alignment, H2Types::UTF16(Self {
+            endian: endian,
+            escape: escape,
+        }))
+    }
+
+    pub fn new_with_escape(endian: Endian, escape: EscapeStyle) -> H2Type {
+        Self::new_aligned_with_escape(Alignment::None, endian, escape)
+    }
+
     fn read_utf16(context: Context, endian: Endian) -> SimpleResult<(u64, char)> {
         let (size, c) = context.read_utf16(endian)?;
 
@@ -44,7 +54,7 @@ impl H2TypeTrait for UTF16 {
     }
 
     fn to_display(&self, offset: Offset) -> SimpleResult<String> {
-        Ok(common::display_character(self.to_char(offset)?))
+        Ok(common::display_character(self.to_char(offset)?, self.escape))
     }
 
     fn can_be_char(&self) -> bool {
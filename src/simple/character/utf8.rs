@@ -5,7 +5,7 @@ use simple_error::SimpleResult;
 use sized_number::Context;
 
 use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset};
-use crate::simple::character::common;
+use crate::simple::character::common::{self, EscapeStyle};
 
 /// Defines a UTF-8 value.
 ///
@@ -13,18 +13,28 @@ use crate::simple::character::common;
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct UTF8 {
+    escape: EscapeStyle,
 }
 
 impl UTF8 {
     pub fn new_aligned(alignment: Alignment) -> H2Type {
-        H2Type::new(alignment, H2Types::UTF8(Self {
-        }))
+        Self::new_aligned_with_escape(alignment, EscapeStyle::C)
     }
 
     pub fn new() -> H2Type {
         Self::new_aligned(Alignment::None)
     }
 
+    pub fn new_aligned_with_escape(alignment: Alignment, escape: EscapeStyle) -> H2Type {
+        H2Type::new(alignment, H2Types::UTF8(Self {
+            escape: escape,
+        }))
+    }
+
+    pub fn new_with_escape(escape: EscapeStyle) -> H2Type {
+        Self::new_aligned_with_escape(Alignment::None, escape)
+    }
+
     fn read_utf8(context: Context) -> SimpleResult<(u64, char)> {
         let (size, c) = context.read_utf8()?;
 
@@ -42,7 +52,7 @@ impl H2TypeTrait for UTF8 {
     }
 
     fn to_display(&self, offset: Offset) -> SimpleResult<String> {
-        Ok(common::display_character(self.to_char(offset)?))
+        Ok(common::display_character(self.to_char(offset)?, self.escape))
     }
 
     fn can_be_char(&self) -> bool {
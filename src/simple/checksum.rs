@@ -0,0 +1,414 @@
+#[cfg(feature = "serialize")]
+use serde::{Serialize, Deserialize};
+
+use simple_error::{bail, SimpleResult};
+use sized_number::SizedDefinition;
+use std::ops::Range;
+
+use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset};
+
+/// The algorithm used to calculate a [`Checksum`].
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum ChecksumAlgorithm {
+    /// The standard CRC-32 (IEEE 802.3), as used by zip, PNG, gzip, etc.
+    CRC32,
+
+    /// CRC-32C (Castagnoli), as used by iSCSI, SCTP, ext4, etc.
+    CRC32C,
+
+    /// Adler-32, as used by zlib.
+    Adler32,
+
+    /// Fletcher-16, over bytes.
+    Fletcher16,
+
+    /// Fletcher-32, over little-endian 16-bit words (an odd byte at the end
+    /// is padded with a zero).
+    Fletcher32,
+
+    /// The 16-bit one's complement checksum used by IP, TCP, UDP, and ICMP
+    /// (RFC 1071), over big-endian 16-bit words.
+    Internet,
+}
+
+impl ChecksumAlgorithm {
+    fn crc32(data: &[u8], polynomial: u32) -> u64 {
+        let crc = data.iter().fold(!0u32, |crc, &b| {
+            (0..8).fold(crc ^ b as u32, |crc, _| {
+                match crc & 1 {
+                    1 => (crc >> 1) ^ polynomial,
+                    _ => crc >> 1,
+                }
+            })
+        });
+
+        !crc as u64
+    }
+
+    fn calculate(&self, data: &[u8]) -> u64 {
+        match self {
+            Self::CRC32  => Self::crc32(data, 0xedb88320),
+            Self::CRC32C => Self::crc32(data, 0x82f63b78),
+            Self::Adler32 => {
+                let (a, b) = data.iter().fold((1u64, 0u64), |(a, b), &byte| {
+                    let a = (a + byte as u64) % 65521;
+
+                    (a, (b + a) % 65521)
+                });
+
+                (b << 16) | a
+            },
+            Self::Fletcher16 => {
+                let (a, b) = data.iter().fold((0u64, 0u64), |(a, b), &byte| {
+                    let a = (a + byte as u64) % 255;
+
+                    (a, (b + a) % 255)
+                });
+
+                (b << 8) | a
+            },
+            Self::Fletcher32 => {
+                let (a, b) = data.chunks(2).fold((0u64, 0u64), |(a, b), word| {
+                    let word = word[0] as u64 | (*word.get(1).unwrap_or(&0) as u64) << 8;
+                    let a = (a + word) % 65535;
+
+                    (a, (b + a) % 65535)
+                });
+
+                (b << 16) | a
+            },
+            Self::Internet => {
+                let mut sum = data.chunks(2).fold(0u64, |sum, word| {
+                    sum + ((word[0] as u64) << 8 | *word.get(1).unwrap_or(&0) as u64)
+                });
+
+                // Fold the carries back in
+                while sum > 0xffff {
+                    sum = (sum & 0xffff) + (sum >> 16);
+                }
+
+                !sum & 0xffff
+            },
+        }
+    }
+}
+
+/// The range of data that a [`Checksum`] covers.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum ChecksumRange {
+    /// A range relative to the start of the checksum field - for example,
+    /// `-8..0` is the eight bytes right before it.
+    Relative(Range<i64>),
+
+    /// An absolute range of the buffer.
+    Absolute(Range<u64>),
+
+    /// The named fields of the enclosing [`crate::composite::H2Struct`],
+    /// from the start of the first one to the end of the last one. They're
+    /// found when the struct is read, so they can be any size.
+    Fields(Vec<String>),
+
+    /// The whole enclosing [`crate::composite::H2Struct`].
+    Struct,
+}
+
+/// Defines a checksum field, which is verified against the data it covers.
+///
+/// The stored value is read as a number (using `definition`), and the actual
+/// checksum is calculated over the configured range. If the range includes
+/// the checksum field itself (such as an IP header checksum), the field is
+/// treated as zeroes.
+///
+/// A checksum over its siblings ([`ChecksumRange::Fields`] or
+/// [`ChecksumRange::Struct`]) has to be a field of a
+/// [`crate::composite::H2Struct`], which finds the range when it's read.
+///
+/// The display is the stored value followed by `(OK)` or
+/// `(MISMATCH, expected ...)`, and the result is available from
+/// [`H2TypeTrait::is_valid`] (and therefore [`crate::ResolvedType::valid`]).
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct Checksum {
+    definition: SizedDefinition,
+    algorithm: ChecksumAlgorithm,
+    range: ChecksumRange,
+}
+
+impl Checksum {
+    pub fn new_aligned(alignment: Alignment, definition: SizedDefinition, algorithm: ChecksumAlgorithm, range: ChecksumRange) -> SimpleResult<H2Type> {
+        if !definition.can_be_u64() {
+            bail!("Checksum definition can't become a u64");
+        }
+
+        Ok(H2Type::new(alignment, H2Types::Checksum(Self {
+            definition: definition,
+            algorithm: algorithm,
+            range: range,
+        })))
+    }
+
+    pub fn new(definition: SizedDefinition, algorithm: ChecksumAlgorithm, range: ChecksumRange) -> SimpleResult<H2Type> {
+        Self::new_aligned(Alignment::None, definition, algorithm, range)
+    }
+
+    /// Turn a [`ChecksumRange::Fields`] or [`ChecksumRange::Struct`] range
+    /// into an absolute range, given the ranges of the fields in the
+    /// enclosing struct. Other ranges don't need the struct, so they stay
+    /// as they are.
+    pub(crate) fn bind(&self, fields: &[(Range<u64>, Option<String>)]) -> SimpleResult<Self> {
+        let ranges = match &self.range {
+            ChecksumRange::Fields(names) => names.iter().map(|name| {
+                match fields.iter().find(|(_, field_name)| field_name.as_ref() == Some(name)) {
+                    Some((range, _)) => Ok(range.clone()),
+                    None => bail!("Checksum field not found: {}", name),
+                }
+            }).collect::<SimpleResult<Vec<_>>>()?,
+            ChecksumRange::Struct => fields.iter().map(|(range, _)| range.clone()).collect(),
+            _ => return Ok(self.clone()),
+        };
+
+        let start = ranges.iter().map(|r| r.start).min();
+        let end = ranges.iter().map(|r| r.end).max();
+
+        match (start, end) {
+            (Some(start), Some(end)) => Ok(Self {
+                definition: self.definition,
+                algorithm: self.algorithm,
+                range: ChecksumRange::Absolute(start..end),
+            }),
+            _ => bail!("Checksum doesn't cover any fields"),
+        }
+    }
+
+    /// Get the absolute range that the checksum covers.
+    fn absolute_range(&self, offset: Offset) -> SimpleResult<Range<u64>> {
+        match &self.range {
+            ChecksumRange::Absolute(range) => Ok(range.clone()),
+            ChecksumRange::Fields(_) | ChecksumRange::Struct => bail!("Checksums over fields have to be in a struct"),
+            ChecksumRange::Relative(range) => {
+                let position = offset.position() as i64;
+
+                if position + range.start < 0 || range.end < range.start {
+                    bail!("Invalid checksum range: {:?}", range);
+                }
+
+                Ok(((position + range.start) as u64)..((position + range.end) as u64))
+            },
+        }
+    }
+
+    /// Calculate the checksum of the covered data.
+    fn calculate(&self, offset: Offset) -> SimpleResult<u64> {
+        let context = offset.get_dynamic()?;
+        let own = offset.position()..(offset.position() + self.definition.size());
+
+        let data = self.absolute_range(offset)?.map(|i| {
+            match own.contains(&i) {
+                true  => Ok(0),
+                false => context.at(i).read_u8(),
+            }
+        }).collect::<SimpleResult<Vec<u8>>>()?;
+
+        Ok(self.algorithm.calculate(&data))
+    }
+
+    fn format(&self, value: u64) -> String {
+        format!("0x{:0width$x}", value, width = (self.definition.size() * 2) as usize)
+    }
+}
+
+impl H2TypeTrait for Checksum {
+    fn is_static(&self) -> bool {
+        true
+    }
+
+    fn actual_size(&self, _offset: Offset) -> SimpleResult<u64> {
+        Ok(self.definition.size())
+    }
+
+    fn to_display(&self, offset: Offset) -> SimpleResult<String> {
+        if let Offset::Static(_) = offset {
+            return Ok("Checksum".to_string());
+        }
+
+        let stored = self.to_u64(offset)?;
+        let expected = self.calculate(offset)?;
+
+        match stored == expected {
+            true  => Ok(format!("{} (OK)", self.format(stored))),
+            false => Ok(format!("{} (MISMATCH, expected {})", self.format(stored), self.format(expected))),
+        }
+    }
+
+    fn is_valid(&self, offset: Offset) -> SimpleResult<bool> {
+        Ok(self.to_u64(offset)? == self.calculate(offset)?)
+    }
+
+    fn can_be_u64(&self) -> bool {
+        true
+    }
+
+    fn to_u64(&self, offset: Offset) -> SimpleResult<u64> {
+        Ok(self.definition.to_u64(offset.get_dynamic()?)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+    use sized_number::{Context, Endian, SizedDisplay};
+
+    use crate::composite::H2Struct;
+    use crate::simple::{BytesDisplay, BytesLength, H2Bytes, H2Number};
+
+    #[test]
+    fn test_algorithms() -> SimpleResult<()> {
+        let tests: Vec<(ChecksumAlgorithm, &[u8], u64)> = vec![
+            (ChecksumAlgorithm::CRC32,      &b"123456789"[..], 0xcbf43926),
+            (ChecksumAlgorithm::CRC32C,     &b"123456789"[..], 0xe3069283),
+            (ChecksumAlgorithm::Adler32,    &b"Wikipedia"[..], 0x11e60398),
+            (ChecksumAlgorithm::Fletcher16, &b"abcde"[..],     0xc8f0),
+            (ChecksumAlgorithm::Fletcher32, &b"abcde"[..],     0xf04fc729),
+            (ChecksumAlgorithm::Fletcher32, &b"abcdef"[..],    0x56502d2a),
+        ];
+
+        for (algorithm, data, expected) in tests {
+            assert_eq!(expected, algorithm.calculate(data));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_crc32() -> SimpleResult<()> {
+        // The data, then a good CRC and a bad one
+        let data = b"123456789\xcb\xf4\x39\x26\xcb\xf4\x39\x27".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = Checksum::new(SizedDefinition::U32(Endian::Big), ChecksumAlgorithm::CRC32, ChecksumRange::Absolute(0..9))?;
+
+        assert_eq!(4, t.actual_size(offset.at(9))?);
+        assert_eq!("0xcbf43926 (OK)", t.to_display(offset.at(9))?);
+        assert_eq!("0xcbf43927 (MISMATCH, expected 0xcbf43926)", t.to_display(offset.at(13))?);
+        assert_eq!(0xcbf43926, t.to_u64(offset.at(9))?);
+
+        assert_eq!(Some(true),  t.resolve(offset.at(9), None)?.valid);
+        assert_eq!(Some(false), t.resolve(offset.at(13), None)?.valid);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ip_header() -> SimpleResult<()> {
+        // An IPv4 header, where the checksum covers the whole header
+        // (including itself)
+        let data = b"\x45\x00\x00\x73\x00\x00\x40\x00\x40\x11\xb8\x61\xc0\xa8\x00\x01\xc0\xa8\x00\xc7".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = H2Struct::new(vec![
+            ("start".to_string(),    H2Number::new(SizedDefinition::U64(Endian::Big), SizedDisplay::Hex(Default::default()))),
+            ("ttl".to_string(),      H2Number::new(SizedDefinition::U8, SizedDisplay::Decimal)),
+            ("protocol".to_string(), H2Number::new(SizedDefinition::U8, SizedDisplay::Decimal)),
+            ("checksum".to_string(), Checksum::new(SizedDefinition::U16(Endian::Big), ChecksumAlgorithm::Internet, ChecksumRange::Relative(-10..10))?),
+            ("source".to_string(),   H2Number::new(SizedDefinition::U32(Endian::Big), SizedDisplay::Hex(Default::default()))),
+            ("dest".to_string(),     H2Number::new(SizedDefinition::U32(Endian::Big), SizedDisplay::Hex(Default::default()))),
+        ])?;
+
+        let r = t.resolve(offset, None)?;
+        assert_eq!("0xb861 (OK)", r.children[3].display);
+        assert_eq!(Some(true), r.children[3].valid);
+
+        // Other fields can't be validated, but the struct is valid because
+        // the checksum is
+        assert_eq!(Some(true), r.valid);
+        assert_eq!(None, r.children[0].valid);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fields() -> SimpleResult<()> {
+        // Two records with variable-length data, where the CRC covers the
+        // tag and the data (including its length); the second CRC is bad
+        let data = b"\x01\x09123456789\x7b\xe1\x68\x6e\x01\x03abc\x23\x92\xe9\x94".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = H2Struct::new(vec![
+            ("tag".to_string(),  H2Number::new(SizedDefinition::U8, SizedDisplay::Decimal)),
+            ("data".to_string(), H2Bytes::new(BytesLength::Prefixed(Box::new(H2Number::new(SizedDefinition::U8, SizedDisplay::Decimal))), BytesDisplay::Hex)?),
+            ("crc".to_string(),  Checksum::new(SizedDefinition::U32(Endian::Big), ChecksumAlgorithm::CRC32, ChecksumRange::Fields(vec!["tag".to_string(), "data".to_string()]))?),
+        ])?;
+
+        let r = t.resolve(offset, None)?;
+        assert_eq!(15, r.actual_range.end);
+        assert_eq!("0x7be1686e (OK)", r.children[2].display);
+        assert_eq!(Some(true), r.valid);
+
+        // A mismatch makes the struct invalid too
+        let r = t.resolve(offset.at(15), None)?;
+        assert_eq!("0x2392e994 (MISMATCH, expected 0x2392e993)", r.children[2].display);
+        assert_eq!(Some(false), r.children[2].valid);
+        assert_eq!(Some(false), r.valid);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_struct() -> SimpleResult<()> {
+        // The same header as test_ip_header, without working out the range
+        let data = b"\x45\x00\x00\x73\x00\x00\x40\x00\x40\x11\xb8\x61\xc0\xa8\x00\x01\xc0\xa8\x00\xc7".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = H2Struct::new(vec![
+            ("start".to_string(),    H2Number::new(SizedDefinition::U64(Endian::Big), SizedDisplay::Hex(Default::default()))),
+            ("ttl".to_string(),      H2Number::new(SizedDefinition::U8, SizedDisplay::Decimal)),
+            ("protocol".to_string(), H2Number::new(SizedDefinition::U8, SizedDisplay::Decimal)),
+            ("checksum".to_string(), Checksum::new(SizedDefinition::U16(Endian::Big), ChecksumAlgorithm::Internet, ChecksumRange::Struct)?),
+            ("source".to_string(),   H2Number::new(SizedDefinition::U32(Endian::Big), SizedDisplay::Hex(Default::default()))),
+            ("dest".to_string(),     H2Number::new(SizedDefinition::U32(Endian::Big), SizedDisplay::Hex(Default::default()))),
+        ])?;
+
+        let r = t.resolve(offset, None)?;
+        assert_eq!("0xb861 (OK)", r.children[3].display);
+        assert_eq!(Some(true), r.valid);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unbound() -> SimpleResult<()> {
+        let data = b"\x00\x00\x00\x00".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        // Outside a struct, there are no fields to cover
+        let t = Checksum::new(SizedDefinition::U16(Endian::Big), ChecksumAlgorithm::Internet, ChecksumRange::Struct)?;
+        assert!(t.to_display(offset).is_err());
+
+        // In a struct, but naming a field that isn't there
+        let t = H2Struct::new(vec![
+            ("a".to_string(),        H2Number::new(SizedDefinition::U16(Endian::Big), SizedDisplay::Decimal)),
+            ("checksum".to_string(), Checksum::new(SizedDefinition::U16(Endian::Big), ChecksumAlgorithm::Internet, ChecksumRange::Fields(vec!["b".to_string()]))?),
+        ])?;
+        assert!(t.resolve(offset, None).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_range() -> SimpleResult<()> {
+        let data = b"\x00\x00".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        // Before the start of the buffer, then past the end
+        let t = Checksum::new(SizedDefinition::U16(Endian::Big), ChecksumAlgorithm::Internet, ChecksumRange::Relative(-4..0))?;
+        assert!(t.to_display(offset).is_err());
+
+        let t = Checksum::new(SizedDefinition::U16(Endian::Big), ChecksumAlgorithm::Internet, ChecksumRange::Relative(0..4))?;
+        assert!(t.to_display(offset).is_err());
+
+        Ok(())
+    }
+}
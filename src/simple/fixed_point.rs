@@ -0,0 +1,191 @@
+#[cfg(feature = "serialize")]
+use serde::{Serialize, Deserialize};
+
+use simple_error::{bail, SimpleResult};
+use sized_number::{Endian, SizedDefinition};
+
+use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset};
+
+/// Defines a binary fixed-point (Q-format) number.
+///
+/// The value is an integer with `fraction_bits` of its bits to the right of
+/// the binary point. Following the ARM convention, `integer_bits` includes the
+/// sign bit for signed values, so Q15 is `(1, 15, signed)` and Q16.16 is
+/// `(16, 16, signed)`. The total must be 8, 16, 32, or 64 bits.
+///
+/// The value is displayed exactly, in decimal; the raw integer is available
+/// with [`H2TypeTrait::to_i64`].
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct FixedPoint {
+    integer_bits: u8,
+    fraction_bits: u8,
+    signed: bool,
+    endian: Endian,
+}
+
+impl FixedPoint {
+    pub fn new_aligned(alignment: Alignment, integer_bits: u8, fraction_bits: u8, signed: bool, endian: Endian) -> SimpleResult<H2Type> {
+        match integer_bits as u32 + fraction_bits as u32 {
+            8 | 16 | 32 | 64 => (),
+            n => bail!("Fixed-point numbers must be 8, 16, 32, or 64 bits, not {}", n),
+        };
+
+        Ok(H2Type::new(alignment, H2Types::FixedPoint(Self {
+            integer_bits: integer_bits,
+            fraction_bits: fraction_bits,
+            signed: signed,
+            endian: endian,
+        })))
+    }
+
+    pub fn new(integer_bits: u8, fraction_bits: u8, signed: bool, endian: Endian) -> SimpleResult<H2Type> {
+        Self::new_aligned(Alignment::None, integer_bits, fraction_bits, signed, endian)
+    }
+
+    fn bits(&self) -> u32 {
+        self.integer_bits as u32 + self.fraction_bits as u32
+    }
+
+    fn definition(&self) -> SizedDefinition {
+        match self.bits() {
+            8  => SizedDefinition::U8,
+            16 => SizedDefinition::U16(self.endian),
+            32 => SizedDefinition::U32(self.endian),
+            _  => SizedDefinition::U64(self.endian),
+        }
+    }
+
+    /// Read the raw integer, sign extended if needed.
+    fn read(&self, offset: Offset) -> SimpleResult<i128> {
+        let raw = self.definition().to_u64(offset.get_dynamic()?)? as i128;
+        let bits = self.bits();
+
+        match self.signed && (raw >> (bits - 1)) & 1 == 1 {
+            true  => Ok(raw - (1i128 << bits)),
+            false => Ok(raw),
+        }
+    }
+}
+
+/// Convert a raw fixed-point value to an exact decimal string.
+fn to_decimal(raw: i128, fraction_bits: u8) -> String {
+    let magnitude = raw.unsigned_abs();
+    let mask = (1u128 << fraction_bits) - 1;
+
+    // Every binary fraction terminates in decimal, so this always finishes
+    let mut fraction = magnitude & mask;
+    let mut digits = String::new();
+    while fraction != 0 {
+        fraction = fraction * 10;
+        digits.push_str(&(fraction >> fraction_bits).to_string());
+        fraction = fraction & mask;
+    }
+
+    if digits.is_empty() {
+        digits.push('0');
+    }
+
+    format!("{}{}.{}", if raw < 0 { "-" } else { "" }, magnitude >> fraction_bits, digits)
+}
+
+impl H2TypeTrait for FixedPoint {
+    fn is_static(&self) -> bool {
+        true
+    }
+
+    fn actual_size(&self, _offset: Offset) -> SimpleResult<u64> {
+        Ok(self.bits() as u64 / 8)
+    }
+
+    fn to_display(&self, offset: Offset) -> SimpleResult<String> {
+        match offset {
+            Offset::Static(_) => Ok("FixedPoint".to_string()),
+            Offset::Dynamic(_) | Offset::Bounded(..) | Offset::Windowed(..) => Ok(to_decimal(self.read(offset)?, self.fraction_bits)),
+        }
+    }
+
+    fn can_be_i64(&self) -> bool {
+        true
+    }
+
+    fn to_i64(&self, offset: Offset) -> SimpleResult<i64> {
+        // An unsigned 64-bit value can be too big
+        let raw = self.read(offset)?;
+        if raw > i64::MAX as i128 {
+            bail!("Fixed-point value {} doesn't fit in an i64", raw);
+        }
+
+        Ok(raw as i64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+    use sized_number::{Context, Endian};
+
+    #[test]
+    fn test_q15() -> SimpleResult<()> {
+        let data = b"\x40\x00\x80\x00\xff\xff\x7f\xff\x00\x00".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = FixedPoint::new(1, 15, true, Endian::Big)?;
+
+        assert_eq!(2, t.actual_size(offset)?);
+        assert_eq!("0.5",                    t.to_display(offset.at(0))?);
+        assert_eq!("-1.0",                   t.to_display(offset.at(2))?);
+        assert_eq!("-0.000030517578125",     t.to_display(offset.at(4))?);
+        assert_eq!("0.999969482421875",      t.to_display(offset.at(6))?);
+        assert_eq!("0.0",                    t.to_display(offset.at(8))?);
+
+        assert_eq!(16384,  t.to_i64(offset.at(0))?);
+        assert_eq!(-32768, t.to_i64(offset.at(2))?);
+        assert_eq!(-1,     t.to_i64(offset.at(4))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_q16_16() -> SimpleResult<()> {
+        let data = b"\x00\x80\x01\x00\x00\x80\xff\xff".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = FixedPoint::new(16, 16, true, Endian::Little)?;
+
+        assert_eq!(4, t.actual_size(offset)?);
+        assert_eq!("1.5",  t.to_display(offset.at(0))?);
+        assert_eq!("-0.5", t.to_display(offset.at(4))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unsigned() -> SimpleResult<()> {
+        let data = b"\xff\x80\xff\xff\xff\xff\xff\xff\xff\xff".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = FixedPoint::new(8, 8, false, Endian::Big)?;
+        assert_eq!("255.5", t.to_display(offset)?);
+        assert_eq!(65408, t.to_i64(offset)?);
+
+        let t = FixedPoint::new(0, 8, false, Endian::Big)?;
+        assert_eq!("0.99609375", t.to_display(offset)?);
+
+        // Too big for an i64
+        let t = FixedPoint::new(32, 32, false, Endian::Big)?;
+        assert_eq!("4294967295.99999999976716935634613037109375", t.to_display(offset.at(2))?);
+        assert!(t.to_i64(offset.at(2)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_size() -> SimpleResult<()> {
+        assert!(FixedPoint::new(4, 8, true, Endian::Big).is_err());
+        assert!(FixedPoint::new(0, 0, true, Endian::Big).is_err());
+
+        Ok(())
+    }
+}
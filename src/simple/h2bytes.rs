@@ -0,0 +1,339 @@
+#[cfg(feature = "serialize")]
+use serde::{Serialize, Deserialize};
+
+use simple_error::{bail, SimpleResult};
+
+use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset};
+use crate::bytes::to_hex;
+use crate::offset::buffer_end;
+
+/// How the length of an [`H2Bytes`] is determined.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum BytesLength {
+    /// A fixed number of bytes.
+    Fixed(u64),
+
+    /// A numeric field (such as [`crate::simple::H2Number`]) immediately
+    /// before the data holds the length in bytes.
+    Prefixed(Box<H2Type>),
+
+    /// Everything up to the end of the buffer (or the end of the bounds, if
+    /// the [`Offset`] is bounded).
+    Remaining,
+}
+
+impl BytesLength {
+    /// Get the position and length of the data, not including a length
+    /// prefix.
+    ///
+    /// The length can come from the data, so this makes sure the end of the
+    /// data fits in a `u64` - callers can add them without checking.
+    pub(crate) fn data_range(&self, offset: Offset) -> SimpleResult<(u64, u64)> {
+        let (position, length) = match self {
+            BytesLength::Fixed(n) => (offset.position(), *n),
+            BytesLength::Prefixed(t) => {
+                match offset.position().checked_add(t.aligned_size(offset)?) {
+                    Some(position) => (position, t.to_u64(offset)?),
+                    None           => bail!("Length prefix runs past the end of the address space"),
+                }
+            },
+            BytesLength::Remaining => {
+                let context = offset.get_dynamic()?;
+
+                // In a bounded offset, the rest of the bounds is remaining
+                let end = match offset.bounds() {
+                    Some(bounds) => bounds.end,
+                    None         => buffer_end(context),
+                };
+
+                match end.checked_sub(offset.position()) {
+                    Some(length) => (offset.position(), length),
+                    None         => bail!("Position {} is past the end of the data", offset.position()),
+                }
+            },
+        };
+
+        if position.checked_add(length).is_none() {
+            bail!("Bytes length is too large: {}", length);
+        }
+
+        Ok((position, length))
+    }
+
+    /// Get the size of the data, including a length prefix.
+    pub(crate) fn size(&self, offset: Offset) -> SimpleResult<u64> {
+        let (position, length) = self.data_range(offset)?;
+
+        Ok(position + length - offset.position())
+    }
+
+    /// Read up to `limit` bytes of the data, returning where it starts, its
+    /// full length, and the bytes that were read. The rest of the data isn't
+    /// read, but the last byte has to exist.
+    pub(crate) fn read(&self, offset: Offset, limit: u64) -> SimpleResult<(u64, u64, Vec<u8>)> {
+        let (position, length) = self.data_range(offset)?;
+        let context = offset.get_dynamic()?;
+
+        if length > limit {
+            context.at(position + length - 1).read_u8()?;
+        }
+
+        let bytes = (position..(position + length.min(limit))).map(|i| {
+            context.at(i).read_u8()
+        }).collect::<SimpleResult<Vec<u8>>>()?;
+
+        Ok((position, length, bytes))
+    }
+}
+
+/// How an [`H2Bytes`] is displayed.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum BytesDisplay {
+    /// One long hex string, like `00010203`.
+    Hex,
+
+    /// A multi-line hexdump, like `hexdump -C`.
+    Hexdump,
+
+    /// Standard base64, with padding.
+    Base64,
+
+    /// The first few bytes in hex, then the total size.
+    Preview(u64),
+}
+
+/// Defines a blob of opaque bytes.
+///
+/// Unlike an [`crate::composite::H2Array`] of [`crate::simple::H2Number`]s,
+/// this doesn't have a child per byte, which makes it much cheaper for padding,
+/// hashes, keys, and unknown regions.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct H2Bytes {
+    length: BytesLength,
+    display: BytesDisplay,
+}
+
+impl H2Bytes {
+    pub fn new_aligned(alignment: Alignment, length: BytesLength, display: BytesDisplay) -> SimpleResult<H2Type> {
+        if let BytesLength::Prefixed(t) = &length {
+            if !t.can_be_u64() {
+                bail!("Length type can't become a u64");
+            }
+        }
+
+        Ok(H2Type::new(alignment, H2Types::H2Bytes(Self {
+            length: length,
+            display: display,
+        })))
+    }
+
+    pub fn new(length: BytesLength, display: BytesDisplay) -> SimpleResult<H2Type> {
+        Self::new_aligned(Alignment::None, length, display)
+    }
+}
+
+fn to_hexdump(position: u64, bytes: &[u8]) -> String {
+    bytes.chunks(16).enumerate().map(|(i, chunk)| {
+        let ascii: String = chunk.iter().map(|&b| {
+            match b {
+                0x20..=0x7e => b as char,
+                _           => '.',
+            }
+        }).collect();
+
+        format!("{:08x}  {:<47}  |{}|", position + (i as u64 * 16), to_hex(chunk, " "), ascii)
+    }).collect::<Vec<String>>().join("\n")
+}
+
+fn to_base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    bytes.chunks(3).map(|chunk| {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = ((b[0] as u32) << 16) | ((b[1] as u32) << 8) | (b[2] as u32);
+
+        (0..4).map(|i| {
+            match i <= chunk.len() {
+                true  => ALPHABET[((n >> (18 - (i * 6))) & 0x3f) as usize] as char,
+                false => '=',
+            }
+        }).collect::<String>()
+    }).collect()
+}
+
+impl H2TypeTrait for H2Bytes {
+    fn is_static(&self) -> bool {
+        match self.length {
+            BytesLength::Fixed(_) => true,
+            _                     => false,
+        }
+    }
+
+    fn actual_size(&self, offset: Offset) -> SimpleResult<u64> {
+        self.length.size(offset)
+    }
+
+    fn to_display(&self, offset: Offset) -> SimpleResult<String> {
+        if let Offset::Static(_) = offset {
+            return Ok("Bytes".to_string());
+        }
+
+        // A preview only needs its first few bytes
+        let limit = match self.display {
+            BytesDisplay::Preview(n) => n,
+            _                        => u64::MAX,
+        };
+
+        let (position, length, bytes) = self.length.read(offset, limit)?;
+
+        Ok(match self.display {
+            BytesDisplay::Hex        => to_hex(&bytes, ""),
+            BytesDisplay::Hexdump    => to_hexdump(position, &bytes),
+            BytesDisplay::Base64     => to_base64(&bytes),
+            BytesDisplay::Preview(n) => {
+                match length > n {
+                    true  => format!("{} ... ({} bytes)", to_hex(&bytes, " "), length),
+                    false => to_hex(&bytes, " "),
+                }
+            },
+        })
+    }
+
+    fn children(&self, offset: Offset) -> SimpleResult<Vec<(Option<String>, H2Type)>> {
+        // Only a prefixed blob has children: the length, and the data
+        match &self.length {
+            BytesLength::Prefixed(t) => {
+                let (_, length) = self.length.data_range(offset)?;
+
+                Ok(vec![
+                    ( Some("size".to_string()), t.as_ref().clone() ),
+                    ( None,                     H2Bytes::new(BytesLength::Fixed(length), self.display)? ),
+                ])
+            },
+            _ => Ok(vec![]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+    use sized_number::{Context, SizedDefinition, SizedDisplay, Endian};
+
+    use crate::simple::H2Number;
+
+    #[test]
+    fn test_fixed_bytes() -> SimpleResult<()> {
+        let data = b"\x00\x01\x02\x03\xfe\xff".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = H2Bytes::new(BytesLength::Fixed(4), BytesDisplay::Hex)?;
+        assert_eq!(true, t.is_static());
+        assert_eq!(4, t.actual_size(Offset::Static(0))?);
+        assert_eq!(4, t.actual_size(offset)?);
+        assert_eq!("00010203", t.to_display(offset)?);
+        assert_eq!("0203feff", t.to_display(offset.at(2))?);
+        assert!(t.to_display(offset.at(3)).is_err());
+
+        // No children, no matter how long it is
+        let r = t.resolve(offset, None)?;
+        assert_eq!(0..4, r.actual_range);
+        assert_eq!(0, r.children.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_prefixed_bytes() -> SimpleResult<()> {
+        let data = b"\x00\x03\xaa\xbb\xcc\xdd".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = H2Bytes::new(
+            BytesLength::Prefixed(Box::new(H2Number::new(SizedDefinition::U16(Endian::Big), SizedDisplay::Decimal))),
+            BytesDisplay::Hex,
+        )?;
+
+        assert_eq!(false, t.is_static());
+        assert_eq!(5, t.actual_size(offset)?);
+        assert_eq!("aabbcc", t.to_display(offset)?);
+
+        let r = t.resolve(offset, None)?;
+        assert_eq!(2, r.children.len());
+        assert_eq!("3", r.children[0].display);
+        assert_eq!(2..5, r.children[1].actual_range);
+        assert_eq!("aabbcc", r.children[1].display);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_remaining_bytes() -> SimpleResult<()> {
+        let data = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = H2Bytes::new(BytesLength::Remaining, BytesDisplay::Hex)?;
+
+        assert_eq!(26, t.actual_size(offset)?);
+        assert_eq!(1,  t.actual_size(offset.at(25))?);
+        assert_eq!(0,  t.actual_size(offset.at(26))?);
+        assert_eq!(16, t.actual_size(offset.at(10))?);
+        assert!(t.actual_size(Offset::Static(0)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_display() -> SimpleResult<()> {
+        let data = b"hello, world!\x00\x01\x02\xff".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = H2Bytes::new(BytesLength::Fixed(17), BytesDisplay::Hexdump)?;
+        assert_eq!(
+            "00000000  68 65 6c 6c 6f 2c 20 77 6f 72 6c 64 21 00 01 02  |hello, world!...|\n00000010  ff                                               |.|",
+            t.to_display(offset)?
+        );
+
+        let t = H2Bytes::new(BytesLength::Fixed(5), BytesDisplay::Base64)?;
+        assert_eq!("aGVsbG8=", t.to_display(offset)?);
+
+        let t = H2Bytes::new(BytesLength::Fixed(6), BytesDisplay::Base64)?;
+        assert_eq!("aGVsbG8s", t.to_display(offset)?);
+
+        let t = H2Bytes::new(BytesLength::Fixed(4), BytesDisplay::Base64)?;
+        assert_eq!("aGVsbA==", t.to_display(offset)?);
+
+        let t = H2Bytes::new(BytesLength::Fixed(17), BytesDisplay::Preview(4))?;
+        assert_eq!("68 65 6c 6c ... (17 bytes)", t.to_display(offset)?);
+
+        let t = H2Bytes::new(BytesLength::Fixed(3), BytesDisplay::Preview(4))?;
+        assert_eq!("68 65 6c", t.to_display(offset)?);
+
+        // A preview doesn't read the whole blob, but it still has to exist
+        let t = H2Bytes::new(BytesLength::Fixed(18), BytesDisplay::Preview(4))?;
+        assert!(t.to_display(offset).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_huge_prefix() -> SimpleResult<()> {
+        // A length that doesn't fit after its own prefix
+        let data = b"\xff\xff\xff\xff\xff\xff\xff\xff\x00\x01".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = H2Bytes::new(
+            BytesLength::Prefixed(Box::new(H2Number::new(SizedDefinition::U64(Endian::Big), SizedDisplay::Decimal))),
+            BytesDisplay::Preview(4),
+        )?;
+
+        assert!(t.actual_size(offset).is_err());
+        assert!(t.to_display(offset).is_err());
+        assert!(t.resolve(offset, None).is_err());
+
+        Ok(())
+    }
+}
@@ -49,7 +49,7 @@ impl H2TypeTrait for H2Number {
     fn to_display(&self, offset: Offset) -> SimpleResult<String> {
         match offset {
             Offset::Static(_) => Ok("Number".to_string()),
-            Offset::Dynamic(context) => {
+            Offset::Dynamic(context) | Offset::Bounded(context, ..) | Offset::Windowed(context, ..) => {
                 self.definition.to_string(context, self.display)
             }
         }
@@ -1,8 +1,8 @@
 #[cfg(feature = "serialize")]
 use serde::{Serialize, Deserialize};
 
-use simple_error::SimpleResult;
-use sized_number::{SizedDefinition, SizedDisplay};
+use simple_error::{bail, SimpleResult};
+use sized_number::{Context, SizedDefinition, SizedDisplay};
 
 use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset};
 
@@ -11,26 +11,57 @@ use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset};
 /// This is defined very similarly to [`crate::simple::H2Number`], with one
 /// additional field: the `target_type`, which is the type of the value that the
 /// pointer points to.
+///
+/// Normally the value is the target's offset in the buffer. An adjusted
+/// pointer ([`H2Pointer::new_adjusted`]) holds some other kind of address,
+/// such as a PE file's RVAs, and the adjustment is added to the value to get
+/// the offset.
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct H2Pointer {
     definition: SizedDefinition,
     display: SizedDisplay,
+    adjustment: i64,
 
     target_type: Box<H2Type>,
 }
 
 impl H2Pointer {
     pub fn new_aligned(alignment: Alignment, definition: SizedDefinition, display: SizedDisplay, target_type: H2Type) -> H2Type {
+        Self::new_adjusted_aligned(alignment, definition, display, 0, target_type)
+    }
+
+    pub fn new(definition: SizedDefinition, display: SizedDisplay, target_type: H2Type) -> H2Type {
+        Self::new_aligned(Alignment::None, definition, display, target_type)
+    }
+
+    pub fn new_adjusted_aligned(alignment: Alignment, definition: SizedDefinition, display: SizedDisplay, adjustment: i64, target_type: H2Type) -> H2Type {
         H2Type::new(alignment, H2Types::H2Pointer(Self {
             definition: definition,
             display: display,
+            adjustment: adjustment,
             target_type: Box::new(target_type),
         }))
     }
 
-    pub fn new(definition: SizedDefinition, display: SizedDisplay, target_type: H2Type) -> H2Type {
-        Self::new_aligned(Alignment::None, definition, display, target_type)
+    pub fn new_adjusted(definition: SizedDefinition, display: SizedDisplay, adjustment: i64, target_type: H2Type) -> H2Type {
+        Self::new_adjusted_aligned(Alignment::None, definition, display, adjustment, target_type)
+    }
+
+    /// Read the value, and adjust it to get the target's offset.
+    fn target(&self, context: Context) -> SimpleResult<u64> {
+        let value = self.definition.to_u64(context)?;
+
+        let target = if self.adjustment < 0 {
+            value.checked_sub(self.adjustment.unsigned_abs())
+        } else {
+            value.checked_add(self.adjustment as u64)
+        };
+
+        match target {
+            Some(target) => Ok(target),
+            None => bail!("Pointer target is out of range: {} + {}", value, self.adjustment),
+        }
     }
 }
 
@@ -46,14 +77,13 @@ impl H2TypeTrait for H2Pointer {
     fn to_display(&self, offset: Offset) -> SimpleResult<String> {
         match offset {
             Offset::Static(_) => Ok(format!("Pointer to {}", self.target_type.to_display(offset)?)),
-            Offset::Dynamic(context) => {
+            Offset::Dynamic(context) | Offset::Bounded(context, ..) | Offset::Windowed(context, ..) => {
                 // Read the current value
-                let target_offset = self.definition.to_u64(context)?;
+                let target_offset = self.target(context)?;
                 let pointer_display = self.definition.to_string(context, self.display)?;
 
                 // Read the target from a separate context
-                let target = Offset::from(context.at(target_offset));
-                let target_display = match self.target_type.to_display(target) {
+                let target_display = match offset.dereference(target_offset).and_then(|target| self.target_type.to_display(target)) {
                     Ok(v) => v,
                     Err(e) => format!("Invalid pointer target: {}", e),
                 };
@@ -67,7 +97,7 @@ impl H2TypeTrait for H2Pointer {
         let context = offset.get_dynamic()?;
 
         Ok(vec![
-            (self.definition.to_u64(context)?, *self.target_type.clone())
+            (self.target(context)?, *self.target_type.clone())
         ])
     }
 }
@@ -137,4 +167,32 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_adjusted_pointer() -> SimpleResult<()> {
+        let data = b"\x10\x02\x01\xff".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        // The value is 0x10, and the target is at 0x10 - 0x0e = 2
+        let t = H2Pointer::new_adjusted(
+            SizedDefinition::U8,
+            SizedDisplay::Hex(Default::default()),
+            -0x0e,
+            H2Number::new(SizedDefinition::U16(Endian::Big), SizedDisplay::Hex(Default::default())),
+        );
+
+        assert_eq!("(ref) 0x10 => 0x01ff", t.to_display(offset)?);
+        assert_eq!(2, t.related(offset)?[0].0);
+
+        // Adjusting below zero is an error
+        let t = H2Pointer::new_adjusted(
+            SizedDefinition::U8,
+            SizedDisplay::Hex(Default::default()),
+            -0x11,
+            H2Number::new(SizedDefinition::U8, SizedDisplay::Hex(Default::default())),
+        );
+        assert!(t.related(offset).is_err());
+
+        Ok(())
+    }
 }
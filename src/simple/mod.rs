@@ -9,5 +9,24 @@ pub use h2number::*;
 mod h2pointer;
 pub use h2pointer::*;
 
+mod h2bytes;
+pub use h2bytes::*;
+
+mod varint;
+pub use varint::*;
+
+mod fixed_point;
+pub use fixed_point::*;
+
+mod bcd;
+pub use bcd::*;
+
+mod checksum;
+pub use checksum::*;
+
+mod bit_number;
+pub use bit_number::*;
+
 pub mod character;
 pub mod network;
+pub mod time;
@@ -0,0 +1,154 @@
+#[cfg(feature = "serialize")]
+use serde::{Serialize, Deserialize};
+
+use simple_error::{bail, SimpleResult};
+use sized_number::Context;
+
+use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset};
+
+/// The most pointers we'll follow, to avoid loops.
+const MAX_POINTERS: usize = 64;
+
+/// Defines a domain name, as encoded in a DNS message (RFC 1035).
+///
+/// A name is a series of labels, each prefixed with a one-byte length, and
+/// ends with either an empty label or a two-byte compression pointer (with
+/// the top two bits set) to the rest of the name elsewhere in the message.
+/// Pointers are relative to the start of the DNS message, which is
+/// `message_start` in the buffer.
+///
+/// The size is just the part that's stored here - up to and including the
+/// empty label or pointer - but the display follows pointers to show the
+/// full name, like `www.example.com`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct DNSName {
+    message_start: u64,
+}
+
+impl DNSName {
+    pub fn new_aligned(alignment: Alignment, message_start: u64) -> H2Type {
+        H2Type::new(alignment, H2Types::DNSName(Self {
+            message_start: message_start,
+        }))
+    }
+
+    pub fn new(message_start: u64) -> H2Type {
+        Self::new_aligned(Alignment::None, message_start)
+    }
+
+    /// Read the name, returning the size of the part stored here and the
+    /// labels.
+    fn read(&self, context: Context) -> SimpleResult<(u64, Vec<String>)> {
+        let mut labels = Vec::new();
+        let mut position = context.position();
+        let mut size = None;
+        let mut pointers = 0;
+
+        loop {
+            let length = context.at(position).read_u8()?;
+
+            match length & 0xc0 {
+                0x00 if length == 0 => {
+                    return Ok((size.unwrap_or(position + 1 - context.position()), labels));
+                },
+                0x00 => {
+                    let label = (0..(length as u64)).map(|i| {
+                        context.at(position + 1 + i).read_u8().map(|b| b as char)
+                    }).collect::<SimpleResult<String>>()?;
+
+                    labels.push(label);
+                    position += 1 + length as u64;
+                },
+                0xc0 => {
+                    let target = ((length as u64 & 0x3f) << 8) | context.at(position + 1).read_u8()? as u64;
+
+                    // The size only counts up to the first pointer
+                    if size.is_none() {
+                        size = Some(position + 2 - context.position());
+                    }
+
+                    pointers += 1;
+                    if pointers > MAX_POINTERS {
+                        bail!("Too many DNS compression pointers");
+                    }
+
+                    position = self.message_start + target;
+                },
+                _ => bail!("Invalid DNS label type: 0x{:02x}", length),
+            }
+        }
+    }
+}
+
+impl H2TypeTrait for DNSName {
+    fn is_static(&self) -> bool {
+        false
+    }
+
+    fn actual_size(&self, offset: Offset) -> SimpleResult<u64> {
+        Ok(self.read(offset.get_dynamic()?)?.0)
+    }
+
+    fn to_display(&self, offset: Offset) -> SimpleResult<String> {
+        match offset {
+            Offset::Static(_) => Ok("DNS Name".to_string()),
+            Offset::Dynamic(_) | Offset::Bounded(..) | Offset::Windowed(..) => Ok(self.to_string(offset)?),
+        }
+    }
+
+    fn can_be_string(&self) -> bool {
+        true
+    }
+
+    fn to_string(&self, offset: Offset) -> SimpleResult<String> {
+        let (_, labels) = self.read(offset.get_dynamic()?)?;
+
+        // The root is just a dot
+        match labels.len() {
+            0 => Ok(".".to_string()),
+            _ => Ok(labels.join(".")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+    use sized_number::Context;
+
+    #[test]
+    fn test_dns_name() -> SimpleResult<()> {
+        // A name, a compressed name that points to part of it, the root, and
+        // a name that points to itself
+        let data = b"\x03www\x07example\x03com\x00\x04mail\xc0\x04\x00\xc0\x19".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+        let t = DNSName::new(0);
+
+        assert_eq!("www.example.com", t.to_display(offset)?);
+        assert_eq!(17, t.actual_size(offset)?);
+
+        assert_eq!("mail.example.com", t.to_display(offset.at(17))?);
+        assert_eq!(7, t.actual_size(offset.at(17))?);
+
+        assert_eq!(".", t.to_display(offset.at(24))?);
+        assert_eq!(1, t.actual_size(offset.at(24))?);
+
+        assert!(t.to_display(offset.at(25)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_message_start() -> SimpleResult<()> {
+        // The message starts at 2, so the pointer is off by two
+        let data = b"\xff\xff\x02hi\x00\x02yo\xc0\x00".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        assert_eq!("yo.hi", DNSName::new(2).to_display(offset.at(6))?);
+        assert_eq!(5, DNSName::new(2).actual_size(offset.at(6))?);
+
+        Ok(())
+    }
+}
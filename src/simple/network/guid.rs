@@ -0,0 +1,181 @@
+#[cfg(feature = "serialize")]
+use serde::{Serialize, Deserialize};
+
+use simple_error::SimpleResult;
+use sized_number::{Context, Endian};
+
+use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset};
+use crate::simple::time::common;
+
+/// The byte order of a [`GUID`]'s first three fields.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum GUIDLayout {
+    /// The first three fields are little endian, and the rest are bytes.
+    /// This is how Windows (COM, PE, GPT, the registry, etc) stores them.
+    Microsoft,
+
+    /// Everything is big endian, as defined in RFC 4122.
+    RFC4122,
+}
+
+/// The number of 100ns intervals between the start of the Gregorian calendar
+/// (1582-10-15), which is the v1 UUID epoch, and the Unix epoch.
+const GREGORIAN_TO_UNIX: u64 = 0x01B2_1DD2_1381_4000;
+
+/// Defines a GUID (or UUID).
+///
+/// A GUID is always 16 bytes long, and is displayed in the standard
+/// `{xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx}` format. If `decode` is set, the
+/// version and variant are displayed as well, along with the timestamp for
+/// v1 (time-based) UUIDs.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct GUID {
+    layout: GUIDLayout,
+    decode: bool,
+}
+
+impl GUID {
+    pub fn new_aligned(alignment: Alignment, layout: GUIDLayout, decode: bool) -> H2Type {
+        H2Type::new(alignment, H2Types::GUID(Self {
+            layout: layout,
+            decode: decode,
+        }))
+    }
+
+    pub fn new(layout: GUIDLayout, decode: bool) -> H2Type {
+        Self::new_aligned(Alignment::None, layout, decode)
+    }
+
+    /// Read the GUID's fields: a u32, two u16s, and eight bytes.
+    fn read_fields(&self, context: Context) -> SimpleResult<(u32, u16, u16, [u8; 8])> {
+        let endian = match self.layout {
+            GUIDLayout::Microsoft => Endian::Little,
+            GUIDLayout::RFC4122   => Endian::Big,
+        };
+
+        let mut data4 = [0u8; 8];
+        for (i, b) in data4.iter_mut().enumerate() {
+            *b = context.at(context.position() + 8 + i as u64).read_u8()?;
+        }
+
+        Ok((
+            context.read_u32(endian)?,
+            context.at(context.position() + 4).read_u16(endian)?,
+            context.at(context.position() + 6).read_u16(endian)?,
+            data4,
+        ))
+    }
+
+    fn variant(data4: &[u8; 8]) -> &'static str {
+        match data4[0] >> 5 {
+            0..=3 => "NCS",
+            4..=5 => "RFC 4122",
+            6     => "Microsoft",
+            _     => "Reserved",
+        }
+    }
+}
+
+/// Format a v1 UUID timestamp (100ns intervals since 1582-10-15) in ISO-8601.
+fn format_v1_timestamp(timestamp: u64) -> String {
+    let intervals = timestamp as i64 - GREGORIAN_TO_UNIX as i64;
+
+    // A 60-bit timestamp can't go past the year 5236, so this always works
+    common::format_iso8601(intervals.div_euclid(10_000_000), (intervals.rem_euclid(10_000_000) * 100) as u32)
+        .unwrap_or_else(|| format!("Invalid timestamp ({})", timestamp))
+}
+
+impl H2TypeTrait for GUID {
+    fn is_static(&self) -> bool {
+        true
+    }
+
+    fn actual_size(&self, _offset: Offset) -> SimpleResult<u64> {
+        Ok(16)
+    }
+
+    fn to_display(&self, offset: Offset) -> SimpleResult<String> {
+        match offset {
+            Offset::Static(_) => Ok("GUID".to_string()),
+            Offset::Dynamic(context) | Offset::Bounded(context, ..) | Offset::Windowed(context, ..) => {
+                let (data1, data2, data3, data4) = self.read_fields(context)?;
+
+                let guid = format!("{{{:08x}-{:04x}-{:04x}-{:02x}{:02x}-{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}}}",
+                    data1, data2, data3,
+                    data4[0], data4[1],
+                    data4[2], data4[3], data4[4], data4[5], data4[6], data4[7],
+                );
+
+                if !self.decode {
+                    return Ok(guid);
+                }
+
+                let version = data3 >> 12;
+                let variant = Self::variant(&data4);
+
+                match (version, variant) {
+                    (1, "RFC 4122") => {
+                        let timestamp = ((data3 as u64 & 0x0fff) << 48) | ((data2 as u64) << 32) | (data1 as u64);
+
+                        Ok(format!("{} (v{}, {}, {})", guid, version, variant, format_v1_timestamp(timestamp)))
+                    },
+                    _ => Ok(format!("{} (v{}, {})", guid, version, variant)),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+    use sized_number::Context;
+
+    #[test]
+    fn test_guid_rfc4122() -> SimpleResult<()> {
+        let data = b"\xc2\x32\xab\x00\x94\x14\x11\xec\xb3\xc8\x9f\x6b\xde\xce\xd8\x46".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        assert_eq!(16, GUID::new(GUIDLayout::RFC4122, false).actual_size(offset)?);
+        assert_eq!("{c232ab00-9414-11ec-b3c8-9f6bdeced846}", GUID::new(GUIDLayout::RFC4122, false).to_display(offset)?);
+        assert_eq!("{c232ab00-9414-11ec-b3c8-9f6bdeced846} (v1, RFC 4122, 2022-02-22T19:22:22Z)", GUID::new(GUIDLayout::RFC4122, true).to_display(offset)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_guid_microsoft() -> SimpleResult<()> {
+        let data = b"\x40\xfc\x29\x6b\x47\xca\x67\x10\xb3\x1d\x00\xdd\x01\x06\x62\xda".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        assert_eq!("{6b29fc40-ca47-1067-b31d-00dd010662da}", GUID::new(GUIDLayout::Microsoft, false).to_display(offset)?);
+        assert_eq!("{40fc296b-47ca-6710-b31d-00dd010662da}", GUID::new(GUIDLayout::RFC4122, false).to_display(offset)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_guid_decode() -> SimpleResult<()> {
+        // A random (v4) UUID, then the nil UUID
+        let data = b"\x91\x9e\x4e\x2d\x7b\x6a\x4c\x36\x8f\x0e\x3b\x2a\x55\x19\x21\x07\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        assert_eq!("{919e4e2d-7b6a-4c36-8f0e-3b2a55192107} (v4, RFC 4122)", GUID::new(GUIDLayout::RFC4122, true).to_display(offset)?);
+        assert_eq!("{00000000-0000-0000-0000-000000000000} (v0, NCS)", GUID::new(GUIDLayout::RFC4122, true).to_display(offset.at(16))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_guid_too_short() -> SimpleResult<()> {
+        let data = b"\x00\x01\x02\x03\x04\x05\x06\x07\x08\x09\x0a\x0b\x0c\x0d\x0e".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        assert!(GUID::new(GUIDLayout::Microsoft, false).to_display(offset).is_err());
+
+        Ok(())
+    }
+}
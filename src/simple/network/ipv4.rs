@@ -41,7 +41,7 @@ impl H2TypeTrait for IPv4 {
     fn to_display(&self, offset: Offset) -> SimpleResult<String> {
         match offset {
             Offset::Static(_) => Ok("IPv4 Address".to_string()),
-            Offset::Dynamic(context) => {
+            Offset::Dynamic(context) | Offset::Bounded(context, ..) | Offset::Windowed(context, ..) => {
                 let number = context.read_u32(self.endian)?;
 
                 Ok(Ipv4Addr::from(number).to_string())
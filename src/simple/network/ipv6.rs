@@ -41,7 +41,7 @@ impl H2TypeTrait for IPv6 {
     fn to_display(&self, offset: Offset) -> SimpleResult<String> {
         match offset {
             Offset::Static(_) => Ok("IPv6 Address".to_string()),
-            Offset::Dynamic(context) => {
+            Offset::Dynamic(context) | Offset::Bounded(context, ..) | Offset::Windowed(context, ..) => {
                 let number = context.read_u128(self.endian)?;
 
                 Ok(Ipv6Addr::from(number).to_string())
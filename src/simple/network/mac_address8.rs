@@ -37,7 +37,7 @@ impl H2TypeTrait for MacAddress8 {
     fn to_display(&self, offset: Offset) -> SimpleResult<String> {
         match offset {
             Offset::Static(_) => Ok("MAC Address 8".to_string()),
-            Offset::Dynamic(context) => {
+            Offset::Dynamic(context) | Offset::Bounded(context, ..) | Offset::Windowed(context, ..) => {
                 let b = context.read_bytes(8)?;
                 let b: [u8; 8] = [
                     b[0],
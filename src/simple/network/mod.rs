@@ -14,3 +14,9 @@ pub use mac_address::*;
 
 mod mac_address8;
 pub use mac_address8::*;
+
+mod guid;
+pub use guid::*;
+
+mod dns_name;
+pub use dns_name::*;
@@ -0,0 +1,94 @@
+/// The earliest time we'll display (0001-01-01T00:00:00Z), in Unix seconds.
+const MINIMUM_SECONDS: i64 = -62135596800;
+
+/// The latest time we'll display (9999-12-31T23:59:59Z), in Unix seconds.
+const MAXIMUM_SECONDS: i64 = 253402300799;
+
+/// Convert a number of days since 1970-01-01 to a (year, month, day).
+///
+/// This is Howard Hinnant's `civil_from_days` algorithm.
+pub fn civil_from_days(days: i64) -> (i64, u64, u64) {
+    let z = days + 719468;
+    let era = (if z >= 0 { z } else { z - 146096 }) / 146097;
+    let doe = (z - era * 146097) as u64;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe as i64 + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
+/// Convert a (year, month, day) to a number of days since 1970-01-01.
+///
+/// This is Howard Hinnant's `days_from_civil` algorithm, the inverse of
+/// [`civil_from_days`].
+pub fn days_from_civil(year: i64, month: u64, day: u64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = (if year >= 0 { year } else { year - 399 }) / 400;
+    let yoe = (year - era * 400) as u64;
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+    era * 146097 + doe as i64 - 719468
+}
+
+/// Format a time, in seconds and nanoseconds since the Unix epoch, as an
+/// ISO-8601 string in UTC.
+///
+/// The fractional seconds are only shown as precisely as they need to be.
+/// Returns `None` if the year would be outside of 0001 - 9999.
+pub fn format_iso8601(seconds: i64, nanoseconds: u32) -> Option<String> {
+    if seconds < MINIMUM_SECONDS || seconds > MAXIMUM_SECONDS || nanoseconds >= 1_000_000_000 {
+        return None;
+    }
+
+    let (year, month, day) = civil_from_days(seconds.div_euclid(86400));
+    let time = seconds.rem_euclid(86400);
+
+    let mut result = format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}", year, month, day, time / 3600, (time / 60) % 60, time % 60);
+    if nanoseconds != 0 {
+        result.push_str(&format!(".{:09}", nanoseconds).trim_end_matches('0'));
+    }
+    result.push('Z');
+
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_iso8601() {
+        let tests: Vec<(i64, u32, Option<&str>)> = vec![
+            //        seconds        nanoseconds  expected
+            (                  0,             0,  Some("1970-01-01T00:00:00Z")),
+            (                 -1,             0,  Some("1969-12-31T23:59:59Z")),
+            (         1600000000,     123000000,  Some("2020-09-13T12:26:40.123Z")),
+            (         1600000000,             1,  Some("2020-09-13T12:26:40.000000001Z")),
+            (          951782400,             0,  Some("2000-02-29T00:00:00Z")),
+            (    MINIMUM_SECONDS,             0,  Some("0001-01-01T00:00:00Z")),
+            (    MAXIMUM_SECONDS,     999999999,  Some("9999-12-31T23:59:59.999999999Z")),
+            (MINIMUM_SECONDS - 1,             0,  None),
+            (MAXIMUM_SECONDS + 1,             0,  None),
+        ];
+
+        for (seconds, nanoseconds, expected) in tests {
+            assert_eq!(expected.map(|s| s.to_string()), format_iso8601(seconds, nanoseconds));
+        }
+    }
+
+    #[test]
+    fn test_days_from_civil() {
+        for days in vec![-719468, -1, 0, 1, 11016, 18518, 2932896] {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days, days_from_civil(year, month, day));
+        }
+
+        assert_eq!(3652, days_from_civil(1980, 1, 1));
+    }
+}
@@ -0,0 +1,9 @@
+//! Types that represent a point in time.
+//!
+//! Keeping these types together in this module are a convention, there's no
+//! firm rule.
+
+mod timestamp;
+pub use timestamp::*;
+
+pub mod common;
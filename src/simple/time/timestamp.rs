@@ -0,0 +1,291 @@
+#[cfg(feature = "serialize")]
+use serde::{Serialize, Deserialize};
+
+use simple_error::{bail, SimpleResult};
+use sized_number::{Context, SizedDefinition};
+
+use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset};
+use crate::simple::time::common;
+
+/// Seconds between 1601-01-01 (the Windows epoch) and 1970-01-01.
+const WINDOWS_EPOCH: i128 = 11644473600;
+
+/// Seconds between 1904-01-01 (the HFS+ epoch) and 1970-01-01.
+const HFS_EPOCH: i128 = 2082844800;
+
+/// Seconds between 1970-01-01 and 2001-01-01 (the Cocoa epoch).
+const COCOA_EPOCH: i128 = 978307200;
+
+/// Seconds between 1970-01-01 and 1980-01-06 (the GPS epoch).
+const GPS_EPOCH: i128 = 315964800;
+
+/// The way a [`Timestamp`]'s numeric value is interpreted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum TimestampFormat {
+    /// Seconds since 1970-01-01.
+    UnixSeconds,
+
+    /// Milliseconds since 1970-01-01.
+    UnixMilliseconds,
+
+    /// Microseconds since 1970-01-01.
+    UnixMicroseconds,
+
+    /// Nanoseconds since 1970-01-01.
+    UnixNanoseconds,
+
+    /// Windows `FILETIME` - 100ns intervals since 1601-01-01.
+    FILETIME,
+
+    /// A packed DOS (FAT, ZIP, etc) time and date. The time is the low 16
+    /// bits and the date is the high 16 bits, so a little-endian 32-bit value
+    /// matches how they're stored on disk. Only has a 2-second resolution.
+    DOS,
+
+    /// Seconds since 1904-01-01, as used by HFS+ and classic Mac OS.
+    HFSPlus,
+
+    /// Seconds since 2001-01-01, as used by Apple's Cocoa (`NSDate`).
+    Cocoa,
+
+    /// Weeks since 1980-01-06, as used by GPS. Leap seconds are ignored.
+    GPSWeeks,
+}
+
+/// Defines a timestamp.
+///
+/// A timestamp is a numeric value - defined by a [`SizedDefinition`], just
+/// like [`crate::simple::H2Number`] - that represents a point in time. It's
+/// displayed in ISO-8601 format, in UTC; values that aren't a sensible time
+/// are displayed as invalid, instead of failing.
+///
+/// The raw numeric value is still available through `to_u64` and `to_i64`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct Timestamp {
+    definition: SizedDefinition,
+    format: TimestampFormat,
+}
+
+impl Timestamp {
+    pub fn new_aligned(alignment: Alignment, definition: SizedDefinition, format: TimestampFormat) -> SimpleResult<H2Type> {
+        if !definition.can_be_u64() {
+            bail!("Timestamp definition must be an integer");
+        }
+
+        if format == TimestampFormat::DOS && definition.size() != 4 {
+            bail!("DOS timestamps must be 32 bits");
+        }
+
+        Ok(H2Type::new(alignment, H2Types::Timestamp(Self {
+            definition: definition,
+            format: format,
+        })))
+    }
+
+    pub fn new(definition: SizedDefinition, format: TimestampFormat) -> SimpleResult<H2Type> {
+        Self::new_aligned(Alignment::None, definition, format)
+    }
+
+    fn read_raw(&self, context: Context) -> SimpleResult<i128> {
+        match self.definition {
+            SizedDefinition::I8 | SizedDefinition::I16(_) | SizedDefinition::I32(_) | SizedDefinition::I64(_) => {
+                Ok(self.definition.to_i64(context)? as i128)
+            },
+            _ => Ok(self.definition.to_u64(context)? as i128),
+        }
+    }
+
+    /// Convert a DOS date and time to Unix seconds, if they're valid.
+    fn dos_to_seconds(raw: i128) -> Option<i128> {
+        let time = (raw & 0xffff) as u64;
+        let date = ((raw >> 16) & 0xffff) as u64;
+
+        let second = (time & 0x1f) * 2;
+        let minute = (time >> 5) & 0x3f;
+        let hour   = time >> 11;
+
+        let day   = date & 0x1f;
+        let month = (date >> 5) & 0x0f;
+        let year  = 1980 + (date >> 9) as i64;
+
+        if second > 59 || minute > 59 || hour > 23 || day < 1 || day > 31 || month < 1 || month > 12 {
+            return None;
+        }
+
+        let days = common::days_from_civil(year, month, day);
+
+        // Make sure the day actually exists in that month (eg, Feb 30)
+        if common::civil_from_days(days) != (year, month, day) {
+            return None;
+        }
+
+        Some((days as i128 * 86400) + (hour * 3600 + minute * 60 + second) as i128)
+    }
+
+    /// Convert the raw value to seconds and nanoseconds since the Unix epoch.
+    fn to_unix(&self, raw: i128) -> Option<(i128, i128)> {
+        match self.format {
+            TimestampFormat::UnixSeconds      => Some((raw, 0)),
+            TimestampFormat::UnixMilliseconds => Some((raw.div_euclid(1_000),         raw.rem_euclid(1_000) * 1_000_000)),
+            TimestampFormat::UnixMicroseconds => Some((raw.div_euclid(1_000_000),     raw.rem_euclid(1_000_000) * 1_000)),
+            TimestampFormat::UnixNanoseconds  => Some((raw.div_euclid(1_000_000_000), raw.rem_euclid(1_000_000_000))),
+            TimestampFormat::FILETIME         => Some((raw.div_euclid(10_000_000) - WINDOWS_EPOCH, raw.rem_euclid(10_000_000) * 100)),
+            TimestampFormat::DOS              => Self::dos_to_seconds(raw).map(|s| (s, 0)),
+            TimestampFormat::HFSPlus          => Some((raw - HFS_EPOCH, 0)),
+            TimestampFormat::Cocoa            => Some((raw + COCOA_EPOCH, 0)),
+            TimestampFormat::GPSWeeks         => Some((GPS_EPOCH + (raw * 604800), 0)),
+        }
+    }
+}
+
+impl H2TypeTrait for Timestamp {
+    fn is_static(&self) -> bool {
+        true
+    }
+
+    fn actual_size(&self, _offset: Offset) -> SimpleResult<u64> {
+        Ok(self.definition.size())
+    }
+
+    fn to_display(&self, offset: Offset) -> SimpleResult<String> {
+        match offset {
+            Offset::Static(_) => Ok("Timestamp".to_string()),
+            Offset::Dynamic(context) | Offset::Bounded(context, ..) | Offset::Windowed(context, ..) => {
+                let raw = self.read_raw(context)?;
+
+                let formatted = self.to_unix(raw).and_then(|(seconds, nanoseconds)| {
+                    if seconds < i64::MIN as i128 || seconds > i64::MAX as i128 {
+                        return None;
+                    }
+
+                    common::format_iso8601(seconds as i64, nanoseconds as u32)
+                });
+
+                match formatted {
+                    Some(s) => Ok(s),
+                    None    => Ok(format!("Invalid timestamp ({})", raw)),
+                }
+            }
+        }
+    }
+
+    fn can_be_u64(&self) -> bool {
+        self.definition.can_be_u64()
+    }
+
+    fn to_u64(&self, offset: Offset) -> SimpleResult<u64> {
+        Ok(self.definition.to_u64(offset.get_dynamic()?)?)
+    }
+
+    fn can_be_i64(&self) -> bool {
+        self.definition.can_be_u64()
+    }
+
+    fn to_i64(&self, offset: Offset) -> SimpleResult<i64> {
+        Ok(self.definition.to_i64(offset.get_dynamic()?)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+    use sized_number::{Context, Endian};
+
+    #[test]
+    fn test_unix() -> SimpleResult<()> {
+        //           --- u32 BE ---  ------------ u64 LE ------------
+        let data = b"\x5f\x5e\x10\x00\x7b\x80\x6e\x87\x74\x01\x00\x00".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = Timestamp::new(SizedDefinition::U32(Endian::Big), TimestampFormat::UnixSeconds)?;
+        assert_eq!(4, t.actual_size(offset)?);
+        assert_eq!("2020-09-13T12:26:40Z", t.to_display(offset)?);
+        assert_eq!(1600000000, t.to_u64(offset)?);
+        assert_eq!(1600000000, t.to_i64(offset)?);
+
+        let t = Timestamp::new(SizedDefinition::U64(Endian::Little), TimestampFormat::UnixMilliseconds)?;
+        assert_eq!("2020-09-13T12:26:40.123Z", t.to_display(offset.at(4))?);
+        assert_eq!(1600000000123, t.to_u64(offset.at(4))?);
+
+        let t = Timestamp::new(SizedDefinition::U64(Endian::Little), TimestampFormat::UnixMicroseconds)?;
+        assert_eq!("1970-01-19T12:26:40.000123Z", t.to_display(offset.at(4))?);
+
+        let t = Timestamp::new(SizedDefinition::U64(Endian::Little), TimestampFormat::UnixNanoseconds)?;
+        assert_eq!("1970-01-01T00:26:40.000000123Z", t.to_display(offset.at(4))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_negative_unix() -> SimpleResult<()> {
+        let data = b"\xff\xff\xff\xff".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = Timestamp::new(SizedDefinition::I32(Endian::Big), TimestampFormat::UnixSeconds)?;
+        assert_eq!("1969-12-31T23:59:59Z", t.to_display(offset)?);
+
+        let t = Timestamp::new(SizedDefinition::U32(Endian::Big), TimestampFormat::UnixSeconds)?;
+        assert_eq!("2106-02-07T06:28:15Z", t.to_display(offset)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_other_epochs() -> SimpleResult<()> {
+        //           ---------- FILETIME ------------  --- HFS+ ---  --- Cocoa ---  GPS
+        let data = b"\x00\x80\xa6\x21\xc9\x89\xd6\x01\xdb\x83\xc0\x80\x25\x0e\x47\x80\x4b\x08".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = Timestamp::new(SizedDefinition::U64(Endian::Little), TimestampFormat::FILETIME)?;
+        assert_eq!("2020-09-13T12:26:40Z", t.to_display(offset)?);
+
+        let t = Timestamp::new(SizedDefinition::U32(Endian::Big), TimestampFormat::HFSPlus)?;
+        assert_eq!("2020-09-13T12:26:40Z", t.to_display(offset.at(8))?);
+
+        let t = Timestamp::new(SizedDefinition::U32(Endian::Big), TimestampFormat::Cocoa)?;
+        assert_eq!("2020-09-13T12:26:40Z", t.to_display(offset.at(12))?);
+
+        let t = Timestamp::new(SizedDefinition::U16(Endian::Little), TimestampFormat::GPSWeeks)?;
+        assert_eq!("2020-09-13T00:00:00Z", t.to_display(offset.at(16))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dos() -> SimpleResult<()> {
+        //           -- valid ----  -- month 13 --  --- Feb 30 ---
+        let data = b"\x54\x63\x2d\x51\x54\x63\xad\x51\x54\x63\x5e\x50".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = Timestamp::new(SizedDefinition::U32(Endian::Little), TimestampFormat::DOS)?;
+        assert_eq!("2020-09-13T12:26:40Z", t.to_display(offset)?);
+        assert_eq!("Invalid timestamp (1370317652)", t.to_display(offset.at(4))?);
+        assert!(t.to_display(offset.at(8))?.starts_with("Invalid timestamp"));
+
+        assert!(Timestamp::new(SizedDefinition::U16(Endian::Little), TimestampFormat::DOS).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_out_of_range() -> SimpleResult<()> {
+        let data = b"\x7f\xff\xff\xff\xff\xff\xff\xff".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = Timestamp::new(SizedDefinition::I64(Endian::Big), TimestampFormat::UnixSeconds)?;
+        assert_eq!("Invalid timestamp (9223372036854775807)", t.to_display(offset)?);
+
+        // The raw value is still available
+        assert_eq!(9223372036854775807, t.to_i64(offset)?);
+
+        // And it still resolves
+        let r = t.resolve(offset, None)?;
+        assert_eq!("Invalid timestamp (9223372036854775807)", r.display);
+        assert_eq!(Some(9223372036854775807), r.as_i64);
+
+        Ok(())
+    }
+}
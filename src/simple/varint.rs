@@ -0,0 +1,415 @@
+#[cfg(feature = "serialize")]
+use serde::{Serialize, Deserialize};
+
+use simple_error::{bail, SimpleResult};
+use sized_number::{Context, Endian};
+
+use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset};
+
+/// The various ways a variable-length integer can be encoded.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum VarintFormat {
+    /// Unsigned LEB128: 7 bits per byte, least significant first, with the
+    /// high bit set on every byte but the last. This is also protobuf's
+    /// `varint`, and is used by WASM and DWARF.
+    LEB128,
+
+    /// Signed LEB128: the same as [`VarintFormat::LEB128`], but the value is
+    /// sign extended from the last bit read.
+    SignedLEB128,
+
+    /// Protobuf's `sint32`/`sint64`: an unsigned LEB128 that's zigzag
+    /// encoded (`0, -1, 1, -2, ...`).
+    ZigZag,
+
+    /// A big-endian VLQ: 7 bits per byte, most significant first, with the
+    /// high bit set on every byte but the last. This is used by MIDI.
+    VLQ,
+
+    /// Git's packfile offset encoding: a big-endian VLQ, except that one is
+    /// added before each shift so there's only one encoding for each value.
+    GitVLQ,
+
+    /// Bitcoin's `CompactSize`: a byte below `0xfd` is the value, otherwise
+    /// `0xfd`, `0xfe`, and `0xff` are followed by a little-endian u16, u32,
+    /// or u64.
+    CompactSize,
+
+    /// QUIC's varint: the top two bits of the first byte are the length (1,
+    /// 2, 4, or 8 bytes), and the rest is a big-endian value.
+    QUIC,
+}
+
+/// Defines a variable-length integer.
+///
+/// Like a [`crate::simple::character::UTF8`] character, the size of a varint
+/// depends on the data, so it needs a [`Offset::Dynamic`] to do anything
+/// useful. Values can be used anywhere a number can, such as the length of a
+/// [`crate::composite::string::LPString`].
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct Varint {
+    format: VarintFormat,
+}
+
+impl Varint {
+    pub fn new_aligned(alignment: Alignment, format: VarintFormat) -> H2Type {
+        H2Type::new(alignment, H2Types::Varint(Self {
+            format: format,
+        }))
+    }
+
+    pub fn new(format: VarintFormat) -> H2Type {
+        Self::new_aligned(Alignment::None, format)
+    }
+
+    /// Read a little-endian base-128 value, returning the size, the value,
+    /// and the last byte read (which the signed version needs).
+    ///
+    /// If the value is `signed`, the 10th byte has to be all sign bits.
+    fn read_leb128(context: Context, signed: bool) -> SimpleResult<(u64, u64, u8)> {
+        let mut value: u64 = 0;
+
+        // A u64 fits in 10 bytes
+        for i in 0..10 {
+            let b = context.at(context.position() + i).read_u8()?;
+            let bits = (b & 0x7f) as u64;
+            let shift = i * 7;
+
+            // The 10th byte only has room for a single bit - or, for a signed
+            // value, the sign extended across the whole byte (a lone 1 would
+            // be +2**63, which doesn't fit)
+            let too_large = match signed {
+                true  => bits != 0 && bits != 0x7f,
+                false => bits > 1,
+            };
+
+            if i == 9 && too_large {
+                bail!("Varint is too large for 64 bits");
+            }
+
+            value = value | (bits << shift);
+
+            if b & 0x80 == 0 {
+                return Ok((i + 1, value, b));
+            }
+        }
+
+        bail!("Varint is too long");
+    }
+
+    /// Read a big-endian base-128 value, optionally adding one before each
+    /// shift (the way Git does).
+    fn read_vlq(context: Context, add_one: bool) -> SimpleResult<(u64, u64)> {
+        let mut value: u64 = 0;
+
+        for i in 0..10 {
+            let b = context.at(context.position() + i).read_u8()?;
+
+            if i > 0 {
+                if add_one {
+                    value = match value.checked_add(1) {
+                        Some(v) => v,
+                        None    => bail!("Varint is too large for 64 bits"),
+                    };
+                }
+
+                if value.leading_zeros() < 7 {
+                    bail!("Varint is too large for 64 bits");
+                }
+            }
+
+            value = (value << 7) | (b & 0x7f) as u64;
+
+            if b & 0x80 == 0 {
+                return Ok((i + 1, value));
+            }
+        }
+
+        bail!("Varint is too long");
+    }
+
+    fn read_compact_size(context: Context) -> SimpleResult<(u64, u64)> {
+        let data = context.at(context.position() + 1);
+
+        match context.read_u8()? {
+            0xfd => Ok((3, data.read_u16(Endian::Little)? as u64)),
+            0xfe => Ok((5, data.read_u32(Endian::Little)? as u64)),
+            0xff => Ok((9, data.read_u64(Endian::Little)?)),
+            b    => Ok((1, b as u64)),
+        }
+    }
+
+    fn read_quic(context: Context) -> SimpleResult<(u64, u64)> {
+        let first = context.read_u8()?;
+
+        match first >> 6 {
+            0 => Ok((1, (first & 0x3f) as u64)),
+            1 => Ok((2, context.read_u16(Endian::Big)? as u64 & 0x3fff)),
+            2 => Ok((4, context.read_u32(Endian::Big)? as u64 & 0x3fff_ffff)),
+            _ => Ok((8, context.read_u64(Endian::Big)? & 0x3fff_ffff_ffff_ffff)),
+        }
+    }
+
+    /// Read the value, returning its size in bytes and the value. The value is
+    /// an [`i128`] so it can hold any [`u64`] or [`i64`].
+    fn read(&self, offset: Offset) -> SimpleResult<(u64, i128)> {
+        let context = offset.get_dynamic()?;
+
+        match self.format {
+            VarintFormat::LEB128 => {
+                let (size, value, _) = Self::read_leb128(context, false)?;
+
+                Ok((size, value as i128))
+            },
+            VarintFormat::SignedLEB128 => {
+                let (size, value, last) = Self::read_leb128(context, true)?;
+                let shift = size * 7;
+
+                // Sign extend if the sign bit (bit 6 of the last byte) is set
+                let value = match shift < 64 && (last & 0x40) != 0 {
+                    true  => (value | (!0u64 << shift)) as i64,
+                    false => value as i64,
+                };
+
+                Ok((size, value as i128))
+            },
+            VarintFormat::ZigZag => {
+                let (size, value, _) = Self::read_leb128(context, false)?;
+
+                Ok((size, ((value >> 1) as i64 ^ -((value & 1) as i64)) as i128))
+            },
+            VarintFormat::VLQ => {
+                let (size, value) = Self::read_vlq(context, false)?;
+
+                Ok((size, value as i128))
+            },
+            VarintFormat::GitVLQ => {
+                let (size, value) = Self::read_vlq(context, true)?;
+
+                Ok((size, value as i128))
+            },
+            VarintFormat::CompactSize => {
+                let (size, value) = Self::read_compact_size(context)?;
+
+                Ok((size, value as i128))
+            },
+            VarintFormat::QUIC => {
+                let (size, value) = Self::read_quic(context)?;
+
+                Ok((size, value as i128))
+            },
+        }
+    }
+}
+
+impl H2TypeTrait for Varint {
+    fn is_static(&self) -> bool {
+        false
+    }
+
+    fn actual_size(&self, offset: Offset) -> SimpleResult<u64> {
+        Ok(self.read(offset)?.0)
+    }
+
+    fn to_display(&self, offset: Offset) -> SimpleResult<String> {
+        match offset {
+            Offset::Static(_) => Ok("Varint".to_string()),
+            Offset::Dynamic(_) | Offset::Bounded(..) | Offset::Windowed(..) => Ok(self.read(offset)?.1.to_string()),
+        }
+    }
+
+    fn can_be_u64(&self) -> bool {
+        true
+    }
+
+    fn to_u64(&self, offset: Offset) -> SimpleResult<u64> {
+        let value = self.read(offset)?.1;
+
+        if value < 0 || value > u64::MAX as i128 {
+            bail!("Varint value {} doesn't fit in a u64", value);
+        }
+
+        Ok(value as u64)
+    }
+
+    fn can_be_i64(&self) -> bool {
+        true
+    }
+
+    fn to_i64(&self, offset: Offset) -> SimpleResult<i64> {
+        let value = self.read(offset)?.1;
+
+        if value < i64::MIN as i128 || value > i64::MAX as i128 {
+            bail!("Varint value {} doesn't fit in an i64", value);
+        }
+
+        Ok(value as i64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+    use sized_number::Context;
+
+    use crate::composite::string::LPString;
+    use crate::simple::character::{ASCII, StrictASCII};
+
+    #[test]
+    fn test_leb128() -> SimpleResult<()> {
+        let tests: Vec<(&[u8], u64, &str)> = vec![
+            (&b"\x00"[..],                                      1, "0"),
+            (&b"\x7f"[..],                                      1, "127"),
+            (&b"\x80\x01"[..],                                  2, "128"),
+            (&b"\xe5\x8e\x26"[..],                              3, "624485"),
+            (&b"\xff\xff\xff\xff\xff\xff\xff\xff\xff\x01"[..], 10, "18446744073709551615"),
+        ];
+
+        for (data, size, expected) in tests {
+            let data = data.to_vec();
+            let offset = Offset::Dynamic(Context::new(&data));
+            let t = Varint::new(VarintFormat::LEB128);
+
+            assert_eq!(size, t.actual_size(offset)?);
+            assert_eq!(expected, t.to_display(offset)?);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_leb128_errors() -> SimpleResult<()> {
+        // Too big, too long, and truncated
+        for data in vec![b"\xff\xff\xff\xff\xff\xff\xff\xff\xff\x02".to_vec(), b"\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x00".to_vec(), b"\x80".to_vec()] {
+            let offset = Offset::Dynamic(Context::new(&data));
+
+            assert!(Varint::new(VarintFormat::LEB128).to_u64(offset).is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_signed() -> SimpleResult<()> {
+        let tests: Vec<(&[u8], VarintFormat, u64, i64)> = vec![
+            (&b"\x02"[..],         VarintFormat::SignedLEB128,   1,         2),
+            (&b"\x7e"[..],         VarintFormat::SignedLEB128,   1,        -2),
+            (&b"\xff\x00"[..],     VarintFormat::SignedLEB128,   2,       127),
+            (&b"\x81\x7f"[..],     VarintFormat::SignedLEB128,   2,      -127),
+            (&b"\xc0\xbb\x78"[..], VarintFormat::SignedLEB128,   3,   -123456),
+            (&b"\x00"[..],         VarintFormat::ZigZag,         1,         0),
+            (&b"\x01"[..],         VarintFormat::ZigZag,         1,        -1),
+            (&b"\x02"[..],         VarintFormat::ZigZag,         1,         1),
+            (&b"\x03"[..],         VarintFormat::ZigZag,         1,        -2),
+            (&b"\xff\x01"[..],     VarintFormat::ZigZag,         2,      -128),
+        ];
+
+        for (data, format, size, expected) in tests {
+            let data = data.to_vec();
+            let offset = Offset::Dynamic(Context::new(&data));
+            let t = Varint::new(format);
+
+            assert_eq!(size, t.actual_size(offset)?);
+            assert_eq!(expected, t.to_i64(offset)?);
+            assert_eq!(expected.to_string(), t.to_display(offset)?);
+        }
+
+        // Negative values can't be a u64
+        let data = b"\x7e".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+        assert!(Varint::new(VarintFormat::SignedLEB128).to_u64(offset).is_err());
+
+        // The extremes take all 10 bytes
+        let tests: Vec<(&[u8], i64)> = vec![
+            (&b"\x80\x80\x80\x80\x80\x80\x80\x80\x80\x7f"[..], i64::MIN),
+            (&b"\xff\xff\xff\xff\xff\xff\xff\xff\xff\x00"[..], i64::MAX),
+        ];
+
+        for (data, expected) in tests {
+            let data = data.to_vec();
+            let offset = Offset::Dynamic(Context::new(&data));
+            let t = Varint::new(VarintFormat::SignedLEB128);
+
+            assert_eq!(10, t.actual_size(offset)?);
+            assert_eq!(expected, t.to_i64(offset)?);
+        }
+
+        // Anything else in the 10th byte is too big, including the single bit
+        // that's fine when unsigned
+        for data in vec![b"\x80\x80\x80\x80\x80\x80\x80\x80\x80\x3f".to_vec(), b"\x80\x80\x80\x80\x80\x80\x80\x80\x80\x01".to_vec()] {
+            let offset = Offset::Dynamic(Context::new(&data));
+            assert!(Varint::new(VarintFormat::SignedLEB128).to_i64(offset).is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_big_endian() -> SimpleResult<()> {
+        let tests: Vec<(&[u8], VarintFormat, u64, u64)> = vec![
+            (&b"\x00"[..],             VarintFormat::VLQ,       1,         0),
+            (&b"\x7f"[..],             VarintFormat::VLQ,       1,       127),
+            (&b"\x81\x00"[..],         VarintFormat::VLQ,       2,       128),
+            (&b"\xff\xff\xff\x7f"[..], VarintFormat::VLQ,       4, 0x0fffffff),
+            (&b"\x7f"[..],             VarintFormat::GitVLQ,    1,       127),
+            (&b"\x80\x00"[..],         VarintFormat::GitVLQ,    2,       128),
+            (&b"\x80\x80\x00"[..],     VarintFormat::GitVLQ,    3,     16512),
+        ];
+
+        for (data, format, size, expected) in tests {
+            let data = data.to_vec();
+            let offset = Offset::Dynamic(Context::new(&data));
+            let t = Varint::new(format);
+
+            assert_eq!(size, t.actual_size(offset)?);
+            assert_eq!(expected, t.to_u64(offset)?);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_prefixed() -> SimpleResult<()> {
+        let tests: Vec<(&[u8], VarintFormat, u64, u64)> = vec![
+            (&b"\xfc"[..],                                 VarintFormat::CompactSize,   1,        252),
+            (&b"\xfd\xfd\x00"[..],                         VarintFormat::CompactSize,   3,        253),
+            (&b"\xfe\x00\x00\x01\x00"[..],                 VarintFormat::CompactSize,   5,      65536),
+            (&b"\xff\x00\x00\x00\x00\x01\x00\x00\x00"[..], VarintFormat::CompactSize,   9, 0x100000000),
+            (&b"\x25"[..],                                 VarintFormat::QUIC,          1,         37),
+            (&b"\x7b\xbd"[..],                             VarintFormat::QUIC,          2,      15293),
+            (&b"\x9d\x7f\x3e\x7d"[..],                     VarintFormat::QUIC,          4,  494878333),
+            (&b"\xc2\x19\x7c\x5e\xff\x14\xe8\x8c"[..],     VarintFormat::QUIC,          8, 151288809941952652),
+        ];
+
+        for (data, format, size, expected) in tests {
+            let data = data.to_vec();
+            let offset = Offset::Dynamic(Context::new(&data));
+            let t = Varint::new(format);
+
+            assert_eq!(size, t.actual_size(offset)?);
+            assert_eq!(expected, t.to_u64(offset)?);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_varint_lpstring() -> SimpleResult<()> {
+        // A protobuf-style string: a varint length, then the data
+        let data = b"\x05hello".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = LPString::new(
+            Varint::new(VarintFormat::LEB128),
+            ASCII::new(StrictASCII::Strict),
+        )?;
+
+        assert_eq!(6, t.actual_size(offset)?);
+        assert_eq!("\"hello\"", t.to_display(offset)?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,779 @@
+use memmap2::Mmap;
+use simple_error::{bail, try_with, SimpleError, SimpleResult};
+use sized_number::Context;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::{H2Type, Offset, ResolvedType};
+
+/// The size of the first [`Window`] that [`resolve_from`] tries, for types
+/// whose size isn't known ahead of time.
+const INITIAL_WINDOW_SIZE: u64 = 4096;
+
+/// Somewhere to read data from, without necessarily having all of it in
+/// memory - a memory-mapped file ([`MmapSource`]), a shared buffer
+/// (`Arc<[u8]>`), or a file or other stream ([`SeekSource`]).
+///
+/// Types are read with a [`sized_number::Context`], which borrows a buffer,
+/// so they can't read from a [`DataSource`] directly. Instead, the part of
+/// the data that's needed is read into a [`Window`], and the type is resolved
+/// against that. [`resolve_from`] takes care of picking the window.
+///
+/// Only resolving goes through a source. To display a value, get its size,
+/// or look at its children or related types, resolve it with
+/// [`resolve_from`] and use the [`ResolvedType`], or use the [`Offset`] from
+/// a [`Window`] that's big enough.
+pub trait DataSource {
+    /// The total size of the data.
+    fn len(&self) -> u64;
+
+    /// Read a range of the data, which has to be inside of it.
+    fn read(&self, range: Range<u64>) -> SimpleResult<Vec<u8>>;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// How far the data can be read without a break, starting at
+    /// `position`. For most sources that's just the end, but a
+    /// [`SegmentedSource`] can have gaps.
+    fn contiguous_end(&self, _position: u64) -> u64 {
+        self.len()
+    }
+}
+
+/// Make sure a range is inside data that's `length` bytes long.
+fn check_range(range: &Range<u64>, length: u64) -> SimpleResult<()> {
+    if range.start > range.end || range.end > length {
+        bail!("Range {}..{} is outside of the data (0..{})", range.start, range.end, length);
+    }
+
+    Ok(())
+}
+
+impl DataSource for Arc<[u8]> {
+    fn len(&self) -> u64 {
+        self.as_ref().len() as u64
+    }
+
+    fn read(&self, range: Range<u64>) -> SimpleResult<Vec<u8>> {
+        check_range(&range, DataSource::len(self))?;
+
+        Ok(self[(range.start as usize)..(range.end as usize)].to_vec())
+    }
+}
+
+/// A memory-mapped file.
+///
+/// The operating system pages the file in as it's read, so only the parts
+/// that are actually used take up memory.
+pub struct MmapSource {
+    map: Mmap,
+}
+
+impl MmapSource {
+    pub fn open(path: &Path) -> SimpleResult<Self> {
+        let file = try_with!(File::open(path), "Couldn't open {}", path.display());
+
+        // This is only safe if nothing else changes the file while it's
+        // mapped, which we have no way to guarantee - the same caveat as any
+        // other tool that maps files
+        let map = try_with!(unsafe { Mmap::map(&file) }, "Couldn't map {}", path.display());
+
+        Ok(Self {
+            map: map,
+        })
+    }
+}
+
+impl DataSource for MmapSource {
+    fn len(&self) -> u64 {
+        self.map.len() as u64
+    }
+
+    fn read(&self, range: Range<u64>) -> SimpleResult<Vec<u8>> {
+        check_range(&range, DataSource::len(self))?;
+
+        Ok(self.map[(range.start as usize)..(range.end as usize)].to_vec())
+    }
+}
+
+/// The pages that a [`SeekSource`] has read, and the order they were read
+/// in (so the oldest can be dropped).
+struct PageCache<R> {
+    reader: R,
+    pages: HashMap<u64, Arc<Vec<u8>>>,
+    order: VecDeque<u64>,
+}
+
+/// A [`Read`] + [`Seek`], such as a [`File`] or a device, with a cache of
+/// recently-read pages.
+///
+/// When the cache is full, the page that was read the longest ago is
+/// dropped.
+pub struct SeekSource<R: Read + Seek> {
+    cache: Mutex<PageCache<R>>,
+    length: u64,
+    page_size: u64,
+    max_pages: usize,
+}
+
+impl<R: Read + Seek> SeekSource<R> {
+    /// Create a source with the given page size and number of pages to keep.
+    pub fn with_cache(mut reader: R, page_size: u64, max_pages: usize) -> SimpleResult<Self> {
+        if page_size == 0 || max_pages == 0 {
+            bail!("The page cache can't be empty");
+        }
+
+        let length = try_with!(reader.seek(SeekFrom::End(0)), "Couldn't find the length of the data");
+
+        Ok(Self {
+            cache: Mutex::new(PageCache {
+                reader: reader,
+                pages: HashMap::new(),
+                order: VecDeque::new(),
+            }),
+            length: length,
+            page_size: page_size,
+            max_pages: max_pages,
+        })
+    }
+
+    /// Create a source with a 16 MiB cache, in 64 KiB pages.
+    pub fn new(reader: R) -> SimpleResult<Self> {
+        Self::with_cache(reader, 64 * 1024, 256)
+    }
+
+    /// Get a page, reading it if it isn't cached.
+    fn page(&self, cache: &mut PageCache<R>, index: u64) -> SimpleResult<Arc<Vec<u8>>> {
+        if let Some(page) = cache.pages.get(&index) {
+            return Ok(page.clone());
+        }
+
+        let start = index * self.page_size;
+        let end = std::cmp::min(start + self.page_size, self.length);
+
+        let mut page = vec![0; (end - start) as usize];
+        try_with!(cache.reader.seek(SeekFrom::Start(start)), "Couldn't seek to {}", start);
+        try_with!(cache.reader.read_exact(&mut page), "Couldn't read {}..{}", start, end);
+
+        let page = Arc::new(page);
+        cache.pages.insert(index, page.clone());
+        cache.order.push_back(index);
+
+        if cache.order.len() > self.max_pages {
+            if let Some(oldest) = cache.order.pop_front() {
+                cache.pages.remove(&oldest);
+            }
+        }
+
+        Ok(page)
+    }
+}
+
+impl<R: Read + Seek> DataSource for SeekSource<R> {
+    fn len(&self) -> u64 {
+        self.length
+    }
+
+    fn read(&self, range: Range<u64>) -> SimpleResult<Vec<u8>> {
+        check_range(&range, self.length)?;
+
+        let mut cache = match self.cache.lock() {
+            Ok(cache) => cache,
+            Err(_)    => bail!("The page cache is poisoned"),
+        };
+
+        let mut result = Vec::with_capacity((range.end - range.start) as usize);
+        let mut position = range.start;
+        while position < range.end {
+            let index = position / self.page_size;
+            let page = self.page(&mut cache, index)?;
+
+            let start = (position - index * self.page_size) as usize;
+            let end = std::cmp::min(page.len(), start + (range.end - position) as usize);
+
+            result.extend_from_slice(&page[start..end]);
+            position += (end - start) as u64;
+        }
+
+        Ok(result)
+    }
+}
+
+/// A segment of a [`SegmentedSource`]: part of another source, at an
+/// address.
+struct Segment {
+    length: u64,
+    source: Arc<dyn DataSource>,
+    source_start: u64,
+}
+
+/// An address space with gaps, such as a process memory dump: a set of
+/// segments at arbitrary 64-bit addresses, each backed by part of another
+/// [`DataSource`] (usually the same file).
+///
+/// Positions are addresses, so types resolved with [`resolve_from`] have
+/// addresses for their ranges, and pointers can be followed by resolving
+/// their related targets the same way. Reading an address that isn't in a
+/// segment fails with an error that starts with `Unmapped address` (see
+/// [`is_unmapped`]).
+pub struct SegmentedSource {
+    segments: BTreeMap<u64, Segment>,
+}
+
+impl SegmentedSource {
+    pub fn new() -> Self {
+        Self {
+            segments: BTreeMap::new(),
+        }
+    }
+
+    /// Map the given range of `source` at `address`. Segments can't overlap,
+    /// but they can be right next to each other.
+    pub fn add_segment(&mut self, address: u64, source: Arc<dyn DataSource>, range: Range<u64>) -> SimpleResult<()> {
+        check_range(&range, source.len())?;
+
+        let length = range.end - range.start;
+        if length == 0 {
+            bail!("Segments can't be empty");
+        }
+
+        let end = match address.checked_add(length) {
+            Some(end) => end,
+            None      => bail!("Segment at 0x{:x} runs past the end of the address space", address),
+        };
+
+        // Check the segments on either side
+        if let Some((&start, segment)) = self.segments.range(..end).next_back() {
+            if start + segment.length > address {
+                bail!("Segment at 0x{:x} overlaps the segment at 0x{:x}", address, start);
+            }
+        }
+
+        self.segments.insert(address, Segment {
+            length: length,
+            source: source,
+            source_start: range.start,
+        });
+
+        Ok(())
+    }
+
+    /// The address ranges of the segments, in order.
+    pub fn segments(&self) -> Vec<Range<u64>> {
+        self.segments.iter().map(|(&start, segment)| start..(start + segment.length)).collect()
+    }
+
+    /// Find the segment that contains the address (or ends right at it).
+    fn find(&self, address: u64) -> Option<(u64, &Segment)> {
+        match self.segments.range(..=address).next_back() {
+            Some((&start, segment)) if address <= start + segment.length => Some((start, segment)),
+            _ => None,
+        }
+    }
+
+    /// Is the whole range mapped?
+    pub fn is_mapped(&self, range: Range<u64>) -> bool {
+        range.start <= range.end && self.contiguous_end(range.start) >= range.end && self.find(range.start).is_some()
+    }
+}
+
+impl Default for SegmentedSource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DataSource for SegmentedSource {
+    /// The end of the last segment.
+    fn len(&self) -> u64 {
+        match self.segments.iter().next_back() {
+            Some((&start, segment)) => start + segment.length,
+            None                    => 0,
+        }
+    }
+
+    fn read(&self, range: Range<u64>) -> SimpleResult<Vec<u8>> {
+        if range.start > range.end {
+            bail!("Invalid range: 0x{:x}..0x{:x}", range.start, range.end);
+        }
+
+        let mut result = Vec::with_capacity((range.end - range.start) as usize);
+        let mut address = range.start;
+
+        while address < range.end {
+            let (start, segment) = match self.find(address) {
+                Some((start, segment)) if address < start + segment.length => (start, segment),
+                _ => bail!("Unmapped address: 0x{:x}", address),
+            };
+
+            let end = std::cmp::min(range.end, start + segment.length);
+            let source_start = segment.source_start + (address - start);
+            result.extend(segment.source.read(source_start..(source_start + end - address))?);
+            address = end;
+        }
+
+        Ok(result)
+    }
+
+    fn contiguous_end(&self, position: u64) -> u64 {
+        let mut end = position;
+
+        while let Some((start, segment)) = self.find(end) {
+            if start + segment.length == end {
+                // This segment ends here; see if another starts right here
+                match self.segments.get(&end) {
+                    Some(next) => end += next.length,
+                    None       => break,
+                }
+            } else {
+                end = start + segment.length;
+            }
+        }
+
+        end
+    }
+}
+
+/// Did reading fail because an address wasn't mapped (see
+/// [`SegmentedSource`])?
+pub fn is_unmapped(error: &SimpleError) -> bool {
+    error.as_str().starts_with("Unmapped address")
+}
+
+/// Part of a [`DataSource`], read into memory so types can be resolved
+/// against it.
+///
+/// Inside the window, positions are relative to the start of the window;
+/// [`Window::resolve`] converts them back, so the [`ResolvedType`] has
+/// positions in the full data. Anything that reads outside the window - a
+/// checksum over earlier data, or a type that's just too big - fails, and
+/// [`crate::simple::BytesLength::Remaining`] stops at the end of the window.
+///
+/// Pointers hold positions in the full data, so they're converted with
+/// [`Offset::dereference`]. A pointer whose target is in the window displays
+/// it, and one whose target is outside shows it as invalid; either way, its
+/// related targets are positions in the full data, and can be resolved with
+/// [`resolve_from`].
+pub struct Window {
+    base: u64,
+    data: Vec<u8>,
+}
+
+impl Window {
+    /// Read a window from the source. The end of the range is cut off at the
+    /// end of the data (or the start of a gap, in a [`SegmentedSource`]).
+    pub fn new(source: &dyn DataSource, range: Range<u64>) -> SimpleResult<Self> {
+        let end = std::cmp::min(range.end, source.contiguous_end(range.start));
+
+        Ok(Self {
+            base: range.start,
+            data: source.read(range.start..end)?,
+        })
+    }
+
+    /// The range of the data that the window covers.
+    pub fn range(&self) -> Range<u64> {
+        self.base..(self.base + self.data.len() as u64)
+    }
+
+    /// The window's data, as a [`Context`]. Positions are relative to the
+    /// start of the window.
+    pub fn context(&self) -> Context {
+        Context::new(&self.data)
+    }
+
+    /// An [`Offset::Windowed`] for the given position in the full data.
+    /// Like the [`Context`], it's relative to the start of the window, and
+    /// it's bounded to the window.
+    pub fn offset(&self, position: u64) -> SimpleResult<Offset> {
+        if position < self.base || position > self.base + self.data.len() as u64 {
+            bail!("Position {} is outside of the window", position);
+        }
+
+        Ok(Offset::Windowed(self.context().at(position - self.base), self.base, 0, self.data.len() as u64, 0))
+    }
+
+    /// Does the type at the given position fit inside the window? If it
+    /// doesn't (or its size can't be worked out), a bigger window might help.
+    fn fits(&self, t: &H2Type, position: u64) -> bool {
+        match self.offset(position) {
+            Ok(offset) => t.aligned_size(offset).is_ok(),
+            Err(_)     => false,
+        }
+    }
+
+    /// Resolve a type at the given position in the full data.
+    pub fn resolve(&self, t: &H2Type, position: u64, name: Option<String>) -> SimpleResult<ResolvedType> {
+        let mut resolved = t.resolve(self.offset(position)?, name)?;
+        rebase(&mut resolved, self.base)?;
+
+        Ok(resolved)
+    }
+}
+
+/// Move a [`ResolvedType`] from window positions to positions in the full
+/// data. The children of a transformed type are in the decoded data, so
+/// they stay where they are.
+///
+/// Bit ranges are numbered in bits, so near the top of a 64-bit address
+/// space they can't be moved without overflowing; that's an error.
+fn rebase(resolved: &mut ResolvedType, base: u64) -> SimpleResult<()> {
+    let add = |n: u64, amount: u64| -> SimpleResult<u64> {
+        match n.checked_add(amount) {
+            Some(n) => Ok(n),
+            None    => bail!("Position is too large to move by {}: {}", amount, n),
+        }
+    };
+
+    resolved.actual_range = add(resolved.actual_range.start, base)?..add(resolved.actual_range.end, base)?;
+    resolved.aligned_range = add(resolved.aligned_range.start, base)?..add(resolved.aligned_range.end, base)?;
+    if let Some(ranges) = resolved.bit_ranges.as_mut() {
+        let bit_base = match base.checked_mul(8) {
+            Some(bit_base) => bit_base,
+            None           => bail!("Position is too large to number its bits: {}", base),
+        };
+
+        for range in ranges.iter_mut() {
+            *range = add(range.start, bit_base)?..add(range.end, bit_base)?;
+        }
+    }
+
+    if resolved.decoded.is_none() {
+        for child in resolved.children.iter_mut() {
+            rebase(child, base)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Resolve a type at the given position in a [`DataSource`], without reading
+/// more of the data than it needs.
+///
+/// If the type is static, exactly its size is read. Otherwise, it's tried
+/// against bigger and bigger [`Window`]s, starting at the position, until it
+/// fits or the window reaches the end of the data (or a gap, which is an
+/// unmapped address). A type that fits in the window but fails to resolve
+/// fails right away. Types that need data from before the position (such as
+/// a DNS name with compression pointers) should be resolved against a
+/// [`Window`] directly.
+///
+/// A type that ends right at the end of the window might have stopped there
+/// because the data did (like [`crate::simple::BytesLength::Remaining`]), so
+/// it's tried again with a bigger window. That way, "the end" is the end of
+/// the data, or the gap.
+pub fn resolve_from(source: &dyn DataSource, t: &H2Type, position: u64, name: Option<String>) -> SimpleResult<ResolvedType> {
+    let mut size = match t.is_static() {
+        true  => t.aligned_size(Offset::Static(position))?,
+        false => INITIAL_WINDOW_SIZE,
+    };
+
+    loop {
+        let window = Window::new(source, position..position.saturating_add(size))?;
+        let end = window.range().end;
+        let can_grow = end < source.contiguous_end(position);
+
+        match window.resolve(t, position, name.clone()) {
+            Ok(resolved) => {
+                if t.is_static() || resolved.aligned_range.end < end || !can_grow {
+                    return Ok(resolved);
+                }
+            },
+            Err(e) => {
+                // If the type fits, the window isn't the problem
+                if window.fits(t, position) {
+                    return Err(e);
+                }
+
+                // If the window can't grow, either the data ran out or the
+                // type ran into a gap
+                if !can_grow {
+                    match end < source.len() {
+                        true  => bail!("Unmapped address: 0x{:x} ({})", end, e),
+                        false => return Err(e),
+                    }
+                }
+            },
+        }
+
+        size = size.saturating_mul(2);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+    use sized_number::{Endian, SizedDefinition, SizedDisplay};
+    use std::io::Cursor;
+
+    use crate::composite::{Constrained, Constraint, ConstraintAction, ConstraintValue, H2Struct};
+    use crate::composite::string::LPString;
+    use crate::simple::{BitNumber, BitOrder, BytesDisplay, BytesLength, H2Bytes, H2Number, H2Pointer};
+    use crate::simple::character::{ASCII, StrictASCII};
+
+    /// 1000 bytes of padding, then a number and a 5000-character string.
+    fn data() -> Vec<u8> {
+        let mut data = vec![0xff; 1000];
+        data.extend_from_slice(b"\x2a\x13\x88");
+        data.extend_from_slice(&vec![b'A'; 5000]);
+
+        data
+    }
+
+    fn record() -> SimpleResult<H2Type> {
+        H2Struct::new(vec![
+            ("number".to_string(), H2Number::new(SizedDefinition::U8, SizedDisplay::Decimal)),
+            ("string".to_string(), LPString::new(H2Number::new(SizedDefinition::U16(Endian::Big), SizedDisplay::Decimal), ASCII::new(StrictASCII::Permissive))?),
+        ])
+    }
+
+    /// A string with a one-byte length.
+    fn short_string() -> SimpleResult<H2Type> {
+        LPString::new(H2Number::new(SizedDefinition::U8, SizedDisplay::Decimal), ASCII::new(StrictASCII::Permissive))
+    }
+
+    /// A number that has to be 1, then a string; it fits, but fails to
+    /// resolve unless the number is 1.
+    fn checked_record() -> SimpleResult<H2Type> {
+        H2Struct::new(vec![
+            ("number".to_string(), Constrained::new(
+                H2Number::new(SizedDefinition::U8, SizedDisplay::Decimal),
+                Constraint::Equals(ConstraintValue::Integer(1)),
+                ConstraintAction::Fail,
+            )?),
+            ("string".to_string(), short_string()?),
+        ])
+    }
+
+    /// A source that counts how many times it's read.
+    struct CountingSource {
+        data: Arc<[u8]>,
+        reads: Mutex<u64>,
+    }
+
+    impl DataSource for CountingSource {
+        fn len(&self) -> u64 {
+            DataSource::len(&self.data)
+        }
+
+        fn read(&self, range: Range<u64>) -> SimpleResult<Vec<u8>> {
+            *self.reads.lock().unwrap() += 1;
+            DataSource::read(&self.data, range)
+        }
+    }
+
+    #[test]
+    fn test_sources() -> SimpleResult<()> {
+        let data = data();
+
+        let path = std::env::temp_dir().join(format!("h2datatype-source-{}", std::process::id()));
+        try_with!(std::fs::write(&path, &data), "Couldn't write {}", path.display());
+        let mmap = MmapSource::open(&path);
+        try_with!(std::fs::remove_file(&path), "Couldn't remove {}", path.display());
+        let mmap = mmap?;
+
+        let arc: Arc<[u8]> = Arc::from(data.clone());
+
+        // Small pages, so reads cross them and the cache fills up
+        let seek = SeekSource::with_cache(Cursor::new(data.clone()), 100, 4)?;
+
+        let sources: Vec<&dyn DataSource> = vec![&arc, &mmap, &seek];
+        for source in sources {
+            assert_eq!(6003, source.len());
+            assert_eq!(data[950..1250].to_vec(), source.read(950..1250)?);
+            assert_eq!(Vec::<u8>::new(), source.read(6003..6003)?);
+            assert!(source.read(6000..6004).is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_from() -> SimpleResult<()> {
+        let source = SeekSource::with_cache(Cursor::new(data()), 512, 8)?;
+
+        // The string is bigger than the first window, so it takes a few
+        let r = resolve_from(&source, &record()?, 1000, None)?;
+        assert_eq!(1000..6003, r.actual_range);
+        assert_eq!(1000..1001, r.children[0].actual_range);
+        assert_eq!(Some(42), r.children[0].as_u64);
+        assert_eq!(1001..6003, r.children[1].actual_range);
+
+        // A static type reads just what it needs
+        let t = H2Number::new(SizedDefinition::U16(Endian::Big), SizedDisplay::Decimal);
+        assert_eq!(Some(5000), resolve_from(&source, &t, 1001, None)?.as_u64);
+
+        // Running off the end fails
+        assert!(resolve_from(&source, &record()?, 999, None).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_from_remaining() -> SimpleResult<()> {
+        let source: Arc<[u8]> = Arc::from(data());
+
+        // The rest of the data is bigger than the first window, but the
+        // remaining bytes still go to the end of the data
+        let t = H2Struct::new(vec![
+            ("number".to_string(), H2Number::new(SizedDefinition::U8, SizedDisplay::Decimal)),
+            ("rest".to_string(), H2Bytes::new(BytesLength::Remaining, BytesDisplay::Hex)?),
+        ])?;
+
+        let r = resolve_from(&source, &t, 1000, None)?;
+        assert_eq!(1000..6003, r.actual_range);
+        assert_eq!(1001..6003, r.children[1].actual_range);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_from_errors() -> SimpleResult<()> {
+        // The record fits in the first window, but the number is 42, so it
+        // fails without reading any more
+        let source = CountingSource { data: Arc::from(data()), reads: Mutex::new(0) };
+        assert!(resolve_from(&source, &checked_record()?, 1000, None).is_err());
+        assert_eq!(1, *source.reads.lock().unwrap());
+
+        // A record that doesn't fit does grow the window
+        let source = CountingSource { data: Arc::from(data()), reads: Mutex::new(0) };
+        assert!(resolve_from(&source, &record()?, 1000, None).is_ok());
+        assert_eq!(2, *source.reads.lock().unwrap());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_window() -> SimpleResult<()> {
+        let source: Arc<[u8]> = Arc::from(data());
+        let window = Window::new(&source, 900..1010)?;
+
+        assert_eq!(900..1010, window.range());
+        assert_eq!(100, window.offset(1000)?.position());
+        assert!(window.offset(899).is_err());
+
+        // The string doesn't fit in the window
+        assert!(window.resolve(&record()?, 1000, None).is_err());
+
+        let t = H2Number::new(SizedDefinition::U8, SizedDisplay::Decimal);
+        assert_eq!(1000..1001, window.resolve(&t, 1000, None)?.actual_range);
+
+        // The end is cut off at the end of the data
+        assert_eq!(6000..6003, Window::new(&source, 6000..7000)?.range());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_window_pointers() -> SimpleResult<()> {
+        // Padding, then a pointer to "hello" right after it
+        let mut data = vec![0; 16];
+        data.extend_from_slice(&24u64.to_le_bytes());
+        data.extend_from_slice(b"\x05hello");
+        let source: Arc<[u8]> = Arc::from(data);
+
+        let pointer = H2Pointer::new(SizedDefinition::U64(Endian::Little), SizedDisplay::Hex(Default::default()), short_string()?);
+
+        // The target is found in the window, even though the window doesn't
+        // start at the beginning of the data
+        let window = Window::new(&source, 16..30)?;
+        let r = window.resolve(&pointer, 16, None)?;
+        assert_eq!(16..24, r.actual_range);
+        assert_eq!("(ref) 0x0000000000000018 => \"hello\"", r.display);
+        assert_eq!(24, r.related[0].0);
+
+        // A target outside the window isn't read from the wrong place
+        let window = Window::new(&source, 16..24)?;
+        let r = window.resolve(&pointer, 16, None)?;
+        assert!(r.display.starts_with("(ref) 0x0000000000000018 => Invalid pointer target"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_segmented() -> SimpleResult<()> {
+        // A snapshot file with three segments: a pointer to "hello" (and
+        // some junk), the segment with "hello", and a segment right after it
+        // that ends in a string that runs off the end
+        let mut file = b"\x10\x00\x00\x40\x00\x00\x00\x00junkjunk".to_vec();
+        file.extend_from_slice(&vec![0; 16]);
+        file.extend_from_slice(b"\x05hello");
+        file.extend_from_slice(&vec![0; 10]);
+        file.extend_from_slice(b"\x00\x00\x00\x00\x00\x00\x0a\x41");
+        let file: Arc<dyn DataSource> = Arc::new(Arc::<[u8]>::from(file));
+
+        let mut source = SegmentedSource::new();
+        source.add_segment(0x7fff00000000, file.clone(), 0..16)?;
+        source.add_segment(0x40000000, file.clone(), 16..48)?;
+        source.add_segment(0x40000020, file.clone(), 48..56)?;
+        assert!(source.add_segment(0x4000001f, file.clone(), 0..1).is_err());
+
+        assert_eq!(vec![0x40000000..0x40000020, 0x40000020..0x40000028, 0x7fff00000000..0x7fff00000010], source.segments());
+        assert_eq!(0x7fff00000010, source.len());
+
+        // Adjacent segments read as one, but gaps don't
+        assert!(source.is_mapped(0x40000000..0x40000028));
+        assert!(!source.is_mapped(0x40000000..0x40000029));
+        assert_eq!(b"hello".to_vec(), source.read(0x40000011..0x40000016)?);
+
+        match source.read(0x3ffffff0..0x40000010) {
+            Ok(_)  => panic!("Read across a gap"),
+            Err(e) => assert!(is_unmapped(&e)),
+        }
+
+        // Follow a pointer from one segment to another
+        let string = short_string()?;
+        let pointer = H2Pointer::new(SizedDefinition::U64(Endian::Little), SizedDisplay::Hex(Default::default()), string.clone());
+
+        let r = resolve_from(&source, &pointer, 0x7fff00000000, None)?;
+        assert_eq!(0x7fff00000000..0x7fff00000008, r.actual_range);
+        assert_eq!(0x40000010, r.related[0].0);
+
+        // The target is in another segment, so it isn't in the display
+        assert!(r.display.starts_with("(ref) 0x0000000040000010 => Invalid pointer target"));
+
+        let target = resolve_from(&source, &r.related[0].1, r.related[0].0, None)?;
+        assert_eq!(0x40000010..0x40000016, target.actual_range);
+        assert_eq!(Some("hello".to_string()), target.as_string);
+
+        // A string that runs into a gap
+        match resolve_from(&source, &string, 0x40000026, None) {
+            Ok(_)  => panic!("Resolved across a gap"),
+            Err(e) => assert!(is_unmapped(&e)),
+        }
+
+        // A record that fits before the gap, but is bad, is just an error
+        match resolve_from(&source, &checked_record()?, 0x40000020, None) {
+            Ok(_)  => panic!("Resolved a bad record"),
+            Err(e) => assert!(!is_unmapped(&e)),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_kernel_addresses() -> SimpleResult<()> {
+        let file: Arc<dyn DataSource> = Arc::new(Arc::<[u8]>::from(b"\xa5\x0f".to_vec()));
+
+        let mut source = SegmentedSource::new();
+        source.add_segment(0xffff800000000000, file.clone(), 0..2)?;
+
+        // Bytes can be anywhere
+        let t = H2Number::new(SizedDefinition::U16(Endian::Big), SizedDisplay::Hex(Default::default()));
+        let r = resolve_from(&source, &t, 0xffff800000000000, None)?;
+        assert_eq!(0xffff800000000000..0xffff800000000002, r.actual_range);
+
+        // Bits this high can't be numbered in a u64
+        let t = BitNumber::new(4, false, BitOrder::MSBFirst, SizedDisplay::Decimal)?;
+        assert!(resolve_from(&source, &t, 0xffff800000000000, None).is_err());
+
+        Ok(())
+    }
+}